@@ -9,16 +9,27 @@ use crate::{
         AnimData, EffectAnimationInfo as GeneralAnim, ItemAnimationInfo as ItemAnim,
         MoveAnimationInfo as MoveAnim, TrapAnimationInfo as TrapAnim,
     },
+    output::JsonStyle,
     rom::Rom,
 };
 
 pub struct AnimationInfoExtractor<'a> {
     rom: &'a mut Rom,
+    json_style: JsonStyle,
 }
 
 impl<'a> AnimationInfoExtractor<'a> {
     pub fn new(rom: &'a mut Rom) -> Self {
-        AnimationInfoExtractor { rom }
+        AnimationInfoExtractor {
+            rom,
+            json_style: JsonStyle::default(),
+        }
+    }
+
+    /// Formatting for the animation info JSON files
+    pub fn with_json_style(mut self, json_style: JsonStyle) -> Self {
+        self.json_style = json_style;
+        self
     }
 
     pub fn parse_and_transform_animation_data(&mut self) -> AnimData {
@@ -60,7 +71,8 @@ impl<'a> AnimationInfoExtractor<'a> {
         let file =
             File::create(&file_path).map_err(|e| format!("Failed to create traps.json: {}", e))?;
 
-        serde_json::to_writer_pretty(file, &trap_table)
+        self.json_style
+            .write(file, &trap_table)
             .map_err(|e| format!("Failed to serialise trap animations: {}", e))?;
 
         println!(
@@ -76,7 +88,8 @@ impl<'a> AnimationInfoExtractor<'a> {
         let file =
             File::create(&file_path).map_err(|e| format!("Failed to create items.json: {}", e))?;
 
-        serde_json::to_writer_pretty(file, &item_table)
+        self.json_style
+            .write(file, &item_table)
             .map_err(|e| format!("Failed to serialise item animations: {}", e))?;
 
         println!(
@@ -105,7 +118,8 @@ impl<'a> AnimationInfoExtractor<'a> {
             .map(|(idx, anim)| (idx.to_string(), anim))
             .collect();
 
-        serde_json::to_writer_pretty(file, &move_map_str)
+        self.json_style
+            .write(file, &move_map_str)
             .map_err(|e| format!("Failed to serialise move animations: {}", e))?;
 
         println!(
@@ -132,7 +146,8 @@ impl<'a> AnimationInfoExtractor<'a> {
             .map(|(idx, anim)| (idx.to_string(), anim))
             .collect();
 
-        serde_json::to_writer_pretty(file, &effect_map)
+        self.json_style
+            .write(file, &effect_map)
             .map_err(|e| format!("Failed to serialise effect animations: {}", e))?;
 
         println!(
@@ -163,7 +178,8 @@ impl<'a> AnimationInfoExtractor<'a> {
             }
         });
 
-        serde_json::to_writer_pretty(file, &summary)
+        self.json_style
+            .write(file, &summary)
             .map_err(|e| format!("Failed to serialise animation summary: {}", e))?;
 
         println!("Animation summary saved to {}", file_path.display());
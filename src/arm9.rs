@@ -21,6 +21,21 @@ pub struct Overlay {
     pub flags: u8,
 }
 
+impl Overlay {
+    /// Converts a RAM address inside this overlay into an offset into `data`
+    pub fn ram_to_file_offset(&self, addr: u32) -> Option<usize> {
+        let offset = addr.checked_sub(self.ram_address)? as usize;
+        (offset < self.data.len()).then_some(offset)
+    }
+
+    /// Returns the `len` bytes loaded at RAM address `addr`, or `None` if any of them fall
+    /// outside the overlay's file data
+    pub fn read_at_ram_address(&self, addr: u32, len: usize) -> Option<&[u8]> {
+        let start = self.ram_to_file_offset(addr)?;
+        self.data.get(start..start.checked_add(len)?)
+    }
+}
+
 pub fn load_overlay_table(
     table_data: &[u8],
     file_callback: impl Fn(u32, u32) -> io::Result<Vec<u8>>,
@@ -105,3 +120,44 @@ pub fn load_overlay_table(
     );
     Ok(overlays)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlay(ram_address: u32, data: Vec<u8>) -> Overlay {
+        Overlay {
+            id: 10,
+            ram_size: data.len() as u32,
+            data,
+            ram_address,
+            block_started_by_symbol_size: 0,
+            static_init_start: 0,
+            static_init_end: 0,
+            file_id: 0,
+            compressed_size: 0,
+            flags: 0,
+        }
+    }
+
+    #[test]
+    fn ram_addresses_map_into_the_overlay_data() {
+        let overlay = overlay(0x022D_C240, (0..16).collect());
+
+        assert_eq!(overlay.ram_to_file_offset(0x022D_C240), Some(0));
+        assert_eq!(overlay.ram_to_file_offset(0x022D_C24F), Some(15));
+        assert_eq!(overlay.ram_to_file_offset(0x022D_C250), None);
+        assert_eq!(overlay.ram_to_file_offset(0x022D_C23F), None);
+
+        assert_eq!(
+            overlay.read_at_ram_address(0x022D_C244, 3),
+            Some(&[4, 5, 6][..])
+        );
+        assert_eq!(
+            overlay.read_at_ram_address(0x022D_C24E, 2),
+            Some(&[14, 15][..])
+        );
+        // Runs past the end of the data
+        assert_eq!(overlay.read_at_ram_address(0x022D_C24E, 3), None);
+    }
+}
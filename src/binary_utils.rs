@@ -1,7 +1,13 @@
 use std::io::{self, Cursor, Read, Seek};
 
+/// Bytes between the cursor and the end of its buffer, 0 if it was seeked past the end.
+/// Readers compare this against their width so a read of exactly the last bytes succeeds.
+fn remaining(cursor: &Cursor<&[u8]>) -> u64 {
+    (cursor.get_ref().len() as u64).saturating_sub(cursor.position())
+}
+
 pub fn read_u8(cursor: &mut Cursor<&[u8]>) -> io::Result<u8> {
-    if cursor.position() >= cursor.get_ref().len() as u64 {
+    if remaining(cursor) < 1 {
         return Err(io::Error::new(
             io::ErrorKind::UnexpectedEof,
             "End of buffer reached",
@@ -14,7 +20,7 @@ pub fn read_u8(cursor: &mut Cursor<&[u8]>) -> io::Result<u8> {
 }
 
 pub fn read_i8(cursor: &mut Cursor<&[u8]>) -> io::Result<i8> {
-    if cursor.position() >= cursor.get_ref().len() as u64 {
+    if remaining(cursor) < 1 {
         return Err(io::Error::new(
             io::ErrorKind::UnexpectedEof,
             "End of buffer reached",
@@ -27,7 +33,7 @@ pub fn read_i8(cursor: &mut Cursor<&[u8]>) -> io::Result<i8> {
 }
 
 pub fn read_u16_le(cursor: &mut Cursor<&[u8]>) -> io::Result<u16> {
-    if cursor.position() + 1 >= cursor.get_ref().len() as u64 {
+    if remaining(cursor) < 2 {
         return Err(io::Error::new(
             io::ErrorKind::UnexpectedEof,
             "End of buffer reached or not enough bytes for u16",
@@ -40,7 +46,7 @@ pub fn read_u16_le(cursor: &mut Cursor<&[u8]>) -> io::Result<u16> {
 }
 
 pub fn read_i16_le(cursor: &mut Cursor<&[u8]>) -> io::Result<i16> {
-    if cursor.position() + 1 >= cursor.get_ref().len() as u64 {
+    if remaining(cursor) < 2 {
         return Err(io::Error::new(
             io::ErrorKind::UnexpectedEof,
             "End of buffer reached or not enough bytes for i16",
@@ -53,7 +59,7 @@ pub fn read_i16_le(cursor: &mut Cursor<&[u8]>) -> io::Result<i16> {
 }
 
 pub fn read_u32_le(cursor: &mut Cursor<&[u8]>) -> io::Result<u32> {
-    if cursor.position() + 3 >= cursor.get_ref().len() as u64 {
+    if remaining(cursor) < 4 {
         return Err(io::Error::new(
             io::ErrorKind::UnexpectedEof,
             "End of buffer reached or not enough bytes for u32",
@@ -66,7 +72,7 @@ pub fn read_u32_le(cursor: &mut Cursor<&[u8]>) -> io::Result<u32> {
 }
 
 pub fn read_i32_le(cursor: &mut Cursor<&[u8]>) -> io::Result<i32> {
-    if cursor.position() + 3 >= cursor.get_ref().len() as u64 {
+    if remaining(cursor) < 4 {
         return Err(io::Error::new(
             io::ErrorKind::UnexpectedEof,
             "End of buffer reached or not enough bytes for i32",
@@ -97,7 +103,7 @@ pub fn seek_to(cursor: &mut Cursor<&[u8]>, position: u64) -> io::Result<()> {
 }
 
 pub fn read_bytes(cursor: &mut Cursor<&[u8]>, length: usize) -> io::Result<Vec<u8>> {
-    if cursor.position() + (length as u64) > cursor.get_ref().len() as u64 {
+    if remaining(cursor) < length as u64 {
         return Err(io::Error::new(
             io::ErrorKind::UnexpectedEof,
             format!("Not enough bytes remaining for read_bytes({})", length),
@@ -117,3 +123,57 @@ pub fn write_u32(data: &mut [u8], value: u32, pos: usize) {
         data[pos + 3] = ((value >> 24) & 0xFF) as u8;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `read` at `position` in an 8 byte buffer
+    fn read_at<T>(
+        position: u64,
+        read: impl Fn(&mut Cursor<&[u8]>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let data = [0u8; 8];
+        let mut cursor = Cursor::new(&data[..]);
+        cursor.set_position(position);
+        read(&mut cursor)
+    }
+
+    #[test]
+    fn reads_of_the_last_bytes_succeed_and_one_byte_short_fails() {
+        assert!(read_at(7, read_u8).is_ok());
+        assert!(read_at(8, read_u8).is_err());
+        assert!(read_at(7, read_i8).is_ok());
+        assert!(read_at(8, read_i8).is_err());
+
+        assert!(read_at(6, read_u16_le).is_ok());
+        assert!(read_at(7, read_u16_le).is_err());
+        assert!(read_at(6, read_i16_le).is_ok());
+        assert!(read_at(7, read_i16_le).is_err());
+
+        assert!(read_at(4, read_u32_le).is_ok());
+        assert!(read_at(5, read_u32_le).is_err());
+        assert!(read_at(4, read_i32_le).is_ok());
+        assert!(read_at(5, read_i32_le).is_err());
+
+        assert!(read_at(5, |c| read_bytes(c, 3)).is_ok());
+        assert!(read_at(6, |c| read_bytes(c, 3)).is_err());
+        // Seeked past the end
+        assert!(read_at(20, read_u32_le).is_err());
+    }
+
+    #[test]
+    fn final_u16_and_u32_decode_little_endian() {
+        let data = [0xFFu8, 0x34, 0x12];
+        let mut cursor = Cursor::new(&data[..]);
+        cursor.set_position(1);
+        assert_eq!(read_u16_le(&mut cursor).unwrap(), 0x1234);
+        assert_eq!(cursor.position(), 3);
+
+        let data = [0xFFu8, 0x78, 0x56, 0x34, 0x12];
+        let mut cursor = Cursor::new(&data[..]);
+        cursor.set_position(1);
+        assert_eq!(read_u32_le(&mut cursor).unwrap(), 0x1234_5678);
+        assert_eq!(cursor.position(), 5);
+    }
+}
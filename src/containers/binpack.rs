@@ -1,3 +1,4 @@
+#[derive(Default)]
 pub struct BinPack {
     files: Vec<Vec<u8>>,
 }
@@ -100,10 +101,16 @@ impl BinPack {
         output
     }
 
+    /// Bounds-checked access to one file, prefer this over indexing for ROM-derived indices
     pub fn get(&self, index: usize) -> Option<&[u8]> {
         self.files.get(index).map(|v| v.as_slice())
     }
 
+    /// Every file in table order
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.files.iter().map(|v| v.as_slice())
+    }
+
     pub fn len(&self) -> usize {
         self.files.len()
     }
@@ -117,7 +124,7 @@ impl BinPack {
     }
 }
 
-// Allow direct indexing
+// Allow direct indexing, panics on out-of-range indices like `Vec`
 impl std::ops::Index<usize> for BinPack {
     type Output = Vec<u8>;
 
@@ -140,3 +147,23 @@ impl<'a> IntoIterator for &'a BinPack {
         self.files.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_and_iter_survive_a_round_trip() {
+        let mut pack = BinPack::default();
+        pack.append(vec![1; 4]);
+        pack.append(vec![2; 6]);
+        let pack = BinPack::from_bytes(&pack.to_bytes(0)).unwrap();
+
+        assert_eq!(pack.get(1), Some(&[2; 6][..]));
+        assert_eq!(pack.get(2), None);
+        assert_eq!(
+            pack.iter().collect::<Vec<_>>(),
+            vec![&[1; 4][..], &[2; 6][..]]
+        );
+    }
+}
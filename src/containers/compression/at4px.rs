@@ -1,4 +1,4 @@
-use crate::containers::{CompressionContainer, ContainerHandler};
+use crate::containers::{compression::px::PxHandler, CompressionContainer, ContainerHandler};
 use std::io::{self};
 
 // AT4PX is a compression container using the PX algorithm. Used throughout the ROM for various
@@ -54,6 +54,39 @@ impl At4pxContainer {
 
         Ok((container_length, container))
     }
+
+    /// Compresses `data` with the PX algorithm and wraps it in an AT4PX container, the inverse
+    /// of `deserialise` followed by `decompress`. Both the container length and the
+    /// decompressed size are stored as u16, so larger inputs are rejected.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn compress(data: &[u8]) -> Result<Vec<u8>, String> {
+        let decompressed_size = u16::try_from(data.len()).map_err(|_| {
+            format!(
+                "Data too large for AT4PX ({} bytes, max {})",
+                data.len(),
+                u16::MAX
+            )
+        })?;
+
+        let (compressed_data, compression_flags) = PxHandler::compress(data)?;
+
+        let container_length = u16::try_from(AT4PX_CONTAINER_HEADER_SIZE + compressed_data.len())
+            .map_err(|_| {
+            format!(
+                "Compressed data too large for AT4PX ({} bytes)",
+                compressed_data.len()
+            )
+        })?;
+
+        let mut container = Vec::with_capacity(container_length as usize);
+        container.extend_from_slice(Self::magic_word());
+        container.extend_from_slice(&container_length.to_le_bytes());
+        container.extend_from_slice(&compression_flags);
+        container.extend_from_slice(&decompressed_size.to_le_bytes());
+        container.extend_from_slice(&compressed_data);
+
+        Ok(container)
+    }
 }
 
 impl ContainerHandler for At4pxContainer {
@@ -1,2 +1,3 @@
-pub mod pkdpx;
 pub mod at4px;
+pub mod pkdpx;
+pub mod px;
@@ -0,0 +1,706 @@
+// PX compression and decompression shared by the compression containers
+//
+// Back references never reach further than PX_LOOKBACK_BUFFER_SIZE bytes, so the decoder only
+// keeps that much history in a ring buffer and can stream its output into any `Write` sink.
+
+use std::{collections::HashMap, io::Write};
+
+const PX_MIN_MATCH_SEQLEN: usize = 3;
+const PX_MAX_MATCH_SEQLEN: usize = 18; // Length is stored in a nibble, offset by the minimum
+const PX_LOOKBACK_BUFFER_SIZE: usize = 4096; // 0x1000
+
+/// Output is handed to the sink in chunks of this size
+const PX_WRITE_CHUNK_SIZE: usize = 4096;
+
+pub struct PxDecompressor<'a> {
+    compression_flags: &'a [u8; 9],
+    length_decompressed: usize,
+}
+
+impl<'a> PxDecompressor<'a> {
+    pub fn new(compression_flags: &'a [u8; 9], length_decompressed: usize) -> Self {
+        PxDecompressor {
+            compression_flags,
+            length_decompressed,
+        }
+    }
+
+    /// Decompresses into an owned buffer, written through `decompress_to` so nothing is copied
+    pub fn decompress(&self, compressed_data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut decompressed = Vec::with_capacity(self.length_decompressed);
+        self.decompress_to(compressed_data, &mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    /// Streams the decompressed bytes into `out`, only the lookback window is kept in memory.
+    /// Returns the number of bytes written.
+    pub fn decompress_to<W: Write + ?Sized>(
+        &self,
+        compressed_data: &[u8],
+        out: &mut W,
+    ) -> Result<usize, String> {
+        let mut output = PxOutput::new(out);
+
+        // Create a lookup table for bit positions
+        let bit_masks = [0x80, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01];
+
+        // Current position in the compressed data
+        let mut data_pos = 0;
+
+        // Main decompression loop
+        while data_pos < compressed_data.len() {
+            // Read control byte that determines how to interpret the next 8 operations
+            let control_byte = compressed_data[data_pos];
+            data_pos += 1;
+
+            // Process each bit in the control byte
+            for bit_mask in bit_masks {
+                if data_pos >= compressed_data.len() {
+                    break;
+                }
+
+                if control_byte & bit_mask != 0 {
+                    output.push(compressed_data[data_pos])?;
+                    data_pos += 1;
+                    continue;
+                }
+
+                if data_pos + 1 >= compressed_data.len() {
+                    return Err("Unexpected end of compressed data".to_string());
+                }
+
+                let first_byte = compressed_data[data_pos];
+                data_pos += 1;
+
+                let high_nibble = first_byte >> 4;
+                let low_nibble = first_byte & 0x0F;
+
+                // A high nibble matching a compression flag encodes a 2 byte nibble pattern
+                if let Some(flag_idx) = self
+                    .compression_flags
+                    .iter()
+                    .position(|&flag| flag == high_nibble)
+                {
+                    let pattern = compute_nibble_pattern(flag_idx, low_nibble);
+                    output.push(pattern.0)?;
+                    output.push(pattern.1)?;
+                    continue;
+                }
+
+                // Otherwise it's a back reference into the output
+                let second_byte = compressed_data[data_pos];
+                data_pos += 1;
+
+                let copy_len = (high_nibble as usize) + PX_MIN_MATCH_SEQLEN;
+                let back_offset =
+                    PX_LOOKBACK_BUFFER_SIZE - ((low_nibble as usize) << 8) - second_byte as usize;
+
+                if back_offset > output.written {
+                    return Err(format!(
+                        "Invalid back reference offset: {} (output size: {})",
+                        back_offset, output.written
+                    ));
+                }
+
+                // Copying byte by byte repeats the pattern when the match overlaps itself
+                for _ in 0..copy_len {
+                    let byte = output.lookback(back_offset);
+                    output.push(byte)?;
+                }
+            }
+
+            // break if we've reached expected length
+            if output.written >= self.length_decompressed {
+                break;
+            }
+        }
+
+        let written = output.finish()?;
+        if written != self.length_decompressed {
+            println!(
+                "WARNING: Expected {} bytes but got {} bytes",
+                self.length_decompressed, written
+            );
+        }
+
+        Ok(written)
+    }
+}
+
+/// Compresses data into the PX stream read by `PxDecompressor`.
+///
+/// The defaults are the format's parameters. A shorter lookback window or maximum match length
+/// only compresses worse, but any `min_match_seqlen` other than 3 changes how match lengths are
+/// encoded: that output can't be decompressed by the game or `PxDecompressor` and is only
+/// useful for comparing compression ratios.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct PxCompressor {
+    min_match_seqlen: usize,
+    max_match_seqlen: usize,
+    lookback_buffer_size: usize,
+    /// Emit back references, off for `PXCompLevel::Level0`/`Level1`
+    back_references: bool,
+    /// Emit 2-byte nibble patterns, off for `PXCompLevel::Level0`
+    nibble_patterns: bool,
+    /// Look for a back reference before trying a nibble pattern
+    search_first: bool,
+}
+
+impl Default for PxCompressor {
+    fn default() -> Self {
+        PxCompressor {
+            min_match_seqlen: PX_MIN_MATCH_SEQLEN,
+            max_match_seqlen: PX_MAX_MATCH_SEQLEN,
+            lookback_buffer_size: PX_LOOKBACK_BUFFER_SIZE,
+            back_references: true,
+            nibble_patterns: true,
+            search_first: true,
+        }
+    }
+}
+
+/// How hard `PxHandler::compress_with` works. Every level produces a stream the game reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub enum PXCompLevel {
+    /// Every byte a literal (all command bits set): output is about 9/8 the input, no searching
+    Level0,
+    /// Literals and 2-byte nibble patterns only. No match search, so about as fast as Level0,
+    /// but only shrinks data with many repeated nibbles (e.g. 4bpp tiles)
+    Level1,
+    /// Level1 plus back references within the last 512 bytes. Far fewer candidates to compare
+    /// than Level3, but misses repeats further back (e.g. identical tiles far apart)
+    Level2,
+    /// Back references across the full 4096 byte window, the smallest output and the slowest
+    Level3,
+}
+
+impl PXCompLevel {
+    fn compressor(self, search_first: bool) -> PxCompressor {
+        let base = PxCompressor {
+            search_first,
+            ..PxCompressor::default()
+        };
+        match self {
+            PXCompLevel::Level0 => PxCompressor {
+                back_references: false,
+                nibble_patterns: false,
+                ..base
+            },
+            PXCompLevel::Level1 => PxCompressor {
+                back_references: false,
+                ..base
+            },
+            PXCompLevel::Level2 => base.with_lookback_buffer_size(512),
+            PXCompLevel::Level3 => base,
+        }
+    }
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl PxCompressor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shortest back reference emitted. Anything but 3 makes the output non ROM-compatible.
+    pub fn with_min_match_seqlen(mut self, min_match_seqlen: usize) -> Self {
+        self.min_match_seqlen = min_match_seqlen;
+        self
+    }
+
+    /// Longest back reference emitted, capped at `min_match_seqlen + 15` (one nibble)
+    pub fn with_max_match_seqlen(mut self, max_match_seqlen: usize) -> Self {
+        self.max_match_seqlen = max_match_seqlen;
+        self
+    }
+
+    /// How far back references may reach, at most 4096 bytes
+    pub fn with_lookback_buffer_size(mut self, lookback_buffer_size: usize) -> Self {
+        self.lookback_buffer_size = lookback_buffer_size;
+        self
+    }
+
+    /// Compresses `data`, returning the 9 compression flags and the PX stream
+    pub fn compress(&self, data: &[u8]) -> Result<([u8; 9], Vec<u8>), String> {
+        self.validate()?;
+
+        // Flags and match lengths share the command nibble. A first pass counts which length
+        // nibbles unrestricted matching would use, the 9 least used become the flags.
+        let mut nibble_use = [0usize; 16];
+        self.encode(data, &[true; 16], &HashMap::new(), |nibble| {
+            nibble_use[nibble as usize] += 1
+        });
+
+        let mut nibbles: Vec<u8> = (0..16).collect();
+        nibbles.sort_by_key(|&nibble| nibble_use[nibble as usize]);
+        let mut compression_flags = [0u8; 9];
+        compression_flags.copy_from_slice(&nibbles[..9]);
+        compression_flags.sort_unstable();
+
+        let mut length_allowed = [true; 16];
+        for &flag in &compression_flags {
+            length_allowed[flag as usize] = false;
+        }
+
+        // Earlier flags win when two patterns produce the same bytes, the decoder doesn't care
+        let mut patterns = HashMap::new();
+        let pattern_flags = if self.nibble_patterns {
+            &compression_flags[..]
+        } else {
+            &[]
+        };
+        for (flag_idx, &flag) in pattern_flags.iter().enumerate() {
+            for low_nibble in 0..16u8 {
+                patterns
+                    .entry(compute_nibble_pattern(flag_idx, low_nibble))
+                    .or_insert((flag << 4) | low_nibble);
+            }
+        }
+
+        let compressed = self.encode(data, &length_allowed, &patterns, |_| ());
+        Ok((compression_flags, compressed))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.min_match_seqlen < 2 {
+            return Err(format!(
+                "min_match_seqlen {} is shorter than a back reference (2 bytes)",
+                self.min_match_seqlen
+            ));
+        }
+        if self.max_match_seqlen < self.min_match_seqlen {
+            return Err(format!(
+                "max_match_seqlen {} is shorter than min_match_seqlen {}",
+                self.max_match_seqlen, self.min_match_seqlen
+            ));
+        }
+        if self.lookback_buffer_size == 0 || self.lookback_buffer_size > PX_LOOKBACK_BUFFER_SIZE {
+            return Err(format!(
+                "lookback_buffer_size {} must be within 1..={}",
+                self.lookback_buffer_size, PX_LOOKBACK_BUFFER_SIZE
+            ));
+        }
+        Ok(())
+    }
+
+    /// Greedy encoder: longest back reference whose length nibble is allowed and a nibble
+    /// pattern (in `search_first` order), then a literal. `on_match` sees the length nibble of
+    /// every back reference.
+    fn encode(
+        &self,
+        data: &[u8],
+        length_allowed: &[bool; 16],
+        patterns: &HashMap<(u8, u8), u8>,
+        mut on_match: impl FnMut(u8),
+    ) -> Vec<u8> {
+        let key_len = self.min_match_seqlen.min(3);
+        let key = |pos: usize| {
+            data[pos..pos + key_len]
+                .iter()
+                .fold(0u32, |key, &byte| (key << 8) | byte as u32)
+        };
+        let mut chains: HashMap<u32, Vec<usize>> = HashMap::new();
+        let insert = |chains: &mut HashMap<u32, Vec<usize>>, pos: usize| {
+            if pos + key_len <= data.len() {
+                chains.entry(key(pos)).or_default().push(pos);
+            }
+        };
+
+        let mut compressed = Vec::with_capacity(data.len() + data.len() / 8 + 1);
+        let mut control_pos = 0;
+        let mut bit = 8;
+        let mut pos = 0;
+
+        while pos < data.len() {
+            if bit == 8 {
+                control_pos = compressed.len();
+                compressed.push(0);
+                bit = 0;
+            }
+            let bit_mask = 0x80u8 >> bit;
+            bit += 1;
+
+            let mut back_reference = None;
+            if self.back_references && self.search_first {
+                back_reference = self.find_match(data, pos, &chains, key, length_allowed);
+            }
+
+            // The decoder wants another byte after a pattern, so never end the stream on one
+            if back_reference.is_none() && pos + 2 < data.len() {
+                if let Some(&command) = patterns.get(&(data[pos], data[pos + 1])) {
+                    compressed.push(command);
+                    insert(&mut chains, pos);
+                    insert(&mut chains, pos + 1);
+                    pos += 2;
+                    continue;
+                }
+            }
+
+            if back_reference.is_none() && self.back_references && !self.search_first {
+                back_reference = self.find_match(data, pos, &chains, key, length_allowed);
+            }
+
+            if let Some((length, back_offset)) = back_reference {
+                let length_nibble = (length - self.min_match_seqlen) as u8;
+                let encoded_offset = PX_LOOKBACK_BUFFER_SIZE - back_offset;
+                compressed.push((length_nibble << 4) | (encoded_offset >> 8) as u8);
+                compressed.push(encoded_offset as u8);
+                on_match(length_nibble);
+                for p in pos..pos + length {
+                    insert(&mut chains, p);
+                }
+                pos += length;
+                continue;
+            }
+
+            compressed[control_pos] |= bit_mask;
+            compressed.push(data[pos]);
+            insert(&mut chains, pos);
+            pos += 1;
+        }
+
+        compressed
+    }
+
+    /// Longest earlier occurrence of the data at `pos` within the lookback window, cut down to
+    /// an allowed length. Returns `(length, back_offset)`.
+    fn find_match(
+        &self,
+        data: &[u8],
+        pos: usize,
+        chains: &HashMap<u32, Vec<usize>>,
+        key: impl Fn(usize) -> u32,
+        length_allowed: &[bool; 16],
+    ) -> Option<(usize, usize)> {
+        let max_length = self
+            .max_match_seqlen
+            .min(self.min_match_seqlen + 15)
+            .min(data.len() - pos);
+        if max_length < self.min_match_seqlen {
+            return None;
+        }
+
+        let mut best = (0, 0);
+        for &candidate in chains.get(&key(pos))?.iter().rev() {
+            let back_offset = pos - candidate;
+            if back_offset > self.lookback_buffer_size {
+                break;
+            }
+            // Matches may run into the bytes being encoded, the decoder copies byte by byte
+            let length = (0..max_length)
+                .take_while(|&i| data[candidate + i] == data[pos + i])
+                .count();
+            if length > best.0 {
+                best = (length, back_offset);
+                if length == max_length {
+                    break;
+                }
+            }
+        }
+
+        let (mut length, back_offset) = best;
+        while length >= self.min_match_seqlen && !length_allowed[length - self.min_match_seqlen] {
+            length -= 1;
+        }
+        (length >= self.min_match_seqlen).then_some((length, back_offset))
+    }
+}
+
+/// Entry points for re-packing data with the default `PxCompressor`
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct PxHandler;
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl PxHandler {
+    /// Compresses `data` at `PXCompLevel::Level3`, searching for back references first.
+    /// Returns the compressed data and the 9 compression flags.
+    pub fn compress(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+        Self::compress_with(data, PXCompLevel::Level3, true)
+    }
+
+    /// Compresses `data` at `level`. Level0 and Level1 skip the match search entirely and
+    /// suit quick re-packing while iterating. Level2 searches a 512 byte window and Level3 the
+    /// full 4096 bytes, trading speed for size. With `search_first` a back reference is
+    /// preferred over a nibble pattern at the same position, which usually compresses better.
+    /// Returns the compressed data and the 9 compression flags.
+    pub fn compress_with(
+        data: &[u8],
+        level: PXCompLevel,
+        search_first: bool,
+    ) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let (compression_flags, compressed_data) = level.compressor(search_first).compress(data)?;
+        Ok((compressed_data, compression_flags.to_vec()))
+    }
+
+    /// Compresses `data` and decompresses the result again before returning it, so a stream
+    /// `PxDecompressor` can't reproduce is caught here instead of when the asset is next read.
+    /// Returns the compressed data and the 9 compression flags.
+    pub fn compress_checked(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+        let (compression_flags, compressed_data) = PxCompressor::new().compress(data)?;
+
+        let roundtrip =
+            PxDecompressor::new(&compression_flags, data.len()).decompress(&compressed_data)?;
+        if let Some(offset) = roundtrip
+            .iter()
+            .zip(data)
+            .position(|(decoded, original)| decoded != original)
+        {
+            return Err(format!(
+                "PX roundtrip mismatch at offset 0x{:X}: expected 0x{:02X}, decoded 0x{:02X}",
+                offset, data[offset], roundtrip[offset]
+            ));
+        }
+        if roundtrip.len() != data.len() {
+            return Err(format!(
+                "PX roundtrip mismatch at offset 0x{:X}: decoded {} bytes, expected {}",
+                roundtrip.len().min(data.len()),
+                roundtrip.len(),
+                data.len()
+            ));
+        }
+
+        Ok((compressed_data, compression_flags.to_vec()))
+    }
+}
+
+/// Ring buffer of the last PX_LOOKBACK_BUFFER_SIZE output bytes in front of the sink
+struct PxOutput<'w, W: Write + ?Sized> {
+    out: &'w mut W,
+    window: Box<[u8; PX_LOOKBACK_BUFFER_SIZE]>,
+    pending: Vec<u8>,
+    written: usize,
+}
+
+impl<'w, W: Write + ?Sized> PxOutput<'w, W> {
+    fn new(out: &'w mut W) -> Self {
+        PxOutput {
+            out,
+            window: Box::new([0; PX_LOOKBACK_BUFFER_SIZE]),
+            pending: Vec::with_capacity(PX_WRITE_CHUNK_SIZE),
+            written: 0,
+        }
+    }
+
+    /// Byte `back_offset` positions before the end of the output, 1 is the last byte
+    fn lookback(&self, back_offset: usize) -> u8 {
+        self.window[(self.written - back_offset) % PX_LOOKBACK_BUFFER_SIZE]
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), String> {
+        self.window[self.written % PX_LOOKBACK_BUFFER_SIZE] = byte;
+        self.written += 1;
+        self.pending.push(byte);
+        if self.pending.len() >= PX_WRITE_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.out
+            .write_all(&self.pending)
+            .map_err(|e| format!("Failed to write decompressed data: {}", e))?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<usize, String> {
+        self.flush()?;
+        Ok(self.written)
+    }
+}
+
+// Helper function to compute nibble patterns for special compression flags
+fn compute_nibble_pattern(flag_idx: usize, low_nibble: u8) -> (u8, u8) {
+    // Simple case - all four nibbles are the same (flag 0)
+    if flag_idx == 0 {
+        // In this case, all our 4 nibbles have the value of the "low_nibble" as their value
+        // Since we're dealing with half bytes, shift one left by 4 and bitwise OR it with the other!
+        let value = (low_nibble << 4) | low_nibble;
+        return (value, value);
+    }
+
+    // For all other flags, start with base value for all nibbles
+    let mut nibble_base = low_nibble;
+
+    // Apply first modification: adjust the base value for all nibbles
+    match flag_idx {
+        1 => nibble_base = nibble_base.wrapping_add(1), // Flag 1: Add 1 to all nibbles
+        5 => nibble_base = nibble_base.wrapping_sub(1), // Flag 5: Subtract 1 from all nibbles
+        _ => (), // All other flags (0, 2-4, 6-8) leave base value unchanged
+    }
+
+    // Create array with the base value
+    let mut nibbles = [nibble_base; 4];
+
+    // Apply second modification: adjust specific nibble based on flag index
+    match flag_idx {
+        // Flags 1-4: Decrement the indexed nibble
+        1..=4 => {
+            nibbles[flag_idx - 1] = nibbles[flag_idx - 1].wrapping_sub(1);
+        }
+        // Flags 5-8: Increment the indexed nibble
+        5..=8 => {
+            nibbles[flag_idx - 5] = nibbles[flag_idx - 5].wrapping_add(1);
+        }
+        // Flag 0 has no second modification (already handled by the initial if)
+        _ => (),
+    }
+
+    // Combine nibbles into two bytes
+    let byte1 = (nibbles[0] << 4) | nibbles[1];
+    let byte2 = (nibbles[2] << 4) | nibbles[3];
+
+    (byte1, byte2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Repeated 4bpp-like tiles around a stretch of noise, so every PX command gets used
+    fn sample_data() -> Vec<u8> {
+        let tile: Vec<u8> = (0..32).map(|i| (i % 5) as u8 * 0x11).collect();
+        let mut data = tile.repeat(20);
+        let mut state = 0x1234_5678u32;
+        for _ in 0..512 {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            data.push((state >> 24) as u8);
+        }
+        data.extend(&tile);
+        data
+    }
+
+    fn decompress(flags: &[u8; 9], compressed: &[u8], length: usize) -> Result<Vec<u8>, String> {
+        PxDecompressor::new(flags, length).decompress(compressed)
+    }
+
+    #[test]
+    fn default_compressor_round_trips_and_other_min_lengths_do_not() {
+        let data = sample_data();
+
+        let (flags, compressed) = PxCompressor::new().compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&flags, &compressed, data.len()).unwrap(), data);
+
+        // A shorter maximum or window only compresses worse, the stream is still valid
+        let (short_flags, short) = PxCompressor::new()
+            .with_max_match_seqlen(8)
+            .with_lookback_buffer_size(256)
+            .compress(&data)
+            .unwrap();
+        assert!(short.len() >= compressed.len());
+        assert_eq!(decompress(&short_flags, &short, data.len()).unwrap(), data);
+
+        // Match lengths are stored relative to the minimum, so the decoder reads them wrong
+        let (flags, compressed) = PxCompressor::new()
+            .with_min_match_seqlen(4)
+            .compress(&data)
+            .unwrap();
+        assert!(decompress(&flags, &compressed, data.len()).map_or(true, |d| d != data));
+
+        assert!(PxCompressor::new()
+            .with_lookback_buffer_size(PX_LOOKBACK_BUFFER_SIZE + 1)
+            .compress(&data)
+            .is_err());
+    }
+
+    /// Sink that records how many writes the decoder made
+    #[derive(Default)]
+    struct CountingSink {
+        bytes: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Write for CountingSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn streamed_output_matches_the_buffered_output() {
+        // Several write chunks worth of data
+        let data = sample_data().repeat(8);
+        assert!(data.len() > 2 * PX_WRITE_CHUNK_SIZE);
+        let (flags, compressed) = PxCompressor::new().compress(&data).unwrap();
+        let decompressor = PxDecompressor::new(&flags, data.len());
+
+        let mut sink = CountingSink::default();
+        let written = decompressor.decompress_to(&compressed, &mut sink).unwrap();
+
+        let buffered = decompressor.decompress(&compressed).unwrap();
+        assert_eq!(buffered, data);
+        assert_eq!(sink.bytes, buffered);
+        assert_eq!(written, data.len());
+        assert!(sink.writes > 1);
+    }
+
+    #[test]
+    fn checked_compression_matches_the_unchecked_output() {
+        let data = sample_data();
+
+        let (compressed, flags) = PxHandler::compress_checked(&data).unwrap();
+        assert_eq!(
+            (compressed.clone(), flags.clone()),
+            PxHandler::compress(&data).unwrap()
+        );
+        let flags: [u8; 9] = flags.try_into().unwrap();
+        assert_eq!(decompress(&flags, &compressed, data.len()).unwrap(), data);
+
+        assert_eq!(
+            PxHandler::compress_checked(&[]).unwrap().0,
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn higher_levels_compress_smaller_and_level0_is_all_literals() {
+        let data = sample_data();
+        assert_eq!(data.len() % 8, 0);
+
+        let levels = [
+            PXCompLevel::Level0,
+            PXCompLevel::Level1,
+            PXCompLevel::Level2,
+            PXCompLevel::Level3,
+        ];
+        let mut sizes = Vec::new();
+        for level in levels {
+            let (compressed, flags) = PxHandler::compress_with(&data, level, true).unwrap();
+            let flags: [u8; 9] = flags.try_into().unwrap();
+            assert_eq!(
+                decompress(&flags, &compressed, data.len()).unwrap(),
+                data,
+                "{:?}",
+                level
+            );
+            sizes.push(compressed.len());
+
+            if level == PXCompLevel::Level0 {
+                // One command byte per 8 literals, every bit set
+                assert_eq!(compressed.len(), data.len() / 8 * 9);
+                assert!(compressed.iter().step_by(9).all(|&command| command == 0xFF));
+            }
+        }
+
+        assert!(
+            sizes.windows(2).all(|pair| pair[1] <= pair[0]),
+            "{:?}",
+            sizes
+        );
+        assert!(sizes[3] < sizes[1], "{:?}", sizes);
+        assert_eq!(
+            PxHandler::compress(&data),
+            PxHandler::compress_with(&data, levels[3], true)
+        );
+    }
+}
@@ -2,10 +2,22 @@ pub mod binpack;
 pub mod compression;
 pub mod sir0;
 
-use std::io;
+use std::io::{self, Write};
+
+use compression::{at4px::At4pxContainer, pkdpx::PkdpxContainer};
 
 pub trait CompressionContainer {
     fn decompress(&self) -> Result<Vec<u8>, String>;
+
+    /// Writes the decompressed data into `out` and returns its length. Containers that can
+    /// stream override this, the default decompresses into memory first.
+    #[allow(dead_code)]
+    fn decompress_to(&self, out: &mut dyn Write) -> Result<usize, String> {
+        let data = self.decompress()?;
+        out.write_all(&data)
+            .map_err(|e| format!("Failed to write decompressed data: {}", e))?;
+        Ok(data.len())
+    }
 }
 
 pub trait ContainerHandler {
@@ -15,3 +27,67 @@ pub trait ContainerHandler {
     }
     fn deserialise(data: &[u8]) -> io::Result<Box<dyn CompressionContainer>>;
 }
+
+/// Container format identified from a file's leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+    Pkdpx,
+    At4px,
+    Sir0,
+    BinPack,
+    Raw,
+}
+
+/// Identifies the container wrapping `data`.
+///
+/// Magics are matched in full, so other `AT*` formats (e.g. `AT3PX`) are `Raw` rather than
+/// being mistaken for AT4PX. BinPack has no magic, it is recognised by its zero first word and
+/// a TOC that fits inside the data.
+pub fn detect(data: &[u8]) -> ContainerKind {
+    if PkdpxContainer::matches(data) {
+        ContainerKind::Pkdpx
+    } else if At4pxContainer::matches(data) {
+        ContainerKind::At4px
+    } else if data.starts_with(b"SIR0") {
+        ContainerKind::Sir0
+    } else if looks_like_binpack(data) {
+        ContainerKind::BinPack
+    } else {
+        ContainerKind::Raw
+    }
+}
+
+fn looks_like_binpack(data: &[u8]) -> bool {
+    if data.len() < 8 || data[0..4] != [0, 0, 0, 0] {
+        return false;
+    }
+    let num_files = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    num_files > 0
+        && num_files
+            .checked_mul(8)
+            .and_then(|toc| toc.checked_add(8))
+            .is_some_and(|toc_end| toc_end <= data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_matches_full_magics_only() {
+        assert_eq!(detect(b"PKDPX\x10\x00"), ContainerKind::Pkdpx);
+        assert_eq!(detect(b"AT4PX\x10\x00"), ContainerKind::At4px);
+        assert_eq!(detect(b"SIR0\x10\x00\x00\x00"), ContainerKind::Sir0);
+        // Other AT formats share the prefix but aren't AT4PX
+        assert_eq!(detect(b"AT3PX\x10\x00"), ContainerKind::Raw);
+        assert_eq!(detect(b"AT"), ContainerKind::Raw);
+
+        // Zero word, one file, TOC of 8 bytes that fits
+        let mut binpack = vec![0, 0, 0, 0, 1, 0, 0, 0];
+        binpack.extend([0; 8]);
+        assert_eq!(detect(&binpack), ContainerKind::BinPack);
+        // Same header claiming more files than the data holds
+        binpack[4] = 100;
+        assert_eq!(detect(&binpack), ContainerKind::Raw);
+    }
+}
@@ -105,6 +105,83 @@ impl Sir0 {
             data_pointer: adjusted_data_pointer,
         })
     }
+
+    /// Builds a SIR0 file around `content`, the inverse of `from_bytes`.
+    ///
+    /// `pointers` are the content-relative offsets of every pointer inside `content`, and
+    /// `data_pointer` is content-relative too, as `from_bytes` returns them. The header and
+    /// every listed pointer are rebased past the 16-byte header. The pointer offset list
+    /// follows the content directly, since `from_bytes` reads everything before it as content,
+    /// and the file is then padded with 0xAA to a 16-byte boundary. Content of any length
+    /// round-trips exactly.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn serialise(content: &[u8], pointers: &[u32], data_pointer: u32) -> Vec<u8> {
+        let header_len = HEADER_LEN as u32;
+        let mut out = Vec::with_capacity(HEADER_LEN + content.len() + pointers.len() + 32);
+        out.extend_from_slice(b"SIR0");
+        out.extend_from_slice(&(data_pointer + header_len).to_le_bytes());
+        // Pointer offset list position, patched once the content is written
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        out.extend_from_slice(content);
+        for &offset in pointers {
+            let at = HEADER_LEN + offset as usize;
+            if let Some(bytes) = out.get_mut(at..at + 4) {
+                let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                bytes.copy_from_slice(&(value + header_len).to_le_bytes());
+            }
+        }
+
+        let pointer_offset_list_pointer = out.len() as u32;
+        out[8..12].copy_from_slice(&pointer_offset_list_pointer.to_le_bytes());
+
+        // The two header pointers come first, then the content pointers in file order
+        let mut offsets: Vec<u32> = pointers.iter().map(|&p| p + header_len).collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+        let mut all_offsets = vec![4, 8];
+        all_offsets.extend(offsets);
+        out.extend(encode_sir0_pointer_offsets(&all_offsets));
+        pad_to_16(&mut out);
+
+        out
+    }
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+fn pad_to_16(out: &mut Vec<u8>) {
+    let padding = (16 - out.len() % 16) % 16;
+    out.resize(out.len() + padding, 0xAA);
+}
+
+/// Encode ascending absolute pointer offsets into the SIR0 list, the inverse of
+/// `decode_sir0_pointer_offsets`. Each entry is the distance from the previous offset, written
+/// big-endian in 7-bit groups with 0x80 marking every byte but the last. Ends with a zero, the
+/// caller pads the file after it.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn encode_sir0_pointer_offsets(offsets: &[u32]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(offsets.len() + 1);
+    let mut previous = 0u32;
+
+    for &offset in offsets {
+        let delta = offset - previous;
+        previous = offset;
+
+        // Emit the highest non-zero 7-bit group first
+        let mut shift = 28;
+        while shift > 0 && (delta >> shift) & 0x7F == 0 {
+            shift -= 7;
+        }
+        while shift > 0 {
+            encoded.push(0x80 | ((delta >> shift) & 0x7F) as u8);
+            shift -= 7;
+        }
+        encoded.push((delta & 0x7F) as u8);
+    }
+
+    encoded.push(0);
+    encoded
 }
 
 /// Decode SIR0 pointer offsets from the encoded format
@@ -146,3 +223,35 @@ pub fn decode_sir0_pointer_offsets(data: &[u8], pointer_offset_list_pointer: u32
     }
     decoded
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialise_round_trips_unaligned_content() {
+        for len in [32, 37, 45] {
+            // Two pointers into the content, one before and one after the data pointer
+            let mut content: Vec<u8> = (0..len as u8).collect();
+            content[4..8].copy_from_slice(&20u32.to_le_bytes());
+            content[28..32].copy_from_slice(&8u32.to_le_bytes());
+            let pointers = [4, 28];
+
+            let file = Sir0::serialise(&content, &pointers, 12);
+            assert_eq!(file.len() % 16, 0);
+
+            let sir0 = Sir0::from_bytes(&file).unwrap();
+            assert_eq!(sir0.content, content, "{} bytes", len);
+            assert_eq!(sir0.data_pointer, 12);
+            assert_eq!(sir0._content_pointer_offsets, pointers);
+        }
+    }
+
+    #[test]
+    fn pointer_offsets_decode_to_what_was_encoded() {
+        let offsets = [4, 8, 0x7F + 8, 0x4000, 0x20_0000, 0x0FFF_FFFF];
+        let encoded = encode_sir0_pointer_offsets(&offsets);
+        assert_eq!(encoded.last(), Some(&0));
+        assert_eq!(decode_sir0_pointer_offsets(&encoded, 0), offsets);
+    }
+}
@@ -1,4 +1,7 @@
-use std::{fmt, io::Cursor};
+use std::{
+    fmt,
+    io::{self, Cursor},
+};
 
 use crate::binary_utils::{self};
 
@@ -12,7 +15,7 @@ pub const SPECIAL_MOVE_DATA_SIZE: usize = 6;
 pub const HEADER_SIZE: usize = 20; // 5 * 4 bytes
 
 // Sound effect constants
-pub const _SFX_SILENCE: u16 = 0x3F00; // 16128 decimal - indicates no sound
+pub const SFX_SILENCE: u16 = 0x3F00; // 16128 decimal - indicates no sound
 
 // Monster animation type special values
 pub const _MONSTER_ANIM_SPIN: u8 = 99; // Rotate through all 8 directions
@@ -40,6 +43,17 @@ impl From<u8> for AnimPointType {
     }
 }
 
+impl AnimPointType {
+    /// Decodes a move's signed `attachment_point_idx`: 0-3 name a `FrameOffset` point, -1 (or
+    /// anything else) means the effect plays at the entity origin
+    pub fn from_attachment_index(index: i8) -> Self {
+        match u8::try_from(index) {
+            Ok(index) => Self::from(index),
+            Err(_) => Self::None,
+        }
+    }
+}
+
 impl fmt::Display for AnimPointType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -78,6 +92,17 @@ impl From<u32> for AnimType {
     }
 }
 
+impl AnimType {
+    /// Whether the effect pipeline renders this type into a sheet (WAN sprites and screen
+    /// effects), the rest are skipped
+    pub fn is_extracted(self) -> bool {
+        matches!(
+            self,
+            Self::WanFile0 | Self::WanFile1 | Self::WanOther | Self::Screen
+        )
+    }
+}
+
 impl fmt::Display for AnimType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -221,6 +246,38 @@ pub struct EffectAnimationInfo {
     pub loop_flag: bool,
 }
 
+impl EffectAnimationInfo {
+    /// Index of the effect.bin file this effect renders from, `None` for types that don't
+    /// read one. Types 1/2 always use files 0/1, screen effects are stored from 0x10C on.
+    pub fn effect_bin_index(&self) -> Option<usize> {
+        match self.anim_type {
+            AnimType::WanFile0 => Some(0),
+            AnimType::WanFile1 => Some(1),
+            AnimType::WanOther => Some(self.file_index as usize),
+            AnimType::Screen => Some(self.file_index as usize + 0x10C),
+            AnimType::Invalid | AnimType::Wat | AnimType::Wba => None,
+        }
+    }
+}
+
+/// Returns `(effect_id, effect_bin_index)` for every effect whose file lies past the end of an
+/// effect.bin with `effect_bin_len` files, sorted by effect ID. These can never render.
+pub fn effects_out_of_range<'a>(
+    effects: impl IntoIterator<Item = (u16, &'a EffectAnimationInfo)>,
+    effect_bin_len: usize,
+) -> Vec<(u16, usize)> {
+    let mut out_of_range: Vec<_> = effects
+        .into_iter()
+        .filter_map(|(effect_id, info)| {
+            info.effect_bin_index()
+                .filter(|&index| index >= effect_bin_len)
+                .map(|index| (effect_id, index))
+        })
+        .collect();
+    out_of_range.sort_unstable();
+    out_of_range
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpecialMoveAnimationInfo {
     pub pokemon_id: u16,
@@ -267,6 +324,7 @@ impl AnimData {
 /// Region-specific data for animation tables
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegionData {
+    /// File offset into overlay 10's data (not a RAM address) where the animation tables start
     pub start_table: u32,
     pub check_addr: u32,
     pub move_animation_table_overlay: u8,
@@ -274,6 +332,7 @@ pub struct RegionData {
     pub effect_animation_table_overlay: u8,
     pub effect_animation_table_offset: u32,
     pub effect_animation_entry_size: u32,
+    /// RAM address of the overlay 10 TILESET_PROPERTIES table, 0 when unknown
     pub tileset_properties_addr: u32,
 }
 
@@ -322,44 +381,94 @@ pub fn get_region_data(game_code: &str) -> Option<RegionData> {
     }
 }
 
+/// Why the animation data tables couldn't be parsed
+#[derive(Debug)]
+pub enum AnimDataError {
+    /// The blob is smaller than the table header
+    TooShort {
+        got: usize,
+        need: usize,
+    },
+    /// A header pointer to `table` points past the end of the blob
+    PointerOutOfBounds {
+        table: &'static str,
+        ptr: u32,
+    },
+    Io(io::Error),
+}
+
+impl From<io::Error> for AnimDataError {
+    fn from(err: io::Error) -> Self {
+        AnimDataError::Io(err)
+    }
+}
+
+impl fmt::Display for AnimDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimDataError::TooShort { got, need } => {
+                write!(f, "Data too short: {} bytes (need {})", got, need)
+            }
+            AnimDataError::PointerOutOfBounds { table, ptr } => {
+                write!(f, "{} table pointer 0x{:X} is out of bounds", table, ptr)
+            }
+            AnimDataError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
 /// Parse animation data from binary blob
-pub fn parse_animation_data(data: &[u8]) -> Result<AnimData, String> {
+pub fn parse_animation_data(data: &[u8]) -> Result<AnimData, AnimDataError> {
     if data.len() < HEADER_SIZE {
-        return Err(format!("Data too short: {} bytes", data.len()));
+        return Err(AnimDataError::TooShort {
+            got: data.len(),
+            need: HEADER_SIZE,
+        });
     }
 
     let mut cursor = Cursor::new(data);
 
-    binary_utils::seek_to(&mut cursor, 0).map_err(|e| e.to_string())?;
-    let trap_table_ptr = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-    let item_table_ptr = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-    let move_table_ptr = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-    let general_table_ptr = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-    let special_move_table_ptr =
-        binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
+    binary_utils::seek_to(&mut cursor, 0)?;
+    let trap_table_ptr = binary_utils::read_u32_le(&mut cursor)?;
+    let item_table_ptr = binary_utils::read_u32_le(&mut cursor)?;
+    let move_table_ptr = binary_utils::read_u32_le(&mut cursor)?;
+    let general_table_ptr = binary_utils::read_u32_le(&mut cursor)?;
+    let special_move_table_ptr = binary_utils::read_u32_le(&mut cursor)?;
+
+    for (table, ptr) in [
+        ("Trap", trap_table_ptr),
+        ("Item", item_table_ptr),
+        ("Move", move_table_ptr),
+        ("General", general_table_ptr),
+        ("Special move", special_move_table_ptr),
+    ] {
+        if ptr as usize > data.len() {
+            return Err(AnimDataError::PointerOutOfBounds { table, ptr });
+        }
+    }
 
     let mut trap_table = Vec::new();
     for offset in (trap_table_ptr as usize..item_table_ptr as usize).step_by(TRAP_DATA_SIZE) {
-        binary_utils::seek_to(&mut cursor, offset as u64).map_err(|e| e.to_string())?;
+        binary_utils::seek_to(&mut cursor, offset as u64)?;
 
         if offset + TRAP_DATA_SIZE > data.len() {
             break;
         }
 
-        let effect_id = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
+        let effect_id = binary_utils::read_u16_le(&mut cursor)?;
         trap_table.push(TrapAnimationInfo { effect_id });
     }
 
     let mut item_table = Vec::new();
     for offset in (item_table_ptr as usize..move_table_ptr as usize).step_by(ITEM_DATA_SIZE) {
-        binary_utils::seek_to(&mut cursor, offset as u64).map_err(|e| e.to_string())?;
+        binary_utils::seek_to(&mut cursor, offset as u64)?;
 
         if offset + ITEM_DATA_SIZE > data.len() {
             break;
         }
 
-        let anim1 = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
-        let anim2 = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
+        let anim1 = binary_utils::read_u16_le(&mut cursor)?;
+        let anim2 = binary_utils::read_u16_le(&mut cursor)?;
 
         item_table.push(ItemAnimationInfo {
             effect_id_1: anim1,
@@ -369,20 +478,20 @@ pub fn parse_animation_data(data: &[u8]) -> Result<AnimData, String> {
 
     let mut raw_move_table = Vec::new();
     for offset in (move_table_ptr as usize..general_table_ptr as usize).step_by(MOVE_DATA_SIZE) {
-        binary_utils::seek_to(&mut cursor, offset as u64).map_err(|e| e.to_string())?;
+        binary_utils::seek_to(&mut cursor, offset as u64)?;
 
         if offset + MOVE_DATA_SIZE > data.len() {
             break;
         }
 
         // Read effect IDs (4 layers)
-        let effect_id_1 = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
-        let effect_id_2 = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
-        let effect_id_3 = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
-        let effect_id_4 = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
+        let effect_id_1 = binary_utils::read_u16_le(&mut cursor)?;
+        let effect_id_2 = binary_utils::read_u16_le(&mut cursor)?;
+        let effect_id_3 = binary_utils::read_u16_le(&mut cursor)?;
+        let effect_id_4 = binary_utils::read_u16_le(&mut cursor)?;
 
         // Read and parse flags byte
-        let flags = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
+        let flags = binary_utils::read_u32_le(&mut cursor)?;
         let projectile_wave_pattern = (flags & 0x7) as u8;
         let dual_target = (flags & 0x8) != 0;
         let skip_fade_in = (flags & 0x10) != 0;
@@ -391,14 +500,12 @@ pub fn parse_animation_data(data: &[u8]) -> Result<AnimData, String> {
         let flag_bit7 = (flags & 0x80) != 0;
 
         // Read animation parameters
-        let projectile_speed = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-        let monster_anim_type = binary_utils::read_u8(&mut cursor).map_err(|e| e.to_string())?;
-        let position_offset_idx = binary_utils::read_i8(&mut cursor).map_err(|e| e.to_string())?;
-        let sound_effect_id = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
-        let special_animation_count =
-            binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
-        let special_animation_start_index =
-            binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
+        let projectile_speed = binary_utils::read_u32_le(&mut cursor)?;
+        let monster_anim_type = binary_utils::read_u8(&mut cursor)?;
+        let position_offset_idx = binary_utils::read_i8(&mut cursor)?;
+        let sound_effect_id = binary_utils::read_u16_le(&mut cursor)?;
+        let special_animation_count = binary_utils::read_u16_le(&mut cursor)?;
+        let special_animation_start_index = binary_utils::read_u16_le(&mut cursor)?;
 
         raw_move_table.push(RawMoveAnimationInfo {
             effect_id_1,
@@ -424,26 +531,26 @@ pub fn parse_animation_data(data: &[u8]) -> Result<AnimData, String> {
     for offset in
         (general_table_ptr as usize..special_move_table_ptr as usize).step_by(GENERAL_DATA_SIZE)
     {
-        binary_utils::seek_to(&mut cursor, offset as u64).map_err(|e| e.to_string())?;
+        binary_utils::seek_to(&mut cursor, offset as u64)?;
 
         if offset + GENERAL_DATA_SIZE > data.len() {
             break;
         }
 
-        let anim_type_value = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
+        let anim_type_value = binary_utils::read_u32_le(&mut cursor)?;
         let anim_type = AnimType::from(anim_type_value);
 
-        let anim_file = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-        let palette_index = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-        let animation_index = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-        let sfx = binary_utils::read_i32_le(&mut cursor).map_err(|e| e.to_string())?;
-        let timing_offset = binary_utils::read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
-        let screen_effect_param = binary_utils::read_u8(&mut cursor).map_err(|e| e.to_string())?;
+        let anim_file = binary_utils::read_u32_le(&mut cursor)?;
+        let palette_index = binary_utils::read_u32_le(&mut cursor)?;
+        let animation_index = binary_utils::read_u32_le(&mut cursor)?;
+        let sfx = binary_utils::read_i32_le(&mut cursor)?;
+        let timing_offset = binary_utils::read_u32_le(&mut cursor)?;
+        let screen_effect_param = binary_utils::read_u8(&mut cursor)?;
 
-        let point_value = binary_utils::read_i8(&mut cursor).map_err(|e| e.to_string())?;
+        let point_value = binary_utils::read_i8(&mut cursor)?;
 
-        let is_non_blocking = binary_utils::read_u8(&mut cursor).map_err(|e| e.to_string())? != 0;
-        let loop_flag = binary_utils::read_u8(&mut cursor).map_err(|e| e.to_string())? != 0;
+        let is_non_blocking = binary_utils::read_u8(&mut cursor)? != 0;
+        let loop_flag = binary_utils::read_u8(&mut cursor)? != 0;
 
         effect_table.push(EffectAnimationInfo {
             anim_type,
@@ -462,19 +569,19 @@ pub fn parse_animation_data(data: &[u8]) -> Result<AnimData, String> {
     let mut special_move_table = Vec::new();
     let data_len = data.len();
     for offset in (special_move_table_ptr as usize..data_len).step_by(SPECIAL_MOVE_DATA_SIZE) {
-        binary_utils::seek_to(&mut cursor, offset as u64).map_err(|e| e.to_string())?;
+        binary_utils::seek_to(&mut cursor, offset as u64)?;
 
         if offset + SPECIAL_MOVE_DATA_SIZE > data_len {
             break;
         }
 
-        let pkmn_id = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
-        let animation = binary_utils::read_u8(&mut cursor).map_err(|e| e.to_string())?;
+        let pkmn_id = binary_utils::read_u16_le(&mut cursor)?;
+        let animation = binary_utils::read_u8(&mut cursor)?;
 
-        let point_value = binary_utils::read_u8(&mut cursor).map_err(|e| e.to_string())?;
+        let point_value = binary_utils::read_u8(&mut cursor)?;
         let point = AnimPointType::from(point_value);
 
-        let sfx = binary_utils::read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
+        let sfx = binary_utils::read_u16_le(&mut cursor)?;
 
         special_move_table.push(SpecialMoveAnimationInfo {
             pokemon_id: pkmn_id,
@@ -492,3 +599,63 @@ pub fn parse_animation_data(data: &[u8]) -> Result<AnimData, String> {
         special_move_table,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(anim_type: AnimType, file_index: u32) -> EffectAnimationInfo {
+        EffectAnimationInfo {
+            anim_type,
+            file_index,
+            palette_index: 0,
+            animation_index: 0,
+            sfx_id: -1,
+            timing_offset: 0,
+            screen_effect_param: 0,
+            attachment_point: -1,
+            is_non_blocking: false,
+            loop_flag: false,
+        }
+    }
+
+    #[test]
+    fn effects_past_the_end_of_effect_bin_are_flagged() {
+        let effects = [
+            (0, effect(AnimType::WanOther, 10)),
+            (1, effect(AnimType::WanOther, 400)),
+            // Screen effects are stored from 0x10C, so 200 lands at 468
+            (2, effect(AnimType::Screen, 200)),
+            (3, effect(AnimType::Screen, 5)),
+            // Types that don't read effect.bin are never flagged
+            (4, effect(AnimType::Wat, 9999)),
+            (5, effect(AnimType::WanFile1, 9999)),
+        ];
+
+        let out_of_range = effects_out_of_range(effects.iter().map(|(id, info)| (*id, info)), 293);
+
+        assert_eq!(out_of_range, vec![(1, 400), (2, 468)]);
+    }
+
+    #[test]
+    fn anim_data_errors_say_which_check_failed() {
+        let err = parse_animation_data(&[0; 10]).unwrap_err();
+        assert!(matches!(err, AnimDataError::TooShort { got: 10, need: 20 }));
+        assert_eq!(err.to_string(), "Data too short: 10 bytes (need 20)");
+
+        let mut data = vec![0u8; 24];
+        data[12..16].copy_from_slice(&0x100u32.to_le_bytes());
+        let err = parse_animation_data(&data).unwrap_err();
+        assert!(matches!(
+            err,
+            AnimDataError::PointerOutOfBounds {
+                table: "General",
+                ptr: 0x100
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "General table pointer 0x100 is out of bounds"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use super::MonsterEntry;
+
+/// Folder suffixes for alternate forms, keyed by national dex number. The list is indexed by
+/// the form's position among the species' monster.md entries (the first entry is form 0),
+/// `None` keeps the plain `pokemon_NNN` folder. Forms past the end of a list are exported as
+/// `form_N`, so extending a species is a matter of adding names here.
+const FORM_NAMES: &[(u16, &[Option<&str>])] = &[
+    // Unown: A-Z, !, ?
+    (
+        201,
+        &[
+            Some("a"),
+            Some("b"),
+            Some("c"),
+            Some("d"),
+            Some("e"),
+            Some("f"),
+            Some("g"),
+            Some("h"),
+            Some("i"),
+            Some("j"),
+            Some("k"),
+            Some("l"),
+            Some("m"),
+            Some("n"),
+            Some("o"),
+            Some("p"),
+            Some("q"),
+            Some("r"),
+            Some("s"),
+            Some("t"),
+            Some("u"),
+            Some("v"),
+            Some("w"),
+            Some("x"),
+            Some("y"),
+            Some("z"),
+            Some("exclamation"),
+            Some("question"),
+        ],
+    ),
+    // Castform
+    (351, &[None, Some("snowy"), Some("sunny"), Some("rainy")]),
+    // Deoxys
+    (
+        386,
+        &[
+            Some("normal"),
+            Some("attack"),
+            Some("defense"),
+            Some("speed"),
+        ],
+    ),
+    // Burmy/Wormadam
+    (412, &[Some("sandy"), Some("plant"), Some("trash")]),
+    (413, &[Some("sandy"), Some("plant"), Some("trash")]),
+    // Cherrim
+    (421, &[Some("overcast"), Some("sunshine")]),
+    // Shellos/Gastrodon
+    (422, &[Some("west"), Some("east")]),
+    (423, &[Some("west"), Some("east")]),
+    // Rotom: its forms arrived in Platinum and were never added to Sky
+    (479, &[None]),
+    // Dialga
+    (483, &[None, Some("primal")]),
+    // Giratina
+    (487, &[Some("altered"), Some("origin")]),
+    // Shaymin
+    (492, &[Some("land"), Some("sky")]),
+];
+
+/// Name of form `form_index` of species `dex_num`, if the table has one
+pub fn form_name(dex_num: u16, form_index: u16) -> Option<&'static str> {
+    FORM_NAMES
+        .iter()
+        .find(|(dex, _)| *dex == dex_num)
+        .and_then(|(_, names)| names.get(form_index as usize).copied().flatten())
+}
+
+/// Number of forms per species among the primary monster.md entries (the first 600, the rest
+/// are gender variants)
+pub fn form_counts(entries: &[MonsterEntry]) -> HashMap<u16, u16> {
+    let mut counts = HashMap::new();
+    for entry in entries.iter().take(600) {
+        *counts.entry(entry.national_pokedex_number).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Species whose monster.md form count exceeds their named forms, as (dex number, forms,
+/// named). Their extra forms fall back to `form_N` folders.
+pub fn unnamed_forms(form_counts: &HashMap<u16, u16>) -> Vec<(u16, u16, u16)> {
+    let mut unnamed: Vec<_> = FORM_NAMES
+        .iter()
+        .filter_map(|&(dex, names)| {
+            let count = *form_counts.get(&dex)?;
+            (count as usize > names.len()).then_some((dex, count, names.len() as u16))
+        })
+        .collect();
+    unnamed.sort_unstable();
+    unnamed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::monster_md::MonsterData, rom_builder::monster_md};
+
+    #[test]
+    fn table_names_forms_and_counts_come_from_monster_md() {
+        assert_eq!(form_name(201, 26), Some("exclamation"));
+        assert_eq!(form_name(351, 1), Some("snowy"));
+        // The base form keeps the plain folder, forms past the table and other species fall
+        // back to `form_N`
+        assert_eq!(form_name(351, 0), None);
+        assert_eq!(form_name(351, 4), None);
+        assert_eq!(form_name(25, 0), None);
+
+        let md = MonsterData::parse(&monster_md(&[
+            (25, 0),
+            (351, 1),
+            (351, 2),
+            (351, 3),
+            (351, 4),
+            (351, 5),
+            (201, 6),
+        ]))
+        .unwrap();
+        let counts = form_counts(&md.entries);
+        assert_eq!(counts[&25], 1);
+        assert_eq!(counts[&351], 5);
+        // Castform has one more form than it has names, Unown fewer
+        assert_eq!(unnamed_forms(&counts), [(351, 5, 4)]);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod animation_info;
 pub mod animation_metadata;
+pub mod form_names;
 pub mod monster_md;
 pub mod tileset_properties;
 
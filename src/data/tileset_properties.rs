@@ -2,6 +2,8 @@ use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
+use crate::output::JsonStyle;
+
 /// 12-byte `tileset_property` struct in the overlay-10 TILESET_PROPERTIES table.
 pub const TILESET_PROPERTY_STRIDE: usize = 12;
 pub const TILESET_COUNT: usize = 170;
@@ -69,8 +71,13 @@ pub fn parse_tileset_properties(
     Ok(props)
 }
 
-pub fn save_json(props: &[TilesetProperty], path: &Path) -> std::io::Result<()> {
-    let json = serde_json::to_string_pretty(props)
+pub fn save_json(
+    props: &[TilesetProperty],
+    path: &Path,
+    json_style: JsonStyle,
+) -> std::io::Result<()> {
+    let json = json_style
+        .to_string(props)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
     std::fs::write(path, json)
 }
@@ -7,6 +7,11 @@
 //! - Output: Chunk ID (index into DPC)
 //!
 //! There are 3 variations for every neighbour config to add visual variety.
+//!
+//! Neighbour bits: S=1, SE=2, E=4, NE=8, N=16, NW=32, W=64, SW=128. A diagonal only shapes the
+//! tile when both orthogonals next to it are the same terrain, so of the 256 masks only 47 are
+//! distinct. Wall and floor are trusted for any mask, the secondary terrain only for the
+//! canonical ones, see `DmaType::Secondary`.
 
 use std::io;
 
@@ -14,11 +19,33 @@ use std::io;
 #[derive(Copy, Clone)]
 pub enum DmaType {
     Wall = 0,
-    /// Water, Lava, Chasm etc.
+    /// Water, Lava, Chasm etc. Only canonical masks (no diagonal without both adjacent
+    /// orthogonals) are valid here, other entries can point at unrelated chunks and are
+    /// looked up as empty by `Dma::get_checked`.
     Secondary = 1,
     Floor = 2,
 }
 
+const S: u8 = 1;
+const SE: u8 = 2;
+const E: u8 = 4;
+const NE: u8 = 8;
+const N: u8 = 16;
+const NW: u8 = 32;
+const W: u8 = 64;
+const SW: u8 = 128;
+
+/// Clears every diagonal bit whose two adjacent orthogonal bits aren't both set
+pub fn canonical_neighbours(neighbours: u8) -> u8 {
+    let mut canonical = neighbours & (N | S | E | W);
+    for (diagonal, a, b) in [(NE, N, E), (NW, N, W), (SE, S, E), (SW, S, W)] {
+        if neighbours & diagonal != 0 && neighbours & a != 0 && neighbours & b != 0 {
+            canonical |= diagonal;
+        }
+    }
+    canonical
+}
+
 pub struct Dma {
     pub chunk_mappings: Vec<u8>,
 }
@@ -45,4 +72,36 @@ impl Dma {
             self.chunk_mappings[base + 2],
         ]
     }
+
+    /// Like `get`, but `None` for a secondary terrain mask the game never looks up, so the
+    /// caller can leave the cell transparent instead of drawing a leftover chunk
+    pub fn get_checked(&self, tile_type: DmaType, neighbors: u8) -> Option<[u8; 3]> {
+        match tile_type {
+            DmaType::Secondary if canonical_neighbours(neighbors) != neighbors => None,
+            _ => Some(self.get(tile_type, neighbors)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_secondary_mask_looks_up_as_empty() {
+        // Every entry points at chunk 5, the leftover chunk a bad mask would draw
+        let dma = Dma::from_bytes(&[5; 0x930]).unwrap();
+        // NE without E isn't a shape the game ever asks for
+        let invalid = N | NE;
+        assert_eq!(canonical_neighbours(invalid), N);
+
+        assert_eq!(dma.get_checked(DmaType::Secondary, invalid), None);
+        assert_eq!(
+            dma.get_checked(DmaType::Secondary, N | NE | E),
+            Some([5; 3])
+        );
+        // Walls and floors trust any mask
+        assert_eq!(dma.get_checked(DmaType::Wall, invalid), Some([5; 3]));
+        assert_eq!(dma.get_checked(DmaType::Floor, invalid), Some([5; 3]));
+    }
 }
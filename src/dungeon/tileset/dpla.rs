@@ -16,7 +16,7 @@ pub struct DplaColourEntry {
 }
 
 pub struct Dpla {
-    pub colours: Vec<DplaColourEntry>, // always length 32
+    pub colours: Vec<DplaColourEntry>, // length 32 when parsed from the ROM
 }
 
 impl Dpla {
@@ -83,7 +83,21 @@ impl Dpla {
         if pal_idx != 10 && pal_idx != 11 {
             return false;
         }
-        let base = if pal_idx == 10 { 0 } else { 16 };
-        (base..base + 16).any(|i| self.colours[i].num_frames > 0)
+        self.entries_for_palette(pal_idx)
+            .iter()
+            .any(|c| c.num_frames > 0)
+    }
+
+    /// The colour entries animating palette 10 or 11. Clamped to what was actually
+    /// parsed, so a short DPLA yields fewer (or no) entries instead of panicking.
+    pub fn entries_for_palette(&self, pal_idx: usize) -> &[DplaColourEntry] {
+        let base = match pal_idx {
+            10 => 0,
+            11 => 16,
+            _ => return &[],
+        };
+        let start = base.min(self.colours.len());
+        let end = (base + 16).min(self.colours.len());
+        &self.colours[start..end]
     }
 }
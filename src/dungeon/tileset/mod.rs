@@ -4,7 +4,9 @@
 //!
 //! # Architecture
 //! The graphics data for a single dungeon environment is split across 5 separate
-//! file arrays in the ROM, offset by a fixed stride (approx 170 files per type).
+//! file arrays in the ROM, one group after another. Every group holds the same number of
+//! files, the stride, which `detect_dungeon_stride` reads off `dungeon.bin` (170 in the NA and
+//! EU releases).
 //!
 //! This module brings them together into a single `DungeonTileset` struct:
 //! - DMA: Autotiling rules.
@@ -23,7 +25,8 @@ pub mod render;
 use std::io;
 
 use crate::containers::{
-    binpack::BinPack, compression::at4px::At4pxContainer, sir0::Sir0, ContainerHandler,
+    self, binpack::BinPack, compression::at4px::At4pxContainer, sir0::Sir0, ContainerHandler,
+    ContainerKind,
 };
 
 pub struct DungeonTileset {
@@ -35,14 +38,60 @@ pub struct DungeonTileset {
     pub dpla: dpla::Dpla,
 }
 
-pub fn extract_tileset(binpack: &BinPack, tileset_id: usize) -> Result<DungeonTileset, io::Error> {
+/// Number of files in each tileset group of `dungeon.bin`.
+///
+/// The groups are DPLA (SIR0), DMA (SIR0), DPC (AT4PX), DPCI (AT4PX) and DPL (raw). DPC is the
+/// first group stored as a bare AT4PX, so the first such file sits at twice the stride. The
+/// start of the DMA group is checked to be SIR0 as well.
+pub fn detect_dungeon_stride(binpack: &BinPack) -> io::Result<usize> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let dpc_start = binpack
+        .iter()
+        .position(|file| containers::detect(file) == ContainerKind::At4px)
+        .ok_or_else(|| invalid("dungeon.bin has no AT4PX file to locate the DPC group".into()))?;
+
+    if dpc_start == 0 || dpc_start % 2 != 0 {
+        return Err(invalid(format!(
+            "First AT4PX file in dungeon.bin is at {}, not twice a group stride",
+            dpc_start
+        )));
+    }
+    let stride = dpc_start / 2;
+
+    if !binpack
+        .get(stride)
+        .is_some_and(|dma| containers::detect(dma) == ContainerKind::Sir0)
+    {
+        return Err(invalid(format!(
+            "dungeon.bin file {} should start the SIR0 DMA group",
+            stride
+        )));
+    }
+    if binpack.len() < stride * 5 {
+        return Err(invalid(format!(
+            "dungeon.bin has {} files, too few for five groups of {}",
+            binpack.len(),
+            stride
+        )));
+    }
+
+    Ok(stride)
+}
+
+/// Loads the five files of `tileset_id`, `stride` is the group size from `detect_dungeon_stride`
+pub fn extract_tileset(
+    binpack: &BinPack,
+    stride: usize,
+    tileset_id: usize,
+) -> Result<DungeonTileset, io::Error> {
     // DPLA: SIR0 → parse directly from content
     let dpla_raw = get_file(binpack, tileset_id)?;
     let dpla_sir0 = Sir0::from_bytes(dpla_raw)?;
     let dpla = dpla::Dpla::from_sir0_content(&dpla_sir0.content, dpla_sir0.data_pointer)?;
 
     // DMA: SIR0 → AT4PX → decompress
-    let dma_raw = get_file(binpack, tileset_id + 170)?;
+    let dma_raw = get_file(binpack, tileset_id + stride)?;
     let dma_sir0 = Sir0::from_bytes(dma_raw)?;
     let dma_at4px = At4pxContainer::deserialise(&dma_sir0.content)?;
     let dma_bytes = dma_at4px
@@ -51,7 +100,7 @@ pub fn extract_tileset(binpack: &BinPack, tileset_id: usize) -> Result<DungeonTi
     let dma = dma::Dma::from_bytes(&dma_bytes)?;
 
     // DPC: AT4PX → decompress
-    let dpc_raw = get_file(binpack, tileset_id + 340)?;
+    let dpc_raw = get_file(binpack, tileset_id + stride * 2)?;
     let dpc_at4px = At4pxContainer::deserialise(dpc_raw)?;
     let dpc_bytes = dpc_at4px
         .decompress()
@@ -59,7 +108,7 @@ pub fn extract_tileset(binpack: &BinPack, tileset_id: usize) -> Result<DungeonTi
     let dpc = dpc::Dpc::from_bytes(&dpc_bytes)?;
 
     // DPCI: AT4PX → decompress
-    let dpci_raw = get_file(binpack, tileset_id + 510)?;
+    let dpci_raw = get_file(binpack, tileset_id + stride * 3)?;
     let dpci_at4px = At4pxContainer::deserialise(dpci_raw)?;
     let dpci_bytes = dpci_at4px
         .decompress()
@@ -67,7 +116,7 @@ pub fn extract_tileset(binpack: &BinPack, tileset_id: usize) -> Result<DungeonTi
     let dpci = dpci::Dpci::from_bytes(&dpci_bytes)?;
 
     // DPL: raw bytes, no wrapping
-    let dpl_raw = get_file(binpack, tileset_id + 680)?;
+    let dpl_raw = get_file(binpack, tileset_id + stride * 4)?;
     let dpl = dpl::Dpl::from_bytes(dpl_raw)?;
 
     Ok(DungeonTileset {
@@ -88,3 +137,57 @@ fn get_file<'a>(binpack: &'a BinPack, index: usize) -> Result<&'a [u8], io::Erro
         )
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// dungeon.bin laid out as five groups of `stride` files followed by `extra` other files
+    fn fixture_dungeon_bin(stride: usize, extra: usize) -> BinPack {
+        let sir0 = Sir0::serialise(&[0; 16], &[], 0);
+        let at4px = At4pxContainer::compress(&[0; 64]).unwrap();
+        let mut binpack = BinPack::default();
+        for group in [&sir0, &sir0, &at4px, &at4px, &vec![0; 64]] {
+            for _ in 0..stride {
+                binpack.append(group.clone());
+            }
+        }
+        for _ in 0..extra {
+            binpack.append(sir0.clone());
+        }
+        binpack
+    }
+
+    #[test]
+    fn detects_the_na_and_eu_stride() {
+        // Both releases use groups of 170, followed by the shadow, ripple and weather files
+        assert_eq!(
+            detect_dungeon_stride(&fixture_dungeon_bin(170, 30)).unwrap(),
+            170
+        );
+    }
+
+    #[test]
+    fn detects_other_strides() {
+        assert_eq!(
+            detect_dungeon_stride(&fixture_dungeon_bin(3, 0)).unwrap(),
+            3
+        );
+        assert_eq!(
+            detect_dungeon_stride(&fixture_dungeon_bin(200, 4)).unwrap(),
+            200
+        );
+    }
+
+    #[test]
+    fn rejects_dungeon_bin_without_tileset_groups() {
+        assert!(detect_dungeon_stride(&BinPack::default()).is_err());
+
+        // Truncated after the DPCI group
+        let mut truncated = BinPack::default();
+        for file in fixture_dungeon_bin(4, 0).iter().take(16) {
+            truncated.append(file.to_vec());
+        }
+        assert!(detect_dungeon_stride(&truncated).is_err());
+    }
+}
@@ -7,8 +7,14 @@ use std::{collections::BTreeMap, fs, io, path::Path};
 use image::{Rgba, RgbaImage};
 use serde::Serialize;
 
-use super::{dma::DmaType, dpci::DPCI_TILE_DIM, dpla::DplaColourEntry, DungeonTileset};
-use crate::data::tileset_properties::TilesetProperty;
+use super::{
+    dma::DmaType,
+    dpci::DPCI_TILE_DIM,
+    dpl::{Rgb, DPL_COLOURS_PER_PAL, DPL_PAL_COUNT},
+    dpla::DplaColourEntry,
+    DungeonTileset,
+};
+use crate::{data::tileset_properties::TilesetProperty, output::JsonStyle};
 
 const N: u8 = 16;
 const S: u8 = 1;
@@ -136,15 +142,20 @@ struct LayoutTile {
     neighbour_bits: u8,
 }
 
+/// Writes the organised sheet and, for animated tilesets or when `dump_all_palettes` is set,
+/// a `.pal.png` holding the 12 base palettes followed by any animation frames. With
+/// `baked_frame` set, animated tilesets also get `_frame{N}.png` rendered at that frame.
 pub fn render_tileset(
     tileset: &DungeonTileset,
     output_dir: &Path,
     property: Option<&TilesetProperty>,
+    dump_all_palettes: bool,
+    baked_frame: Option<usize>,
 ) -> Result<TilesetMetadata, io::Error> {
     let dungeon_name = crate::dungeon::dungeon_names::tileset_name(tileset.tileset_id);
     let name = format!("{:03}_{}", tileset.tileset_id, dungeon_name);
 
-    let sheet = render_organised_sheet(tileset);
+    let sheet = render_organised_sheet(tileset, &tileset.dpl.palettes);
     sheet
         .save(output_dir.join(format!("{}.png", name)))
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
@@ -152,7 +163,13 @@ pub fn render_tileset(
     let (pal10_frames, pal11_frames) = animation_frame_counts(tileset);
     let animated = pal10_frames > 0 || pal11_frames > 0;
 
-    if animated {
+    if let Some(frame) = baked_frame.filter(|_| animated) {
+        render_tileset_at_frame(tileset, frame)
+            .save(output_dir.join(format!("{}_frame{}.png", name, frame)))
+            .map_err(io::Error::other)?;
+    }
+
+    if animated || dump_all_palettes {
         let pal_tex = create_palette_texture(tileset, pal10_frames, pal11_frames);
         pal_tex
             .save(output_dir.join(format!("{}.pal.png", name)))
@@ -176,14 +193,8 @@ pub fn render_tileset(
         animated,
         palette_10_frames: pal10_frames,
         palette_11_frames: pal11_frames,
-        durations_palette_10: tileset.dpla.colours[0..16]
-            .iter()
-            .map(|c| c.duration)
-            .collect(),
-        durations_palette_11: tileset.dpla.colours[16..32]
-            .iter()
-            .map(|c| c.duration)
-            .collect(),
+        durations_palette_10: palette_durations(tileset, 10),
+        durations_palette_11: palette_durations(tileset, 11),
         map_color,
         weather_effect,
         is_water_tileset,
@@ -191,7 +202,7 @@ pub fn render_tileset(
     })
 }
 
-pub fn write_layout_json(output_dir: &Path) -> Result<(), io::Error> {
+pub fn write_layout_json(output_dir: &Path, json_style: JsonStyle) -> Result<(), io::Error> {
     let mut neighbour_bits = BTreeMap::new();
     for (name, val) in [
         ("N", N),
@@ -236,7 +247,8 @@ pub fn write_layout_json(output_dir: &Path) -> Result<(), io::Error> {
         tiles,
     };
 
-    let json = serde_json::to_string_pretty(&layout)
+    let json = json_style
+        .to_string(&layout)
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
     fs::write(output_dir.join("layout.json"), json)?;
     Ok(())
@@ -245,14 +257,34 @@ pub fn write_layout_json(output_dir: &Path) -> Result<(), io::Error> {
 pub fn write_tilesets_json(
     metadata: &[TilesetMetadata],
     output_dir: &Path,
+    json_style: JsonStyle,
 ) -> Result<(), io::Error> {
-    let json = serde_json::to_string_pretty(metadata)
+    let json = json_style
+        .to_string(metadata)
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
     fs::write(output_dir.join("tilesets.json"), json)?;
     Ok(())
 }
 
-fn render_organised_sheet(tileset: &DungeonTileset) -> RgbaImage {
+/// Renders the organised sheet with palettes 10 and 11 swapped for their DPLA colours at
+/// animation `frame`. Each colour loops on its own cycle, frame 0 is the first DPLA colour
+/// rather than the static DPL one.
+pub fn render_tileset_at_frame(tileset: &DungeonTileset, frame: usize) -> RgbaImage {
+    let mut palettes = tileset.dpl.palettes;
+    for pal_idx in [10, 11] {
+        for (ci, entry) in tileset.dpla.entries_for_palette(pal_idx).iter().enumerate() {
+            if !entry.frames.is_empty() && ci < DPL_COLOURS_PER_PAL {
+                palettes[pal_idx][ci] = entry.frames[frame % entry.frames.len()];
+            }
+        }
+    }
+    render_organised_sheet(tileset, &palettes)
+}
+
+fn render_organised_sheet(
+    tileset: &DungeonTileset,
+    palettes: &[[Rgb; DPL_COLOURS_PER_PAL]; DPL_PAL_COUNT],
+) -> RgbaImage {
     let mut img = RgbaImage::new(FRAME_WIDTH as u32, FRAME_HEIGHT as u32);
 
     // Iterate through TileTypes
@@ -274,13 +306,18 @@ fn render_organised_sheet(tileset: &DungeonTileset) -> RgbaImage {
                 let col = i % COLS_PER_TILE_TYPE;
                 let row = i / COLS_PER_TILE_TYPE;
 
-                // Get the specific variant ID
-                let chunk_mappings = tileset.dma.get(*tile_type, *neighbour_bits as u8);
+                // Get the specific variant ID, invalid secondary masks stay transparent
+                let Some(chunk_mappings) =
+                    tileset.dma.get_checked(*tile_type, *neighbour_bits as u8)
+                else {
+                    continue;
+                };
                 let chunk_id = chunk_mappings[v_idx] as usize;
 
                 render_chunk_at(
                     &mut img,
                     tileset,
+                    palettes,
                     chunk_id,
                     tile_type_base_x + variant_offset_x + (col * CHUNK_PX),
                     row * CHUNK_PX,
@@ -295,6 +332,7 @@ fn render_organised_sheet(tileset: &DungeonTileset) -> RgbaImage {
 fn render_chunk_at(
     img: &mut RgbaImage,
     tileset: &DungeonTileset,
+    palettes: &[[Rgb; DPL_COLOURS_PER_PAL]; DPL_PAL_COUNT],
     chunk_id: usize,
     bx: usize,
     by: usize,
@@ -314,10 +352,10 @@ fn render_chunk_at(
         }
 
         let pixels = tileset.dpci.decode_tile(ti);
-        let pal = if (mapping.palette_idx as usize) < 12 {
-            &tileset.dpl.palettes[mapping.palette_idx as usize]
+        let pal = if (mapping.palette_idx as usize) < DPL_PAL_COUNT {
+            &palettes[mapping.palette_idx as usize]
         } else {
-            &tileset.dpl.palettes[0]
+            &palettes[0]
         };
 
         for py in 0..DPCI_TILE_DIM {
@@ -360,13 +398,13 @@ fn create_palette_texture(
 
     write_animation_rows(
         &mut img,
-        &tileset.dpla.colours[0..16],
+        tileset.dpla.entries_for_palette(10),
         base_rows,
         pal10_frames,
     );
     write_animation_rows(
         &mut img,
-        &tileset.dpla.colours[16..32],
+        tileset.dpla.entries_for_palette(11),
         base_rows + pal10_frames,
         pal11_frames,
     );
@@ -398,9 +436,21 @@ fn write_animation_rows(
     }
 }
 
+/// Per-colour durations for palette 10/11, padded with 0 to 16 entries for a short DPLA.
+fn palette_durations(tileset: &DungeonTileset, pal_idx: usize) -> Vec<u16> {
+    let mut durations: Vec<u16> = tileset
+        .dpla
+        .entries_for_palette(pal_idx)
+        .iter()
+        .map(|c| c.duration)
+        .collect();
+    durations.resize(16, 0);
+    durations
+}
+
 fn animation_frame_counts(tileset: &DungeonTileset) -> (usize, usize) {
-    let pal10 = detect_real_animation(&tileset.dpla.colours[0..16]);
-    let pal11 = detect_real_animation(&tileset.dpla.colours[16..32]);
+    let pal10 = detect_real_animation(tileset.dpla.entries_for_palette(10));
+    let pal11 = detect_real_animation(tileset.dpla.entries_for_palette(11));
     (pal10, pal11)
 }
 
@@ -420,3 +470,112 @@ fn detect_real_animation(entries: &[DplaColourEntry]) -> usize {
     }
     0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dungeon::tileset::{
+            dma::Dma,
+            dpc::{Dpc, TileMapping, DPC_TILES_PER_CHUNK},
+            dpci::Dpci,
+            dpl::Dpl,
+            dpla::Dpla,
+        },
+        test_support::scratch_dir,
+    };
+
+    fn fixture_tileset(dpla: Dpla) -> DungeonTileset {
+        DungeonTileset {
+            tileset_id: 0,
+            dma: Dma {
+                chunk_mappings: vec![0; 0x930],
+            },
+            dpc: Dpc { chunks: Vec::new() },
+            dpci: Dpci { tiles: Vec::new() },
+            dpl: Dpl {
+                palettes: [[Rgb::default(); DPL_COLOURS_PER_PAL]; DPL_PAL_COUNT],
+            },
+            dpla,
+        }
+    }
+
+    #[test]
+    fn static_tileset_gets_a_palette_texture_only_when_dumping_all() {
+        let tileset = fixture_tileset(Dpla {
+            colours: Vec::new(),
+        });
+        let name = format!("000_{}", crate::dungeon::dungeon_names::tileset_name(0));
+
+        let dir = scratch_dir("tileset_static_palette");
+        let metadata = render_tileset(&tileset, &dir, None, false, None).unwrap();
+        assert!(!metadata.animated);
+        assert!(!dir.join(format!("{}.pal.png", name)).exists());
+
+        render_tileset(&tileset, &dir, None, true, None).unwrap();
+        let pal = image::open(dir.join(format!("{}.pal.png", name))).unwrap();
+        assert_eq!((pal.width(), pal.height()), (16, 12));
+    }
+
+    #[test]
+    fn short_dpla_pads_durations_instead_of_panicking() {
+        let entry = |duration, frames: Vec<Rgb>| DplaColourEntry {
+            num_frames: frames.len() as u16,
+            duration,
+            frames,
+        };
+        let red = Rgb { r: 255, g: 0, b: 0 };
+        let blue = Rgb { r: 0, g: 0, b: 255 };
+        let tileset = fixture_tileset(Dpla {
+            colours: vec![entry(4, vec![red, blue]), entry(6, Vec::new())],
+        });
+
+        let dir = scratch_dir("tileset_short_dpla");
+        let metadata = render_tileset(&tileset, &dir, None, false, None).unwrap();
+
+        assert!(metadata.animated);
+        assert_eq!(metadata.palette_10_frames, 2);
+        assert_eq!(metadata.palette_11_frames, 0);
+        assert_eq!(metadata.durations_palette_10.len(), 16);
+        assert_eq!(&metadata.durations_palette_10[..3], &[4, 6, 0]);
+        assert_eq!(metadata.durations_palette_11, vec![0; 16]);
+    }
+
+    #[test]
+    fn animated_pixels_change_between_frames() {
+        let red = Rgb { r: 255, g: 0, b: 0 };
+        let blue = Rgb { r: 0, g: 0, b: 255 };
+        let still = DplaColourEntry {
+            num_frames: 0,
+            duration: 0,
+            frames: Vec::new(),
+        };
+        let cycling = DplaColourEntry {
+            num_frames: 2,
+            duration: 4,
+            frames: vec![red, blue],
+        };
+        let mut tileset = fixture_tileset(Dpla {
+            colours: vec![still, cycling],
+        });
+        // Every chunk is one tile of colour 1 drawn with the animated palette 10
+        let mapping = TileMapping {
+            palette_idx: 10,
+            ..Default::default()
+        };
+        tileset.dpc.chunks = vec![[mapping; DPC_TILES_PER_CHUNK]];
+        tileset.dpci.tiles = vec![[0x11; 32]];
+
+        let opaque = |img: &RgbaImage| -> Vec<[u8; 4]> {
+            img.pixels().filter(|p| p.0[3] > 0).map(|p| p.0).collect()
+        };
+        let first = opaque(&render_tileset_at_frame(&tileset, 0));
+        let second = opaque(&render_tileset_at_frame(&tileset, 1));
+
+        assert!(!first.is_empty());
+        assert!(first.iter().all(|p| *p == [255, 0, 0, 255]));
+        assert!(second.iter().all(|p| *p == [0, 0, 255, 255]));
+        // The cycle loops back round
+        assert_eq!(opaque(&render_tileset_at_frame(&tileset, 2)), first);
+    }
+}
@@ -7,18 +7,49 @@ use crate::{
         self,
         tileset::{self, render},
     },
+    output::JsonStyle,
     progress::write_progress,
+    report::{ItemOutcome, StageReport},
     rom::Rom,
 };
-const MAX_TILESET_ID: usize = 170;
+
+/// The last entries of each `dungeon.bin` group are full-screen backgrounds rather than
+/// tilesets (144-169 in the NA and EU releases)
+const BACKGROUND_ENTRIES: usize = 26;
 
 pub struct DungeonBinExtractor<'a> {
     rom: &'a Rom,
+    dump_all_palettes: bool,
+    baked_frame: Option<usize>,
+    json_style: JsonStyle,
 }
 
 impl<'a> DungeonBinExtractor<'a> {
     pub fn new(rom: &'a Rom) -> Self {
-        DungeonBinExtractor { rom }
+        DungeonBinExtractor {
+            rom,
+            dump_all_palettes: false,
+            baked_frame: None,
+            json_style: JsonStyle::default(),
+        }
+    }
+
+    /// Writes a `.pal.png` for every tileset, not only the animated ones
+    pub fn with_palette_dump_all(mut self, dump_all_palettes: bool) -> Self {
+        self.dump_all_palettes = dump_all_palettes;
+        self
+    }
+
+    /// Also renders animated tilesets with their palette animation at `frame` baked in
+    pub fn with_baked_frame(mut self, frame: Option<usize>) -> Self {
+        self.baked_frame = frame;
+        self
+    }
+
+    /// Formatting for `layout.json` and `tilesets.json`
+    pub fn with_json_style(mut self, json_style: JsonStyle) -> Self {
+        self.json_style = json_style;
+        self
     }
 
     pub fn extract_dungeon_tilesets(
@@ -27,53 +58,48 @@ impl<'a> DungeonBinExtractor<'a> {
         output_dir: &Path,
         progress_path: &Path,
         properties: Option<&[TilesetProperty]>,
-    ) -> io::Result<()> {
-        let dungeon_bin_id = self
-            .rom
-            .fnt
-            .get_file_id("DUNGEON/dungeon.bin")
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "dungeon.bin not found"))?;
-
-        let dungeon_bin_data = self
-            .rom
-            .fat
-            .get_file_data(dungeon_bin_id as usize, &self.rom.data)
-            .ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidData, "Failed to extract dungeon.bin")
-            })?;
-
-        println!("Parsing dungeon.bin...");
-        let binpack = BinPack::from_bytes(dungeon_bin_data)?;
-        println!("dungeon.bin contains {} files", binpack.len());
+    ) -> io::Result<StageReport> {
+        let (binpack, stride) = self.load_dungeon_bin()?;
 
         let ids: Vec<usize> = match tileset_ids {
-            Some(ids) => ids.into_iter().filter(|&id| id < MAX_TILESET_ID).collect(),
-            None => (0..MAX_TILESET_ID)
-                .filter(|id| !(144..170).contains(id))
-                .collect(),
+            Some(ids) => ids.into_iter().filter(|&id| id < stride).collect(),
+            None => (0..stride.saturating_sub(BACKGROUND_ENTRIES)).collect(),
         };
+        write_progress(progress_path, 0, ids.len(), "dungeon_tileset", "running");
 
         fs::create_dir_all(output_dir)?;
-        render::write_layout_json(output_dir)?;
+        render::write_layout_json(output_dir, self.json_style)?;
 
         let mut all_metadata = Vec::new();
+        let mut report = StageReport::default();
 
         for (i, &tileset_id) in ids.iter().enumerate() {
             println!("Extracting tileset {}...", tileset_id);
 
             let property = properties.and_then(|p| p.get(tileset_id));
 
-            match tileset::extract_tileset(&binpack, tileset_id) {
-                Ok(tileset) => match render::render_tileset(&tileset, output_dir, property) {
+            match tileset::extract_tileset(&binpack, stride, tileset_id) {
+                Ok(tileset) => match render::render_tileset(
+                    &tileset,
+                    output_dir,
+                    property,
+                    self.dump_all_palettes,
+                    self.baked_frame,
+                ) {
                     Ok(meta) => {
                         let status = if meta.animated { "animated" } else { "static" };
                         println!("  -> {} ({})", meta.filename, status);
                         all_metadata.push(meta);
+                        report.add(ItemOutcome::Processed);
+                    }
+                    Err(e) => {
+                        eprintln!("  -> Error rendering tileset {}: {}", tileset_id, e);
+                        report.add(ItemOutcome::Failed);
                     }
-                    Err(e) => eprintln!("  -> Error rendering tileset {}: {}", tileset_id, e),
                 },
                 Err(e) => {
                     eprintln!("  -> Error extracting tileset {}: {}", tileset_id, e);
+                    report.add(ItemOutcome::Failed);
                 }
             }
 
@@ -86,7 +112,7 @@ impl<'a> DungeonBinExtractor<'a> {
             );
         }
 
-        render::write_tilesets_json(&all_metadata, output_dir)?;
+        render::write_tilesets_json(&all_metadata, output_dir, self.json_style)?;
 
         // Shadow extraction
         let shadow_output_dir = output_dir.parent().unwrap().join("shadows");
@@ -112,6 +138,87 @@ impl<'a> DungeonBinExtractor<'a> {
             eprintln!("  -> Error extracting weather assets: {}", e);
         }
 
-        Ok(())
+        Ok(report)
+    }
+
+    /// Tilesets `extract_dungeon_tilesets(None, ..)` would render, without rendering them
+    pub fn count_tilesets(&self) -> io::Result<usize> {
+        let (_, stride) = self.load_dungeon_bin()?;
+        Ok(stride.saturating_sub(BACKGROUND_ENTRIES))
+    }
+
+    /// Reads dungeon.bin and detects how many files each tileset group holds
+    fn load_dungeon_bin(&self) -> io::Result<(BinPack, usize)> {
+        let dungeon_bin_id = self
+            .rom
+            .fnt
+            .get_file_id("DUNGEON/dungeon.bin")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "dungeon.bin not found"))?;
+
+        let dungeon_bin_data = self
+            .rom
+            .fat
+            .get_file_data(dungeon_bin_id as usize, &self.rom.data)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Failed to extract dungeon.bin")
+            })?;
+
+        println!("Parsing dungeon.bin...");
+        let binpack = BinPack::from_bytes(dungeon_bin_data)?;
+        println!("dungeon.bin contains {} files", binpack.len());
+        let stride = tileset::detect_dungeon_stride(&binpack)?;
+        println!("dungeon.bin tileset groups hold {} files each", stride);
+
+        Ok((binpack, stride))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        containers::{compression::at4px::At4pxContainer, sir0::Sir0},
+        rom_builder::RomBuilder,
+        test_support::scratch_dir,
+    };
+
+    #[test]
+    fn broken_tilesets_are_reported_and_the_json_still_written() {
+        let dir = scratch_dir("dungeon_stage");
+        // Two tilesets ahead of the background entries, in five groups of that stride. The
+        // stub DPLA files are too short, so both fail to extract.
+        let stride = BACKGROUND_ENTRIES + 2;
+        let sir0 = Sir0::serialise(&[0; 16], &[], 0);
+        let at4px = At4pxContainer::compress(&[0; 64]).unwrap();
+        let mut dungeon_bin = BinPack::default();
+        for group in [&sir0, &sir0, &at4px, &at4px, &vec![0; 64]] {
+            for _ in 0..stride {
+                dungeon_bin.append(group.clone());
+            }
+        }
+        let rom_path = dir.join("fixture.nds");
+        fs::write(
+            &rom_path,
+            RomBuilder::new("C2SE")
+                .file("DUNGEON/dungeon.bin", dungeon_bin.to_bytes(0))
+                .build(),
+        )
+        .unwrap();
+        let rom = Rom::new(&rom_path).unwrap();
+
+        let extractor = DungeonBinExtractor::new(&rom);
+        assert_eq!(extractor.count_tilesets().unwrap(), 2);
+        let output_dir = dir.join("DUNGEON").join("tilesets");
+        let report = extractor
+            .extract_dungeon_tilesets(None, &output_dir, &dir.join("progress.json"), None)
+            .unwrap();
+
+        assert_eq!((report.processed, report.failed), (0, 2));
+        assert!(output_dir.join("layout.json").is_file());
+        let listed: Vec<serde_json::Value> =
+            serde_json::from_slice(&fs::read(output_dir.join("tilesets.json")).unwrap()).unwrap();
+        assert!(listed.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
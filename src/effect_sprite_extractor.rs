@@ -1,32 +1,44 @@
 use std::{
     collections::HashMap,
-    fs::{self, File},
+    fs,
     io::{self},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
 };
 
+use image::RgbaImage;
+
 use crate::{
     containers::{
+        self,
         binpack::BinPack,
         compression::pkdpx::PkdpxContainer,
         sir0::{self},
-        ContainerHandler,
+        ContainerHandler, ContainerKind,
     },
-    data::animation_info::{AnimType, EffectAnimationInfo, MoveAnimationInfo},
+    data::animation_info::{self, AnimPointType, AnimType, EffectAnimationInfo, MoveAnimationInfo},
     graphics::{
+        atlas::generator,
+        palette,
         screen_effect::{parse_screen_effect, render_screen_frame, ScreenEffectFile},
         wan::{
-            model::WanFile,
-            parser::{parse_wan_from_sir0_content, parse_wan_palette_only},
-            renderer, AnimationStructure, ImgPiece, PaletteList,
+            model::{Animation, SequenceFrame, WanFile},
+            parser::{
+                detect_wan_type, parse_wan_from_sir0_content, parse_wan_palette_only,
+                FallbackPalette, ParseOptions, DEFAULT_MAX_SEQUENCE_FRAMES,
+            },
+            renderer, AnimationStructure, DurationUnit, ImgPiece, PaletteList,
         },
         WanType,
     },
     move_effects_index::{
-        AnimationDetails, AnimationSequence, EffectDefinition, EffectLayer, MoveData,
+        AnimationDetails, AnimationSequence, AudioCue, EffectDefinition, EffectLayer, MoveData,
         MoveEffectTrigger, MoveEffectsIndex, ScreenEffect, ScreenFrameInfo, SpriteEffect,
     },
+    output::{self, DirSink, ImageFormat, JsonStyle, OutputSink},
     progress::write_progress,
+    report::StageReport,
     rom::Rom,
 };
 
@@ -37,8 +49,38 @@ pub struct EffectAssetPipeline<'a> {
     effect_bin: Option<BinPack>,
     base_palette: Option<PaletteList>,
     base_wan_file292: Option<WanFile>,
+    /// Rendered non-directional sheets keyed by (file_index, animation_index, palette_index).
+    /// Several effect IDs resolve to the same sheet, so each is only composited once per run.
+    render_cache: HashMap<RenderKey, Option<RenderedSheet>>,
+    render_cache_hits: usize,
+    duration_unit: DurationUnit,
+    max_frame_dimension: u32,
+    emit_dot: bool,
+    sprite_scale: u32,
+    native_bounds: bool,
+    dump_frames: bool,
+    include_effectless_moves: bool,
+    export_base_palette: bool,
+    export_palettes: bool,
+    skip_existing: bool,
+    /// ROM mtime while `skip_existing` is on, sheets written after it are left alone
+    up_to_date_since: Option<SystemTime>,
+    trim_sheets: bool,
+    merge_index: bool,
+    sink: Arc<dyn OutputSink>,
+    json_style: JsonStyle,
+    image_format: ImageFormat,
+    strict: bool,
+    fallback_palette: FallbackPalette,
+    max_sequence_frames: usize,
 }
 
+/// (file_index, animation_index, palette_index). The palette index only varies for the shared
+/// WAN files 0/1, where it changes the rendered colours.
+type RenderKey = (usize, usize, u8);
+/// (sheet, frame_width, frame_height, native-bounds origin offset)
+type RenderedSheet = (RgbaImage, u32, u32, Option<[i32; 2]>);
+
 impl<'a> EffectAssetPipeline<'a> {
     pub fn new(rom: &'a Rom) -> Self {
         EffectAssetPipeline {
@@ -47,9 +89,150 @@ impl<'a> EffectAssetPipeline<'a> {
             effect_bin: None,
             base_palette: None,
             base_wan_file292: None,
+            render_cache: HashMap::new(),
+            render_cache_hits: 0,
+            duration_unit: DurationUnit::default(),
+            max_frame_dimension: renderer::DEFAULT_MAX_FRAME_DIMENSION,
+            emit_dot: false,
+            sprite_scale: 1,
+            native_bounds: false,
+            dump_frames: false,
+            include_effectless_moves: false,
+            export_base_palette: false,
+            export_palettes: false,
+            skip_existing: false,
+            up_to_date_since: None,
+            trim_sheets: false,
+            merge_index: false,
+            sink: Arc::new(DirSink),
+            json_style: JsonStyle::default(),
+            image_format: ImageFormat::default(),
+            strict: false,
+            fallback_palette: FallbackPalette::default(),
+            max_sequence_frames: DEFAULT_MAX_SEQUENCE_FRAMES,
         }
     }
 
+    /// Fails an effect on the first recoverable WAN parse problem instead of using fallbacks.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Palette for effects with image data but no palette rows, the character fallback greys
+    /// by default.
+    pub fn with_fallback_palette(mut self, fallback_palette: FallbackPalette) -> Self {
+        self.fallback_palette = fallback_palette;
+        self
+    }
+
+    /// Sends effect sheets and the asset index to `sink` instead of loose files.
+    pub fn with_sink(mut self, sink: Arc<dyn OutputSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Formatting for `asset_index.json` and the other JSON files the pipeline writes.
+    pub fn with_json_style(mut self, json_style: JsonStyle) -> Self {
+        self.json_style = json_style;
+        self
+    }
+
+    /// Container for effect sheets, also used for the sheet paths in `asset_index.json`.
+    pub fn with_image_format(mut self, image_format: ImageFormat) -> Self {
+        self.image_format = image_format;
+        self
+    }
+
+    /// Upscales every saved sheet by an integer factor (nearest-neighbour), 0 is treated as 1.
+    pub fn with_sprite_scale(mut self, sprite_scale: u32) -> Self {
+        self.sprite_scale = sprite_scale.max(1);
+        self
+    }
+
+    /// Also writes the move -> effect graph as `asset_index.dot` next to `asset_index.json`.
+    pub fn with_dot_export(mut self, emit_dot: bool) -> Self {
+        self.emit_dot = emit_dot;
+        self
+    }
+
+    /// Caps the width/height of any rendered effect frame, larger canvases are treated as corrupt.
+    pub fn with_max_frame_dimension(mut self, max_frame_dimension: u32) -> Self {
+        self.max_frame_dimension = max_frame_dimension;
+        self
+    }
+
+    /// Cuts animation sequences without an end marker off at this many frames.
+    pub fn with_max_sequence_frames(mut self, max_sequence_frames: usize) -> Self {
+        self.max_sequence_frames = max_sequence_frames;
+        self
+    }
+
+    /// Crops non-directional sheets to their tight bounds instead of centring them on the
+    /// effect origin, and records the frame offset as `origin_offset`.
+    pub fn with_native_bounds(mut self, native_bounds: bool) -> Self {
+        self.native_bounds = native_bounds;
+        self
+    }
+
+    /// Crops the transparent border all frames of a centred non-directional sheet share, and
+    /// records where the kept area sat in the centred frame as `trim_offset`.
+    pub fn with_sheet_trim(mut self, trim_sheets: bool) -> Self {
+        self.trim_sheets = trim_sheets;
+        self
+    }
+
+    /// Debug aid: also writes every frame of a non-directional effect to `frames/` as
+    /// `{effect_id}_frame{N}_x{X}_y{Y}.png`, where X/Y is the frame's sprite offset.
+    pub fn with_frame_dump(mut self, dump_frames: bool) -> Self {
+        self.dump_frames = dump_frames;
+        self
+    }
+
+    /// Also lists moves with no extracted effect (status moves without a visual) in
+    /// `asset_index.json`, with an empty `effects` list, so the move set is complete.
+    pub fn with_effectless_moves(mut self, include_effectless_moves: bool) -> Self {
+        self.include_effectless_moves = include_effectless_moves;
+        self
+    }
+
+    /// Debug aid: also writes the effect.bin[292] base palette that effect palettes are merged
+    /// into as `EFFECT/base_palette.png` (one row per palette, one pixel per colour) and
+    /// `EFFECT/base_palette.json`.
+    pub fn with_base_palette_export(mut self, export_base_palette: bool) -> Self {
+        self.export_base_palette = export_base_palette;
+        self
+    }
+
+    /// Also writes each sprite effect's palette rows (merged with the base palette when the
+    /// effect uses it) as `EFFECT/{effect_id}.gpl` and `EFFECT/{effect_id}.act`.
+    pub fn with_palette_export(mut self, export_palettes: bool) -> Self {
+        self.export_palettes = export_palettes;
+        self
+    }
+
+    /// Effects whose sheets are already on disk and newer than the ROM file aren't parsed or
+    /// rendered again, their entries are carried over from the existing `asset_index.json`.
+    /// That assumes the earlier run used the same options. `asset_index.json` is left alone
+    /// when it would come out identical.
+    pub fn with_skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    /// Merges this run's entries into the `asset_index.json` already in the output directory
+    /// instead of replacing it, so partial runs add up. This run's entries win on conflicts.
+    pub fn with_index_merge(mut self, merge_index: bool) -> Self {
+        self.merge_index = merge_index;
+        self
+    }
+
+    /// Sets the unit used for all exported frame durations, frames by default like the atlases.
+    pub fn with_duration_unit(mut self, duration_unit: DurationUnit) -> Self {
+        self.duration_unit = duration_unit;
+        self
+    }
+
     /// Renders sprites, saves them, and generates a final `asset_index.json`
     pub fn run(
         &mut self,
@@ -58,19 +241,35 @@ impl<'a> EffectAssetPipeline<'a> {
         output_dir: &Path,
         progress_path: &Path,
         total_effects: usize,
-    ) -> io::Result<()> {
+    ) -> io::Result<StageReport> {
         println!("\n--- Starting Effect Asset Pipeline ---");
 
         self.load_bin_containers()?;
+        self.report_out_of_range_effects(effects_map);
+        if self.skip_existing {
+            self.up_to_date_since = self.rom.modified().ok();
+        }
 
         let sprites_dir = output_dir.join("EFFECT");
-        fs::create_dir_all(&sprites_dir)?;
 
-        let mut index = MoveEffectsIndex::new();
+        if self.export_base_palette {
+            if let Err(e) = self.save_base_palette(&sprites_dir) {
+                eprintln!("Failed to export the base palette: {}", e);
+            }
+        }
+
+        let mut index = MoveEffectsIndex::new(self.duration_unit);
         let mut effects_processed = 0;
         let mut effects_skipped = 0;
         let mut errors = 0;
 
+        // Entries of up-to-date effects are reused, so nothing of them is parsed or rendered
+        let mut previous_index = self
+            .up_to_date_since
+            .and_then(|_| MoveEffectsIndex::load(&output_dir.join("asset_index.json")).ok())
+            .filter(|previous| previous.duration_unit == self.duration_unit);
+        let mut effects_reused = 0;
+
         let mut sorted_effect_ids: Vec<_> = effects_map.keys().collect();
         sorted_effect_ids.sort();
 
@@ -83,6 +282,22 @@ impl<'a> EffectAssetPipeline<'a> {
                 effect_id, anim_type
             );
 
+            if let Some(entry) =
+                self.reusable_entry(previous_index.as_mut(), *effect_id, &sprites_dir)
+            {
+                println!(" -> Skipping: sheet is up to date");
+                effects_reused += 1;
+                write_progress(
+                    progress_path,
+                    effects_processed + effects_reused,
+                    total_effects,
+                    "move_effect_sprites",
+                    "skipped",
+                );
+                index.effects.insert(effect_id.to_string(), entry);
+                continue;
+            }
+
             let effect_entry = match anim_type {
                 AnimType::WanOther => {
                     match self.process_sprite_effect(*effect_id, effect_info, &sprites_dir, None) {
@@ -90,7 +305,7 @@ impl<'a> EffectAssetPipeline<'a> {
                             effects_processed += 1;
                             write_progress(
                                 progress_path,
-                                effects_processed,
+                                effects_processed + effects_reused,
                                 total_effects,
                                 "move_effect_sprites",
                                 "running",
@@ -115,7 +330,7 @@ impl<'a> EffectAssetPipeline<'a> {
                             effects_processed += 1;
                             write_progress(
                                 progress_path,
-                                effects_processed,
+                                effects_processed + effects_reused,
                                 total_effects,
                                 "move_effect_sprites",
                                 "running",
@@ -140,7 +355,7 @@ impl<'a> EffectAssetPipeline<'a> {
                             effects_processed += 1;
                             write_progress(
                                 progress_path,
-                                effects_processed,
+                                effects_processed + effects_reused,
                                 total_effects,
                                 "move_effect_sprites",
                                 "running",
@@ -164,7 +379,7 @@ impl<'a> EffectAssetPipeline<'a> {
                             effects_processed += 1;
                             write_progress(
                                 progress_path,
-                                effects_processed,
+                                effects_processed + effects_reused,
                                 total_effects,
                                 "move_effect_sprites",
                                 "running",
@@ -197,6 +412,10 @@ impl<'a> EffectAssetPipeline<'a> {
         println!("Populating moves data...");
         self.populate_moves_data(&mut index, moves_map);
 
+        if self.merge_index {
+            self.merge_existing_index(&mut index, output_dir);
+        }
+
         // Write the complete index to disk
         self.save_index(&index, output_dir)?;
 
@@ -204,10 +423,46 @@ impl<'a> EffectAssetPipeline<'a> {
         println!("Effect Asset Pipeline Complete!");
         println!("  Sprites Processed: {}", effects_processed);
         println!("  Effects Skipped (by design): {}", effects_skipped);
+        println!("  Effects Up To Date: {}", effects_reused);
+        println!(
+            "  Sheets Rendered: {} (cache hits: {})",
+            self.render_cache.len(),
+            self.render_cache_hits
+        );
         println!("  Errors: {}", errors);
         println!("---------------------------------");
 
-        Ok(())
+        Ok(StageReport {
+            processed: effects_processed,
+            skipped: effects_skipped + effects_reused,
+            failed: errors,
+        })
+    }
+
+    /// Takes effect `effect_id`'s entry out of the previous run's index for
+    /// `with_skip_existing`, if every sheet it refers to is on disk and newer than the ROM
+    fn reusable_entry(
+        &self,
+        previous_index: Option<&mut MoveEffectsIndex>,
+        effect_id: u16,
+        sprites_dir: &Path,
+    ) -> Option<EffectDefinition> {
+        let since = self.up_to_date_since?;
+        let previous_index = previous_index?;
+        let key = effect_id.to_string();
+        let extension = self.image_format.extension();
+        let sheets: Vec<PathBuf> = match previous_index.effects.get(&key)? {
+            EffectDefinition::Sprite(sprite) if sprite.is_directional => (0..8)
+                .map(|direction| {
+                    sprites_dir.join(format!("{}_dir{}.{}", effect_id, direction, extension))
+                })
+                .collect(),
+            _ => vec![sprites_dir.join(format!("{}.{}", effect_id, extension))],
+        };
+        if !output::files_up_to_date(&sheets, since) {
+            return None;
+        }
+        previous_index.effects.remove(&key)
     }
 
     /// Determines if an effect is directional based on ROM behavior.
@@ -246,25 +501,25 @@ impl<'a> EffectAssetPipeline<'a> {
         // Cache already scanned effect sprites
         self.ensure_effect_wan_cached(file_index)?;
 
-        // For shared WAN files (0/1), clone and apply palette_index offset per-effect
-        let wan_file_ref = if override_file_index.is_some() && effect_info.palette_index > 0 {
-            let mut cloned = self.wan_cache.get(&file_index).unwrap().clone();
-            let offset = effect_info.palette_index as u8;
-            let pal_count = cloned.custom_palette.len().max(1) as u8;
-            for frame in &mut cloned.frame_data {
-                for piece in &mut frame.pieces {
-                    piece.palette_index = piece.palette_index.wrapping_add(offset) % pal_count;
-                }
-            }
-            Some(cloned)
-        } else {
-            None
-        };
-
+        let wan_file_ref = self.palette_shifted_wan(file_index, effect_info, override_file_index);
         let wan_file = wan_file_ref
             .as_ref()
             .unwrap_or_else(|| self.wan_cache.get(&file_index).unwrap());
 
+        if self.export_palettes {
+            if let Err(e) = palette::export_palette_rows(
+                &wan_file.custom_palette,
+                sprites_dir,
+                &effect_id.to_string(),
+                self.sink.as_ref(),
+            ) {
+                eprintln!(
+                    " -> Failed to export palette for effect {}: {}",
+                    effect_id, e
+                );
+            }
+        }
+
         // Determine directionality based on ROM behavior
         let (is_directional, can_render_all_directions) =
             self.check_directional_effect(wan_file, base_anim_index);
@@ -293,16 +548,155 @@ impl<'a> EffectAssetPipeline<'a> {
                     base_anim_index, wan_file.max_sequences_per_group
                 );
             }
+            let palette_key = if wan_file_ref.is_some() {
+                effect_info.palette_index as u8
+            } else {
+                0
+            };
+            let key = (file_index, base_anim_index, palette_key);
+            let rendered = match self.render_cache.get(&key) {
+                Some(cached) => {
+                    self.render_cache_hits += 1;
+                    cached.clone()
+                }
+                None => {
+                    let rendered = if wan_file.frame_data.is_empty()
+                        && !wan_file.img_data.is_empty()
+                    {
+                        // Static effect, the WAN has tiles but no animation info
+                        renderer::render_static_effect(wan_file, self.max_frame_dimension)
+                            .map(|sheet| sheet.map(|(img, w, h)| (img, w, h, None)))
+                    } else if self.native_bounds {
+                        renderer::render_effect_animation_sheet_native(
+                            wan_file,
+                            base_anim_index,
+                            self.max_frame_dimension,
+                            self.strict,
+                        )
+                        .map(|sheet| sheet.map(|(img, w, h, (ox, oy))| (img, w, h, Some([ox, oy]))))
+                    } else {
+                        renderer::render_effect_animation_sheet(
+                            wan_file,
+                            base_anim_index,
+                            self.max_frame_dimension,
+                            self.strict,
+                        )
+                        .map(|sheet| sheet.map(|(img, w, h)| (img, w, h, None)))
+                    }
+                    .map_err(|e| {
+                        io::Error::other(format!("Failed to render sprite sheet: {:?}", e))
+                    })?;
+                    self.render_cache.insert(key, rendered.clone());
+                    rendered
+                }
+            };
+
+            if self.dump_frames && !wan_file.frame_data.is_empty() {
+                self.dump_effect_frames(effect_id, wan_file, base_anim_index, sprites_dir)?;
+            }
+
             self.process_non_directional_effect(
                 effect_id,
                 effect_info,
                 wan_file,
                 base_anim_index,
+                rendered,
                 sprites_dir,
             )
         }
     }
 
+    /// For shared WAN files (0/1), clones the cached file with the effect's palette_index offset
+    /// applied to every piece. `None` when the cached file can be used as is.
+    fn palette_shifted_wan(
+        &self,
+        file_index: usize,
+        effect_info: &EffectAnimationInfo,
+        override_file_index: Option<usize>,
+    ) -> Option<WanFile> {
+        if override_file_index.is_none() || effect_info.palette_index == 0 {
+            return None;
+        }
+
+        let mut cloned = self.wan_cache.get(&file_index)?.clone();
+        let offset = effect_info.palette_index as u8;
+        let pal_count = cloned.palette_count().max(1) as u8;
+        for frame in &mut cloned.frame_data {
+            for piece in &mut frame.pieces {
+                piece.palette_index = piece.palette_index.wrapping_add(offset) % pal_count;
+            }
+        }
+        Some(cloned)
+    }
+
+    /// Writes sprite effect `effect_id` to `EFFECT/{effect_id}_preview.gif` under `output_dir`
+    /// with `renderer::export_animation_gif` and returns the path. Directional effects preview
+    /// their first direction, screen and sound-only effects are rejected.
+    pub fn export_preview_gif(
+        &mut self,
+        effect_id: u16,
+        effect_info: &EffectAnimationInfo,
+        output_dir: &Path,
+        fps_override: Option<u16>,
+    ) -> io::Result<PathBuf> {
+        let override_file_index = match effect_info.anim_type {
+            AnimType::WanOther => None,
+            AnimType::WanFile0 => Some(0),
+            AnimType::WanFile1 => Some(1),
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Effect {} is not a sprite effect ({:?})", effect_id, other),
+                ))
+            }
+        };
+        let file_index = override_file_index.unwrap_or(effect_info.file_index as usize);
+
+        self.load_bin_containers()?;
+        self.ensure_effect_wan_cached(file_index)?;
+        let wan_file_ref = self.palette_shifted_wan(file_index, effect_info, override_file_index);
+        let wan_file = wan_file_ref
+            .as_ref()
+            .unwrap_or_else(|| self.wan_cache.get(&file_index).unwrap());
+
+        let sprites_dir = output_dir.join("EFFECT");
+        fs::create_dir_all(&sprites_dir)?;
+        let path = sprites_dir.join(format!("{}_preview.gif", effect_id));
+        renderer::export_animation_gif(
+            wan_file,
+            effect_info.animation_index as usize,
+            &path,
+            fps_override,
+            self.max_frame_dimension,
+            self.strict,
+        )?;
+
+        Ok(path)
+    }
+
+    /// Writes each frame of sequence `anim_index` as a separate PNG for `with_frame_dump`
+    fn dump_effect_frames(
+        &self,
+        effect_id: u16,
+        wan_file: &WanFile,
+        anim_index: usize,
+        sprites_dir: &Path,
+    ) -> io::Result<()> {
+        let frames = renderer::render_animation_frames(
+            wan_file,
+            anim_index,
+            self.max_frame_dimension,
+            self.strict,
+        )
+        .map_err(|e| io::Error::other(format!("Failed to render frames: {:?}", e)))?;
+        let frames_dir = sprites_dir.join("frames");
+        for (i, (image, (x, y))) in frames.iter().enumerate() {
+            let path = frames_dir.join(format!("{}_frame{}_x{}_y{}.png", effect_id, i, x, y));
+            self.save_effect_sprite_png(image, &path)?;
+        }
+        Ok(())
+    }
+
     /// Calculates the unified canvas box that encompasses all 8 directional animations.
     fn calculate_unified_canvas_box(
         &self,
@@ -399,10 +793,17 @@ impl<'a> EffectAssetPipeline<'a> {
                 wan_file,
                 anim_index,
                 Some(unified_canvas_box),
+                self.max_frame_dimension,
+                self.strict,
             ) {
                 Ok(Some((sprite_sheet, _fw, _fh))) => {
-                    // Save with direction suffix: {effect_id}_dir{0-7}.png
-                    let sheet_filename = format!("{}_dir{}.png", effect_id, direction);
+                    // Save with direction suffix: {effect_id}_dir{0-7}.png (or .webp)
+                    let sheet_filename = format!(
+                        "{}_dir{}.{}",
+                        effect_id,
+                        direction,
+                        self.image_format.extension()
+                    );
                     let sheet_path = sprites_dir.join(&sheet_filename);
                     self.save_effect_sprite_png(&sprite_sheet, &sheet_path)?;
 
@@ -434,7 +835,7 @@ impl<'a> EffectAssetPipeline<'a> {
         }
 
         // Build effect definition with directional info
-        let effect_definition = self.build_sprite_effect_definition_directional(
+        let mut effect_definition = self.build_sprite_effect_definition_directional(
             effect_info,
             effect_id,
             base_anim_index,
@@ -444,6 +845,9 @@ impl<'a> EffectAssetPipeline<'a> {
             true,
             8,
         );
+        if let EffectDefinition::Sprite(sprite) = &mut effect_definition {
+            sprite.anim_header_unknowns = wan_file.anim_header_unknowns;
+        }
 
         println!(
             " -> SUCCESS: 8 directional sprite sheets saved (unified {}x{})",
@@ -452,19 +856,33 @@ impl<'a> EffectAssetPipeline<'a> {
         Ok(Some(effect_definition))
     }
 
-    /// Processes a non-directional effect by rendering a single sprite sheet.
+    /// Processes a non-directional effect by saving its (possibly cached) single sprite sheet.
     fn process_non_directional_effect(
         &self,
         effect_id: u16,
         effect_info: &EffectAnimationInfo,
         wan_file: &WanFile,
         anim_index: usize,
+        rendered: Option<RenderedSheet>,
         sprites_dir: &Path,
     ) -> io::Result<Option<EffectDefinition>> {
-        match renderer::render_effect_animation_sheet(wan_file, anim_index) {
-            Ok(Some((sprite_sheet, frame_width, frame_height))) => {
+        match rendered {
+            Some((mut sprite_sheet, mut frame_width, mut frame_height, origin_offset)) => {
+                // Native bounds sheets are already tight
+                let mut trim_offset = None;
+                if self.trim_sheets && origin_offset.is_none() {
+                    if let Some((trimmed, width, height, offset)) =
+                        renderer::trim_sheet(&sprite_sheet, frame_width, frame_height)
+                    {
+                        sprite_sheet = trimmed;
+                        frame_width = width;
+                        frame_height = height;
+                        trim_offset = Some(offset);
+                    }
+                }
+
                 // Save single sprite sheet
-                let sheet_filename = format!("{}.png", effect_id);
+                let sheet_filename = format!("{}.{}", effect_id, self.image_format.extension());
                 let sheet_path = sprites_dir.join(&sheet_filename);
                 self.save_effect_sprite_png(&sprite_sheet, &sheet_path)?;
                 println!(
@@ -473,14 +891,24 @@ impl<'a> EffectAssetPipeline<'a> {
                 );
 
                 // Get animation sequence for timing data
+                let static_sequence;
                 let animation_sequence = match &wan_file.animations {
                     AnimationStructure::Effect(groups) => {
                         groups.first().and_then(|group| group.get(anim_index))
                     }
                     AnimationStructure::Character(_) => None,
                 };
-
-                let effect_definition = self.build_sprite_effect_definition_directional(
+                // Static effects have no sequences, give the one-frame sheet a "play" animation
+                let animation_sequence =
+                    if animation_sequence.is_none() && wan_file.frame_data.is_empty() {
+                        static_sequence =
+                            Animation::new(vec![SequenceFrame::new(0, 1, 0, (0, 0), (0, 0))]);
+                        Some(&static_sequence)
+                    } else {
+                        animation_sequence
+                    };
+
+                let mut effect_definition = self.build_sprite_effect_definition_directional(
                     effect_info,
                     effect_id,
                     anim_index,
@@ -490,17 +918,20 @@ impl<'a> EffectAssetPipeline<'a> {
                     false,
                     1,
                 );
+                if let EffectDefinition::Sprite(sprite) = &mut effect_definition {
+                    sprite.anim_header_unknowns = wan_file.anim_header_unknowns;
+                    let scale = self.sprite_scale as i32;
+                    sprite.origin_offset = origin_offset.map(|[x, y]| [x * scale, y * scale]);
+                    sprite.trim_offset =
+                        trim_offset.map(|[x, y]| [x * self.sprite_scale, y * self.sprite_scale]);
+                }
 
                 Ok(Some(effect_definition))
             }
-            Ok(None) => {
+            None => {
                 println!(" -> WARNING: Animation is empty or has no visible pixels. Skipping.");
                 Ok(None)
             }
-            Err(e) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to render sprite sheet: {:?}", e),
-            )),
         }
     }
 
@@ -516,12 +947,20 @@ impl<'a> EffectAssetPipeline<'a> {
         is_directional: bool,
         direction_count: u8,
     ) -> EffectDefinition {
+        // Sheets are upscaled on save, so the cell size must follow
+        let frame_width = frame_width * self.sprite_scale;
+        let frame_height = frame_height * self.sprite_scale;
+
         // Handle case where animation sequence is missing
         let animation_sequence = match animation_sequence {
             Some(anim) => anim,
             None => {
                 return EffectDefinition::Sprite(SpriteEffect {
-                    sprite_sheet: format!("res://effect_sprites/{}.png", effect_id),
+                    sprite_sheet: format!(
+                        "res://effect_sprites/{}.{}",
+                        effect_id,
+                        self.image_format.extension()
+                    ),
                     frame_width: frame_width.max(1),
                     frame_height: frame_height.max(1),
                     animations: HashMap::new(),
@@ -529,6 +968,10 @@ impl<'a> EffectAssetPipeline<'a> {
                     direction_count,
                     base_animation_index: base_animation_index as u32,
                     is_non_blocking: effect_info.is_non_blocking,
+                    origin_offset: None,
+                    trim_offset: None,
+                    anim_header_unknowns: [0; 5],
+                    audio: AudioCue::from_effect(effect_info.sfx_id, effect_info.timing_offset),
                 });
             }
         };
@@ -537,9 +980,9 @@ impl<'a> EffectAssetPipeline<'a> {
             .frames
             .iter()
             .map(|frame| {
-                let duration_sec = ticks_to_seconds(frame.duration);
+                let duration = self.duration_unit.convert(frame.duration);
                 // zero out the offsets in the JSON since they're now baked into animation sheet
-                [duration_sec, 0.0, 0.0]
+                [duration, 0.0, 0.0]
             })
             .collect();
 
@@ -559,7 +1002,7 @@ impl<'a> EffectAssetPipeline<'a> {
         let animation_details = if is_simple {
             AnimationDetails::Simple {
                 frame_count: frame_details.len(),
-                duration: frame_details.get(0).map(|f| f[0]).unwrap_or(0.1),
+                duration: frame_details.first().map(|f| f[0]).unwrap_or(0.1),
             }
         } else {
             AnimationDetails::Complex {
@@ -572,16 +1015,24 @@ impl<'a> EffectAssetPipeline<'a> {
             "play".to_string(),
             AnimationSequence {
                 looping: effect_info.loop_flag,
+                hit_frame: animation_sequence
+                    .frames
+                    .iter()
+                    .position(|frame| frame.is_effect_point()),
                 details: animation_details,
             },
         );
 
         // For directional effects, sprite_sheet is the base path without _dir{N} suffix
-        // Client will append _dir{direction}.png based on attacker direction
+        // Client will append _dir{direction}.png (or .webp) based on attacker direction
         let sprite_sheet_path = if is_directional {
             format!("res://effect_sprites/{}", effect_id)
         } else {
-            format!("res://effect_sprites/{}.png", effect_id)
+            format!(
+                "res://effect_sprites/{}.{}",
+                effect_id,
+                self.image_format.extension()
+            )
         };
 
         EffectDefinition::Sprite(SpriteEffect {
@@ -593,6 +1044,10 @@ impl<'a> EffectAssetPipeline<'a> {
             direction_count,
             base_animation_index: base_animation_index as u32,
             is_non_blocking: effect_info.is_non_blocking,
+            origin_offset: None,
+            trim_offset: None,
+            anim_header_unknowns: [0; 5],
+            audio: AudioCue::from_effect(effect_info.sfx_id, effect_info.timing_offset),
         })
     }
 
@@ -627,11 +1082,25 @@ impl<'a> EffectAssetPipeline<'a> {
                 }
             }
 
-            if !move_effects.is_empty() {
+            // -1 is the ROM's "origin" value, anything outside -1..=3 has no FrameOffset point
+            let attachment_point =
+                AnimPointType::from_attachment_index(move_info.attachment_point_idx);
+            if attachment_point == AnimPointType::None && move_info.attachment_point_idx != -1 {
+                println!(
+                    "Warning: move {} has attachment point index {}, which names no frame offset. \
+                     Its effects play at the origin.",
+                    move_id, move_info.attachment_point_idx
+                );
+            }
+
+            if !move_effects.is_empty() || self.include_effectless_moves {
                 index.moves.insert(
                     move_id.to_string(),
                     MoveData {
                         effects: move_effects,
+                        attachment_point: (attachment_point != AnimPointType::None)
+                            .then_some(attachment_point),
+                        audio: AudioCue::from_move(move_info.sound_effect_id),
                     },
                 );
             }
@@ -648,14 +1117,16 @@ impl<'a> EffectAssetPipeline<'a> {
             .effect_bin
             .as_ref()
             .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "effect.bin not loaded"))?;
-        if effect_index >= effect_bin.len() {
-            return Err(io::Error::new(
+        let sprite_data = effect_bin.get(effect_index).ok_or_else(|| {
+            io::Error::new(
                 io::ErrorKind::InvalidData,
-                format!("Effect index {} out of range for effect.bin", effect_index),
-            ));
-        }
-
-        let sprite_data = &effect_bin[effect_index];
+                format!(
+                    "Effect index {} out of range for effect.bin ({} files)",
+                    effect_index,
+                    effect_bin.len()
+                ),
+            )
+        })?;
         let mut wan_file = self.parse_wan_from_data(sprite_data, WanType::Effect, false)?;
 
         if let Some(base_palette) = &self.base_palette {
@@ -681,46 +1152,125 @@ impl<'a> EffectAssetPipeline<'a> {
         Ok(())
     }
 
+    /// Folds the `asset_index.json` of an earlier run into `index` for `with_index_merge`
+    fn merge_existing_index(&self, index: &mut MoveEffectsIndex, output_dir: &Path) {
+        let path = output_dir.join("asset_index.json");
+        if !path.exists() {
+            println!("No existing asset_index.json to merge, writing a fresh one");
+            return;
+        }
+        match MoveEffectsIndex::load(&path) {
+            Ok(existing) => {
+                println!(
+                    "Merging {} effects and {} moves from the existing asset_index.json",
+                    existing.effects.len(),
+                    existing.moves.len()
+                );
+                index.merge(existing);
+            }
+            Err(e) => eprintln!(
+                "Failed to read existing asset_index.json, replacing it: {}",
+                e
+            ),
+        }
+    }
+
     fn save_index(&self, index: &MoveEffectsIndex, output_dir: &Path) -> io::Result<()> {
         let output_path = output_dir.join("asset_index.json");
         println!("Writing final index to {}...", output_path.display());
 
-        let file = File::create(&output_path)?;
-        serde_json::to_writer_pretty(file, index)
+        let json = self
+            .json_style
+            .to_vec(index)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        if self.up_to_date_since.is_some() && fs::read(&output_path).is_ok_and(|old| old == json) {
+            println!("asset_index.json is unchanged, not rewriting it");
+        } else {
+            self.sink.write_file(&output_path, &json)?;
+        }
+
+        if self.emit_dot {
+            let dot_path = output_dir.join("asset_index.dot");
+            println!("Writing move/effect graph to {}...", dot_path.display());
+            self.sink.write_file(&dot_path, index.to_dot().as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the base palette as a swatch PNG plus JSON of its RGBA rows
+    fn save_base_palette(&self, sprites_dir: &Path) -> io::Result<()> {
+        let base_palette = self
+            .base_palette
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "base palette not loaded"))?;
+
+        let width = base_palette.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let mut swatch = RgbaImage::new(width as u32, base_palette.len().max(1) as u32);
+        for (row, palette) in base_palette.iter().enumerate() {
+            for (column, &(r, g, b, a)) in palette.iter().enumerate() {
+                swatch.put_pixel(column as u32, row as u32, image::Rgba([r, g, b, a]));
+            }
+        }
+        let png = output::encode_optimised_png(&swatch, &oxipng::Options::from_preset(2))?;
+        self.sink
+            .write_file(&sprites_dir.join("base_palette.png"), &png)?;
 
+        let rows: Vec<Vec<[u8; 4]>> = base_palette
+            .iter()
+            .map(|palette| palette.iter().map(|&(r, g, b, a)| [r, g, b, a]).collect())
+            .collect();
+        let json = self.json_style.to_vec(&rows).map_err(io::Error::other)?;
+        self.sink
+            .write_file(&sprites_dir.join("base_palette.json"), &json)?;
+
+        println!(
+            " -> Wrote base palette ({} rows) to {}",
+            rows.len(),
+            sprites_dir.display()
+        );
         Ok(())
     }
 
     fn save_effect_sprite_png(&self, image: &image::RgbaImage, path: &Path) -> io::Result<()> {
-        let temp_path = path.with_extension("temp.png");
-        image
-            .save(&temp_path)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        if let Some(since) = self.up_to_date_since {
+            if output::files_up_to_date(&[path.to_path_buf()], since) {
+                println!(" -> {} is up to date, not rewriting it", path.display());
+                return Ok(());
+            }
+        }
 
         // No difference between compression setting 6 and 2 size wise just so much faster
         let mut options = oxipng::Options::from_preset(2);
         options.bit_depth_reduction = true;
         options.interlace = None;
 
-        match oxipng::optimize(
-            &oxipng::InFile::Path(temp_path.clone()),
-            &oxipng::OutFile::Path(Some(path.to_path_buf())),
-            &options,
-        ) {
-            Ok(_) => {
-                let _ = fs::remove_file(temp_path);
-                Ok(())
-            }
-            Err(e) => {
-                fs::rename(temp_path, path)?;
-                eprintln!(
-                    "Warning: oxipng optimisation failed for {}: {}. File saved unoptimised.",
-                    path.display(),
-                    e
-                );
-                Ok(())
-            }
+        let scaled = generator::upscale_nearest(image, self.sprite_scale);
+        let encoded = output::encode_image(&scaled, self.image_format, Some(&options))?;
+        self.sink.write_file(path, &encoded)
+    }
+
+    /// Lists effects whose file index lies outside effect.bin before any rendering starts
+    fn report_out_of_range_effects(&self, effects_map: &HashMap<u16, EffectAnimationInfo>) {
+        let Some(effect_bin) = &self.effect_bin else {
+            return;
+        };
+        let out_of_range = animation_info::effects_out_of_range(
+            effects_map.iter().map(|(&id, info)| (id, info)),
+            effect_bin.len(),
+        );
+        if out_of_range.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "Warning: {} effects reference files past the end of effect.bin ({} files) and \
+             will not render:",
+            out_of_range.len(),
+            effect_bin.len()
+        );
+        for (effect_id, index) in out_of_range {
+            eprintln!("  - Effect {}: file index {}", effect_id, index);
         }
     }
 
@@ -750,12 +1300,11 @@ impl<'a> EffectAssetPipeline<'a> {
         );
 
         let base_palette_index = 292;
-        if effect_bin.len() > base_palette_index {
+        if let Some(base_palette_data) = effect_bin.get(base_palette_index) {
             println!(
                 "Loading Base Palette from effect.bin[{}]...",
                 base_palette_index
             );
-            let base_palette_data = &effect_bin[base_palette_index];
 
             // Parse palette-only for the base_palette field (existing behavior)
             match self.parse_wan_from_data(base_palette_data, WanType::Effect, true) {
@@ -795,8 +1344,7 @@ impl<'a> EffectAssetPipeline<'a> {
         // Pre-cache shared WAN files 0 and 1 by merging file 292 images with file 0/1 animations
         if let Some(ref base_wan) = self.base_wan_file292 {
             for shared_idx in [0usize] {
-                if shared_idx < effect_bin.len() {
-                    let sprite_data = &effect_bin[shared_idx];
+                if let Some(sprite_data) = effect_bin.get(shared_idx) {
                     match self.parse_wan_from_data(sprite_data, WanType::Effect, false) {
                         Ok(anim_wan) => {
                             // Merge: file 292 provides palette only,
@@ -859,6 +1407,7 @@ impl<'a> EffectAssetPipeline<'a> {
                                 },
                                 max_sequences_per_group: anim_wan.max_sequences_per_group,
                                 offset_table_size: 0,
+                                anim_header_unknowns: anim_wan.anim_header_unknowns,
                             };
                             println!(
                                 " -> Shared WAN file {} merged successfully (frames: {}, sequences: {}).",
@@ -898,7 +1447,7 @@ impl<'a> EffectAssetPipeline<'a> {
         wan_type: WanType,
         palette_only: bool,
     ) -> io::Result<WanFile> {
-        let decompressed_data = if data.starts_with(b"PKDPX") {
+        let decompressed_data = if containers::detect(data) == ContainerKind::Pkdpx {
             match PkdpxContainer::deserialise(data) {
                 Ok(pkdpx) => pkdpx.decompress().map_err(|e| {
                     io::Error::new(
@@ -912,7 +1461,7 @@ impl<'a> EffectAssetPipeline<'a> {
             data.to_vec()
         };
 
-        if !decompressed_data.starts_with(b"SIR0") {
+        if containers::detect(&decompressed_data) != ContainerKind::Sir0 {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "Data is not in SIR0 format",
@@ -927,10 +1476,38 @@ impl<'a> EffectAssetPipeline<'a> {
             ));
         }
 
+        // The effect parser misreads character WANs instead of failing, so reject them up front.
+        // Unknown img_types are left to the parser, palette-only reads don't touch frame data.
+        let detected = detect_wan_type(&sir0_data.content, sir0_data.data_pointer);
+        if let Some(detected) = detected.filter(|_| !palette_only) {
+            if detected != wan_type {
+                println!(
+                    " -> WARNING: Expected a {} WAN but the header says {}, skipping",
+                    wan_type, detected
+                );
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "WAN type mismatch: expected {}, found {}",
+                        wan_type, detected
+                    ),
+                ));
+            }
+        }
+
         let parse_result = if palette_only {
             parse_wan_palette_only(&sir0_data.content, sir0_data.data_pointer)
         } else {
-            parse_wan_from_sir0_content(&sir0_data.content, sir0_data.data_pointer, wan_type)
+            parse_wan_from_sir0_content(
+                &sir0_data.content,
+                sir0_data.data_pointer,
+                wan_type,
+                &ParseOptions {
+                    strict: self.strict,
+                    fallback_palette: self.fallback_palette,
+                    max_sequence_frames: self.max_sequence_frames,
+                },
+            )
         };
 
         parse_result.map_err(|e| {
@@ -957,14 +1534,14 @@ impl<'a> EffectAssetPipeline<'a> {
             .effect_bin
             .as_ref()
             .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "effect.bin not loaded"))?;
-        if file_index >= effect_bin.len() {
-            return Err(io::Error::new(
+        let screen_data = effect_bin.get(file_index).ok_or_else(|| {
+            io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("Screen effect file_index {} out of range", file_index),
-            ));
-        }
+            )
+        })?;
 
-        let screen = parse_screen_effect_from_data(&effect_bin[file_index])?;
+        let screen = parse_screen_effect_from_data(screen_data)?;
         if screen.frames.is_empty() {
             println!(
                 " -> WARNING: screen effect {} has no frames, skipping",
@@ -981,7 +1558,8 @@ impl<'a> EffectAssetPipeline<'a> {
             image::imageops::overlay(&mut sheet, &fimg, (i as u32 * 256) as i64, 0);
         }
 
-        let sheet_path = sprites_dir.join(format!("{}.png", effect_id));
+        let sheet_path =
+            sprites_dir.join(format!("{}.{}", effect_id, self.image_format.extension()));
         self.save_effect_sprite_png(&sheet, &sheet_path)?;
         println!(
             " -> SUCCESS: Screen sheet saved to {}",
@@ -992,40 +1570,40 @@ impl<'a> EffectAssetPipeline<'a> {
             .frames
             .iter()
             .map(|f| ScreenFrameInfo {
-                duration: ticks_to_seconds(f.duration),
+                duration: self.duration_unit.convert(f.duration),
                 alpha: f.alpha,
                 row_height: f.row_height,
             })
             .collect();
 
         Ok(Some(EffectDefinition::Screen(ScreenEffect {
-            effect_name: format!("ScreenEffect_{}", effect_id),
-            sprite_sheet: format!("res://effect_sprites/{}.png", effect_id),
-            frame_width: 256,
-            frame_height: 160,
+            screen_effect_param: effect_info.screen_effect_param,
+            sprite_sheet: format!(
+                "res://effect_sprites/{}.{}",
+                effect_id,
+                self.image_format.extension()
+            ),
+            frame_width: 256 * self.sprite_scale,
+            frame_height: 160 * self.sprite_scale,
             frame_count: screen.frames.len(),
             looping: effect_info.loop_flag,
             is_non_blocking: effect_info.is_non_blocking,
+            audio: AudioCue::from_effect(effect_info.sfx_id, effect_info.timing_offset),
             frames: frames_meta,
         })))
     }
 }
 
-/// Convert raw animation ticks (1/60s) to seconds.
-fn ticks_to_seconds(ticks: u16) -> f32 {
-    (ticks as f32 / 59.8261 * 10000.0).round() / 10000.0
-}
-
 /// Decompress (PKDPX or raw), unwrap SIR0, and parse a screen effect.
 fn parse_screen_effect_from_data(data: &[u8]) -> io::Result<ScreenEffectFile> {
-    let decompressed = if data.starts_with(b"PKDPX") {
+    let decompressed = if containers::detect(data) == ContainerKind::Pkdpx {
         PkdpxContainer::deserialise(data)?
             .decompress()
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
     } else {
         data.to_vec()
     };
-    if !decompressed.starts_with(b"SIR0") {
+    if containers::detect(&decompressed) != ContainerKind::Sir0 {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
             "Screen effect data is not SIR0",
@@ -1042,3 +1620,427 @@ fn parse_screen_effect_from_data(data: &[u8]) -> io::Result<ScreenEffectFile> {
 
     Ok(parse_screen_effect(&sir0.content, sir0.data_pointer)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graphics::wan::{
+            builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+            flags,
+        },
+        rom_builder::RomBuilder,
+        test_support::{scratch_dir, MemorySink},
+    };
+
+    /// ROM whose effect.bin holds the same one-frame effect WAN in every slot up to the base
+    /// palette file 292
+    fn fixture_rom(dir: &Path) -> Rom {
+        fixture_rom_with(dir, vec![BuilderSeqFrame::new(0, 4)])
+    }
+
+    /// Like `fixture_rom`, with `sequence` as the effect's only animation
+    fn fixture_rom_with(dir: &Path, sequence: Vec<BuilderSeqFrame>) -> Rom {
+        let effect = WanBuilder::new(WanType::Effect)
+            .palette(vec![(0, 0, 0), (250, 250, 120)])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, 252, 508)])
+            .group(vec![sequence])
+            .anim_header_unknowns([7, 0, 0, 0, 9])
+            .build_sir0();
+        let mut effect_bin = BinPack::default();
+        for _ in 0..=292 {
+            effect_bin.append(effect.clone());
+        }
+
+        let rom_path = dir.join("fixture.nds");
+        let rom_bytes = RomBuilder::new("C2SE")
+            .file("EFFECT/effect.bin", effect_bin.to_bytes(0))
+            .build();
+        fs::write(&rom_path, rom_bytes).unwrap();
+        Rom::new(&rom_path).unwrap()
+    }
+
+    #[test]
+    fn not_relocated_effect_palette_keeps_its_own_rows() {
+        let dir = scratch_dir("effects_palette_offset");
+        let effect = |colour, palette_offset| {
+            WanBuilder::new(WanType::Effect)
+                .palette(vec![(0, 0, 0), colour])
+                .image(vec![0x11; 32])
+                .frame(vec![BuilderPiece::new(0, 252, 508)])
+                .group(vec![vec![BuilderSeqFrame::new(0, 4)]])
+                .palette_offset(palette_offset)
+                .build_sir0()
+        };
+        let mut effect_bin = BinPack::default();
+        effect_bin.append(effect((250, 250, 120), 0));
+        effect_bin.append(effect((200, 0, 0), 0xFF));
+        effect_bin.append(effect((0, 0, 200), 0x10D));
+        for _ in 3..=292 {
+            effect_bin.append(effect((250, 250, 120), 0));
+        }
+        let rom_path = dir.join("fixture.nds");
+        let rom_bytes = RomBuilder::new("C2SE")
+            .file("EFFECT/effect.bin", effect_bin.to_bytes(0))
+            .build();
+        fs::write(&rom_path, rom_bytes).unwrap();
+        let rom = Rom::new(&rom_path).unwrap();
+
+        let mut pipeline = EffectAssetPipeline::new(&rom);
+        pipeline.load_bin_containers().unwrap();
+        pipeline.ensure_effect_wan_cached(1).unwrap();
+        pipeline.ensure_effect_wan_cached(2).unwrap();
+
+        // 0xFF stays out of the base palette instead of landing at row 15
+        let palette = &pipeline.wan_cache[&1].custom_palette;
+        assert_eq!(palette.len(), 1);
+        assert_eq!(palette[0][1], (200, 0, 0, 255));
+        // 0x10D merges at row 13
+        let palette = &pipeline.wan_cache[&2].custom_palette;
+        assert_eq!(palette.len(), 14);
+        assert_eq!(palette[0][1], (250, 250, 120, 255));
+        assert_eq!(palette[13][1], (0, 0, 200, 255));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn character_wan_in_effect_bin_is_skipped() {
+        let dir = scratch_dir("effects_character_wan");
+        let rom = fixture_rom(&dir);
+        let character = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (250, 250, 120)])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 4)]; 8])
+            .build_sir0();
+        let pipeline = EffectAssetPipeline::new(&rom);
+
+        let err = pipeline
+            .parse_wan_from_data(&character, WanType::Effect, false)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("WAN type mismatch"), "{}", err);
+        // The same data is fine where a character WAN is expected
+        assert!(pipeline
+            .parse_wan_from_data(&character, WanType::Character, false)
+            .is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn out_of_range_effect_index_is_an_error_not_a_panic() {
+        let dir = scratch_dir("effects_out_of_range");
+        let rom = fixture_rom(&dir);
+        let mut pipeline = EffectAssetPipeline::new(&rom);
+        pipeline.load_bin_containers().unwrap();
+
+        let err = pipeline.ensure_effect_wan_cached(5000).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "Effect index 5000 out of range for effect.bin (293 files)"
+        );
+        assert!(!pipeline.wan_cache.contains_key(&5000));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn base_palette_export_has_a_row_per_palette() {
+        let dir = scratch_dir("effects_base_palette");
+        let effect = |palettes: usize| {
+            let mut builder = WanBuilder::new(WanType::Effect);
+            for row in 0..palettes {
+                builder = builder.palette(vec![(0, 0, 0), (row as u8 * 40, 0, 0)]);
+            }
+            builder
+                .image(vec![0x11; 32])
+                .frame(vec![BuilderPiece::new(0, 252, 508)])
+                .group(vec![vec![BuilderSeqFrame::new(0, 4)]])
+                .build_sir0()
+        };
+        let mut effect_bin = BinPack::default();
+        for _ in 0..292 {
+            effect_bin.append(effect(1));
+        }
+        effect_bin.append(effect(3));
+        let rom_path = dir.join("fixture.nds");
+        let rom_bytes = RomBuilder::new("C2SE")
+            .file("EFFECT/effect.bin", effect_bin.to_bytes(0))
+            .build();
+        fs::write(&rom_path, rom_bytes).unwrap();
+        let rom = Rom::new(&rom_path).unwrap();
+
+        let output_dir = dir.join("output");
+        let sink = Arc::new(MemorySink::default());
+        let mut pipeline = EffectAssetPipeline::new(&rom)
+            .with_base_palette_export(true)
+            .with_sink(sink.clone());
+        pipeline
+            .run(
+                &HashMap::new(),
+                &HashMap::new(),
+                &output_dir,
+                &dir.join("progress.json"),
+                1,
+            )
+            .unwrap();
+
+        // Palette-only parsing reads to the end of the file, so the fixture's trailing WAN
+        // data shows up as extra rows after the three real ones
+        let loaded_rows = pipeline.base_palette.as_ref().unwrap().len();
+        assert!(loaded_rows >= 3);
+        let effect_dir = output_dir.join("EFFECT");
+        let rows: Vec<Vec<[u8; 4]>> =
+            serde_json::from_slice(&sink.file(&effect_dir.join("base_palette.json")).unwrap())
+                .unwrap();
+        assert_eq!(rows.len(), loaded_rows);
+        assert_eq!(rows[1][1], [40, 0, 0, 255]);
+        assert_eq!(rows[2][1], [80, 0, 0, 255]);
+        let swatch =
+            image::load_from_memory(&sink.file(&effect_dir.join("base_palette.png")).unwrap())
+                .unwrap();
+        assert_eq!(swatch.height() as usize, loaded_rows);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Move entry with the four effect layer IDs and nothing else set
+    fn move_info(effect_ids: [u16; 4], attachment_point_idx: i8) -> MoveAnimationInfo {
+        MoveAnimationInfo {
+            effect_id_1: effect_ids[0],
+            effect_id_2: effect_ids[1],
+            effect_id_3: effect_ids[2],
+            effect_id_4: effect_ids[3],
+            projectile_wave_pattern: 0,
+            dual_target: false,
+            skip_fade_in: false,
+            face_direction_with_delay: false,
+            add_delay: false,
+            flag_bit7: false,
+            projectile_speed: 0,
+            pokemon_anim_id: 0,
+            attachment_point_idx,
+            sound_effect_id: 0x3F00,
+            special_animations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn effectless_move_is_only_listed_when_asked() {
+        let dir = scratch_dir("effects_effectless_move");
+        let rom = fixture_rom(&dir);
+        let moves_map = HashMap::from([(14, move_info([0; 4], -1))]);
+
+        let populate = |include_effectless_moves| {
+            let mut index = MoveEffectsIndex::new(DurationUnit::default());
+            EffectAssetPipeline::new(&rom)
+                .with_effectless_moves(include_effectless_moves)
+                .populate_moves_data(&mut index, &moves_map);
+            index
+        };
+
+        assert!(populate(false).moves.is_empty());
+        let index = populate(true);
+        let entry = &index.moves["14"];
+        assert!(entry.effects.is_empty());
+        assert!(entry.attachment_point.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn sprite_effect_info(file_index: u32) -> EffectAnimationInfo {
+        EffectAnimationInfo {
+            anim_type: AnimType::WanOther,
+            file_index,
+            palette_index: 0,
+            animation_index: 0,
+            sfx_id: -1,
+            timing_offset: 0,
+            screen_effect_param: 0,
+            attachment_point: -1,
+            is_non_blocking: false,
+            loop_flag: false,
+        }
+    }
+
+    #[test]
+    fn asset_index_keeps_the_anim_header_unknowns() {
+        let dir = scratch_dir("effects_anim_header_unknowns");
+        let rom = fixture_rom(&dir);
+        let output_dir = dir.join("output");
+        let effects = HashMap::from([(0u16, sprite_effect_info(5))]);
+
+        let sink = Arc::new(MemorySink::default());
+        EffectAssetPipeline::new(&rom)
+            .with_sink(sink.clone())
+            .run(
+                &effects,
+                &HashMap::new(),
+                &output_dir,
+                &dir.join("progress.json"),
+                1,
+            )
+            .unwrap();
+
+        let index: serde_json::Value =
+            serde_json::from_slice(&sink.file(&output_dir.join("asset_index.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            index["effects"]["0"]["anim_header_unknowns"],
+            serde_json::json!([7, 0, 0, 0, 9]),
+            "{}",
+            index
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sheet_trim_records_its_offset_in_the_effect_definition() {
+        let dir = scratch_dir("effects_sheet_trim");
+        let rom = fixture_rom(&dir);
+        let output_dir = dir.join("output");
+        let effects = HashMap::from([(0u16, sprite_effect_info(5))]);
+
+        let effect = |trim_sheets| {
+            let sink = Arc::new(MemorySink::default());
+            EffectAssetPipeline::new(&rom)
+                .with_sheet_trim(trim_sheets)
+                .with_sink(sink.clone())
+                .run(
+                    &effects,
+                    &HashMap::new(),
+                    &output_dir,
+                    &dir.join("progress.json"),
+                    1,
+                )
+                .unwrap();
+            let index: serde_json::Value =
+                serde_json::from_slice(&sink.file(&output_dir.join("asset_index.json")).unwrap())
+                    .unwrap();
+            index["effects"]["0"].clone()
+        };
+
+        assert!(effect(false).get("trim_offset").is_none());
+        // The fixture's single 8x8 tile already fills its frame, so nothing is cut off
+        let trimmed = effect(true);
+        assert_eq!(
+            trimmed["trim_offset"],
+            serde_json::json!([0, 0]),
+            "{}",
+            trimmed
+        );
+        assert_eq!(trimmed["frame_width"], 8);
+        assert_eq!(trimmed["frame_height"], 8);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skip_existing_reuses_up_to_date_effects_without_writing() {
+        let dir = scratch_dir("effects_skip_existing");
+        let rom = fixture_rom(&dir);
+        let output_dir = dir.join("output");
+        let progress = dir.join("progress.json");
+        // File 0 is pre-cached with the base palette, so use one that is only parsed on demand
+        let effects = HashMap::from([(0u16, sprite_effect_info(5))]);
+        let moves = HashMap::new();
+
+        let first = EffectAssetPipeline::new(&rom)
+            .run(&effects, &moves, &output_dir, &progress, 1)
+            .unwrap();
+        assert_eq!(first.processed, 1);
+        assert!(output_dir.join("EFFECT").join("0.png").is_file());
+
+        let sink = Arc::new(MemorySink::default());
+        let mut pipeline = EffectAssetPipeline::new(&rom)
+            .with_skip_existing(true)
+            .with_sink(sink.clone());
+        let second = pipeline
+            .run(&effects, &moves, &output_dir, &progress, 1)
+            .unwrap();
+
+        assert_eq!((second.processed, second.skipped), (0, 1));
+        assert_eq!(sink.len(), 0);
+        assert!(!pipeline.wan_cache.contains_key(&5));
+        let progress: serde_json::Value =
+            serde_json::from_slice(&fs::read(&progress).unwrap()).unwrap();
+        assert_eq!(progress["status"], "skipped");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn effects_sharing_a_file_and_animation_render_once() {
+        let dir = scratch_dir("effects_render_cache");
+        let rom = fixture_rom(&dir);
+        let output_dir = dir.join("output");
+        let effects = HashMap::from([(0u16, sprite_effect_info(5)), (1, sprite_effect_info(5))]);
+
+        let sink = Arc::new(MemorySink::default());
+        let mut pipeline = EffectAssetPipeline::new(&rom).with_sink(sink.clone());
+        let report = pipeline
+            .run(
+                &effects,
+                &HashMap::new(),
+                &output_dir,
+                &dir.join("progress.json"),
+                2,
+            )
+            .unwrap();
+
+        assert_eq!(report.processed, 2);
+        assert_eq!(pipeline.render_cache.len(), 1);
+        assert_eq!(pipeline.render_cache_hits, 1);
+        for id in 0..2 {
+            let sheet = output_dir.join("EFFECT").join(format!("{}.png", id));
+            assert!(
+                sink.file(&sheet).is_some(),
+                "{} is missing",
+                sheet.display()
+            );
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn flagged_sequence_frame_is_the_hit_frame() {
+        let dir = scratch_dir("effects_hit_frame");
+        let mut hit = BuilderSeqFrame::new(0, 4);
+        hit.flag = flags::FRAME_HIT_MASK;
+        let rom = fixture_rom_with(
+            &dir,
+            vec![BuilderSeqFrame::new(0, 4), hit, BuilderSeqFrame::new(0, 4)],
+        );
+        let output_dir = dir.join("output");
+        let effects = HashMap::from([(0u16, sprite_effect_info(5))]);
+
+        let sink = Arc::new(MemorySink::default());
+        EffectAssetPipeline::new(&rom)
+            .with_sink(sink.clone())
+            .run(
+                &effects,
+                &HashMap::new(),
+                &output_dir,
+                &dir.join("progress.json"),
+                1,
+            )
+            .unwrap();
+
+        let index: serde_json::Value =
+            serde_json::from_slice(&sink.file(&output_dir.join("asset_index.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            index["effects"]["0"]["animations"]["play"]["hit_frame"], 1,
+            "{}",
+            index
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
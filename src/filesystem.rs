@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, io::Cursor};
 
 // A FatEntry contains the file location
 pub struct FatEntry {
@@ -58,20 +58,26 @@ impl FileAllocationTable {
         Ok(FileAllocationTable { entries })
     }
 
-    pub fn get_file_data<'a>(&self, file_id: usize, rom_data: &'a [u8]) -> Option<&'a [u8]> {
-        if file_id >= self.entries.len() {
-            return None;
-        }
-
-        let entry = &self.entries[file_id];
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
 
-        if entry.start_address as usize > rom_data.len()
-            || entry.end_address as usize > rom_data.len()
-        {
-            return None;
-        }
+    /// Bytes of file `file_id`. A file may end exactly at the end of a trimmed ROM, anything
+    /// reaching past it or with its end before its start is `None`.
+    pub fn get_file_data<'a>(&self, file_id: usize, rom_data: &'a [u8]) -> Option<&'a [u8]> {
+        let entry = self.entries.get(file_id)?;
+        rom_data.get(entry.start_address as usize..entry.end_address as usize)
+    }
 
-        Some(&rom_data[entry.start_address as usize..entry.end_address as usize])
+    /// Same as `get_file_data`, but wrapped in a `Cursor` positioned at the start of the file.
+    ///
+    /// No bytes are copied, the cursor borrows `rom_data` and cannot outlive it.
+    pub fn get_file_cursor<'a>(
+        &self,
+        file_id: usize,
+        rom_data: &'a [u8],
+    ) -> Option<Cursor<&'a [u8]>> {
+        self.get_file_data(file_id, rom_data).map(Cursor::new)
     }
 }
 
@@ -88,7 +94,7 @@ pub enum FntEntry {
 
 /// Base ID for directories in the NDS filesystem
 /// Directories have IDs starting from 0xF000, with their index added to this base
-const DIRECTORY_ID_BASE: u16 = 0xF000;
+pub(crate) const DIRECTORY_ID_BASE: u16 = 0xF000;
 const ESTIMATED_ENTRIES_PER_SUBTABLE: usize = 16;
 const ESTIMATED_FILES_PER_DIRECTORY: usize = 8;
 
@@ -291,6 +297,11 @@ impl FileNameTable {
         Ok(())
     }
 
+    /// Highest file ID named by the FNT, or `None` if it lists no files
+    pub fn max_file_id(&self) -> Option<u16> {
+        self.file_names.keys().copied().max()
+    }
+
     /// Get a file ID for a given path
     pub fn get_file_id(&self, path: &str) -> Option<u16> {
         let parts: Vec<&str> = path.split('/').collect();
@@ -354,3 +365,30 @@ impl FileNameTable {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{binary_utils, rom_builder::RomBuilder};
+
+    #[test]
+    fn file_cursor_reads_the_start_of_monster_md() {
+        let rom_data = RomBuilder::new("C2SE")
+            .file("BALANCE/monster.md", b"MD\0\0\x2A\0\0\0".to_vec())
+            .build();
+        let header_u32 =
+            |offset: usize| u32::from_le_bytes(rom_data[offset..offset + 4].try_into().unwrap());
+        let fnt = FileNameTable::read_from_rom(&rom_data, header_u32(0x40)).unwrap();
+        let fat = FileAllocationTable::read_from_rom(&rom_data, header_u32(0x48), header_u32(0x4C))
+            .unwrap();
+
+        let file_id = fnt.get_file_id("BALANCE/monster.md").unwrap() as usize;
+        let mut cursor = fat.get_file_cursor(file_id, &rom_data).unwrap();
+        assert_eq!(cursor.position(), 0);
+        assert_eq!(binary_utils::read_bytes(&mut cursor, 4).unwrap(), b"MD\0\0");
+        assert_eq!(binary_utils::read_u32_le(&mut cursor).unwrap(), 42);
+        assert!(binary_utils::read_u8(&mut cursor).is_err());
+
+        assert!(fat.get_file_cursor(file_id + 1, &rom_data).is_none());
+    }
+}
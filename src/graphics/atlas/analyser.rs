@@ -65,6 +65,7 @@ pub struct AnalysedFrame {
 pub fn analyse_frames(
     wan_files: &HashMap<String, WanFile>,
     dex_num: u16,
+    max_frame_dimension: u32,
 ) -> Result<FrameAnalysis, AtlasError> {
     let mut ordered_frames = Vec::new();
 
@@ -104,10 +105,17 @@ pub fn analyse_frames(
                             }
 
                             // Extract the frame image
-                            let frame_image = match extract_frame(wan_file, frame_index) {
-                                Ok(img) => img,
-                                Err(_) => continue,
-                            };
+                            let frame_image =
+                                match extract_frame(wan_file, frame_index, max_frame_dimension) {
+                                    Ok(img) => img,
+                                    Err(e) => {
+                                        println!(
+                                            "  Warning: Skipping frame {}: {}",
+                                            frame_index, e
+                                        );
+                                        continue;
+                                    }
+                                };
 
                             // Get the WAN coordinate bounds for this frame
                             let wan_bounds = get_wan_frame_bounds(wan_file, frame_index);
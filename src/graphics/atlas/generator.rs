@@ -3,14 +3,17 @@
 //! Handles layout calculation, frame positioning using anchor-based system,
 //! deduplication, and final atlas image creation.
 
-use crate::graphics::atlas::analyser::{calculate_anchor_point, FrameAnalysis};
+use crate::graphics::{
+    atlas::analyser::{calculate_anchor_point, FrameAnalysis},
+    wan::WanFile,
+};
 
 use std::{
     collections::{hash_map::Entry, HashMap},
     hash::{Hash, Hasher},
 };
 
-use image::{imageops, RgbaImage};
+use image::{imageops, GrayImage, Luma, RgbaImage};
 use twox_hash::XxHash64;
 
 #[derive(Debug, Clone)]
@@ -191,6 +194,26 @@ pub fn deduplicate_frames(frames: &[RgbaImage]) -> (Vec<RgbaImage>, Vec<usize>)
     (unique_frames_vec, frame_mapping)
 }
 
+/// Moves each frame's opaque content to the top left of its cell, so frames that only differ
+/// in transparent margins compare equal in `deduplicate_frames`.
+///
+/// Also returns, per frame, where its content sat in the cell before trimming.
+pub fn trim_frames(frames: &[RgbaImage]) -> (Vec<RgbaImage>, Vec<[u32; 2]>) {
+    frames.iter().map(trim_to_corner).unzip()
+}
+
+/// Moves the opaque content of `frame` to its top left corner, keeping the frame size.
+/// Fully transparent frames are returned as is with a zero offset.
+fn trim_to_corner(frame: &RgbaImage) -> (RgbaImage, [u32; 2]) {
+    let Some([x, y, width, height]) = opaque_bounds(frame) else {
+        return (frame.clone(), [0, 0]);
+    };
+    let mut trimmed = RgbaImage::new(frame.width(), frame.height());
+    let content = imageops::crop_imm(frame, x, y, width, height).to_image();
+    imageops::replace(&mut trimmed, &content, 0, 0);
+    (trimmed, [x, y])
+}
+
 /// Calculate a 64-bit hash of an image frame for fast comparison
 fn calculate_frame_hash(frame: &RgbaImage) -> u64 {
     let mut hasher = XxHash64::default();
@@ -211,3 +234,124 @@ fn frames_are_identical(a: &RgbaImage, b: &RgbaImage) -> bool {
 fn overlay_image(canvas: &mut RgbaImage, image: &RgbaImage, x: i32, y: i32) {
     imageops::overlay(canvas, image, x as i64, y as i64);
 }
+
+/// Nearest-neighbour upscale by an integer factor so pixel art stays crisp.
+/// A factor of 0 or 1 returns an unchanged copy.
+pub fn upscale_nearest(image: &RgbaImage, scale: u32) -> RgbaImage {
+    if scale <= 1 {
+        return image.clone();
+    }
+    image::imageops::resize(
+        image,
+        image.width() * scale,
+        image.height() * scale,
+        image::imageops::FilterType::Nearest,
+    )
+}
+
+/// Tight box around the non-transparent pixels of a frame as `[x, y, width, height]` in
+/// frame-local pixels, or `None` if the frame is fully transparent.
+pub fn opaque_bounds(frame: &RgbaImage) -> Option<[u32; 4]> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in frame.enumerate_pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        bounds = Some(match bounds {
+            None => (x, y, x, y),
+            Some((min_x, min_y, max_x, max_y)) => {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            }
+        });
+    }
+    bounds.map(|(min_x, min_y, max_x, max_y)| [min_x, min_y, max_x - min_x + 1, max_y - min_y + 1])
+}
+
+/// Collects the distinct colours of an atlas in first-seen order, with fully transparent
+/// pixels folded into a single leading `[0, 0, 0, 0]` entry.
+pub fn extract_palette(atlas: &RgbaImage) -> Vec<[u8; 4]> {
+    let mut palette = vec![[0, 0, 0, 0]];
+    for pixel in atlas.pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        if !palette.contains(&pixel.0) {
+            palette.push(pixel.0);
+        }
+    }
+    palette
+}
+
+/// Palette row 0 of `wan` in the ROM's index order, in the same form as `extract_palette`.
+/// Entry 0 is always transparent, the renderer never draws it.
+pub fn source_palette(wan: &WanFile) -> Option<Vec<[u8; 4]>> {
+    let row = wan.palette(0)?;
+    Some(
+        row.iter()
+            .enumerate()
+            .map(|(i, &(r, g, b, a))| if i == 0 { [0, 0, 0, 0] } else { [r, g, b, a] })
+            .collect(),
+    )
+}
+
+/// Maps every atlas pixel to its position in `palette` (from `extract_palette`), so the atlas
+/// can be redrawn at runtime with a swapped palette. Transparent pixels map to 0.
+///
+/// Returns `None` if the palette has more than 256 entries or is missing a colour.
+pub fn index_atlas(atlas: &RgbaImage, palette: &[[u8; 4]]) -> Option<GrayImage> {
+    if palette.len() > 256 {
+        return None;
+    }
+    let lookup: HashMap<[u8; 4], u8> = palette
+        .iter()
+        .enumerate()
+        .map(|(i, &colour)| (colour, i as u8))
+        .collect();
+
+    let mut indices = GrayImage::new(atlas.width(), atlas.height());
+    for (x, y, pixel) in atlas.enumerate_pixels() {
+        let index = if pixel[3] == 0 {
+            0
+        } else {
+            *lookup.get(&pixel.0)?
+        };
+        indices.put_pixel(x, y, Luma([index]));
+    }
+    Some(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// 8x8 transparent frame with a 2x2 opaque block at (x, y)
+    fn frame_with_block(x: u32, y: u32) -> RgbaImage {
+        let mut frame = RgbaImage::new(8, 8);
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            frame.put_pixel(x + dx, y + dy, Rgba([200, 40, 40, 255]));
+        }
+        frame
+    }
+
+    #[test]
+    fn frames_differing_only_in_margins_collapse_once_trimmed() {
+        let frames = vec![frame_with_block(1, 2), frame_with_block(5, 3)];
+
+        let (unique, _) = deduplicate_frames(&frames);
+        assert_eq!(unique.len(), 2);
+
+        let (trimmed, trim_offsets) = trim_frames(&frames);
+        let (unique, mapping) = deduplicate_frames(&trimmed);
+        assert_eq!(unique.len(), 1);
+        assert_eq!(mapping, vec![0, 0]);
+        assert_eq!(trim_offsets, vec![[1, 2], [5, 3]]);
+        assert_eq!(opaque_bounds(&unique[0]), Some([0, 0, 2, 2]));
+    }
+
+    #[test]
+    fn lower_right_pixels_give_a_lower_right_bounding_box() {
+        assert_eq!(opaque_bounds(&frame_with_block(6, 6)), Some([6, 6, 2, 2]));
+        assert_eq!(opaque_bounds(&RgbaImage::new(8, 8)), None);
+    }
+}
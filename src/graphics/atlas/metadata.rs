@@ -3,16 +3,21 @@
 //! Creates a JSON file describing the atlas layout, animations,
 //! directions, frame properties, and anchor point for positioning.
 
-use std::{collections::HashMap, fs::File, path::Path};
+use std::{collections::HashMap, path::Path};
 
+use image::RgbaImage;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    data::animation_metadata as AmData,
+    data::{animation_info::AnimPointType, animation_metadata as AmData},
     graphics::{
-        atlas::{analyser::FrameAnalysis, generator::AtlasLayout},
-        wan::{AnimationStructure, WanFile},
+        atlas::{
+            analyser::FrameAnalysis,
+            generator::{self, AtlasLayout},
+        },
+        wan::{self, Animation, AnimationStructure, DurationUnit, WanFile},
     },
+    output::{ImageFormat, JsonStyle, OutputSink},
 };
 
 const SINGLE_DIRECTION_ANIMATIONS: &[u8] = &[5];
@@ -29,22 +34,47 @@ pub struct AtlasMetadata {
     pub anchor_y: i32,
     pub total_frames_in_atlas: u32,
     pub shadow_size: u8,
+    /// Unit of every `FrameInfo::duration` in this file
+    pub duration_unit: DurationUnit,
+    /// Colours used by the atlas as `[r, g, b, a]`, transparent first. Only written for
+    /// indexed output or `index_image`, so recolouring tools don't need to parse the PNG's
+    /// PLTE chunk. An indexed PNG's PLTE holds the same colours in the same order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Vec<[u8; 4]>>,
+    /// Greyscale PNG the size of the atlas where each pixel is an index into `palette`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_image: Option<String>,
+    /// Placeholder for an entry with nothing renderable, the atlas is a 1x1 transparent image
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub empty: bool,
+    /// Raw Unk#6 to Unk#10 of each source WAN's animation info header keyed by source bin,
+    /// kept for format research
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub anim_header_unknowns: HashMap<String, [u16; 5]>,
+    /// Named animations (Walk, Attack, Sleep, ...) keyed by name. Each lists its directions,
+    /// and each direction its frames in playback order with the atlas frame index and duration.
     pub animations: HashMap<String, AtlasAnimationInfo>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AtlasAnimationInfo {
+    /// Index of the animation group in the WAN
     pub anim_id: u8,
     pub name: String,
     pub source_bin: String,
     pub directions: Vec<DirectionInfo>,
+    /// Distinct directions in the WAN group. Directions that repeat an earlier one are not
+    /// counted, so 1 means the engine should reuse one frame set rather than rotate.
+    pub direction_count: usize,
     /// Only used for Sleep animation group
     pub single_direction: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DirectionInfo {
+    /// Direction index within the animation group, 0 is down and the rest go anticlockwise
     pub direction: u8,
+    /// Sequence frames in playback order, repeated atlas frames included
     pub frames: Vec<FrameInfo>,
 }
 
@@ -56,8 +86,9 @@ pub struct FrameInfo {
     pub sheet_x: u32,
     /// Top left Y coordinate of this frame's cell in the atlas sheet (in pixels).
     pub sheet_y: u32,
-    /// Duration this frame is displayed (in game ticks, typically 1/60th sec).
-    pub duration: u16,
+    /// Duration this frame is displayed, in the metadata's `duration_unit`.
+    #[serde(serialize_with = "wan::serialize_duration")]
+    pub duration: f32,
     /// X offset to apply when drawing (from WAN SequenceFrame data).
     /// These are additive animation offsets, NOT positioning offsets.
     pub offset_x: i32,
@@ -68,14 +99,28 @@ pub struct FrameInfo {
     pub shadow_offset_x: i32,
     /// Y offset for placing the shadow sprite, relative to entity origin.
     pub shadow_offset_y: i32,
-    /// Head position relative to entity origin (0,0).
+    /// Head position relative to entity origin (0,0). The four body part positions are
+    /// omitted when the WAN has no offset entry or offsets are disabled in `AtlasConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub head_pos: Option<[i32; 2]>,
     /// Left hand position relative to entity origin (0,0).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub lhand_pos: Option<[i32; 2]>,
     /// Right hand position relative to entity origin (0,0).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub rhand_pos: Option<[i32; 2]>,
     /// Centre position relative to entity origin (0,0).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub centre_pos: Option<[i32; 2]>,
+    /// Opaque pixel extent of the frame as `[x, y, width, height]` relative to its cell, for
+    /// generating hitboxes. Only written when enabled in `AtlasConfig`, and omitted for fully
+    /// transparent frames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opaque_bounds: Option<[u32; 4]>,
+    /// With `trim_before_dedup`, where this frame's content sat in its cell before it was moved
+    /// to the cell's top left corner. Draw the atlas cell shifted by this to re-centre it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim_offset: Option<[u32; 2]>,
     /// True if the primary/secondary effect should play during this frame.
     pub is_effect_frame: bool,
     /// True if the animation should return to idle after this frame.
@@ -84,16 +129,128 @@ pub struct FrameInfo {
     pub is_rush_frame: bool,
 }
 
-/// Generates the complete AtlasMetadata structure
+impl AtlasMetadata {
+    /// Metadata for a placeholder atlas, used when an entry has no renderable sprite
+    pub fn placeholder(
+        dex_num: u16,
+        shadow_size: u8,
+        duration_unit: DurationUnit,
+        image_format: ImageFormat,
+    ) -> Self {
+        AtlasMetadata {
+            atlas_image: format!("{:03}_atlas.{}", dex_num, image_format.extension()),
+            frame_width: 1,
+            frame_height: 1,
+            anchor_x: 0,
+            anchor_y: 0,
+            total_frames_in_atlas: 0,
+            shadow_size,
+            duration_unit,
+            palette: None,
+            index_image: None,
+            empty: true,
+            anim_header_unknowns: HashMap::new(),
+            animations: HashMap::new(),
+        }
+    }
+
+    /// Multiplies every pixel coordinate by `scale` to match an upscaled atlas image.
+    pub fn scale(&mut self, scale: u32) {
+        if scale <= 1 {
+            return;
+        }
+        let s = scale as i32;
+        let scale_pos = |pos: &mut Option<[i32; 2]>| {
+            if let Some([x, y]) = pos {
+                *x *= s;
+                *y *= s;
+            }
+        };
+
+        self.frame_width *= scale;
+        self.frame_height *= scale;
+        self.anchor_x *= s;
+        self.anchor_y *= s;
+
+        for frame in self
+            .animations
+            .values_mut()
+            .flat_map(|anim| anim.directions.iter_mut())
+            .flat_map(|dir| dir.frames.iter_mut())
+        {
+            frame.sheet_x *= scale;
+            frame.sheet_y *= scale;
+            frame.offset_x *= s;
+            frame.offset_y *= s;
+            frame.shadow_offset_x *= s;
+            frame.shadow_offset_y *= s;
+            scale_pos(&mut frame.head_pos);
+            scale_pos(&mut frame.lhand_pos);
+            scale_pos(&mut frame.rhand_pos);
+            scale_pos(&mut frame.centre_pos);
+            if let Some(bounds) = &mut frame.opaque_bounds {
+                bounds.iter_mut().for_each(|v| *v *= scale);
+            }
+            if let Some(offset) = &mut frame.trim_offset {
+                offset.iter_mut().for_each(|v| *v *= scale);
+            }
+        }
+    }
+
+    /// Fills every frame's `opaque_bounds` from its unique atlas frame, shifted back by the
+    /// frame's `trim_offset` so they stay relative to the untrimmed cell
+    pub fn set_opaque_bounds(&mut self, unique_frames: &[RgbaImage]) {
+        let bounds: Vec<Option<[u32; 4]>> =
+            unique_frames.iter().map(generator::opaque_bounds).collect();
+        for frame in self
+            .animations
+            .values_mut()
+            .flat_map(|anim| anim.directions.iter_mut())
+            .flat_map(|dir| dir.frames.iter_mut())
+        {
+            let [dx, dy] = frame.trim_offset.unwrap_or_default();
+            frame.opaque_bounds = bounds
+                .get(frame.idx as usize)
+                .copied()
+                .flatten()
+                .map(|[x, y, width, height]| [x + dx, y + dy, width, height]);
+        }
+    }
+}
+
+/// Counts the distinct directions of an animation group. Empty directions are skipped and a
+/// direction playing the same meta-frames with the same timing and offsets as an earlier one
+/// is an alias of it. Never less than 1.
+fn distinct_direction_count(group: &[Animation]) -> usize {
+    let mut distinct = Vec::new();
+    for direction in group.iter().filter(|anim| !anim.frames.is_empty()) {
+        let sequence: Vec<_> = direction
+            .frames
+            .iter()
+            .map(|frame| (frame.frame_index, frame.duration, frame.offset))
+            .collect();
+        if !distinct.contains(&sequence) {
+            distinct.push(sequence);
+        }
+    }
+    distinct.len().max(1)
+}
+
+/// Generates the complete AtlasMetadata structure. `trim_offsets` is indexed like
+/// `frame_mapping` and left empty when frames weren't trimmed.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_metadata(
     wan_files: &HashMap<String, WanFile>,
     analysis: &FrameAnalysis,
-    frame_width: u32,
-    frame_height: u32,
     layout: &AtlasLayout,
     frame_mapping: &[usize],
+    trim_offsets: &[[u32; 2]],
     shadow_size: u8,
+    duration_unit: DurationUnit,
+    include_offsets: bool,
+    image_format: ImageFormat,
 ) -> Result<AtlasMetadata, super::AtlasError> {
+    let (frame_width, frame_height) = layout.frame_size;
     let mut output_animations: HashMap<String, AtlasAnimationInfo> = HashMap::new();
     let total_unique_frames = frame_mapping.iter().max().map_or(0, |&max_idx| max_idx + 1);
 
@@ -148,17 +305,19 @@ pub fn generate_metadata(
         // The double-push hack inflates meta_frame indices by 1 relative to the
         // offset table, so subtract 1 to get the correct offset entry
         let offset_index = analysed_frame.original_wan_frame_index.saturating_sub(1);
-        let frame_offset_data = wan_file.body_part_offset_data.get(offset_index);
-
-        // Body part offsets are in WAN coordinates (relative to entity origin)
-        let convert_offset = |orig_offset: Option<(i16, i16)>| -> Option<[i32; 2]> {
-            orig_offset.map(|(ox, oy)| [ox as i32, oy as i32])
+        // Body part offsets are in WAN coordinates (relative to entity origin), these are the
+        // positions move effects attach to
+        let attachment = |point: AnimPointType| -> Option<[i32; 2]> {
+            wan_file
+                .attachment_position(offset_index, point)
+                .filter(|_| include_offsets)
+                .map(|(ox, oy)| [ox as i32, oy as i32])
         };
 
-        let head_pos = convert_offset(frame_offset_data.map(|fod| fod.head));
-        let lhand_pos = convert_offset(frame_offset_data.map(|fod| fod.lhand));
-        let rhand_pos = convert_offset(frame_offset_data.map(|fod| fod.rhand));
-        let centre_pos = convert_offset(frame_offset_data.map(|fod| fod.centre));
+        let head_pos = attachment(AnimPointType::Head);
+        let lhand_pos = attachment(AnimPointType::LeftHand);
+        let rhand_pos = attachment(AnimPointType::RightHand);
+        let centre_pos = attachment(AnimPointType::Centre);
 
         // Shadow offset is relative to entity origin
         let shadow_offset_x = analysed_frame.original_shadow_x as i32;
@@ -168,12 +327,14 @@ pub fn generate_metadata(
             idx: unique_atlas_index_u32,
             sheet_x,
             sheet_y,
-            duration: original_seq_frame.duration,
+            duration: duration_unit.convert(original_seq_frame.duration),
             // These are the additive animation offsets from the SequenceFrame
             offset_x: original_seq_frame.offset.0 as i32,
             offset_y: original_seq_frame.offset.1 as i32,
             shadow_offset_x,
             shadow_offset_y,
+            opaque_bounds: None,
+            trim_offset: trim_offsets.get(original_global_index).copied(),
             is_effect_frame: original_seq_frame.is_effect_point(),
             is_return_frame: original_seq_frame.is_return_point(),
             is_rush_frame: original_seq_frame.is_rush_point(),
@@ -185,12 +346,23 @@ pub fn generate_metadata(
 
         let anim_output_info = output_animations
             .entry(animation_info.name.to_string())
-            .or_insert_with(|| AtlasAnimationInfo {
-                anim_id: *anim_id,
-                name: animation_info.name.to_string(),
-                source_bin: analysed_frame.source_bin.clone(),
-                directions: Vec::new(),
-                single_direction: SINGLE_DIRECTION_ANIMATIONS.contains(anim_id),
+            .or_insert_with(|| {
+                let single_direction = SINGLE_DIRECTION_ANIMATIONS.contains(anim_id);
+                let direction_count = match &wan_file.animations {
+                    _ if single_direction => 1,
+                    AnimationStructure::Character(groups) => groups
+                        .get(analysed_frame.group_idx)
+                        .map_or(1, |group| distinct_direction_count(group)),
+                    AnimationStructure::Effect(_) => 1,
+                };
+                AtlasAnimationInfo {
+                    anim_id: *anim_id,
+                    name: animation_info.name.to_string(),
+                    source_bin: analysed_frame.source_bin.clone(),
+                    directions: Vec::new(),
+                    direction_count,
+                    single_direction,
+                }
             });
 
         let dir_output_info = match anim_output_info
@@ -217,20 +389,58 @@ pub fn generate_metadata(
     }
 
     Ok(AtlasMetadata {
-        atlas_image: format!("{:03}_atlas.png", analysis.dex_num),
+        atlas_image: format!("{:03}_atlas.{}", analysis.dex_num, image_format.extension()),
         frame_width,
         frame_height,
         anchor_x: layout.anchor_x,
         anchor_y: layout.anchor_y,
         total_frames_in_atlas: total_unique_frames as u32,
         shadow_size,
+        duration_unit,
+        palette: None,
+        index_image: None,
+        empty: false,
+        anim_header_unknowns: wan_files
+            .iter()
+            .map(|(source_bin, wan_file)| (source_bin.clone(), wan_file.anim_header_unknowns))
+            .collect(),
         animations: output_animations,
     })
 }
 
 /// Saves the generated AtlasMetadata to a JSON file
-pub fn save_metadata(metadata: &AtlasMetadata, path: &Path) -> Result<(), super::AtlasError> {
-    let file = File::create(path)?;
-    serde_json::to_writer_pretty(file, metadata)?;
+pub fn save_metadata(
+    metadata: &AtlasMetadata,
+    path: &Path,
+    sink: &dyn OutputSink,
+    json_style: JsonStyle,
+) -> Result<(), super::AtlasError> {
+    let json = json_style.to_vec(metadata)?;
+    sink.write_file(path, &json)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wan::SequenceFrame;
+
+    fn direction(frame_index: u16) -> Animation {
+        Animation::new(vec![SequenceFrame::new(frame_index, 4, 0, (0, 0), (0, 0))])
+    }
+
+    #[test]
+    fn direction_count_skips_aliases_and_empty_directions() {
+        let full: Vec<Animation> = (0..8).map(direction).collect();
+        assert_eq!(distinct_direction_count(&full), 8);
+
+        // Sleep style group, every direction plays the same frames
+        let single = vec![direction(3); 8];
+        assert_eq!(distinct_direction_count(&single), 1);
+
+        let mut one_filled = vec![Animation::empty(); 8];
+        one_filled[0] = direction(0);
+        assert_eq!(distinct_direction_count(&one_filled), 1);
+        assert_eq!(distinct_direction_count(&[]), 1);
+    }
+}
@@ -7,13 +7,20 @@ use std::{
     collections::HashMap,
     fs, io,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use image::{ImageError, RgbaImage};
 use oxipng::{self};
 use serde_json;
 
-use crate::graphics::wan::{WanError, WanFile};
+use crate::{
+    graphics::wan::{
+        parser::DEFAULT_MAX_SEQUENCE_FRAMES, renderer::DEFAULT_MAX_FRAME_DIMENSION, DurationUnit,
+        WanError, WanFile,
+    },
+    output::{self, DirSink, ImageFormat, JsonStyle, OutputSink},
+};
 
 pub mod analyser;
 pub mod generator;
@@ -29,6 +36,35 @@ pub struct AtlasConfig {
     pub debug: bool,
     pub use_indexed_colour: bool,
     pub use_4bit_depth: bool,
+    /// Unit for frame durations written to the atlas JSON
+    pub duration_unit: DurationUnit,
+    /// Frames wider or taller than this are skipped instead of rendered
+    pub max_frame_dimension: u32,
+    /// Animation sequences without an end marker are cut off at this many frames
+    pub max_sequence_frames: usize,
+    /// Integer nearest-neighbour upscale applied to the final atlas, 1 keeps native size
+    pub scale: u32,
+    /// Write head/hand/centre attachment points per frame, off shrinks the metadata JSON
+    pub include_offsets: bool,
+    /// Also write `{dex}_atlas_indices.png` holding each pixel's palette index, for engines
+    /// that swap palettes at runtime
+    pub export_indices: bool,
+    /// Record each frame's opaque pixel extent as `opaque_bounds` in the metadata, for
+    /// hitbox generation
+    pub export_opaque_bounds: bool,
+    /// Move each frame's opaque content to the top left of its cell before deduplicating, so
+    /// frames differing only in transparent margins share a cell. Per-frame `trim_offset`s
+    /// in the metadata put them back. Frames are still trimmed with deduplication off.
+    pub trim_before_dedup: bool,
+    /// Write indexed atlases with the WAN's palette row 0 as PLTE in ROM order (0 transparent,
+    /// 1-15 as stored) instead of the order colours first appear in, so palette swaps line up
+    pub preserve_palette_order: bool,
+    /// Destination for the atlas PNG and JSON, debug frames always go to disk
+    pub sink: Arc<dyn OutputSink>,
+    /// Formatting for the atlas JSON
+    pub json_style: JsonStyle,
+    /// Container for the atlas image, the index buffer and debug frames stay PNG
+    pub image_format: ImageFormat,
 }
 
 impl Default for AtlasConfig {
@@ -41,6 +77,54 @@ impl Default for AtlasConfig {
             debug: false,
             use_indexed_colour: true,
             use_4bit_depth: true,
+            duration_unit: DurationUnit::default(),
+            max_frame_dimension: DEFAULT_MAX_FRAME_DIMENSION,
+            max_sequence_frames: DEFAULT_MAX_SEQUENCE_FRAMES,
+            scale: 1,
+            include_offsets: true,
+            export_indices: false,
+            export_opaque_bounds: false,
+            trim_before_dedup: false,
+            preserve_palette_order: false,
+            sink: Arc::new(DirSink),
+            json_style: JsonStyle::default(),
+            image_format: ImageFormat::default(),
+        }
+    }
+}
+
+/// Pixel format of a written atlas image, read back from its PNG header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Palette PNG with 4 bits per pixel or fewer
+    Indexed4Bit,
+    Indexed8Bit,
+    /// Any direct colour image, e.g. WebP or when the indexed optimisation failed
+    Rgba,
+}
+
+impl ColorMode {
+    /// Reads the colour type and bit depth from a PNG's IHDR chunk, anything that isn't a PNG
+    /// counts as RGBA
+    fn of_png(png: &[u8]) -> Self {
+        const PNG_COLOUR_TYPE_INDEXED: u8 = 3;
+        if !png.starts_with(b"\x89PNG") {
+            return ColorMode::Rgba;
+        }
+        match (png.get(24), png.get(25)) {
+            (Some(&depth), Some(&PNG_COLOUR_TYPE_INDEXED)) if depth <= 4 => ColorMode::Indexed4Bit,
+            (Some(_), Some(&PNG_COLOUR_TYPE_INDEXED)) => ColorMode::Indexed8Bit,
+            _ => ColorMode::Rgba,
+        }
+    }
+}
+
+impl std::fmt::Display for ColorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorMode::Indexed4Bit => write!(f, "4-bit indexed"),
+            ColorMode::Indexed8Bit => write!(f, "8-bit indexed"),
+            ColorMode::Rgba => write!(f, "RGBA"),
         }
     }
 }
@@ -52,6 +136,8 @@ pub struct AtlasResult {
     pub _frame_dimensions: (u32, u32),
     pub image_path: PathBuf,
     pub _metadata_path: PathBuf,
+    /// Format the atlas PNG actually ended up in
+    pub color_mode: ColorMode,
 }
 
 #[derive(Debug)]
@@ -100,6 +186,16 @@ impl std::fmt::Display for AtlasError {
     }
 }
 
+impl AtlasError {
+    /// Whether the Pokemon should be skipped rather than treated as a failure.
+    ///
+    /// `NoFramesFound` means the WAN files were present but nothing was renderable (e.g. the
+    /// animation data failed to parse). `NoWanFilesProvided` is a caller bug and stays an error.
+    pub fn is_skippable(&self) -> bool {
+        matches!(self, AtlasError::NoFramesFound)
+    }
+}
+
 /// Creates a sprite atlas and associated metadata for a Pokemon
 ///
 /// This function orchestrates the analysis, layout, generation, and metadata creation
@@ -118,15 +214,20 @@ pub fn create_pokemon_atlas(
         return Err(AtlasError::NoWanFilesProvided);
     }
 
+    // WAN present but nothing to render, bail before analysing any frames
+    if wan_files.values().all(|wan| wan.frame_data.is_empty()) {
+        return Err(AtlasError::NoFramesFound);
+    }
+
     let pokemon_dir = output_dir.join(folder_name);
-    fs::create_dir_all(&pokemon_dir)?;
 
     // Analyse Frames
     println!(
         "Analysing frames for Pokemon #{:03} (Dex #{:03})...",
         pokemon_id, dex_num
     );
-    let mut frame_analysis = analyser::analyse_frames(wan_files, dex_num)?;
+    let mut frame_analysis =
+        analyser::analyse_frames(wan_files, dex_num, config.max_frame_dimension)?;
 
     if frame_analysis.ordered_frames.is_empty() {
         return Err(AtlasError::NoFramesFound);
@@ -163,6 +264,12 @@ pub fn create_pokemon_atlas(
         generator::prepare_frames(&mut frame_analysis, frame_width, frame_height)?;
     println!("  Prepared {} frames for atlas.", prepared_frames.len());
 
+    let (prepared_frames, trim_offsets) = if config.trim_before_dedup {
+        generator::trim_frames(&prepared_frames)
+    } else {
+        (prepared_frames, Vec::new())
+    };
+
     let (unique_frames, frame_mapping) = if config.deduplicate_frames {
         let (unique, mapping) = generator::deduplicate_frames(&prepared_frames);
         println!(
@@ -195,38 +302,102 @@ pub fn create_pokemon_atlas(
     );
 
     println!("  Generating atlas image...");
-    let atlas_image = generator::generate_atlas(&unique_frames, &atlas_layout)?;
+    let mut atlas_image = generator::generate_atlas(&unique_frames, &atlas_layout)?;
 
     println!("  Generating metadata...");
-    let metadata = metadata::generate_metadata(
+    let mut metadata = metadata::generate_metadata(
         wan_files,
         &frame_analysis,
-        frame_width,
-        frame_height,
         &atlas_layout,
         &frame_mapping,
+        &trim_offsets,
         monster_shadow_size,
+        config.duration_unit,
+        config.include_offsets,
+        config.image_format,
     )?;
 
+    if config.export_opaque_bounds {
+        metadata.set_opaque_bounds(&unique_frames);
+    }
+
+    // The ROM palette is only usable if it covers every colour in the atlas
+    let source_palette = if config.preserve_palette_order {
+        let palette = wan_files
+            .iter()
+            .min_by_key(|(name, _)| name.as_str())
+            .and_then(|(_, wan)| generator::source_palette(wan))
+            .filter(|palette| generator::index_atlas(&atlas_image, palette).is_some());
+        if palette.is_none() {
+            println!(
+                "  Warning: atlas colours aren't all in palette row 0, palette order not preserved"
+            );
+        }
+        palette
+    } else {
+        None
+    };
+
+    if config.use_indexed_colour || config.export_indices {
+        metadata.palette =
+            Some(source_palette.unwrap_or_else(|| generator::extract_palette(&atlas_image)));
+    }
+
+    // Scale last so layout and dedup run on native pixels, then image and coords move together
+    let scale = config.scale.max(1);
+    if scale > 1 {
+        println!("  Upscaling atlas x{}...", scale);
+        atlas_image = generator::upscale_nearest(&atlas_image, scale);
+        metadata.scale(scale);
+    }
+
     // Save Results
-    let atlas_filename = format!("{:03}_atlas.png", dex_num);
+    let atlas_filename = format!("{:03}_atlas.{}", dex_num, config.image_format.extension());
     let atlas_path = pokemon_dir.join(&atlas_filename);
     let metadata_filename = format!("{:03}_atlas.json", dex_num);
     let metadata_path = pokemon_dir.join(&metadata_filename);
 
     println!("  Saving atlas image to {}...", atlas_path.display());
 
-    if config.use_indexed_colour {
-        if let Err(e) = save_indexed_atlas(&atlas_image, &atlas_path, config) {
-            println!("  Warning: Failed to save with indexed palette: {}", e);
-            atlas_image.save(&atlas_path)?;
-        }
+    // WebP has no palette mode, indexed settings only apply to PNG
+    let color_mode = if config.image_format == ImageFormat::WebP {
+        let webp = output::encode_webp(&atlas_image)?;
+        config.sink.write_file(&atlas_path, &webp)?;
+        ColorMode::Rgba
+    } else if let (true, Some(palette)) = (config.use_indexed_colour, &metadata.palette) {
+        // Keep the PLTE in the metadata palette's order, oxipng would otherwise sort it
+        save_palette_ordered_atlas(&atlas_image, palette, &atlas_path, config)?
     } else {
-        atlas_image.save(&atlas_path)?;
+        let png = output::encode_png(&atlas_image)?;
+        config.sink.write_file(&atlas_path, &png)?;
+        ColorMode::of_png(&png)
+    };
+
+    if config.export_indices {
+        let palette = metadata.palette.as_deref().unwrap_or_default();
+        match generator::index_atlas(&atlas_image, palette) {
+            Some(indices) => {
+                let indices_filename = format!("{:03}_atlas_indices.png", dex_num);
+                let indices_path = pokemon_dir.join(&indices_filename);
+                println!("  Saving index buffer to {}...", indices_path.display());
+                let png = output::encode_luma_png(&indices)?;
+                config.sink.write_file(&indices_path, &png)?;
+                metadata.index_image = Some(indices_filename);
+            }
+            None => println!(
+                "  Warning: atlas uses {} colours, too many for an 8-bit index buffer",
+                palette.len()
+            ),
+        }
     }
 
     println!("  Saving metadata to {}...", metadata_path.display());
-    metadata::save_metadata(&metadata, &metadata_path)?;
+    metadata::save_metadata(
+        &metadata,
+        &metadata_path,
+        config.sink.as_ref(),
+        config.json_style,
+    )?;
 
     if config.debug {
         println!("  Saving debug frames...");
@@ -249,51 +420,359 @@ pub fn create_pokemon_atlas(
     );
 
     Ok(AtlasResult {
-        _dimensions: atlas_layout.dimensions,
-        _frame_dimensions: (frame_width, frame_height),
+        _dimensions: atlas_image.dimensions(),
+        _frame_dimensions: (metadata.frame_width, metadata.frame_height),
+        image_path: atlas_path,
+        _metadata_path: metadata_path,
+        color_mode,
+    })
+}
+
+/// Writes a 1x1 transparent atlas and metadata marked `empty` for an entry that couldn't be
+/// rendered, keeping the output folders contiguous for consumers that expect every entry.
+pub fn create_placeholder_atlas(
+    dex_num: u16,
+    shadow_size: u8,
+    config: &AtlasConfig,
+    output_dir: &Path,
+    folder_name: &str,
+) -> Result<AtlasResult, AtlasError> {
+    let pokemon_dir = output_dir.join(folder_name);
+    let atlas_path = pokemon_dir.join(format!(
+        "{:03}_atlas.{}",
+        dex_num,
+        config.image_format.extension()
+    ));
+    let metadata_path = pokemon_dir.join(format!("{:03}_atlas.json", dex_num));
+
+    let atlas_image = RgbaImage::new(1, 1);
+    let encoded = output::encode_image(&atlas_image, config.image_format, None)?;
+    config.sink.write_file(&atlas_path, &encoded)?;
+
+    let metadata = metadata::AtlasMetadata::placeholder(
+        dex_num,
+        shadow_size,
+        config.duration_unit,
+        config.image_format,
+    );
+    metadata::save_metadata(
+        &metadata,
+        &metadata_path,
+        config.sink.as_ref(),
+        config.json_style,
+    )?;
+
+    Ok(AtlasResult {
+        _dimensions: (1, 1),
+        _frame_dimensions: (1, 1),
         image_path: atlas_path,
         _metadata_path: metadata_path,
+        color_mode: ColorMode::of_png(&encoded),
     })
 }
 
-/// Save an atlas image using indexed colour for smaller file size
+/// Save an atlas image using indexed colour for smaller file size. Returns the format that was
+/// actually written, which is RGBA if the optimisation failed.
 pub fn save_indexed_atlas(
     atlas_image: &RgbaImage,
     path: &Path,
     config: &AtlasConfig,
-) -> Result<(), AtlasError> {
-    // Save the atlas image at full quality
-    let temp_path = path.with_extension("temp.png");
-    atlas_image.save(&temp_path).map_err(AtlasError::Image)?;
+) -> Result<ColorMode, AtlasError> {
+    let mut options = oxipng::Options::from_preset(2);
+
+    // Enable bit depth reduction for 4-bit output
+    options.bit_depth_reduction = config.use_4bit_depth;
 
-    if config.use_4bit_depth {
-        let mut options = oxipng::Options::from_preset(2);
+    // A failed optimisation is recoverable, encode_optimised_png keeps the unoptimised image instead
+    let png = output::encode_optimised_png(atlas_image, &options)?;
+    config.sink.write_file(path, &png)?;
 
-        // Enable bit depth reduction for 4-bit output
-        options.bit_depth_reduction = true;
+    Ok(ColorMode::of_png(&png))
+}
 
-        let in_path = temp_path.to_path_buf();
-        let out_path = path.to_path_buf();
+/// Save an atlas as an indexed PNG whose PLTE is `palette` in order. oxipng may still shrink
+/// the bit depth and recompress, but palette sorting and colour type changes are turned off.
+/// Falls back to `save_indexed_atlas` if a pixel isn't in `palette`.
+pub fn save_palette_ordered_atlas(
+    atlas_image: &RgbaImage,
+    palette: &[[u8; 4]],
+    path: &Path,
+    config: &AtlasConfig,
+) -> Result<ColorMode, AtlasError> {
+    let Some(indices) = generator::index_atlas(atlas_image, palette) else {
+        return save_indexed_atlas(atlas_image, path, config);
+    };
+
+    let mut options = oxipng::Options::from_preset(2);
+    options.bit_depth_reduction = config.use_4bit_depth;
+    options.palette_reduction = false;
+    options.color_type_reduction = false;
+    options.grayscale_reduction = false;
+
+    let png = output::optimise_png(output::encode_indexed_png(&indices, palette)?, &options);
+    config.sink.write_file(path, &png)?;
+
+    Ok(ColorMode::of_png(&png))
+}
 
-        // Optimise using InFile and OutFile with PathBuf values
-        oxipng::optimize(
-            &oxipng::InFile::Path(in_path),
-            &oxipng::OutFile::Path(Some(out_path)),
-            &options,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graphics::wan::{
+            builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+            parser::{parse_wan_from_sir0_content, ParseOptions},
+            WanType,
+        },
+        test_support::{png_palette, scratch_dir},
+    };
+
+    /// Character WAN with one 8x8 frame using palette colours 1 and 2. Frame 0 stands in
+    /// for the null frame ROM WANs start with.
+    fn two_colour_wan() -> WanFile {
+        let mut image = vec![0x11; 16];
+        image.extend([0x22; 16]);
+        let (content, data_pointer) = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40), (40, 40, 200)])
+            .image(image)
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 4)]; 8])
+            .build();
+        parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions::default(),
         )
-        .map_err(|e| AtlasError::MetadataError(format!("PNG optimisation failed: {}", e)))?;
+        .unwrap()
+    }
 
-        // Remove temporary file
-        if let Err(e) = std::fs::remove_file(&temp_path) {
-            println!("  Warning: Failed to remove temporary file: {}", e);
+    #[test]
+    fn indexed_atlas_metadata_lists_the_palette() {
+        let dir = scratch_dir("atlas_metadata_palette");
+        let wan_files = HashMap::from([("merged".to_string(), two_colour_wan())]);
+
+        for use_indexed_colour in [true, false] {
+            let config = AtlasConfig {
+                use_indexed_colour,
+                ..AtlasConfig::default()
+            };
+            let result =
+                create_pokemon_atlas(&wan_files, 25, 25, 1, &config, &dir, "pokemon_025").unwrap();
+            let metadata: serde_json::Value =
+                serde_json::from_slice(&fs::read(&result._metadata_path).unwrap()).unwrap();
+
+            if use_indexed_colour {
+                // Transparent plus the two opaque colours
+                let palette: Vec<[u8; 4]> =
+                    serde_json::from_value(metadata["palette"].clone()).unwrap();
+                assert_eq!(palette.len(), 3);
+                assert_eq!(palette[0], [0, 0, 0, 0]);
+            } else {
+                assert!(metadata.get("palette").is_none());
+            }
         }
-    } else if let Err(e) = std::fs::rename(&temp_path, path) {
-        println!("  Warning: Failed to rename file: {}", e);
-        if let Err(e) = std::fs::copy(&temp_path, path) {
-            return Err(AtlasError::Io(e));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unwritable_atlas_path_returns_an_error() {
+        let dir = scratch_dir("atlas_unwritable");
+        // A file where the output folder should be, unlike a read-only folder this also fails
+        // when the tests run as root
+        let blocker = dir.join("pokemon_025");
+        fs::write(&blocker, b"not a folder").unwrap();
+        let atlas_path = blocker.join("025_atlas.png");
+
+        let mut atlas = RgbaImage::new(8, 8);
+        atlas.put_pixel(1, 1, image::Rgba([200, 40, 40, 255]));
+        let config = AtlasConfig::default();
+
+        assert!(matches!(
+            save_indexed_atlas(&atlas, &atlas_path, &config),
+            Err(AtlasError::Io(_))
+        ));
+        let palette = generator::extract_palette(&atlas);
+        assert!(matches!(
+            save_palette_ordered_atlas(&atlas, &palette, &atlas_path, &config),
+            Err(AtlasError::Io(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn omitting_offsets_shrinks_the_metadata() {
+        let dir = scratch_dir("atlas_metadata_offsets");
+        let wan_files = HashMap::from([("merged".to_string(), two_colour_wan())]);
+
+        let metadata_len = |include_offsets| {
+            let config = AtlasConfig {
+                include_offsets,
+                ..AtlasConfig::default()
+            };
+            let result =
+                create_pokemon_atlas(&wan_files, 25, 25, 1, &config, &dir, "pokemon_025").unwrap();
+            fs::read(&result._metadata_path).unwrap().len()
+        };
+
+        assert!(metadata_len(false) < metadata_len(true));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn color_mode_matches_the_written_png() {
+        let dir = scratch_dir("atlas_color_mode");
+        let wan_files = HashMap::from([("merged".to_string(), two_colour_wan())]);
+
+        for (use_indexed_colour, expected) in
+            [(true, ColorMode::Indexed4Bit), (false, ColorMode::Rgba)]
+        {
+            let config = AtlasConfig {
+                use_indexed_colour,
+                ..AtlasConfig::default()
+            };
+            let result =
+                create_pokemon_atlas(&wan_files, 25, 25, 1, &config, &dir, "pokemon_025").unwrap();
+            assert_eq!(result.color_mode, expected);
+            let png = fs::read(&result.image_path).unwrap();
+            assert_eq!(ColorMode::of_png(&png), expected);
         }
-        let _ = std::fs::remove_file(&temp_path);
+
+        // 8-bit depth palette PNGs and anything unreadable are told apart too
+        let mut header = b"\x89PNG".to_vec();
+        header.resize(26, 0);
+        header[24] = 8;
+        header[25] = 3;
+        assert_eq!(ColorMode::of_png(&header), ColorMode::Indexed8Bit);
+        assert_eq!(ColorMode::of_png(&header[4..]), ColorMode::Rgba);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn preserved_plte_order_matches_the_source_palette() {
+        let dir = scratch_dir("atlas_palette_order");
+        // Only indices 5 and 2 are drawn, a palette built from the atlas would renumber them
+        let mut image = vec![0x55; 16];
+        image.extend([0x22; 16]);
+        let (content, data_pointer) = WanBuilder::new(WanType::Character)
+            .palette(vec![
+                (0, 0, 0),
+                (10, 10, 10),
+                (200, 40, 40),
+                (20, 20, 20),
+                (30, 30, 30),
+                (40, 40, 200),
+            ])
+            .image(image)
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 4)]; 8])
+            .build();
+        let wan = parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let source: Vec<[u8; 4]> = wan
+            .palette(0)
+            .unwrap()
+            .iter()
+            .map(|&(r, g, b, a)| [r, g, b, a])
+            .collect();
+        let wan_files = HashMap::from([("merged".to_string(), wan)]);
+        let config = AtlasConfig {
+            preserve_palette_order: true,
+            ..AtlasConfig::default()
+        };
+
+        let result =
+            create_pokemon_atlas(&wan_files, 25, 25, 1, &config, &dir, "pokemon_025").unwrap();
+        let plte = png_palette(&fs::read(&result.image_path).unwrap());
+
+        // oxipng may drop unused trailing entries, never reorder the rest
+        assert!(plte.len() >= 6, "{:?}", plte);
+        assert_eq!(plte[0], [0, 0, 0, 0]);
+        assert_eq!(plte[1..], source[1..plte.len()]);
+        assert_eq!(plte[2], [200, 40, 40, 255]);
+        assert_eq!(plte[5], [40, 40, 200, 255]);
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
-    Ok(())
+    #[test]
+    fn animations_list_each_direction_in_playback_order() {
+        let dir = scratch_dir("atlas_metadata_animations");
+        let mut directions = vec![vec![BuilderSeqFrame::new(1, 3)]; 8];
+        directions[0] = vec![
+            BuilderSeqFrame::new(0, 4),
+            BuilderSeqFrame::new(1, 6),
+            BuilderSeqFrame::new(0, 8),
+        ];
+        // Frame 0 stands in for the null frame, sequence frames 0 and 1 draw frames 1 and 2
+        let (content, data_pointer) = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40), (40, 40, 200)])
+            .image(vec![0x11; 32])
+            .image(vec![0x22; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(1, -4, -8)])
+            .group(directions)
+            .build();
+        let wan = parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let wan_files = HashMap::from([("merged".to_string(), wan)]);
+
+        let result = create_pokemon_atlas(
+            &wan_files,
+            25,
+            25,
+            1,
+            &AtlasConfig::default(),
+            &dir,
+            "pokemon_025",
+        )
+        .unwrap();
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&fs::read(&result._metadata_path).unwrap()).unwrap();
+
+        let walk = &metadata["animations"]["Walk"];
+        assert_eq!(walk["anim_id"], 0);
+        let directions = walk["directions"].as_array().unwrap();
+        assert_eq!(directions.len(), 8);
+        let playback = |direction: &serde_json::Value| -> Vec<(u64, u64)> {
+            direction["frames"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|frame| {
+                    (
+                        frame["idx"].as_u64().unwrap(),
+                        frame["duration"].as_u64().unwrap(),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(directions[0]["direction"], 0);
+        let down = playback(&directions[0]);
+        assert_eq!(down.iter().map(|&(_, d)| d).collect::<Vec<_>>(), [4, 6, 8]);
+        // The repeated frame points back at the same atlas cell
+        assert_eq!(down[0].0, down[2].0);
+        assert_ne!(down[0].0, down[1].0);
+        assert_eq!(directions[7]["direction"], 7);
+        assert_eq!(playback(&directions[7]), [(down[1].0, 3)]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,8 +1,10 @@
 pub mod atlas;
 pub mod wan;
 
+pub mod palette;
 pub mod portrait;
 pub mod screen_effect;
+pub mod tiles;
 pub mod wte;
 
 pub use wan::WanType;
@@ -0,0 +1,94 @@
+//! Palette export for image editors.
+//!
+//! WAN palettes are written as GIMP `.gpl` and Adobe `.act` files so artists can edit sprite
+//! colours without pulling them out of the exported PNGs. Tuples are `(r, g, b, a)` in true
+//! RGB order as the parser stores them, alpha is dropped.
+
+use std::{fmt::Write as _, io, path::Path};
+
+use crate::{
+    graphics::wan::{PaletteList, RgbaTuple},
+    output::OutputSink,
+};
+
+/// Colours an Adobe colour table holds
+const ACT_COLOURS: usize = 256;
+
+/// Writes `palette` as a GIMP palette, 16 columns wide so WAN rows line up. The file stem is
+/// used as the palette name.
+pub fn export_gpl(palette: &[RgbaTuple], path: &Path, sink: &dyn OutputSink) -> io::Result<()> {
+    let name = path
+        .file_stem()
+        .map_or_else(|| "palette".into(), |stem| stem.to_string_lossy());
+
+    let mut gpl = format!("GIMP Palette\nName: {}\nColumns: 16\n#\n", name);
+    for (i, &(r, g, b, _)) in palette.iter().enumerate() {
+        let _ = writeln!(
+            gpl,
+            "{:3} {:3} {:3}\trow {} index {}",
+            r,
+            g,
+            b,
+            i / 16,
+            i % 16
+        );
+    }
+    sink.write_file(path, gpl.as_bytes())
+}
+
+/// Writes `palette` as a 768 byte Adobe colour table. Only the first 256 colours fit, unused
+/// entries are black.
+pub fn export_act(palette: &[RgbaTuple], path: &Path, sink: &dyn OutputSink) -> io::Result<()> {
+    let mut act = vec![0u8; ACT_COLOURS * 3];
+    for (entry, &(r, g, b, _)) in act.chunks_exact_mut(3).zip(palette) {
+        entry.copy_from_slice(&[r, g, b]);
+    }
+    sink.write_file(path, &act)
+}
+
+/// Writes every row of `rows` back to back as `{stem}.gpl` and `{stem}.act` in `dir`
+pub fn export_palette_rows(
+    rows: &PaletteList,
+    dir: &Path,
+    stem: &str,
+    sink: &dyn OutputSink,
+) -> io::Result<()> {
+    let colours: Vec<RgbaTuple> = rows.iter().flatten().copied().collect();
+    if colours.len() > ACT_COLOURS {
+        println!(
+            "  Warning: {} has {} colours, {}.act only keeps the first {}",
+            stem,
+            colours.len(),
+            stem,
+            ACT_COLOURS
+        );
+    }
+    export_gpl(&colours, &dir.join(format!("{}.gpl", stem)), sink)?;
+    export_act(&colours, &dir.join(format!("{}.act", stem)), sink)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::MemorySink;
+
+    #[test]
+    fn palette_rows_export_as_gpl_and_act() {
+        let rows: PaletteList = vec![
+            vec![(0, 0, 0, 0), (255, 128, 1, 255)],
+            vec![(10, 20, 30, 255)],
+        ];
+        let sink = MemorySink::default();
+        export_palette_rows(&rows, Path::new("out"), "025_palette", &sink).unwrap();
+
+        let gpl = String::from_utf8(sink.file(Path::new("out/025_palette.gpl")).unwrap()).unwrap();
+        assert!(gpl.starts_with("GIMP Palette\nName: 025_palette\nColumns: 16\n#\n"));
+        assert!(gpl.contains("255 128   1\trow 0 index 1"));
+        assert!(gpl.contains(" 10  20  30\trow 0 index 2"));
+
+        let act = sink.file(Path::new("out/025_palette.act")).unwrap();
+        assert_eq!(act.len(), 768);
+        assert_eq!(act[3..9], [255, 128, 1, 10, 20, 30]);
+        assert!(act[9..].iter().all(|&b| b == 0));
+    }
+}
@@ -1,16 +1,16 @@
-use std::{
-    collections::HashMap,
-    convert::TryInto,
-    fs::File,
-    io::Write,
-    path::{Path, PathBuf},
-};
+use std::{collections::HashMap, convert::TryInto, fmt, path::Path, str::FromStr};
 
 use image::RgbaImage;
-use oxipng::{self, InFile, OutFile};
-use serde_json;
-
-use crate::containers::{compression::at4px::At4pxContainer, ContainerHandler};
+use oxipng::{self};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    containers::{compression::at4px::At4pxContainer, ContainerHandler},
+    graphics::tiles::{decode_tiled_indices, TILE_DIM},
+    output::{self, ImageFormat, JsonStyle, OutputSink},
+    report::{ItemOutcome, StageReport},
+};
 
 /// Represents a single portrait image from the KAO file
 #[derive(Clone, Debug)]
@@ -54,71 +54,20 @@ impl Portrait {
         let decompressed = at4px_container.decompress()?;
 
         const IMG_DIM: u32 = 40;
-        const TILE_DIM: usize = 8;
-        const GRID_DIM: usize = 5;
-        const PIXELS_PER_TILE: usize = TILE_DIM * TILE_DIM;
-        const TOTAL_PIXELS: usize = (IMG_DIM * IMG_DIM) as usize;
-
-        let mut image_buffer = vec![0u8; (IMG_DIM * IMG_DIM * 4) as usize];
-
-        let mut tile_positions = Vec::with_capacity(GRID_DIM * GRID_DIM);
-        for tile_id in 0..(GRID_DIM * GRID_DIM) {
-            let tile_x = (tile_id % GRID_DIM) as u32;
-            let tile_y = (tile_id / GRID_DIM) as u32;
-            tile_positions.push((tile_x * TILE_DIM as u32, tile_y * TILE_DIM as u32));
-        }
-
-        let expected_pixels = TOTAL_PIXELS;
-        let actual_pixels = decompressed.len() * 2; // Each byte has 2 pixels
-        let pixel_count = std::cmp::min(expected_pixels, actual_pixels);
-
-        // Process decompressed data (each byte contains two 4-bit pixels)
-        for (byte_idx, &byte) in decompressed.iter().enumerate().take(pixel_count / 2) {
-            let colour_idx1 = byte & 0xF; // Low nibble
-            let colour_idx2 = (byte >> 4) & 0xF; // High nibble
-
-            for i in 0..2 {
-                let idx = byte_idx * 2 + i;
-
-                // Calculate which tile this pixel belongs to
-                let tile_id = idx / PIXELS_PER_TILE;
-
-                if tile_id >= tile_positions.len() {
-                    continue; // So no out of bounds error
-                }
+        const GRID_DIM: usize = IMG_DIM as usize / TILE_DIM;
 
-                // Get pre-calculated tile position
-                let (tile_x, tile_y) = tile_positions[tile_id];
+        let indices = decode_tiled_indices(&decompressed, GRID_DIM, GRID_DIM, 4);
 
-                // Calculate position within the current tile
-                let idx_in_tile = idx - (PIXELS_PER_TILE * tile_id);
-                let in_tile_x = (idx_in_tile % TILE_DIM) as u32;
-                let in_tile_y = (idx_in_tile / TILE_DIM) as u32;
-
-                let final_x = tile_x + in_tile_x;
-                let final_y = tile_y + in_tile_y;
-
-                if final_x >= IMG_DIM || final_y >= IMG_DIM {
-                    continue; // So no out of bounds error
-                }
-
-                // Get colour index based on which nibble we're processing
-                let colour_idx = if i == 0 { colour_idx1 } else { colour_idx2 } as usize;
-
-                if colour_idx >= self.palette.len() {
-                    continue;
-                }
-
-                // Calculate position in the RGBA buffer, 4 bytes per pixel
-                let buffer_pos = ((final_y * IMG_DIM + final_x) * 4) as usize;
-
-                // Copy colour data to buffer
-                let colour = &self.palette[colour_idx];
-                image_buffer[buffer_pos] = colour[0];
-                image_buffer[buffer_pos + 1] = colour[1];
-                image_buffer[buffer_pos + 2] = colour[2];
-                image_buffer[buffer_pos + 3] = 255; // Alpha
-            }
+        let mut image_buffer = vec![0u8; (IMG_DIM * IMG_DIM * 4) as usize];
+        for (i, &colour_idx) in indices.iter().enumerate() {
+            let Some(colour) = self.palette.get(colour_idx as usize) else {
+                continue;
+            };
+
+            // Portraits have no transparent index, every pixel is opaque
+            let buffer_pos = i * 4;
+            image_buffer[buffer_pos..buffer_pos + 3].copy_from_slice(colour);
+            image_buffer[buffer_pos + 3] = 255;
         }
 
         RgbaImage::from_raw(IMG_DIM, IMG_DIM, image_buffer)
@@ -135,15 +84,16 @@ pub const _KAO_TILE_DIM: usize = 8;
 pub const _KAO_META_DIM: usize = 5;
 pub const KAO_FIRST_TOC_OFFSET: usize = 160;
 
+/// One contiguous TOC + portrait data block
 #[derive(Debug)]
-pub struct KaoFile {
+struct KaoSegment {
     data: Vec<u8>,
     toc_start_offset: usize,
     pokemon_count: usize,
 }
 
-impl KaoFile {
-    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+impl KaoSegment {
+    fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
         // First 160 bytes are padding
         let toc_start_offset = KAO_FIRST_TOC_OFFSET;
 
@@ -162,27 +112,26 @@ impl KaoFile {
         let pokemon_entry_size = KAO_PORTRAITS_PER_POKEMON * KAO_PORTRAIT_POINTER_SIZE;
         let pokemon_count = toc_size_bytes / pokemon_entry_size;
 
-        Ok(KaoFile {
+        Ok(KaoSegment {
             data,
             toc_start_offset,
             pokemon_count,
         })
     }
 
-    pub fn get_portrait(&self, index: usize, subindex: usize) -> Result<Option<Portrait>, String> {
-        if index >= self.pokemon_count {
-            return Err(format!(
-                "Portrait index {} out of bounds (max {})",
-                index, self.pokemon_count
-            ));
-        }
-        if subindex >= KAO_PORTRAITS_PER_POKEMON {
-            return Err(format!(
-                "Subindex {} out of bounds (max {})",
-                subindex, KAO_PORTRAITS_PER_POKEMON
-            ));
-        }
+    /// Whether the TOC has a portrait pointer for this slot, without decoding it
+    fn has_portrait(&self, index: usize, subindex: usize) -> bool {
+        let toc_entry_pos = self.toc_start_offset
+            + (index * KAO_PORTRAITS_PER_POKEMON * KAO_PORTRAIT_POINTER_SIZE)
+            + (subindex * KAO_PORTRAIT_POINTER_SIZE);
 
+        self.data
+            .get(toc_entry_pos..toc_entry_pos + 4)
+            .map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap()))
+            .is_some_and(|pointer| pointer >= 0 && (pointer as usize) < self.data.len())
+    }
+
+    fn get_portrait(&self, index: usize, subindex: usize) -> Result<Option<Portrait>, String> {
         let toc_entry_pos = self.toc_start_offset
             + (index * KAO_PORTRAITS_PER_POKEMON * KAO_PORTRAIT_POINTER_SIZE)
             + (subindex * KAO_PORTRAIT_POINTER_SIZE);
@@ -212,6 +161,268 @@ impl KaoFile {
     }
 }
 
+/// Portraits from one or more KAO segments. Indices run across segments in order, so the
+/// first Pokemon of the second segment follows the last of the first.
+#[derive(Debug)]
+pub struct KaoFile {
+    segments: Vec<KaoSegment>,
+    pokemon_count: usize,
+}
+
+impl KaoFile {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+        Self::from_segments(vec![data])
+    }
+
+    /// Loads a KAO split across several files, each with its own padding, TOC and data
+    pub fn from_segments(segments: Vec<Vec<u8>>) -> Result<Self, String> {
+        if segments.is_empty() {
+            return Err("No KAO segments provided".to_string());
+        }
+
+        let segments = segments
+            .into_iter()
+            .enumerate()
+            .map(|(i, data)| {
+                KaoSegment::from_bytes(data).map_err(|e| format!("KAO segment {}: {}", i, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let pokemon_count = segments.iter().map(|seg| seg.pokemon_count).sum();
+
+        Ok(KaoFile {
+            segments,
+            pokemon_count,
+        })
+    }
+
+    pub fn get_portrait(&self, index: usize, subindex: usize) -> Result<Option<Portrait>, String> {
+        if index >= self.pokemon_count {
+            return Err(format!(
+                "Portrait index {} out of bounds (max {})",
+                index, self.pokemon_count
+            ));
+        }
+        if subindex >= KAO_PORTRAITS_PER_POKEMON {
+            return Err(format!(
+                "Subindex {} out of bounds (max {})",
+                subindex, KAO_PORTRAITS_PER_POKEMON
+            ));
+        }
+
+        let mut local_index = index;
+        for segment in &self.segments {
+            if local_index < segment.pokemon_count {
+                return segment.get_portrait(local_index, subindex);
+            }
+            local_index -= segment.pokemon_count;
+        }
+        unreachable!("index was checked against the total pokemon_count")
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn get_portrait_by_emotion(
+        &self,
+        index: usize,
+        emotion: Emotion,
+    ) -> Result<Option<Portrait>, String> {
+        self.get_portrait(index, emotion.as_subindex())
+    }
+
+    /// Subindices that have a portrait for Pokemon `index`, read from the TOC so games or
+    /// hacks that populate other slots are picked up. Empty if `index` is out of range.
+    pub fn emotions_present(&self, index: usize) -> Vec<usize> {
+        let mut local_index = index;
+        for segment in &self.segments {
+            if local_index < segment.pokemon_count {
+                return (0..KAO_PORTRAITS_PER_POKEMON)
+                    .filter(|&subindex| segment.has_portrait(local_index, subindex))
+                    .collect();
+            }
+            local_index -= segment.pokemon_count;
+        }
+        Vec::new()
+    }
+}
+
+/// Names of the KAO expression slots as used by Explorers of Sky. Odd subindices hold the
+/// mirrored version of the slot before them.
+const KAO_EMOTION_NAMES: [&str; KAO_PORTRAITS_PER_POKEMON / 2] = [
+    "normal",
+    "happy",
+    "pain",
+    "angry",
+    "worried",
+    "sad",
+    "crying",
+    "shouting",
+    "teary_eyed",
+    "determined",
+    "joyous",
+    "inspired",
+    "surprised",
+    "dizzy",
+    "special0",
+    "special1",
+    "sigh",
+    "stunned",
+    "special2",
+    "special3",
+];
+
+/// KAO expression slots as used by Explorers of Sky. Each expression is followed by its
+/// mirrored version, and the discriminant is the subindex `KaoFile::get_portrait` takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emotion {
+    /// Subindex 0
+    Normal = 0,
+    /// Subindex 1, mirror of `Normal`
+    NormalFlipped,
+    /// Subindex 2
+    Happy,
+    /// Subindex 3, mirror of `Happy`
+    HappyFlipped,
+    /// Subindex 4
+    Pain,
+    /// Subindex 5, mirror of `Pain`
+    PainFlipped,
+    /// Subindex 6
+    Angry,
+    /// Subindex 7, mirror of `Angry`
+    AngryFlipped,
+    /// Subindex 8
+    Worried,
+    /// Subindex 9, mirror of `Worried`
+    WorriedFlipped,
+    /// Subindex 10
+    Sad,
+    /// Subindex 11, mirror of `Sad`
+    SadFlipped,
+    /// Subindex 12
+    Crying,
+    /// Subindex 13, mirror of `Crying`
+    CryingFlipped,
+    /// Subindex 14
+    Shouting,
+    /// Subindex 15, mirror of `Shouting`
+    ShoutingFlipped,
+    /// Subindex 16
+    TearyEyed,
+    /// Subindex 17, mirror of `TearyEyed`
+    TearyEyedFlipped,
+    /// Subindex 18
+    Determined,
+    /// Subindex 19, mirror of `Determined`
+    DeterminedFlipped,
+    /// Subindex 20
+    Joyous,
+    /// Subindex 21, mirror of `Joyous`
+    JoyousFlipped,
+    /// Subindex 22
+    Inspired,
+    /// Subindex 23, mirror of `Inspired`
+    InspiredFlipped,
+    /// Subindex 24
+    Surprised,
+    /// Subindex 25, mirror of `Surprised`
+    SurprisedFlipped,
+    /// Subindex 26
+    Dizzy,
+    /// Subindex 27, mirror of `Dizzy`
+    DizzyFlipped,
+    /// Subindex 28
+    Special0,
+    /// Subindex 29, mirror of `Special0`
+    Special0Flipped,
+    /// Subindex 30
+    Special1,
+    /// Subindex 31, mirror of `Special1`
+    Special1Flipped,
+    /// Subindex 32
+    Sigh,
+    /// Subindex 33, mirror of `Sigh`
+    SighFlipped,
+    /// Subindex 34
+    Stunned,
+    /// Subindex 35, mirror of `Stunned`
+    StunnedFlipped,
+    /// Subindex 36
+    Special2,
+    /// Subindex 37, mirror of `Special2`
+    Special2Flipped,
+    /// Subindex 38
+    Special3,
+    /// Subindex 39, mirror of `Special3`
+    Special3Flipped,
+}
+
+impl Emotion {
+    /// Every slot in subindex order
+    pub const ALL: [Emotion; KAO_PORTRAITS_PER_POKEMON] = [
+        Emotion::Normal,
+        Emotion::NormalFlipped,
+        Emotion::Happy,
+        Emotion::HappyFlipped,
+        Emotion::Pain,
+        Emotion::PainFlipped,
+        Emotion::Angry,
+        Emotion::AngryFlipped,
+        Emotion::Worried,
+        Emotion::WorriedFlipped,
+        Emotion::Sad,
+        Emotion::SadFlipped,
+        Emotion::Crying,
+        Emotion::CryingFlipped,
+        Emotion::Shouting,
+        Emotion::ShoutingFlipped,
+        Emotion::TearyEyed,
+        Emotion::TearyEyedFlipped,
+        Emotion::Determined,
+        Emotion::DeterminedFlipped,
+        Emotion::Joyous,
+        Emotion::JoyousFlipped,
+        Emotion::Inspired,
+        Emotion::InspiredFlipped,
+        Emotion::Surprised,
+        Emotion::SurprisedFlipped,
+        Emotion::Dizzy,
+        Emotion::DizzyFlipped,
+        Emotion::Special0,
+        Emotion::Special0Flipped,
+        Emotion::Special1,
+        Emotion::Special1Flipped,
+        Emotion::Sigh,
+        Emotion::SighFlipped,
+        Emotion::Stunned,
+        Emotion::StunnedFlipped,
+        Emotion::Special2,
+        Emotion::Special2Flipped,
+        Emotion::Special3,
+        Emotion::Special3Flipped,
+    ];
+
+    pub fn as_subindex(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_subindex(subindex: usize) -> Option<Self> {
+        Self::ALL.get(subindex).copied()
+    }
+
+    pub fn is_flipped(self) -> bool {
+        self.as_subindex() % 2 == 1
+    }
+
+    /// Name used in atlas metadata keys, mirrored slots get a `_flipped` suffix
+    pub fn name(self) -> String {
+        let base = KAO_EMOTION_NAMES[self.as_subindex() / 2];
+        if self.is_flipped() {
+            format!("{}_flipped", base)
+        } else {
+            base.to_string()
+        }
+    }
+}
+
 pub enum AtlasType {
     Pokedex,
     Expressions,
@@ -219,28 +430,102 @@ pub enum AtlasType {
 
 pub const PORTRAIT_SIZE: u8 = 40;
 
+/// How portraits are arranged in the atlas grid, filled left to right then top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PortraitLayout {
+    /// Near-square grid, `ceil(sqrt(count))` columns
+    #[default]
+    Square,
+    /// A fixed number of columns, the rows grow to fit
+    FixedColumns(u32),
+    /// The square grid widened and heightened to power-of-two canvas dimensions, with as many
+    /// columns as fit the widened canvas
+    PowerOfTwo,
+}
+
+impl PortraitLayout {
+    /// Columns and canvas (width, height) in pixels for `count` portraits
+    pub fn grid(&self, count: usize) -> (u32, u32, u32) {
+        let size = PORTRAIT_SIZE as u32;
+        let square_columns = ((count as f32).sqrt().ceil() as u32).max(1);
+        let columns = match *self {
+            PortraitLayout::Square => square_columns,
+            PortraitLayout::FixedColumns(columns) => columns.max(1),
+            PortraitLayout::PowerOfTwo => (square_columns * size).next_power_of_two() / size,
+        };
+        let rows = (count as u32).div_ceil(columns);
+
+        let (width, height) = (columns * size, rows * size);
+        match self {
+            PortraitLayout::PowerOfTwo => (
+                columns,
+                width.next_power_of_two(),
+                height.max(1).next_power_of_two(),
+            ),
+            _ => (columns, width, height),
+        }
+    }
+}
+
+impl fmt::Display for PortraitLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortraitLayout::Square => write!(f, "square"),
+            PortraitLayout::FixedColumns(columns) => write!(f, "columns={}", columns),
+            PortraitLayout::PowerOfTwo => write!(f, "power-of-two"),
+        }
+    }
+}
+
+impl FromStr for PortraitLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        match lower.as_str() {
+            "square" => Ok(PortraitLayout::Square),
+            "power-of-two" | "pot" => Ok(PortraitLayout::PowerOfTwo),
+            other => other
+                .strip_prefix("columns=")
+                .and_then(|columns| columns.parse::<u32>().ok())
+                .filter(|&columns| columns > 0)
+                .map(PortraitLayout::FixedColumns)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown portrait layout '{}', expected square, power-of-two or columns=N",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+/// Portrait atlas JSON: the layout it was packed with, and each portrait's top-left corner
+/// keyed by name under `portraits`
+#[derive(Serialize)]
+struct PortraitAtlasMetadata<'a> {
+    layout: String,
+    columns: u32,
+    portraits: &'a HashMap<String, (usize, usize)>,
+}
+
 pub fn create_portrait_atlas(
     kao_file: &KaoFile,
     atlas_type: &AtlasType,
-    output_path: &PathBuf,
-) -> Result<RgbaImage, String> {
-    let max_portraits = match atlas_type {
-        AtlasType::Pokedex => 552,
-        AtlasType::Expressions => 535,
-    };
-
-    let total_portrait_count = count_portraits(kao_file, atlas_type);
+    layout: PortraitLayout,
+    output_path: &Path,
+    sink: &dyn OutputSink,
+    json_style: JsonStyle,
+    image_format: ImageFormat,
+) -> Result<(RgbaImage, StageReport), String> {
+    let entries = atlas_entries(kao_file, atlas_type);
+    let total_portrait_count = entries.len();
 
-    // Calculate optimal layout
-    let frames_per_row = (total_portrait_count as f32).sqrt().ceil() as u32;
-    let rows = (total_portrait_count as u32).div_ceil(frames_per_row);
-
-    let atlas_width = frames_per_row * PORTRAIT_SIZE as u32;
-    let atlas_height = rows * PORTRAIT_SIZE as u32;
+    let (frames_per_row, atlas_width, atlas_height) = layout.grid(total_portrait_count);
 
     println!(
-        "Creating atlas with dimensions: {}x{} for {} portraits",
-        atlas_width, atlas_height, total_portrait_count
+        "Creating {} atlas with dimensions: {}x{} for {} portraits",
+        layout, atlas_width, atlas_height, total_portrait_count
     );
 
     let mut atlas = RgbaImage::new(atlas_width, atlas_height);
@@ -250,87 +535,56 @@ pub fn create_portrait_atlas(
         *pixel = image::Rgba([0, 0, 0, 0]);
     }
 
-    let mut current_portrait_idx = 0;
-    let mut portrait_metadata: HashMap<String, (usize, usize)> = HashMap::new();
-
-    match atlas_type {
-        AtlasType::Pokedex => {
-            for pokemon_id in 0..max_portraits {
-                if pokemon_id > 535 && pokemon_id < 551 {
-                    continue;
-                }
-
-                if let Ok(Some(portrait)) = kao_file.get_portrait(pokemon_id as usize, 0) {
-                    let grid_x = current_portrait_idx % frames_per_row;
-                    let grid_y = current_portrait_idx / frames_per_row;
-
-                    let x = grid_x * PORTRAIT_SIZE as u32;
-                    let y = grid_y * PORTRAIT_SIZE as u32;
-
-                    if let Ok(portrait_image) = portrait.to_rgba_image() {
-                        copy_image_to_atlas(&mut atlas, &portrait_image, x as usize, y as usize);
-
-                        portrait_metadata.insert(
-                            format!("mon_{:03}", pokemon_id + 1),
-                            (x as usize, y as usize),
-                        );
+    // Decoding dominates, so it runs in parallel. The results keep entry order and are
+    // composited serially, so a portrait that fails to decode doesn't take a grid slot and the
+    // atlas is the same whatever order the threads finish in.
+    let decoded: Vec<(String, Result<Option<RgbaImage>, String>)> = entries
+        .into_par_iter()
+        .map(|(key, pokemon_id, subindex)| {
+            let image = kao_file
+                .get_portrait(pokemon_id, subindex)
+                .and_then(|portrait| portrait.map(|p| p.to_rgba_image()).transpose());
+            (key, image)
+        })
+        .collect();
 
-                        current_portrait_idx += 1;
-                    }
-                }
+    let mut portrait_metadata: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut report = StageReport::default();
+    let mut grid_index = 0;
+
+    for (key, image) in decoded {
+        let portrait_image = match image {
+            Ok(Some(image)) => image,
+            Ok(None) => {
+                report.add(ItemOutcome::Skipped);
+                continue;
             }
-        }
-        AtlasType::Expressions => {
-            let emotion_indices = [2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 22, 24, 26, 32, 34];
-            let ignore_portraits = [143, 144, 177, 415];
+            Err(e) => {
+                println!("  Warning: portrait {} failed to decode: {}", key, e);
+                report.add(ItemOutcome::Failed);
+                continue;
+            }
+        };
+        report.add(ItemOutcome::Processed);
 
-            for pokemon_id in 0..max_portraits {
-                if ignore_portraits.contains(&pokemon_id) {
-                    continue;
-                }
+        let grid_x = grid_index as u32 % frames_per_row;
+        let grid_y = grid_index as u32 / frames_per_row;
 
-                let mut emotion_idx = 1;
-                for &emotion_index in &emotion_indices {
-                    if pokemon_id == 37
-                        || pokemon_id == 146
-                        || (pokemon_id == 64 && emotion_index > 4)
-                    {
-                        continue;
-                    }
+        let x = grid_x * PORTRAIT_SIZE as u32;
+        let y = grid_y * PORTRAIT_SIZE as u32;
 
-                    if let Ok(Some(portrait)) =
-                        kao_file.get_portrait(pokemon_id as usize, emotion_index)
-                    {
-                        let grid_x = current_portrait_idx % frames_per_row;
-                        let grid_y = current_portrait_idx / frames_per_row;
-
-                        let x = grid_x * PORTRAIT_SIZE as u32;
-                        let y = grid_y * PORTRAIT_SIZE as u32;
-
-                        if let Ok(portrait_image) = portrait.to_rgba_image() {
-                            copy_image_to_atlas(
-                                &mut atlas,
-                                &portrait_image,
-                                x as usize,
-                                y as usize,
-                            );
-
-                            portrait_metadata.insert(
-                                format!("mon_{:03}_{}", pokemon_id + 1, emotion_idx),
-                                (x as usize, y as usize),
-                            );
-
-                            emotion_idx += 1;
-                            current_portrait_idx += 1;
-                        }
-                    }
-                }
-            }
-        }
+        copy_image_to_atlas(&mut atlas, &portrait_image, x as usize, y as usize);
+        portrait_metadata.insert(key, (x as usize, y as usize));
+        grid_index += 1;
     }
 
     let metadata_output_path = output_path.with_extension("json");
-    match save_metadata(&portrait_metadata, &metadata_output_path) {
+    let metadata = PortraitAtlasMetadata {
+        layout: layout.to_string(),
+        columns: frames_per_row,
+        portraits: &portrait_metadata,
+    };
+    match save_metadata(&metadata, &metadata_output_path, sink, json_style) {
         Ok(_) => {
             println!("Successfully saved portrait metadata");
         }
@@ -341,17 +595,16 @@ pub fn create_portrait_atlas(
 
     println!("Saving atlas to {}...", output_path.display());
 
-    atlas
-        .save(output_path)
-        .map_err(|e| format!("Failed to save atlas image: {}", e))?;
+    let mut options = oxipng::Options::from_preset(4);
+    options.bit_depth_reduction = true;
 
-    if let Err(e) = optimise_portrait_png(output_path) {
-        println!("Warning: PNG optimisation failed: {}", e);
-    } else {
-        println!("PNG optimisation complete");
-    }
+    // An oxipng failure only prints a warning and keeps the unoptimised image
+    let encoded = output::encode_image(&atlas, image_format, Some(&options))
+        .map_err(|e| format!("Failed to encode atlas image: {}", e))?;
+    sink.write_file(output_path, &encoded)
+        .map_err(|e| format!("Failed to save atlas image: {}", e))?;
 
-    Ok(atlas)
+    Ok((atlas, report))
 }
 
 fn copy_image_to_atlas(atlas: &mut RgbaImage, portrait: &RgbaImage, x: usize, y: usize) {
@@ -366,92 +619,356 @@ fn copy_image_to_atlas(atlas: &mut RgbaImage, portrait: &RgbaImage, x: usize, y:
     }
 }
 
-fn save_metadata(metadata: &HashMap<String, (usize, usize)>, path: &PathBuf) -> Result<(), String> {
-    let json_string = serde_json::to_string_pretty(&metadata)
-        .map_err(|e| format!("Failed to serialise HashMap: {}", e))?;
-
-    let mut file = File::create(path).map_err(|e| format!("Failed to create file: {}", e))?;
-
-    file.write_all(json_string.as_bytes())
+fn save_metadata(
+    metadata: &PortraitAtlasMetadata,
+    path: &Path,
+    sink: &dyn OutputSink,
+    json_style: JsonStyle,
+) -> Result<(), String> {
+    let json_string = json_style
+        .to_string(metadata)
+        .map_err(|e| format!("Failed to serialise portrait metadata: {}", e))?;
+
+    sink.write_file(path, json_string.as_bytes())
         .map_err(|e| format!("Failed to write to file: {}", e))?;
 
     Ok(())
 }
 
-/// Optimises a PNG file using oxipng for better compression
-fn optimise_portrait_png(path: &Path) -> Result<(), String> {
-    let temp_path = path.with_extension("temp.png");
-
-    // If the file was already saved at this path, rename it to temp
-    if path.exists() {
-        std::fs::rename(path, &temp_path)
-            .map_err(|e| format!("Failed to prepare temp file: {}", e))?;
-    } else {
-        return Err("Image file not found at expected path".to_string());
-    }
-
-    let mut options = oxipng::Options::from_preset(4);
-
-    options.bit_depth_reduction = true;
-
-    oxipng::optimize(
-        &InFile::Path(temp_path.clone()),
-        &OutFile::Path(Some(path.to_path_buf())),
-        &options,
-    )
-    .map_err(|e| format!("PNG optimisation failed: {}", e))?;
-
-    // Remove the temporary file
-    if let Err(e) = std::fs::remove_file(&temp_path) {
-        println!("  Warning: Failed to remove temporary file: {}", e);
-    }
-
-    Ok(())
+/// Number of portraits the KAO TOC lists for an `atlas_type` atlas. `create_portrait_atlas`
+/// packs all of them except the ones that fail to decode.
+pub fn count_portraits(kao_file: &KaoFile, atlas_type: &AtlasType) -> usize {
+    atlas_entries(kao_file, atlas_type).len()
 }
 
-fn count_portraits(kao_file: &KaoFile, atlas_type: &AtlasType) -> usize {
-    let mut count = 0;
-    let max_portraits = match atlas_type {
-        AtlasType::Pokedex => 552,
-        AtlasType::Expressions => 535,
-    };
+/// Pokemon whose expression portraits are left out of the expressions atlas
+const EXPRESSION_SKIPPED_POKEMON: [usize; 6] = [37, 143, 144, 146, 177, 415];
+
+/// Lists the (metadata key, pokemon index, subindex) of every portrait that goes in the atlas.
+///
+/// The Pokedex atlas takes the neutral portrait (slot 0). The expressions atlas takes every
+/// other unmirrored slot the KAO TOC actually has, keyed by expression name. Portraits are
+/// listed by their TOC pointer alone, decoding them is left to the caller.
+fn atlas_entries(kao_file: &KaoFile, atlas_type: &AtlasType) -> Vec<(String, usize, usize)> {
+    let mut entries = Vec::new();
 
     match atlas_type {
         AtlasType::Pokedex => {
-            for pokemon_id in 0..max_portraits {
+            for pokemon_id in 0..552 {
                 if pokemon_id > 535 && pokemon_id < 551 {
                     continue;
                 }
 
-                if let Ok(Some(_)) = kao_file.get_portrait(pokemon_id as usize, 0) {
-                    count += 1;
+                if kao_file
+                    .emotions_present(pokemon_id)
+                    .contains(&Emotion::Normal.as_subindex())
+                {
+                    entries.push((
+                        format!("mon_{:03}", pokemon_id + 1),
+                        pokemon_id,
+                        Emotion::Normal.as_subindex(),
+                    ));
                 }
             }
         }
         AtlasType::Expressions => {
-            let emotion_indices = [2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 22, 24, 26, 32, 34];
-            let ignore_portraits = [143, 144, 177, 415];
-
-            for pokemon_id in 0..max_portraits {
-                if ignore_portraits.contains(&pokemon_id) {
+            for pokemon_id in 0..535 {
+                if EXPRESSION_SKIPPED_POKEMON.contains(&pokemon_id) {
                     continue;
                 }
 
-                for &emotion_index in &emotion_indices {
-                    if pokemon_id == 37
-                        || pokemon_id == 146
-                        || (pokemon_id == 64 && emotion_index > 4)
-                    {
+                for emotion in kao_file
+                    .emotions_present(pokemon_id)
+                    .into_iter()
+                    .filter_map(Emotion::from_subindex)
+                {
+                    // Normal is in the Pokedex atlas, flipped slots are mirrors
+                    if emotion == Emotion::Normal || emotion.is_flipped() {
                         continue;
                     }
-
-                    if let Ok(Some(_)) = kao_file.get_portrait(pokemon_id as usize, emotion_index) {
-                        count += 1;
+                    // Only Happy and Pain are usable for Pokemon 64
+                    if pokemon_id == 64 && emotion.as_subindex() > Emotion::Pain.as_subindex() {
+                        continue;
                     }
+
+                    entries.push((
+                        format!("mon_{:03}_{}", pokemon_id + 1, emotion.name()),
+                        pokemon_id,
+                        emotion.as_subindex(),
+                    ));
                 }
             }
         }
     }
 
-    count
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::MemorySink;
+
+    /// Single-segment KAO where each `portraits` entry is the normal portrait of the next
+    /// Pokemon, as raw palette + AT4PX bytes. Every other slot is empty.
+    fn kao_fixture(portraits: &[Vec<u8>]) -> Vec<u8> {
+        let toc_size = portraits.len() * KAO_PORTRAITS_PER_POKEMON * KAO_PORTRAIT_POINTER_SIZE;
+        let mut toc = vec![-1i32; portraits.len() * KAO_PORTRAITS_PER_POKEMON];
+        let mut data = Vec::new();
+        for (pokemon_id, portrait) in portraits.iter().enumerate() {
+            toc[pokemon_id * KAO_PORTRAITS_PER_POKEMON] =
+                (KAO_FIRST_TOC_OFFSET + toc_size + data.len()) as i32;
+            data.extend_from_slice(portrait);
+        }
+
+        let mut kao = vec![0u8; KAO_FIRST_TOC_OFFSET];
+        kao.extend(toc.iter().flat_map(|pointer| pointer.to_le_bytes()));
+        kao.extend(data);
+        kao
+    }
+
+    /// Single-Pokemon KAO with the same portrait in every slot of `subindices`
+    fn kao_with_slots(subindices: &[usize], portrait: &[u8]) -> Vec<u8> {
+        let toc_size = KAO_PORTRAITS_PER_POKEMON * KAO_PORTRAIT_POINTER_SIZE;
+        let mut toc = [-1i32; KAO_PORTRAITS_PER_POKEMON];
+        for &subindex in subindices {
+            toc[subindex] = (KAO_FIRST_TOC_OFFSET + toc_size) as i32;
+        }
+
+        let mut kao = vec![0u8; KAO_FIRST_TOC_OFFSET];
+        kao.extend(toc.iter().flat_map(|pointer| pointer.to_le_bytes()));
+        kao.extend_from_slice(portrait);
+        kao
+    }
+
+    #[test]
+    fn recompressed_portrait_decodes_to_the_same_pixels() {
+        let palette: Vec<u8> = (0..16u8)
+            .flat_map(|i| [i * 16, 255 - i * 16, i * 8])
+            .collect();
+        // 40x40 at 4bpp, a different nibble pattern in each 8x8 tile
+        let tiles: Vec<u8> = (0..800u32)
+            .map(|i| ((i / 32 + i % 7) * 0x13) as u8)
+            .collect();
+        let mut portrait_bytes = palette.clone();
+        portrait_bytes.extend(At4pxContainer::compress(&tiles).unwrap());
+
+        let portrait = Portrait::from_bytes(&portrait_bytes).unwrap();
+        let original = portrait.to_rgba_image().unwrap();
+        let decompressed = At4pxContainer::deserialise(&portrait.compressed_data)
+            .unwrap()
+            .decompress()
+            .unwrap();
+        assert_eq!(decompressed, tiles);
+
+        let mut repacked = palette;
+        repacked.extend(At4pxContainer::compress(&decompressed).unwrap());
+        let repacked = Portrait::from_bytes(&repacked).unwrap();
+        assert_eq!(repacked.to_rgba_image().unwrap(), original);
+        assert_eq!(original.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn undecodable_portraits_are_reported_and_left_out_of_the_atlas() {
+        let mut good = vec![0u8; KAO_IMG_PAL_SIZE];
+        good[3..6].copy_from_slice(&[0xF8, 0x80, 0x08]);
+        // 40x40 at 4bpp, every pixel palette index 1
+        good.extend(At4pxContainer::compress(&[0x11; 800]).unwrap());
+        let mut corrupt = vec![0u8; KAO_IMG_PAL_SIZE];
+        corrupt.extend_from_slice(b"not an AT4PX container");
+        let kao = KaoFile::from_bytes(kao_fixture(&[good, corrupt])).unwrap();
+        assert_eq!(count_portraits(&kao, &AtlasType::Pokedex), 2);
+
+        let sink = MemorySink::default();
+        let path = Path::new("PORTRAIT/pokedex_atlas.png");
+        let (atlas, report) = create_portrait_atlas(
+            &kao,
+            &AtlasType::Pokedex,
+            PortraitLayout::FixedColumns(32),
+            path,
+            &sink,
+            JsonStyle::default(),
+            ImageFormat::Png,
+        )
+        .unwrap();
+
+        assert_eq!((report.processed, report.failed), (1, 1));
+        assert_eq!(atlas.get_pixel(0, 0).0, [0xF8, 0x80, 0x08, 255]);
+        let json: serde_json::Value =
+            serde_json::from_slice(&sink.file(&path.with_extension("json")).unwrap()).unwrap();
+        assert_eq!(json["portraits"]["mon_001"], serde_json::json!([0, 0]));
+        assert!(json["portraits"].get("mon_002").is_none());
+    }
+
+    #[test]
+    fn atlas_metadata_nests_portraits_beside_the_layout() {
+        let portraits = HashMap::from([
+            ("layout".to_string(), (0, 0)),
+            ("0025_normal".to_string(), (40, 0)),
+        ]);
+        let metadata = PortraitAtlasMetadata {
+            layout: PortraitLayout::FixedColumns(32).to_string(),
+            columns: 32,
+            portraits: &portraits,
+        };
+        let sink = MemorySink::default();
+        let path = Path::new("PORTRAIT/expressions_atlas.json");
+        save_metadata(&metadata, path, &sink, JsonStyle::default()).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&sink.file(path).unwrap()).unwrap();
+        assert_eq!(json["columns"], 32);
+        assert_eq!(json["layout"], PortraitLayout::FixedColumns(32).to_string());
+        // A portrait key can't collide with the layout fields
+        assert_eq!(json["portraits"]["layout"], serde_json::json!([0, 0]));
+        assert_eq!(json["portraits"]["0025_normal"], serde_json::json!([40, 0]));
+        assert_eq!(json.as_object().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn second_segment_portraits_follow_the_first() {
+        // Solid portrait of palette index 1, coloured `rgb`
+        let solid = |rgb: [u8; 3]| {
+            let mut bytes = vec![0u8; KAO_IMG_PAL_SIZE];
+            bytes[3..6].copy_from_slice(&rgb);
+            bytes.extend(At4pxContainer::compress(&[0x11; 800]).unwrap());
+            bytes
+        };
+        let red = [0xF8, 0, 0];
+        let blue = [0, 0, 0xF8];
+        let kao = KaoFile::from_segments(vec![
+            kao_fixture(&[solid(red), solid(red)]),
+            kao_fixture(&[solid(blue)]),
+        ])
+        .unwrap();
+
+        let colour = |index| {
+            let portrait = kao.get_portrait(index, 0).unwrap().unwrap();
+            let [r, g, b, _] = portrait.to_rgba_image().unwrap().get_pixel(0, 0).0;
+            [r, g, b]
+        };
+        assert_eq!(colour(1), red);
+        assert_eq!(colour(2), blue);
+        assert!(kao.get_portrait(2, 1).unwrap().is_none());
+        assert!(kao.get_portrait(3, 0).is_err());
+    }
+
+    #[test]
+    fn portraits_in_unexpected_slots_are_still_exported() {
+        let mut portrait = vec![0u8; KAO_IMG_PAL_SIZE];
+        portrait.extend(At4pxContainer::compress(&[0x11; 800]).unwrap());
+        // Special0 and Special3 were missing from the old fixed slot list
+        let kao = KaoFile::from_bytes(kao_with_slots(&[0, 28, 38], &portrait)).unwrap();
+        assert_eq!(kao.emotions_present(0), vec![0, 28, 38]);
+
+        let sink = MemorySink::default();
+        let path = Path::new("PORTRAIT/expressions_atlas.png");
+        let (_, report) = create_portrait_atlas(
+            &kao,
+            &AtlasType::Expressions,
+            PortraitLayout::FixedColumns(32),
+            path,
+            &sink,
+            JsonStyle::default(),
+            ImageFormat::Png,
+        )
+        .unwrap();
+
+        assert_eq!(report.processed, 2);
+        let json: serde_json::Value =
+            serde_json::from_slice(&sink.file(&path.with_extension("json")).unwrap()).unwrap();
+        let keys: Vec<&String> = json["portraits"].as_object().unwrap().keys().collect();
+        assert_eq!(keys.len(), 2);
+        assert!(json["portraits"].get("mon_001_special0").is_some());
+        assert!(json["portraits"].get("mon_001_special3").is_some());
+    }
+
+    #[test]
+    fn emotions_map_to_their_subindices() {
+        for (subindex, emotion) in Emotion::ALL.into_iter().enumerate() {
+            assert_eq!(emotion.as_subindex(), subindex);
+            assert_eq!(Emotion::from_subindex(subindex), Some(emotion));
+            assert_eq!(emotion.is_flipped(), subindex % 2 == 1);
+        }
+        assert_eq!(Emotion::from_subindex(KAO_PORTRAITS_PER_POKEMON), None);
+        assert_eq!(Emotion::Pain.as_subindex(), 4);
+        assert_eq!(Emotion::Special3Flipped.as_subindex(), 39);
+        assert_eq!(Emotion::Happy.name(), "happy");
+        assert_eq!(Emotion::HappyFlipped.name(), "happy_flipped");
+
+        let mut portrait = vec![0u8; KAO_IMG_PAL_SIZE];
+        portrait.extend(At4pxContainer::compress(&[0x11; 800]).unwrap());
+        let kao = KaoFile::from_bytes(kao_with_slots(&[0, 28], &portrait)).unwrap();
+        assert_eq!(
+            kao.emotions_present(0),
+            vec![
+                Emotion::Normal.as_subindex(),
+                Emotion::Special0.as_subindex()
+            ]
+        );
+        assert!(kao
+            .get_portrait_by_emotion(0, Emotion::Special0)
+            .unwrap()
+            .is_some());
+        assert!(kao
+            .get_portrait_by_emotion(0, Emotion::Happy)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn parallel_and_serial_atlases_are_identical() {
+        // A distinct solid colour per Pokemon, with an undecodable portrait partway through so
+        // later portraits have to move up a grid slot
+        let portraits: Vec<Vec<u8>> = (0..40u8)
+            .map(|i| {
+                let mut portrait = vec![0u8; KAO_IMG_PAL_SIZE];
+                if i == 17 {
+                    portrait.extend_from_slice(b"not an AT4PX container");
+                } else {
+                    portrait[3..6].copy_from_slice(&[i * 6, 255 - i * 6, 0x80]);
+                    portrait.extend(At4pxContainer::compress(&[0x11; 800]).unwrap());
+                }
+                portrait
+            })
+            .collect();
+        let kao = KaoFile::from_bytes(kao_fixture(&portraits)).unwrap();
+        let path = Path::new("PORTRAIT/pokedex_atlas.png");
+
+        let build = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let sink = MemorySink::default();
+            let (atlas, report) = pool
+                .install(|| {
+                    create_portrait_atlas(
+                        &kao,
+                        &AtlasType::Pokedex,
+                        PortraitLayout::FixedColumns(8),
+                        path,
+                        &sink,
+                        JsonStyle::default(),
+                        ImageFormat::Png,
+                    )
+                })
+                .unwrap();
+            let json: serde_json::Value =
+                serde_json::from_slice(&sink.file(&path.with_extension("json")).unwrap()).unwrap();
+            (atlas.into_raw(), sink.file(path).unwrap(), json, report)
+        };
+
+        let (serial_rgba, serial_png, serial_json, serial_report) = build(1);
+        let (parallel_rgba, parallel_png, parallel_json, parallel_report) = build(8);
+        assert_eq!((serial_report.processed, serial_report.failed), (39, 1));
+        assert_eq!((parallel_report.processed, parallel_report.failed), (39, 1));
+        assert!(serial_rgba == parallel_rgba);
+        assert!(serial_png == parallel_png);
+        assert_eq!(serial_json, parallel_json);
+        // The Pokemon after the corrupt one takes its slot
+        assert_eq!(
+            serial_json["portraits"]["mon_019"],
+            serde_json::json!([40, 80])
+        );
+    }
 }
@@ -222,7 +222,7 @@ pub fn parse_screen_effect(
 
 /// Render one frame onto a 256x160 RGBA canvas.
 ///
-/// Per-pixel transparency is baked, identically to the WAN renderer. 
+/// Per-pixel transparency is baked, identically to the WAN renderer.
 /// The per-frame global `alpha` is deliberately not baked. It is
 /// emitted as metadata for the client.
 pub fn render_screen_frame(file: &ScreenEffectFile, frame: &ScreenFrame) -> RgbaImage {
@@ -0,0 +1,67 @@
+//! # Tiled image data
+//!
+//! WAN pieces and KAO portraits store pixels as consecutive 8x8 tiles, left to right then top
+//! to bottom. 4bpp tiles are 32 bytes with the low nibble first, 8bpp tiles are 64 bytes.
+
+/// Width and height of a single tile in pixels
+pub const TILE_DIM: usize = 8;
+
+/// Linearises `tiles_x` by `tiles_y` tiles of `bpp` (4 or 8) pixel data into a row-major grid
+/// of palette indices, `tiles_x * 8` wide. Pixels past the end of `buffer` are 0.
+pub fn decode_tiled_indices(buffer: &[u8], tiles_x: usize, tiles_y: usize, bpp: u8) -> Vec<u8> {
+    let width = tiles_x * TILE_DIM;
+    let mut indices = vec![0u8; width * tiles_y * TILE_DIM];
+    let bytes_per_tile = TILE_DIM * TILE_DIM * bpp as usize / 8;
+
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let tile_start = (ty * tiles_x + tx) * bytes_per_tile;
+            for y in 0..TILE_DIM {
+                for x in 0..TILE_DIM {
+                    let pixel_in_tile = y * TILE_DIM + x;
+                    let index = if bpp == 8 {
+                        buffer.get(tile_start + pixel_in_tile).copied()
+                    } else {
+                        buffer.get(tile_start + pixel_in_tile / 2).map(|&byte| {
+                            if pixel_in_tile % 2 == 0 {
+                                byte & 0x0F
+                            } else {
+                                byte >> 4
+                            }
+                        })
+                    };
+                    if let Some(index) = index {
+                        indices[(ty * TILE_DIM + y) * width + tx * TILE_DIM + x] = index;
+                    }
+                }
+            }
+        }
+    }
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_linearise_left_to_right_with_the_low_nibble_first() {
+        // Two 4bpp tiles side by side: the first all 1/2 nibble pairs, the second all 3
+        let mut buffer = vec![0x21; 32];
+        buffer.extend([0x33; 32]);
+
+        let indices = decode_tiled_indices(&buffer, 2, 1, 4);
+
+        assert_eq!(indices.len(), 16 * 8);
+        assert_eq!(&indices[..10], &[1, 2, 1, 2, 1, 2, 1, 2, 3, 3]);
+        // Second row of the grid continues the first tile, not the second
+        assert_eq!(&indices[16..18], &[1, 2]);
+        assert!(indices[8..16].iter().all(|&index| index == 3));
+
+        // 8bpp reads one byte per pixel, missing data stays 0
+        let indices = decode_tiled_indices(&[7; 64], 2, 1, 8);
+        assert_eq!(indices[7], 7);
+        assert_eq!(indices[8], 0);
+    }
+}
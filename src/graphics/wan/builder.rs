@@ -0,0 +1,611 @@
+//! Synthetic WAN builder
+//!
+//! Emits minimal but valid character/effect WAN content (the SIR0 payload, or the whole SIR0
+//! file with `build_sir0`) so the parser and renderer can be exercised without a ROM. Compiled
+//! for tests and with the `wan-builder` feature.
+//!
+//! ```ignore
+//! let (content, data_pointer) = WanBuilder::new(WanType::Effect)
+//!     .palette(vec![(0, 0, 0); 16])
+//!     .image(vec![0x11; 32])
+//!     .frame(vec![BuilderPiece::new(0, 256, 512)])
+//!     .group(vec![vec![BuilderSeqFrame::new(0, 4)]])
+//!     .build();
+//! let options = ParseOptions::default();
+//! let wan = parse_wan_from_sir0_content(&content, data_pointer, WanType::Effect, &options)?;
+//! ```
+
+// Fixture tooling, outside of test builds nothing in the crate calls it
+#![cfg_attr(not(test), allow(dead_code))]
+
+use crate::{
+    containers::sir0::Sir0,
+    graphics::wan::{flags, WanType},
+};
+
+/// One OAM piece of a meta frame. Offsets use the same convention the parser reports:
+/// signed, centred on 0 for characters and unsigned, centred on 256/512 for effects.
+#[derive(Debug, Clone, Copy)]
+pub struct BuilderPiece {
+    pub tile_num: u16,
+    pub palette_index: u8,
+    pub x_offset: i16,
+    pub y_offset: i16,
+    /// Index into `DIM_TABLE`
+    pub resolution_idx: usize,
+    pub h_flip: bool,
+    pub v_flip: bool,
+}
+
+impl BuilderPiece {
+    /// An 8x8 piece using palette 0
+    pub fn new(tile_num: u16, x_offset: i16, y_offset: i16) -> Self {
+        Self {
+            tile_num,
+            palette_index: 0,
+            x_offset,
+            y_offset,
+            resolution_idx: 0,
+            h_flip: false,
+            v_flip: false,
+        }
+    }
+}
+
+/// One entry of an animation sequence
+#[derive(Debug, Clone, Copy)]
+pub struct BuilderSeqFrame {
+    pub frame_index: u16,
+    /// Must be non-zero, a zero duration terminates the sequence
+    pub duration: u8,
+    pub flag: u8,
+    pub offset: (i16, i16),
+    pub shadow: (i16, i16),
+}
+
+impl BuilderSeqFrame {
+    pub fn new(frame_index: u16, duration: u8) -> Self {
+        Self {
+            frame_index,
+            duration,
+            flag: 0,
+            offset: (0, 0),
+            shadow: (0, 0),
+        }
+    }
+}
+
+/// Builds WAN bytes from palettes, raw image strips, meta frames and animation groups.
+///
+/// Character WANs treat each group as `[direction]` sequences, effect WANs as `[sequence]`.
+/// Effect WANs need at least one group, the parser uses group 0 to find the end of the meta
+/// frame table.
+#[derive(Debug, Clone)]
+pub struct WanBuilder {
+    wan_type: WanType,
+    palettes: Vec<Vec<(u8, u8, u8)>>,
+    images: Vec<Vec<u8>>,
+    frames: Vec<Vec<BuilderPiece>>,
+    groups: Vec<Vec<Vec<BuilderSeqFrame>>>,
+    palette_offset: u16,
+    colour_256: bool,
+    anim_header_unknowns: [u16; 5],
+}
+
+impl WanBuilder {
+    pub fn new(wan_type: WanType) -> Self {
+        Self {
+            wan_type,
+            palettes: Vec::new(),
+            images: Vec::new(),
+            frames: Vec::new(),
+            groups: Vec::new(),
+            palette_offset: 0,
+            colour_256: false,
+            anim_header_unknowns: [0; 5],
+        }
+    }
+
+    /// Adds a 16 colour palette row, padded or truncated to 16 entries
+    pub fn palette(mut self, mut colours: Vec<(u8, u8, u8)>) -> Self {
+        colours.resize(16, (0, 0, 0));
+        self.palettes.push(colours);
+        self
+    }
+
+    /// Adds an image as raw 4bpp pixel bytes, referenced by `BuilderPiece::tile_num`
+    pub fn image(mut self, pixels: Vec<u8>) -> Self {
+        self.images.push(pixels);
+        self
+    }
+
+    /// Adds a meta frame, referenced by `BuilderSeqFrame::frame_index`
+    pub fn frame(mut self, pieces: Vec<BuilderPiece>) -> Self {
+        self.frames.push(pieces);
+        self
+    }
+
+    /// Adds an animation group made of sequences
+    pub fn group(mut self, sequences: Vec<Vec<BuilderSeqFrame>>) -> Self {
+        self.groups.push(sequences);
+        self
+    }
+
+    /// Effect only, written to palette info Unk#5
+    pub fn palette_offset(mut self, palette_offset: u16) -> Self {
+        self.palette_offset = palette_offset;
+        self
+    }
+
+    /// Character only: sets Is256ColourSpr and marks every piece as 256 colour, images are then
+    /// raw 8bpp pixel bytes
+    pub fn colour_256(mut self) -> Self {
+        self.colour_256 = true;
+        self
+    }
+
+    /// Written to the animation info header's Unk#6 - Unk#10
+    pub fn anim_header_unknowns(mut self, unknowns: [u16; 5]) -> Self {
+        self.anim_header_unknowns = unknowns;
+        self
+    }
+
+    /// Returns the WAN content and the data pointer to pass to `parse_wan_from_sir0_content`
+    pub fn build(&self) -> (Vec<u8>, u32) {
+        match self.wan_type {
+            WanType::Character => self.build_character(),
+            WanType::Effect => self.build_effect(),
+        }
+    }
+
+    /// Returns the WAN wrapped in a SIR0 file, as the ROM's bin packs store them
+    pub fn build_sir0(&self) -> Vec<u8> {
+        let (content, data_pointer) = self.build();
+        Sir0::serialise(&content, &[], data_pointer)
+    }
+
+    fn build_character(&self) -> (Vec<u8>, u32) {
+        let mut w = ByteWriter::new();
+        let chunk_tables = self.write_images(&mut w);
+
+        // The parser sizes the palette block by where the image data table starts
+        let ptr_palette_block = w.pos();
+        self.write_palette_block(&mut w, 0x80);
+        let ptr_image_table = w.pos();
+        for &ptr in &chunk_tables {
+            w.u32(ptr);
+        }
+
+        let ptr_palette_info = w.pos();
+        w.u32(ptr_palette_block);
+        w.u16(0); // Unk#3
+        w.u16(16); // colours per row
+        w.u16(0); // Unk#4
+        w.u16(0xFF); // Unk#5
+        w.u32(0);
+
+        let ptr_image_info = w.pos();
+        w.u32(ptr_image_table);
+        w.u32(ptr_palette_info);
+        w.u16(0); // Unk#13
+        w.u16(self.colour_256 as u16); // Is256ColourSpr
+        w.u16(1); // Unk#11
+        w.u16(self.images.len() as u16);
+
+        let frame_ptrs: Vec<u32> = self
+            .frames
+            .iter()
+            .map(|pieces| {
+                let ptr = w.pos();
+                for (i, piece) in pieces.iter().enumerate() {
+                    write_character_piece(&mut w, piece, self.colour_256, i + 1 == pieces.len());
+                }
+                ptr
+            })
+            .collect();
+
+        // The offsets table must directly follow the ref table, it marks the table's end
+        let ptr_frame_refs = w.pos();
+        for &ptr in &frame_ptrs {
+            w.u32(ptr);
+        }
+        let ptr_offsets = w.pos();
+        for _ in &frame_ptrs {
+            w.zeros(16);
+        }
+
+        let ptr_group_table = self.write_groups(&mut w, write_character_seq_frame);
+
+        let ptr_anim_info = w.pos();
+        w.u32(ptr_frame_refs);
+        w.u32(ptr_offsets);
+        w.u32(ptr_group_table);
+        w.u16(self.groups.len() as u16);
+        for &unk in &self.anim_header_unknowns {
+            w.u16(unk); // Unk#6 - Unk#10
+        }
+
+        let data_pointer = w.pos();
+        w.u32(ptr_anim_info);
+        w.u32(ptr_image_info);
+        w.u16(1); // character image type
+        w.u16(0);
+        w.zeros(16);
+
+        (w.into_inner(), data_pointer)
+    }
+
+    fn build_effect(&self) -> (Vec<u8>, u32) {
+        let mut w = ByteWriter::new();
+        let chunk_tables = self.write_images(&mut w);
+
+        // The parser sizes the palette block by where palette info starts
+        let ptr_palette_block = w.pos();
+        self.write_palette_block(&mut w, 0);
+        let ptr_palette_info = w.pos();
+        w.u32(ptr_palette_block);
+        w.u16(0);
+        w.u16(16);
+        w.u16(0);
+        w.u16(self.palette_offset);
+        w.u32(0);
+
+        let ptr_image_table = w.pos();
+        for &ptr in &chunk_tables {
+            w.u32(ptr);
+        }
+
+        let ptr_image_info = w.pos();
+        w.u32(ptr_image_table);
+        w.u32(ptr_palette_info);
+        w.u16(0);
+        w.u16(0); // 4bpp
+        w.u16(1);
+        w.u16(self.images.len() as u16);
+
+        let frame_ptrs: Vec<u32> = self
+            .frames
+            .iter()
+            .map(|pieces| {
+                let ptr = w.pos();
+                for (i, piece) in pieces.iter().enumerate() {
+                    write_effect_piece(&mut w, piece, i + 1 == pieces.len());
+                }
+                ptr
+            })
+            .collect();
+
+        let seq_ptrs = self.write_sequences(&mut w, write_effect_seq_frame);
+
+        // Group 0's sequence pointer array must directly follow the ref table, it marks the end
+        let ptr_frame_refs = w.pos();
+        for &ptr in &frame_ptrs {
+            w.u32(ptr);
+        }
+        let ptr_group_table = self.write_group_table(&mut w, &seq_ptrs);
+
+        let ptr_anim_info = w.pos();
+        w.u32(ptr_frame_refs);
+        w.u32(0);
+        w.u32(ptr_group_table);
+        w.u16(self.groups.len() as u16);
+        for &unk in &self.anim_header_unknowns {
+            w.u16(unk); // Unk#6 - Unk#10
+        }
+
+        let data_pointer = w.pos();
+        w.u32(ptr_anim_info);
+        w.u32(ptr_image_info);
+        w.zeros(16);
+
+        (w.into_inner(), data_pointer)
+    }
+
+    /// Writes pixel strips and one chunk table per image, returning the chunk table pointers
+    fn write_images(&self, w: &mut ByteWriter) -> Vec<u32> {
+        // Keep every pointer non-zero, a null pixel pointer means "fill with zeros"
+        w.zeros(16);
+        let pixel_ptrs: Vec<u32> = self
+            .images
+            .iter()
+            .map(|pixels| {
+                let ptr = w.pos();
+                w.bytes(pixels);
+                ptr
+            })
+            .collect();
+
+        self.images
+            .iter()
+            .zip(pixel_ptrs)
+            .map(|(pixels, pixel_ptr)| {
+                let ptr = w.pos();
+                w.u32(pixel_ptr);
+                w.u16(pixels.len() as u16);
+                w.u16(0);
+                w.u32(0); // z-sort
+                w.zeros(12); // terminator
+                ptr
+            })
+            .collect()
+    }
+
+    fn write_palette_block(&self, w: &mut ByteWriter, padding: u8) {
+        for row in &self.palettes {
+            for &(r, g, b) in row {
+                w.bytes(&[r, g, b, padding]);
+            }
+        }
+    }
+
+    fn write_sequences(
+        &self,
+        w: &mut ByteWriter,
+        write_frame: fn(&mut ByteWriter, &BuilderSeqFrame),
+    ) -> Vec<Vec<u32>> {
+        self.groups
+            .iter()
+            .map(|sequences| {
+                sequences
+                    .iter()
+                    .map(|frames| {
+                        let ptr = w.pos();
+                        for frame in frames {
+                            write_frame(w, frame);
+                        }
+                        w.zeros(12); // zero duration terminator
+                        ptr
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn write_group_table(&self, w: &mut ByteWriter, seq_ptrs: &[Vec<u32>]) -> u32 {
+        let array_ptrs: Vec<u32> = seq_ptrs
+            .iter()
+            .map(|ptrs| {
+                let ptr = w.pos();
+                for &p in ptrs {
+                    w.u32(p);
+                }
+                ptr
+            })
+            .collect();
+
+        let ptr_group_table = w.pos();
+        for (array_ptr, ptrs) in array_ptrs.iter().zip(seq_ptrs) {
+            w.u32(if ptrs.is_empty() { 0 } else { *array_ptr });
+            w.u16(ptrs.len() as u16);
+            w.u16(0); // loop start
+        }
+        ptr_group_table
+    }
+
+    fn write_groups(
+        &self,
+        w: &mut ByteWriter,
+        write_frame: fn(&mut ByteWriter, &BuilderSeqFrame),
+    ) -> u32 {
+        let seq_ptrs = self.write_sequences(w, write_frame);
+        self.write_group_table(w, &seq_ptrs)
+    }
+}
+
+fn write_character_piece(
+    w: &mut ByteWriter,
+    piece: &BuilderPiece,
+    colour_256: bool,
+    is_last: bool,
+) {
+    let shape = (piece.resolution_idx as u16 >> 2) & 0x3;
+    let size = piece.resolution_idx as u16 & 0x3;
+
+    let mut attr0 = (piece.y_offset as i8 as u8 as u16) | (shape << 14);
+    if colour_256 {
+        attr0 |= flags::ATTR0_COL_PAL_MASK;
+    }
+    let mut attr1 = ((piece.x_offset + 256) as u16 & 0x1FF) | (size << 14);
+    if piece.h_flip {
+        attr1 |= flags::ATTR1_HFLIP_MASK;
+    }
+    if piece.v_flip {
+        attr1 |= flags::ATTR1_VFLIP_MASK;
+    }
+    if is_last {
+        attr1 |= flags::ATTR1_IS_LAST_MASK;
+    }
+    let attr2 = ((piece.palette_index as u16) << 12) | (piece.tile_num & 0x3FF);
+
+    w.u16(piece.tile_num);
+    w.u16(0);
+    w.u16(attr0);
+    w.u16(attr1);
+    w.u16(attr2);
+}
+
+fn write_effect_piece(w: &mut ByteWriter, piece: &BuilderPiece, is_last: bool) {
+    let shape = (piece.resolution_idx as u16 >> 2) & 0x3;
+    let size = piece.resolution_idx as u16 & 0x3;
+
+    let y_data = (piece.y_offset as u16 & 0x3FF) | (shape << 14);
+    let mut x_data = (piece.x_offset as u16 & 0x1FF) | (size << 14);
+    if is_last {
+        x_data |= 1 << 11;
+    }
+    if piece.h_flip {
+        x_data |= 1 << 12;
+    }
+    if piece.v_flip {
+        x_data |= 1 << 13;
+    }
+
+    w.zeros(3);
+    w.bytes(&[0]); // draw value
+    w.u16(y_data);
+    w.u16(x_data);
+    w.bytes(&[piece.tile_num as u8, piece.palette_index << 4]);
+}
+
+fn write_character_seq_frame(w: &mut ByteWriter, frame: &BuilderSeqFrame) {
+    w.bytes(&[frame.duration, frame.flag]);
+    w.u16(frame.frame_index);
+    w.i16(frame.offset.0);
+    w.i16(frame.offset.1);
+    w.i16(frame.shadow.0);
+    w.i16(frame.shadow.1);
+}
+
+fn write_effect_seq_frame(w: &mut ByteWriter, frame: &BuilderSeqFrame) {
+    w.bytes(&[frame.duration, frame.flag]);
+    w.u16(frame.frame_index);
+    w.i16(frame.offset.0);
+    w.i16(frame.offset.1);
+    w.zeros(4);
+}
+
+/// Little-endian append-only buffer
+struct ByteWriter {
+    buf: Vec<u8>,
+}
+
+impl ByteWriter {
+    fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    fn pos(&self) -> u32 {
+        self.buf.len() as u32
+    }
+
+    fn bytes(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    fn zeros(&mut self, count: usize) {
+        self.buf.resize(self.buf.len() + count, 0);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn i16(&mut self, value: i16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wan::{
+        parser::{parse_wan_from_sir0_content, ParseOptions},
+        AnimationStructure, WanFile,
+    };
+
+    fn parse(builder: &WanBuilder, wan_type: WanType) -> WanFile {
+        let (content, data_pointer) = builder.build();
+        parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            wan_type,
+            &ParseOptions {
+                strict: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn character_wan_round_trips_through_parser() {
+        let mut flipped = BuilderPiece::new(1, -8, -16);
+        flipped.palette_index = 1;
+        flipped.h_flip = true;
+        flipped.v_flip = true;
+        let mut walk = BuilderSeqFrame::new(1, 6);
+        walk.flag = flags::FRAME_HIT_MASK;
+        walk.offset = (2, -3);
+        walk.shadow = (1, 4);
+
+        let builder = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (255, 0, 0)])
+            .palette(vec![(0, 0, 0), (0, 0, 255)])
+            .image(vec![0x11; 32])
+            .image(vec![0x21; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, -8, -16), flipped])
+            .group(vec![vec![BuilderSeqFrame::new(0, 4), walk]]);
+        let wan = parse(&builder, WanType::Character);
+
+        assert_eq!(wan.wan_type, WanType::Character);
+        assert_eq!(wan.palette_count(), 2);
+        assert_eq!(wan.palette(0).unwrap()[1], (255, 0, 0, 255));
+        assert_eq!(wan.palette(1).unwrap()[1], (0, 0, 255, 255));
+        assert_eq!(wan.img_data.len(), 2);
+        assert_eq!(wan.img_data[1].img_px, vec![0x21; 32]);
+
+        assert_eq!(wan.frame_data.len(), 2);
+        let piece = &wan.frame_data[1].pieces[1];
+        assert_eq!(piece.tile_num, 1);
+        assert_eq!(piece.palette_index, 1);
+        assert_eq!((piece.x_offset, piece.y_offset), (-8, -16));
+        assert!(piece.h_flip && piece.v_flip);
+        assert_eq!(piece.resolution_idx, 0);
+
+        let AnimationStructure::Character(groups) = &wan.animations else {
+            panic!("character WAN parsed as effect");
+        };
+        assert_eq!(groups.len(), 1);
+        let frames = &groups[0][0].frames;
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].frame_index, frames[0].duration), (0, 4));
+        assert_eq!((frames[1].frame_index, frames[1].duration), (1, 6));
+        assert_eq!(frames[1].flag, flags::FRAME_HIT_MASK);
+        assert_eq!(frames[1].offset, (2, -3));
+        assert_eq!(frames[1].shadow, (1, 4));
+    }
+
+    #[test]
+    fn effect_wan_round_trips_through_parser() {
+        let mut wide = BuilderPiece::new(1, 248, 500);
+        wide.resolution_idx = 1;
+        let builder = WanBuilder::new(WanType::Effect)
+            .palette(vec![(0, 0, 0), (0, 255, 0)])
+            .image(vec![0x11; 32])
+            .image(vec![0x11; 128])
+            .frame(vec![BuilderPiece::new(0, 256, 512)])
+            .frame(vec![wide])
+            .group(vec![
+                vec![BuilderSeqFrame::new(0, 3)],
+                vec![BuilderSeqFrame::new(1, 5), BuilderSeqFrame::new(0, 2)],
+            ])
+            .palette_offset(0x10D);
+        let wan = parse(&builder, WanType::Effect);
+
+        assert_eq!(wan.wan_type, WanType::Effect);
+        assert_eq!(wan.palette_offset, 13);
+        assert_eq!(wan.palette(0).unwrap()[1], (0, 255, 0, 255));
+        assert_eq!(wan.img_data.len(), 2);
+        assert_eq!(wan.frame_data.len(), 2);
+        let piece = &wan.frame_data[1].pieces[0];
+        assert_eq!((piece.x_offset, piece.y_offset), (248, 500));
+        assert_eq!(piece.resolution_idx, 1);
+
+        let AnimationStructure::Effect(groups) = &wan.animations else {
+            panic!("effect WAN parsed as character");
+        };
+        assert_eq!(groups[0].len(), 2);
+        let durations: Vec<_> = groups[0][1].frames.iter().map(|f| f.duration).collect();
+        assert_eq!(durations, [5, 2]);
+        assert_eq!(groups[0][1].frames[0].frame_index, 1);
+    }
+}
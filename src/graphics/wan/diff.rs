@@ -0,0 +1,160 @@
+//! Structural comparison of two WAN files
+//!
+//! Used to spot which sprites changed between ROM versions (NA/EU/JP) or between a base
+//! ROM and a hack. Only structure is compared, not rendered pixels.
+
+use std::fmt;
+
+use super::{AnimationStructure, WanFile, WanType};
+
+/// Differences between two WAN files. Every `(a, b)` pair holds the value from each side.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WanDiff {
+    pub wan_type: Option<(WanType, WanType)>,
+    pub frame_count: Option<(usize, usize)>,
+    pub img_piece_count: Option<(usize, usize)>,
+    pub palette_count: Option<(usize, usize)>,
+    /// Palette indices present in both files whose colours differ
+    pub changed_palettes: Vec<usize>,
+    pub group_count: Option<(usize, usize)>,
+    /// (group, sequence) pairs that are missing on one side or whose frames differ
+    pub changed_animations: Vec<(usize, usize)>,
+}
+
+impl WanDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == WanDiff::default()
+    }
+}
+
+impl fmt::Display for WanDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "identical");
+        }
+        if let Some((a, b)) = self.wan_type {
+            writeln!(f, "wan type: {} -> {}", a, b)?;
+        }
+        if let Some((a, b)) = self.frame_count {
+            writeln!(f, "frame count: {} -> {}", a, b)?;
+        }
+        if let Some((a, b)) = self.img_piece_count {
+            writeln!(f, "image pieces: {} -> {}", a, b)?;
+        }
+        if let Some((a, b)) = self.palette_count {
+            writeln!(f, "palette count: {} -> {}", a, b)?;
+        }
+        if !self.changed_palettes.is_empty() {
+            writeln!(f, "changed palettes: {:?}", self.changed_palettes)?;
+        }
+        if let Some((a, b)) = self.group_count {
+            writeln!(f, "animation groups: {} -> {}", a, b)?;
+        }
+        for (group, seq) in &self.changed_animations {
+            writeln!(f, "animation changed: group {} sequence {}", group, seq)?;
+        }
+        Ok(())
+    }
+}
+
+fn changed<T: PartialEq + Copy>(a: T, b: T) -> Option<(T, T)> {
+    (a != b).then_some((a, b))
+}
+
+/// Compares two WAN files and reports frame-count, palette, and animation-structure differences.
+pub fn diff(a: &WanFile, b: &WanFile) -> WanDiff {
+    let palette_len = a.custom_palette.len().min(b.custom_palette.len());
+    let changed_palettes = (0..palette_len)
+        .filter(|&i| a.custom_palette[i] != b.custom_palette[i])
+        .collect();
+
+    let groups_a = animation_groups(&a.animations);
+    let groups_b = animation_groups(&b.animations);
+
+    let mut changed_animations = Vec::new();
+    for group in 0..groups_a.len().max(groups_b.len()) {
+        let seqs_a = groups_a.get(group).map_or(0, |g| g.len());
+        let seqs_b = groups_b.get(group).map_or(0, |g| g.len());
+        for seq in 0..seqs_a.max(seqs_b) {
+            let anim_a = groups_a.get(group).and_then(|g| g.get(seq));
+            let anim_b = groups_b.get(group).and_then(|g| g.get(seq));
+            let same = match (anim_a, anim_b) {
+                (Some(x), Some(y)) => {
+                    x.frames.len() == y.frames.len()
+                        && x.frames.iter().zip(&y.frames).all(|(fa, fb)| {
+                            fa.frame_index == fb.frame_index
+                                && fa.duration == fb.duration
+                                && fa.flag == fb.flag
+                                && fa.offset == fb.offset
+                                && fa.shadow == fb.shadow
+                        })
+                }
+                _ => false,
+            };
+            if !same {
+                changed_animations.push((group, seq));
+            }
+        }
+    }
+
+    WanDiff {
+        wan_type: changed(a.wan_type, b.wan_type),
+        frame_count: changed(a.frame_data.len(), b.frame_data.len()),
+        img_piece_count: changed(a.img_data.len(), b.img_data.len()),
+        palette_count: changed(a.custom_palette.len(), b.custom_palette.len()),
+        changed_palettes,
+        group_count: changed(groups_a.len(), groups_b.len()),
+        changed_animations,
+    }
+}
+
+fn animation_groups(animations: &AnimationStructure) -> &Vec<Vec<super::Animation>> {
+    match animations {
+        AnimationStructure::Character(groups) | AnimationStructure::Effect(groups) => groups,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wan::{
+        builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+        parser::{parse_wan_from_sir0_content, ParseOptions},
+    };
+
+    fn parse(builder: WanBuilder) -> WanFile {
+        let (content, data_pointer) = builder.build();
+        parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn sprite() -> WanBuilder {
+        WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (255, 0, 0)])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 4)]; 8])
+    }
+
+    #[test]
+    fn one_extra_frame_is_the_only_difference() {
+        let a = parse(sprite());
+        let b = parse(sprite().frame(vec![BuilderPiece::new(0, -4, -4)]));
+
+        assert!(diff(&a, &a).is_empty());
+        let wan_diff = diff(&a, &b);
+        assert_eq!(
+            wan_diff,
+            WanDiff {
+                frame_count: Some((1, 2)),
+                ..Default::default()
+            }
+        );
+        assert_eq!(wan_diff.to_string(), "frame count: 1 -> 2\n");
+    }
+}
@@ -8,6 +8,9 @@ use std::{
     io::{self},
 };
 
+#[cfg(any(test, feature = "wan-builder"))]
+pub mod builder;
+pub mod diff;
 pub mod model;
 pub mod parser;
 pub mod renderer;
@@ -48,6 +51,19 @@ pub const DIM_TABLE: [(usize, usize); 12] = [
     (4, 8), // 11: 32x64
 ];
 
+/// OAM shape (attr0 bits 14-15) and size (attr1 bits 14-15) each take 4 values, giving
+/// `shape * 4 + size`. Shape 3 is prohibited on the DS, so only indices 0-11 are real.
+const _: () = assert!(DIM_TABLE.len() == 3 * 4);
+
+/// Maps OAM shape/size bits to a `DIM_TABLE` index.
+///
+/// Only the low 2 bits of each are used. Returns `None` for the prohibited shape 3, which can
+/// only come from corrupt data.
+pub fn resolution_index(shape: u16, size: u16) -> Option<usize> {
+    let idx = (((shape & 0x3) << 2) | (size & 0x3)) as usize;
+    (idx < DIM_TABLE.len()).then_some(idx)
+}
+
 // Attribute flag masks
 pub mod flags {
     pub const _ATTR0_FLAG_BITS_MASK: u16 = 0xFC00; // 1111 1100 0000 0000
@@ -77,6 +93,8 @@ pub enum WanError {
     InvalidDataStructure(String),
     Io(io::Error),
     OutOfBounds(String),
+    /// A recoverable problem that strict parsing refuses to paper over with a fallback
+    Degraded(String),
 }
 
 impl From<io::Error> for WanError {
@@ -91,6 +109,7 @@ impl fmt::Display for WanError {
             WanError::InvalidDataStructure(msg) => write!(f, "Invalid WAN data: {}", msg),
             WanError::Io(err) => write!(f, "I/O error: {}", err),
             WanError::OutOfBounds(msg) => write!(f, "Out of bounds: {}", msg),
+            WanError::Degraded(msg) => write!(f, "Strict parsing failed: {}", msg),
         }
     }
 }
@@ -100,9 +119,31 @@ impl From<WanError> for io::Error {
         match err {
             WanError::Io(io_err) => io_err,
             WanError::InvalidDataStructure(msg) => io::Error::new(io::ErrorKind::InvalidData, msg),
-            WanError::OutOfBounds(msg) => {
-                io::Error::new(io::ErrorKind::InvalidInput, msg)
+            WanError::OutOfBounds(msg) => io::Error::new(io::ErrorKind::InvalidInput, msg),
+            WanError::Degraded(msg) => io::Error::new(io::ErrorKind::InvalidData, msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_valid_shape_and_size_lands_in_dim_table() {
+        for shape in 0..4u16 {
+            for size in 0..4u16 {
+                let idx = resolution_index(shape, size);
+                if shape == 3 {
+                    assert_eq!(idx, None, "shape {} size {}", shape, size);
+                } else {
+                    let idx = idx.unwrap();
+                    assert_eq!(idx, (shape * 4 + size) as usize);
+                    assert!(idx < DIM_TABLE.len());
+                }
             }
         }
+        // Bits above the low 2 are ignored
+        assert_eq!(resolution_index(0b110, 0b101), Some(9));
     }
 }
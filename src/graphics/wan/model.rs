@@ -3,9 +3,12 @@
 //! This module defines the core data structures used to represent
 //! WAN sprite data
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr, sync::Once};
+
+use serde::{Deserialize, Serialize, Serializer};
 
 use super::{flags, WanType, DIM_TABLE, TEX_SIZE};
+use crate::data::animation_info::AnimPointType;
 
 pub type RgbaTuple = (u8, u8, u8, u8);
 pub type Palette = Vec<RgbaTuple>;
@@ -33,6 +36,111 @@ pub struct WanFile {
     pub palette_offset: u16,
     pub max_sequences_per_group: u16,
     pub offset_table_size: usize,
+    /// Raw Unk#6 to Unk#10 from the animation info header, kept for format research
+    pub anim_header_unknowns: [u16; 5],
+}
+
+impl WanFile {
+    /// Number of palette rows a piece's `palette_index` can select
+    pub fn palette_count(&self) -> usize {
+        self.custom_palette.len()
+    }
+
+    /// Palette row `index`, or `None` if the WAN doesn't have that many rows
+    pub fn palette(&self, index: usize) -> Option<&[RgbaTuple]> {
+        self.custom_palette.get(index).map(Vec::as_slice)
+    }
+
+    /// Distinct palette rows referenced by the pieces of every meta-frame that direction
+    /// `direction` of animation group `group` plays, in ascending order. Empty if the
+    /// animation doesn't exist. For effect WANs `direction` is the sequence index.
+    #[allow(dead_code)]
+    pub fn palettes_used_by_animation(&self, group: usize, direction: usize) -> Vec<usize> {
+        let groups = match &self.animations {
+            AnimationStructure::Character(groups) | AnimationStructure::Effect(groups) => groups,
+        };
+        let Some(animation) = groups.get(group).and_then(|g| g.get(direction)) else {
+            return Vec::new();
+        };
+
+        let mut palettes: Vec<usize> = animation
+            .frames
+            .iter()
+            .filter_map(|frame| self.frame_data.get(frame.frame_index as usize))
+            .flat_map(|meta_frame| meta_frame.pieces.iter())
+            .map(|piece| piece.palette_index as usize)
+            .collect();
+        palettes.sort_unstable();
+        palettes.dedup();
+        palettes
+    }
+
+    /// Where an effect attached at `point` sits on offset entry `offset_index`. `None` when
+    /// the WAN has no offset data for that entry (effect WANs never do), the game then plays
+    /// the effect at the entity origin.
+    pub fn attachment_position(
+        &self,
+        offset_index: usize,
+        point: AnimPointType,
+    ) -> Option<(i16, i16)> {
+        self.body_part_offset_data
+            .get(offset_index)
+            .and_then(|offset| offset.point(point))
+    }
+
+    /// Checks that every meta-frame can be rendered as parsed: each piece's palette row exists,
+    /// tile numbers are inside `img_data` (8bpp effect tiles resolve through the tile lookup), and
+    /// no frame is empty. Parsing with fallbacks can leave any of these broken, so the issues
+    /// are returned for the caller to log or skip the entry on.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut issues = Vec::new();
+
+        if self.img_data.is_empty() && !self.frame_data.is_empty() {
+            issues.push("Meta-frames exist but there is no image data".to_string());
+        }
+
+        for (frame_idx, frame) in self.frame_data.iter().enumerate() {
+            if frame.pieces.is_empty() {
+                issues.push(format!("Frame {} has no pieces", frame_idx));
+            }
+
+            for (piece_idx, piece) in frame.pieces.iter().enumerate() {
+                if piece.palette_index as usize >= self.palette_count() {
+                    issues.push(format!(
+                        "Frame {} piece {} uses palette {} but only {} exist",
+                        frame_idx,
+                        piece_idx,
+                        piece.palette_index,
+                        self.palette_count()
+                    ));
+                }
+
+                let tile = piece.tile_num as usize;
+                if let (true, Some(lookup)) = (piece.is_256_colour, &self.tile_lookup_8bpp) {
+                    if !lookup.contains_key(&tile) {
+                        issues.push(format!(
+                            "Frame {} piece {} uses 8bpp tile {} which isn't in the tile lookup",
+                            frame_idx, piece_idx, tile
+                        ));
+                    }
+                } else if tile >= self.img_data.len() {
+                    issues.push(format!(
+                        "Frame {} piece {} uses tile {} but only {} images exist",
+                        frame_idx,
+                        piece_idx,
+                        tile,
+                        self.img_data.len()
+                    ));
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
 }
 /// A collection of image data strips
 #[derive(Debug, Clone)]
@@ -85,11 +193,23 @@ impl MetaFramePiece {
         }
     }
 
+    /// Size in 8x8 tiles. An out-of-range `resolution_idx` (only possible for pieces built by
+    /// hand, the parser already validates it) degrades to a single 8x8 tile instead of
+    /// panicking, with a warning printed the first time it happens.
     pub fn get_dimensions(&self) -> (usize, usize) {
         DIM_TABLE
             .get(self.resolution_idx)
             .copied()
-            .unwrap_or((1, 1))
+            .unwrap_or_else(|| {
+                static WARN_ONCE: Once = Once::new();
+                WARN_ONCE.call_once(|| {
+                    eprintln!(
+                        "Warning: meta frame piece resolution index {} is out of range, using 8x8",
+                        self.resolution_idx
+                    );
+                });
+                (1, 1)
+            })
     }
 
     pub fn get_bounds(&self) -> (i16, i16, i16, i16) {
@@ -128,6 +248,78 @@ impl FrameOffset {
             centre,
         }
     }
+
+    /// Position of a move attachment point, `None` for `AnimPointType::None`
+    pub fn point(&self, point: AnimPointType) -> Option<(i16, i16)> {
+        match point {
+            AnimPointType::Head => Some(self.head),
+            AnimPointType::LeftHand => Some(self.lhand),
+            AnimPointType::RightHand => Some(self.rhand),
+            AnimPointType::Centre => Some(self.centre),
+            AnimPointType::None => None,
+        }
+    }
+}
+
+/// DS refresh rate, animation ticks are one screen refresh each
+const TICKS_PER_SECOND: f32 = 59.8261;
+
+/// Unit used for every animation duration written to exported metadata
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationUnit {
+    /// Raw game ticks, exported as-is. One tick per DS refresh, about 1/59.83 s
+    #[default]
+    Frames,
+    Seconds,
+    Millis,
+}
+
+impl DurationUnit {
+    /// Converts raw animation ticks into this unit
+    pub fn convert(self, ticks: u16) -> f32 {
+        match self {
+            DurationUnit::Frames => ticks as f32,
+            DurationUnit::Seconds => (ticks as f32 / TICKS_PER_SECOND * 10000.0).round() / 10000.0,
+            DurationUnit::Millis => (ticks as f32 / TICKS_PER_SECOND * 100000.0).round() / 100.0,
+        }
+    }
+}
+
+/// `serialize_with` for a converted duration. Whole values, which is every `Frames` duration,
+/// are written as integers so tick counts stay `12` rather than `12.0`.
+pub fn serialize_duration<S: Serializer>(duration: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if duration.fract() == 0.0 && duration.abs() <= u32::MAX as f32 {
+        serializer.serialize_i64(*duration as i64)
+    } else {
+        serializer.serialize_f32(*duration)
+    }
+}
+
+impl fmt::Display for DurationUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationUnit::Frames => write!(f, "frames"),
+            DurationUnit::Seconds => write!(f, "seconds"),
+            DurationUnit::Millis => write!(f, "millis"),
+        }
+    }
+}
+
+impl FromStr for DurationUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "frames" | "ticks" => Ok(DurationUnit::Frames),
+            "seconds" | "s" => Ok(DurationUnit::Seconds),
+            "millis" | "ms" => Ok(DurationUnit::Millis),
+            other => Err(format!(
+                "unknown duration unit '{}', expected frames, seconds or millis",
+                other
+            )),
+        }
+    }
 }
 
 /// A frame in an animation sequence
@@ -191,4 +383,177 @@ impl Animation {
     pub fn empty() -> Self {
         Self { frames: Vec::new() }
     }
+
+    /// Length of one playthrough in game ticks (1/60s)
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn total_duration_frames(&self) -> u32 {
+        self.frames.iter().map(|f| f.duration as u32).sum()
+    }
+
+    /// Returns the sequence frame on screen once `time_frames` ticks have elapsed.
+    ///
+    /// A frame is shown from the tick it starts on until the tick before the next one starts,
+    /// so with durations `[2, 3, 4]` ticks 0-1 show frame 0, 2-4 frame 1 and 5-8 frame 2.
+    /// Past the end, a looping animation wraps around and a non-looping one holds its last
+    /// frame. Returns `None` for an empty animation.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn frame_at(&self, time_frames: u32, looping: bool) -> Option<&SequenceFrame> {
+        let total = self.total_duration_frames();
+        if total == 0 {
+            return self.frames.first();
+        }
+
+        let time = if looping {
+            time_frames % total
+        } else {
+            time_frames
+        };
+
+        let mut elapsed = 0u32;
+        for frame in &self.frames {
+            elapsed += frame.duration as u32;
+            if time < elapsed {
+                return Some(frame);
+            }
+        }
+        self.frames.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wan::{
+        builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+        parser::{parse_wan_from_sir0_content, ParseOptions},
+    };
+
+    /// Two palette rows, group 0 draws with row 0 and group 1 with row 1
+    fn two_palette_wan() -> WanFile {
+        let mut second_row = BuilderPiece::new(0, 0, 0);
+        second_row.palette_index = 1;
+        let (content, data_pointer) = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (255, 0, 0)])
+            .palette(vec![(0, 0, 0), (0, 255, 0)])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, 0, 0)])
+            .frame(vec![second_row])
+            .group(vec![vec![BuilderSeqFrame::new(0, 1)]; 8])
+            .group(vec![vec![BuilderSeqFrame::new(1, 1)]; 8])
+            .build();
+        parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn duration_units_convert_and_keep_frames_integer() {
+        let write = |unit: DurationUnit| {
+            serialize_duration(&unit.convert(12), serde_json::value::Serializer).unwrap()
+        };
+
+        assert_eq!(write(DurationUnit::Frames), serde_json::json!(12));
+        assert!(write(DurationUnit::Frames).is_i64());
+        // 0.2s is only approximate. A tick is one DS refresh, 1/59.8261s rather than 1/60s, so
+        // 12 ticks are about 0.2006s. The tolerance allows for that, the divisor isn't wrong.
+        let seconds = write(DurationUnit::Seconds).as_f64().unwrap();
+        assert!((seconds - 0.2).abs() < 0.001, "{}", seconds);
+        let millis = write(DurationUnit::Millis).as_f64().unwrap();
+        assert!((millis - 200.0).abs() < 1.0, "{}", millis);
+        assert_eq!(DurationUnit::default(), DurationUnit::Frames);
+    }
+
+    #[test]
+    fn frame_at_uses_half_open_intervals() {
+        let animation = Animation::new(
+            [2, 3, 4]
+                .into_iter()
+                .enumerate()
+                .map(|(i, duration)| SequenceFrame::new(i as u16, duration, 0, (0, 0), (0, 0)))
+                .collect(),
+        );
+        assert_eq!(animation.total_duration_frames(), 9);
+
+        let frame_index = |time, looping| animation.frame_at(time, looping).unwrap().frame_index;
+        let expected = [(0, 0), (1, 0), (2, 1), (4, 1), (5, 2), (8, 2)];
+        for (time, index) in expected {
+            assert_eq!(frame_index(time, false), index, "tick {}", time);
+            // One full playthrough later the looping animation is at the same frame
+            assert_eq!(frame_index(time + 9, true), index, "tick {} looped", time);
+        }
+        assert_eq!(frame_index(9, false), 2);
+        assert_eq!(frame_index(100, false), 2);
+        assert!(Animation::empty().frame_at(0, true).is_none());
+    }
+
+    #[test]
+    fn out_of_range_resolution_degrades_to_8x8() {
+        let piece = MetaFramePiece::new(MetaFramePieceArgs {
+            tile_num: 0,
+            palette_index: 0,
+            h_flip: false,
+            v_flip: false,
+            x_offset: 10,
+            y_offset: 20,
+            resolution_idx: DIM_TABLE.len() + 3,
+            is_256_colour: false,
+        });
+        assert_eq!(piece.get_dimensions(), (1, 1));
+        assert_eq!(piece.get_bounds(), (10, 20, 18, 28));
+    }
+
+    #[test]
+    fn out_of_range_palette_index_returns_none() {
+        let wan = two_palette_wan();
+        assert_eq!(wan.palette_count(), 2);
+        assert_eq!(wan.palette(1).unwrap()[1], (0, 255, 0, 255));
+        assert!(wan.palette(wan.palette_count()).is_none());
+        assert!(wan.palette(usize::MAX).is_none());
+    }
+
+    #[test]
+    fn validate_reports_bad_palettes_tiles_and_empty_frames() {
+        let mut wan = two_palette_wan();
+        assert_eq!(wan.validate(), Ok(()));
+
+        let piece = &mut wan.frame_data[1].pieces[0];
+        piece.palette_index = 2;
+        piece.tile_num = 9;
+        let mut empty = wan.frame_data[0].clone();
+        empty.pieces.clear();
+        wan.frame_data.push(empty);
+
+        let issues = wan.validate().unwrap_err();
+        assert_eq!(issues.len(), 3, "{:?}", issues);
+        assert!(issues[0].contains("uses palette 2 but only 2 exist"));
+        assert!(issues[1].contains("uses tile 9 but only 1 images exist"));
+        assert_eq!(issues[2], "Frame 2 has no pieces");
+    }
+
+    #[test]
+    fn attachment_index_3_resolves_to_the_centre_offset() {
+        let mut wan = two_palette_wan();
+        wan.body_part_offset_data = vec![FrameOffset::new((0, -20), (-8, -4), (8, -4), (1, -9))];
+
+        let centre = AnimPointType::from_attachment_index(3);
+        assert_eq!(centre, AnimPointType::Centre);
+        assert_eq!(wan.attachment_position(0, centre), Some((1, -9)));
+        // No offset entry for the frame, or the origin marker, leaves it at the origin
+        assert_eq!(wan.attachment_position(1, centre), None);
+        let origin = AnimPointType::from_attachment_index(-1);
+        assert_eq!(wan.attachment_position(0, origin), None);
+    }
+
+    #[test]
+    fn animation_drawn_with_palette_0_reports_only_0() {
+        let wan = two_palette_wan();
+        assert_eq!(wan.palettes_used_by_animation(0, 0), vec![0]);
+        assert_eq!(wan.palettes_used_by_animation(1, 7), vec![1]);
+        assert!(wan.palettes_used_by_animation(2, 0).is_empty());
+        assert!(wan.palettes_used_by_animation(0, 8).is_empty());
+    }
 }
@@ -21,25 +21,175 @@ use crate::{
     },
 };
 
-/// Parse WAN file from SIR0 content that has already been extracted
+/// Longest animation sequence read before giving up on finding its end marker
+pub const DEFAULT_MAX_SEQUENCE_FRAMES: usize = 1024;
+
+/// Warns and returns true once `frames` has reached `limit`
+fn sequence_limit_reached(frames: &[SequenceFrame], ptr: u32, limit: usize) -> bool {
+    if frames.len() < limit {
+        return false;
+    }
+    println!(
+        "  - Warning: Sequence at 0x{:X} has no end marker within {} frames, truncating",
+        ptr, limit
+    );
+    true
+}
+
+/// Palette given to effect WANs that have image data but no palette rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FallbackPalette {
+    /// The character fallback: index 0 transparent, the rest placeholder greys, so the tiles
+    /// stay visible
+    #[default]
+    Greys,
+    /// Every entry transparent, the effect renders as nothing
+    Transparent,
+}
+
+/// Settings for `parse_wan_from_sir0_content` beyond the WAN type
+#[derive(Debug, Clone, Copy)]
+pub struct ParseOptions {
+    /// Fail with `WanError::Degraded` on the first recoverable problem instead of falling back
+    pub strict: bool,
+    pub fallback_palette: FallbackPalette,
+    /// A corrupt sequence without a terminator is cut off at this many frames with a warning
+    pub max_sequence_frames: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            fallback_palette: FallbackPalette::default(),
+            max_sequence_frames: DEFAULT_MAX_SEQUENCE_FRAMES,
+        }
+    }
+}
+
+/// Palette row for an effect WAN whose palette block is missing or empty, 256 entries wide in
+/// 8bpp mode so every index resolves
+fn effect_fallback_palette(
+    is_256_colour: bool,
+    fallback: FallbackPalette,
+) -> Vec<(u8, u8, u8, u8)> {
+    let len = if is_256_colour { 256 } else { 16 };
+    if fallback == FallbackPalette::Transparent {
+        return vec![(0, 0, 0, 0); len];
+    }
+
+    let mut greys = vec![(0, 0, 0, 0)];
+    ensure_complete_palette(&mut greys);
+    (0..len)
+        .map(|i| {
+            if i == 0 {
+                greys[0]
+            } else {
+                greys[1 + (i - 1) % 15]
+            }
+        })
+        .collect()
+}
+
+/// Pixel/palette layout of an effect WAN, decoded once from the Is256ColourSpr header field
+/// and used for both palette and image decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EffectColourMode {
+    /// 0: 4bpp tiles, one ImgPiece per image, 16 colour palette rows
+    Indexed4bpp,
+    /// 1: 8bpp tiles, one ImgPiece per chunk, each 16 colour block fills entries 16-31 of a
+    /// 256 entry row. A handful of effect files (e.g. effect267) store 4 instead, their pixel
+    /// data and palette block are laid out exactly like the value 1 case.
+    Indexed8bpp,
+}
+
+impl EffectColourMode {
+    /// Decodes Is256ColourSpr. Values other than 0, 1 and 4 are unknown and read as 8bpp with
+    /// a warning (or `WanError::Degraded` when strict), since that's how their pixels decode.
+    fn decode(value: u16, strict: bool) -> Result<Self, WanError> {
+        match value {
+            0 => Ok(EffectColourMode::Indexed4bpp),
+            1 | 4 => Ok(EffectColourMode::Indexed8bpp),
+            _ => {
+                recoverable(
+                    strict,
+                    format!(
+                        "Unknown Is256ColourSpr value {}, treating it as 8bpp",
+                        value
+                    ),
+                )?;
+                Ok(EffectColourMode::Indexed8bpp)
+            }
+        }
+    }
+
+    fn is_256_colour(self) -> bool {
+        self == EffectColourMode::Indexed8bpp
+    }
+}
+
+/// Effect palette info Unk#5 value for palettes that aren't placed into the shared base palette.
+/// Only effect_0001 and effect_0262 use it, everything else stores 0x10D (row 13).
+const EFFECT_PALETTE_NOT_RELOCATED: u16 = 0xFF;
+
+/// Decodes effect palette info Unk#5 into the base palette row the effect's palette starts at.
+/// Returns 0 (no merge) for `EFFECT_PALETTE_NOT_RELOCATED`, otherwise the low nibble.
+fn decode_effect_palette_offset(unk5: u16) -> u16 {
+    if unk5 == EFFECT_PALETTE_NOT_RELOCATED {
+        0
+    } else {
+        unk5 % 16
+    }
+}
+
+/// Reads the WAN header's img_type (after the AnimInfo and ImageDataInfo pointers) to tell
+/// character WANs (1) from effect WANs (2 or 3). `None` for any other value or a header that
+/// doesn't fit in `content`.
+pub fn detect_wan_type(content: &[u8], data_pointer: u32) -> Option<WanType> {
+    let offset = data_pointer as usize + 8;
+    let img_type = u16::from_le_bytes([*content.get(offset)?, *content.get(offset + 1)?]);
+    match img_type {
+        1 => Some(WanType::Character),
+        2 | 3 => Some(WanType::Effect),
+        _ => None,
+    }
+}
+
+/// Parse WAN file from SIR0 content that has already been extracted.
+///
+/// With `options.strict` set, the first recoverable problem (missing palette, unreadable image
+/// data, etc.) is returned as `WanError::Degraded` instead of being replaced by a fallback.
 pub fn parse_wan_from_sir0_content(
     content: &[u8],
     data_pointer: u32,
     wan_type: WanType,
+    options: &ParseOptions,
 ) -> Result<WanFile, WanError> {
     let mut cursor = Cursor::new(content);
     cursor.seek(SeekFrom::Start(data_pointer as u64))?;
 
     match wan_type {
-        WanType::Character => parse_character_wan(&mut cursor, content.len() as u64),
-        WanType::Effect => parse_effect_wan(content, data_pointer),
+        WanType::Character => parse_character_wan(&mut cursor, content.len() as u64, options),
+        WanType::Effect => parse_effect_wan(content, data_pointer, options),
     }
 }
 
+/// Reports a recoverable parse problem. Strict parsing turns it into an error, otherwise it is
+/// printed as a warning and the caller carries on with its fallback.
+pub(crate) fn recoverable(strict: bool, message: String) -> Result<(), WanError> {
+    if strict {
+        return Err(WanError::Degraded(message));
+    }
+    println!("  - Warning: {}", message);
+    Ok(())
+}
+
 pub fn parse_character_wan(
     cursor: &mut Cursor<&[u8]>,
     buffer_size: u64,
+    options: &ParseOptions,
 ) -> Result<WanFile, WanError> {
+    let strict = options.strict;
     // Store current position to check for minimal header
     let start_pos = cursor.position();
 
@@ -110,9 +260,11 @@ pub fn parse_character_wan(
         )));
     }
 
-    // Skip unknown values Unk#13, Is256ColourSpr, Unk#11
     read_u16_le(cursor).map_err(WanError::Io)?; // Unk#13 - ALWAYS 0
-    read_u16_le(cursor).map_err(WanError::Io)?; // Is256ColourSpr - ALWAYS 0
+
+    // Is256ColourSpr is 0 for every character sprite in the ROM, but decode it anyway so
+    // patched or custom 8bpp sprites render instead of coming out as 4bpp noise
+    let colour_mode = EffectColourMode::decode(read_u16_le(cursor).map_err(WanError::Io)?, strict)?;
     read_u16_le(cursor).map_err(WanError::Io)?; // Unk#11 - ALWAYS 1 unless empty
 
     // Read number of images
@@ -144,14 +296,27 @@ pub fn parse_character_wan(
         ptr_palette_data_block as u64,
         ptr_image_data_table as u64,
         16,
+        strict,
     ) {
         Ok(data) => data,
+        Err(e @ WanError::Degraded(_)) => return Err(e),
         Err(e) => {
-            println!("  - Warning: Failed to read palette data: {:?}", e);
-            println!("  - Using default palette");
+            recoverable(
+                strict,
+                format!(
+                    "Failed to read palette data: {:?}, using default palette",
+                    e
+                ),
+            )?;
             vec![vec![(0, 0, 0, 0); 16]]
         }
     };
+    // An 8bpp sprite indexes the stored colours as one 256 entry palette
+    let palette_data = if colour_mode.is_256_colour() {
+        vec![palette_data.into_iter().flatten().take(256).collect()]
+    } else {
+        palette_data
+    };
 
     // Read image data table
     cursor
@@ -165,18 +330,21 @@ pub fn parse_character_wan(
         ptr_imgs.push(ptr);
     }
 
-    let img_data = match read_image_data(cursor, &ptr_imgs, buffer_size) {
+    let img_data = match read_image_data(cursor, &ptr_imgs, buffer_size, strict) {
         Ok(data) => data,
+        Err(e @ WanError::Degraded(_)) => return Err(e),
         Err(e) => {
-            println!("  - Warning: Failed to read image data: {:?}", e);
-            println!("  - Using empty image data");
+            recoverable(
+                strict,
+                format!("Failed to read image data: {:?}, using empty image data", e),
+            )?;
             Vec::new()
         }
     };
 
     if ptr_anim_info as u64 >= buffer_size - 16 {
         // Need at least 16 bytes for header
-        println!("  - Warning: Animation info is missing or invalid");
+        recoverable(strict, "Animation info is missing or invalid".to_string())?;
         return Ok(WanFile {
             img_data,
             frame_data: Vec::new(),
@@ -189,6 +357,7 @@ pub fn parse_character_wan(
             tile_lookup_8bpp: None,
             max_sequences_per_group: 0,
             offset_table_size: 0,
+            anim_header_unknowns: [0; 5],
         });
     }
 
@@ -214,9 +383,10 @@ pub fn parse_character_wan(
 
     let anim_groups_num = read_u16_le(cursor).map_err(WanError::Io)?;
 
-    // Skip unknown values (Unk#6 through Unk#10)
-    for _ in 0..5 {
-        read_u16_le(cursor).map_err(WanError::Io)?;
+    // Unknown values (Unk#6 through Unk#10), not used for rendering
+    let mut anim_header_unknowns = [0u16; 5];
+    for unk in &mut anim_header_unknowns {
+        *unk = read_u16_le(cursor).map_err(WanError::Io)?;
     }
 
     // Read animation groups
@@ -227,44 +397,62 @@ pub fn parse_character_wan(
     ) {
         Ok(result) => result,
         Err(e) => {
-            println!("  - Warning: Failed to read animation groups: {:?}", e);
+            recoverable(strict, format!("Failed to read animation groups: {:?}", e))?;
             (Vec::new(), Vec::new())
         }
     };
 
     // Read meta frames
-    let (meta_frames, pointer_count) = match read_meta_frames(
+    let (mut meta_frames, pointer_count) = match read_meta_frames(
         cursor,
         ptr_meta_frames_ref_table as u64,
         ptr_offsets_table as u64,
     ) {
         Ok(result) => result,
         Err(e) => {
-            println!("  - Warning: Failed to read meta frames: {:?}", e);
+            recoverable(strict, format!("Failed to read meta frames: {:?}", e))?;
             (Vec::new(), 0)
         }
     };
 
+    // 256 colour OBJs ignore the palette number, they all read the single flattened row
+    if colour_mode.is_256_colour() {
+        for piece in meta_frames
+            .iter_mut()
+            .flat_map(|frame| frame.pieces.iter_mut())
+            .filter(|piece| piece.is_256_colour)
+        {
+            piece.palette_index = 0;
+        }
+    }
+
     // Read offset data (use pointer_count, not meta_frames.len(), because
     // the double-push hack inflates meta_frames but the ROM offset table
     // has exactly one entry per pointer)
     let offset_data = match read_offset_data(cursor, ptr_offsets_table as u64, pointer_count) {
         Ok(offsets) => offsets,
         Err(e) => {
-            println!("  - Warning: Failed to read offset data: {:?}", e);
+            recoverable(strict, format!("Failed to read offset data: {:?}", e))?;
             Vec::new()
         }
     };
 
     // Read animation sequences
-    let animation_data =
-        match read_animation_sequence_character(cursor, &animation_groups, &anim_sequences) {
-            Ok(data) => data,
-            Err(e) => {
-                println!("  - Warning: Failed to read animation sequences: {:?}", e);
-                Vec::new()
-            }
-        };
+    let animation_data = match read_animation_sequence_character(
+        cursor,
+        &animation_groups,
+        &anim_sequences,
+        options.max_sequence_frames,
+    ) {
+        Ok(data) => data,
+        Err(e) => {
+            recoverable(
+                strict,
+                format!("Failed to read animation sequences: {:?}", e),
+            )?;
+            Vec::new()
+        }
+    };
 
     let frame_data = meta_frames;
 
@@ -280,6 +468,7 @@ pub fn parse_character_wan(
         tile_lookup_8bpp: None,
         max_sequences_per_group: 8,
         offset_table_size: pointer_count,
+        anim_header_unknowns,
     })
 }
 
@@ -287,6 +476,7 @@ fn read_animation_sequence_character(
     cursor: &mut Cursor<&[u8]>,
     animation_groups: &[Vec<u32>],
     _anim_sequences: &[u32],
+    max_frames: usize,
 ) -> Result<Vec<Vec<Animation>>, WanError> {
     let buffer_size = cursor.get_ref().len() as u64;
     let mut all_animations = Vec::new();
@@ -310,7 +500,9 @@ fn read_animation_sequence_character(
             let mut sequence_frames = Vec::new();
 
             loop {
-                if cursor.position() + 12 > buffer_size {
+                if cursor.position() + 12 > buffer_size
+                    || sequence_limit_reached(&sequence_frames, ptr, max_frames)
+                {
                     break;
                 }
 
@@ -343,7 +535,12 @@ fn read_animation_sequence_character(
     Ok(all_animations)
 }
 
-fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
+fn parse_effect_wan(
+    data: &[u8],
+    ptr_wan: u32,
+    options: &ParseOptions,
+) -> Result<WanFile, WanError> {
+    let strict = options.strict;
     let mut cursor = Cursor::new(data);
     cursor.seek(SeekFrom::Start(ptr_wan as u64))?;
 
@@ -358,7 +555,7 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
 
     let mut custom_palette = vec![];
     let mut img_data = vec![];
-    let mut is_256_colour_val = 0;
+    let mut colour_mode = EffectColourMode::Indexed4bpp;
     let mut palette_offset = 0;
 
     if ptr_image_data_info > 0 {
@@ -366,7 +563,7 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
         let ptr_image_data_table = read_u32_le(&mut cursor)?;
         let ptr_palette_info = read_u32_le(&mut cursor)?;
         read_u16_le(&mut cursor)?;
-        is_256_colour_val = read_u16_le(&mut cursor)?;
+        colour_mode = EffectColourMode::decode(read_u16_le(&mut cursor)?, strict)?;
         read_u16_le(&mut cursor)?;
         let nb_imgs = read_u16_le(&mut cursor)?;
         if ptr_palette_info > 0 {
@@ -376,12 +573,13 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
             read_u16_le(&mut cursor)?;
             read_u16_le(&mut cursor)?;
             let unk5 = read_u16_le(&mut cursor)?;
-            palette_offset = unk5 % 16;
+            palette_offset = decode_effect_palette_offset(unk5);
             custom_palette = read_effect_palette_data(
                 &mut cursor,
                 ptr_palette_data_block as u64,
                 ptr_palette_info as u64,
-                is_256_colour_val as usize,
+                colour_mode,
+                strict,
             )?;
         }
         if ptr_image_data_table > 0 {
@@ -390,7 +588,7 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
             for _ in 0..nb_imgs {
                 ptr_imgs.push(read_u32_le(&mut cursor)?);
             }
-            if is_256_colour_val != 0 {
+            if colour_mode.is_256_colour() {
                 // Create one ImgPiece per image chunk
                 for &ptr_img in &ptr_imgs {
                     if ptr_img == 0 {
@@ -459,7 +657,18 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
         }
     }
 
-    let tile_lookup_8bpp = if is_256_colour_val != 0 {
+    if custom_palette.is_empty() && !img_data.is_empty() {
+        recoverable(
+            strict,
+            "Effect has image data but no palette, using fallback palette".to_string(),
+        )?;
+        custom_palette.push(effect_fallback_palette(
+            colour_mode.is_256_colour(),
+            options.fallback_palette,
+        ));
+    }
+
+    let tile_lookup_8bpp = if colour_mode.is_256_colour() {
         Some(build_8bpp_tile_lookup(&img_data))
     } else {
         None
@@ -468,6 +677,7 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
     let mut frame_data = vec![];
     let mut max_sequences_per_group: u16 = 1;
     let mut animation_groups: Vec<Vec<Animation>> = Vec::new();
+    let mut anim_header_unknowns = [0u16; 5];
 
     if ptr_anim_info > 0 {
         cursor.seek(SeekFrom::Start(ptr_anim_info as u64))?;
@@ -475,6 +685,9 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
         read_u32_le(&mut cursor)?;
         let ptr_anim_group_table = read_u32_le(&mut cursor)?;
         let nb_anim_groups = read_u16_le(&mut cursor)?;
+        for unk in &mut anim_header_unknowns {
+            *unk = read_u16_le(&mut cursor)?; // Unk#6 - Unk#10
+        }
 
         // Parse the animation group table to get the list of animation sequence pointers
         // Seek to start of anim group table to get the meta frame table boundary
@@ -483,8 +696,11 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
 
         // Parse the meta-frames.
         cursor.seek(SeekFrom::Start(ptr_meta_frames_ref_table as u64))?;
-        frame_data =
-            read_effect_meta_frames(&mut cursor, meta_frames_end_ptr, is_256_colour_val != 0)?;
+        frame_data = read_effect_meta_frames(
+            &mut cursor,
+            meta_frames_end_ptr,
+            colour_mode.is_256_colour(),
+        )?;
 
         // Parse animation groups - store per-group for proper ROM behavior
         // ROM uses animation_index as sequence index into group 0 ONLY
@@ -508,7 +724,11 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
                     let seq_ptr = read_u32_le(&mut cursor)?;
                     if seq_ptr > 0 {
                         let inner_pos = cursor.position();
-                        let sequence = read_animation_sequence(&mut cursor, seq_ptr)?;
+                        let sequence = read_animation_sequence(
+                            &mut cursor,
+                            seq_ptr,
+                            options.max_sequence_frames,
+                        )?;
                         group_animations.push(sequence);
                         cursor.seek(SeekFrom::Start(inner_pos))?;
                     } else {
@@ -535,6 +755,7 @@ fn parse_effect_wan(data: &[u8], ptr_wan: u32) -> Result<WanFile, WanError> {
         tile_lookup_8bpp,
         max_sequences_per_group,
         offset_table_size: 0,
+        anim_header_unknowns,
     })
 }
 
@@ -557,12 +778,18 @@ fn build_8bpp_tile_lookup(img_data: &[ImgPiece]) -> HashMap<usize, usize> {
     lookup
 }
 
-fn read_animation_sequence(cursor: &mut Cursor<&[u8]>, ptr: u32) -> Result<Animation, WanError> {
+fn read_animation_sequence(
+    cursor: &mut Cursor<&[u8]>,
+    ptr: u32,
+    max_frames: usize,
+) -> Result<Animation, WanError> {
     let original_pos = cursor.position();
     cursor.seek(SeekFrom::Start(ptr as u64))?;
     let mut frames = Vec::new();
     loop {
-        if cursor.position() + 12 > cursor.get_ref().len() as u64 {
+        if cursor.position() + 12 > cursor.get_ref().len() as u64
+            || sequence_limit_reached(&frames, ptr, max_frames)
+        {
             break;
         }
 
@@ -640,7 +867,7 @@ fn read_effect_meta_frames(
             let dim_type = y_data / 16384;
             let h_flip = (dim_data / 2 % 2) == 1;
             let v_flip = (dim_data / 4 % 2) == 1;
-            let resolution_idx = (dim_type * 4 + dim_data / 8) as usize;
+            let resolution_idx = checked_resolution_index(dim_type, dim_data / 8);
             let palette_index = (palette_data / 16) as u8;
 
             pieces.push(MetaFramePiece::new(MetaFramePieceArgs {
@@ -684,7 +911,7 @@ pub fn parse_wan_palette_only(content: &[u8], data_pointer: u32) -> Result<WanFi
     let _ptr_image_data_table = read_u32_le(&mut cursor)?;
     let ptr_palette_info = read_u32_le(&mut cursor)?;
     read_u16_le(&mut cursor)?; // unk13
-    let is_256_colour_val = read_u16_le(&mut cursor)?;
+    let colour_mode = EffectColourMode::decode(read_u16_le(&mut cursor)?, false)?;
 
     if ptr_palette_info == 0 || (ptr_palette_info as u64) >= buffer_size {
         return Err(WanError::InvalidDataStructure(format!(
@@ -713,7 +940,8 @@ pub fn parse_wan_palette_only(content: &[u8], data_pointer: u32) -> Result<WanFi
         &mut cursor,
         ptr_palette_data_block as u64,
         palette_end_ptr,
-        is_256_colour_val as usize,
+        colour_mode,
+        false,
     )?;
 
     if palette_data.is_empty() {
@@ -730,10 +958,11 @@ pub fn parse_wan_palette_only(content: &[u8], data_pointer: u32) -> Result<WanFi
         custom_palette: palette_data,
         effect_specific_palette: None,
         wan_type: WanType::Effect,
-        palette_offset: palette_offset_info,
+        palette_offset: decode_effect_palette_offset(palette_offset_info),
         tile_lookup_8bpp: None,
         max_sequences_per_group: 0,
         offset_table_size: 0,
+        anim_header_unknowns: [0; 5],
     })
 }
 
@@ -743,16 +972,20 @@ fn read_palette_data(
     ptr_palette_data_block: u64,
     end_ptr: u64,
     nb_colours_per_row: usize,
+    strict: bool,
 ) -> Result<PaletteList, WanError> {
     debug_assert!(
         ptr_palette_data_block > 0,
         "Palette data block pointer is zero"
     );
     debug_assert!(end_ptr > 0, "End pointer is zero");
-    debug_assert!(
-        end_ptr > ptr_palette_data_block,
-        "Invalid palette block range"
-    );
+    // An empty block (no palette stored) falls through to the default palette below
+    if end_ptr < ptr_palette_data_block {
+        return Err(WanError::InvalidDataStructure(format!(
+            "Invalid palette block range: {:#x}..{:#x}",
+            ptr_palette_data_block, end_ptr
+        )));
+    }
 
     let _buffer_size = cursor.get_ref().len() as u64;
 
@@ -782,13 +1015,13 @@ fn read_palette_data(
                 WanError::Io(e)
             })?;
 
-            let blue = read_u8(cursor).map_err(|e| {
-                println!("ERROR: Failed to read blue component");
+            let green = read_u8(cursor).map_err(|e| {
+                println!("ERROR: Failed to read green component");
                 WanError::Io(e)
             })?;
 
-            let green = read_u8(cursor).map_err(|e| {
-                println!("ERROR: Failed to read green component");
+            let blue = read_u8(cursor).map_err(|e| {
+                println!("ERROR: Failed to read blue component");
                 WanError::Io(e)
             })?;
 
@@ -797,7 +1030,7 @@ fn read_palette_data(
                 WanError::Io(e)
             })?;
 
-            palette.push((red, blue, green, 255));
+            palette.push((red, green, blue, 255));
         }
 
         ensure_complete_palette(&mut palette);
@@ -806,7 +1039,10 @@ fn read_palette_data(
     }
 
     if custom_palette.is_empty() {
-        println!("  No palettes found, creating default palette");
+        recoverable(
+            strict,
+            "No palettes found, creating default palette".to_string(),
+        )?;
         let mut default_palette = vec![(0, 0, 0, 0)];
         ensure_complete_palette(&mut default_palette);
         custom_palette.push(default_palette);
@@ -859,7 +1095,8 @@ fn read_effect_palette_data(
     cursor: &mut Cursor<&[u8]>,
     ptr_palette_data_block: u64,
     end_ptr: u64,
-    is_256_colour: usize,
+    colour_mode: EffectColourMode,
+    strict: bool,
 ) -> Result<PaletteList, WanError> {
     cursor
         .seek(SeekFrom::Start(ptr_palette_data_block))
@@ -868,27 +1105,46 @@ fn read_effect_palette_data(
     let total_bytes = end_ptr - ptr_palette_data_block;
     let mut custom_palette = Vec::new();
 
-    if is_256_colour == 1 || is_256_colour == 4 {
-        // 256-colour (8bpp) mode
-        let num_palettes = (total_bytes as usize / (16 * 4)).max(1);
+    // In 8bpp mode each stored 16 colour block fills entries 16-31 of a 256 entry row,
+    // entries 0-15 stay transparent
+    if colour_mode.is_256_colour() {
+        const COLOURS_PER_BLOCK: usize = 16;
+        const ROW_BASE: usize = 16;
+        let block_bytes = (COLOURS_PER_BLOCK * 4) as u64;
+
+        let num_palettes = (total_bytes / block_bytes).max(1) as usize;
+        let leftover_colours = (total_bytes % block_bytes) / 4;
+        if total_bytes >= block_bytes && leftover_colours > 0 {
+            recoverable(
+                strict,
+                format!(
+                    "8bpp palette block has {} trailing colours that don't fill a 16 colour \
+                     block, they are ignored",
+                    leftover_colours
+                ),
+            )?;
+        }
+
         for _ in 0..num_palettes {
             let mut palette_row = vec![(0, 0, 0, 0); 256];
 
-            for j in 0..16 {
+            for j in 0..COLOURS_PER_BLOCK {
                 if cursor.position() + 4 > end_ptr {
                     break;
                 }
 
                 let r_raw = read_u8(cursor)?;
-                let b_raw = read_u8(cursor)?;
                 let g_raw = read_u8(cursor)?;
+                let b_raw = read_u8(cursor)?;
                 let _padding = read_u8(cursor)?;
 
                 let r = (((r_raw as u32 / 8 * 8) * 32) / 31).min(255) as u8;
                 let g = (((g_raw as u32 / 8 * 8) * 32) / 31).min(255) as u8;
                 let b = (((b_raw as u32 / 8 * 8) * 32) / 31).min(255) as u8;
 
-                palette_row[16 + j] = (r, b, g, 255);
+                if let Some(entry) = palette_row.get_mut(ROW_BASE + j) {
+                    *entry = (r, g, b, 255);
+                }
             }
             custom_palette.push(palette_row);
         }
@@ -904,12 +1160,12 @@ fn read_effect_palette_data(
                     break;
                 }
                 let r = read_u8(cursor)?;
-                let b = read_u8(cursor)?; // Reads B then G
                 let g = read_u8(cursor)?;
+                let b = read_u8(cursor)?;
                 let _padding = read_u8(cursor)?;
 
                 let alpha = if colour_idx == 0 { 0 } else { 255 };
-                palette_row.push((r, b, g, alpha));
+                palette_row.push((r, g, b, alpha));
             }
             if palette_row.len() == colours_per_row {
                 custom_palette.push(palette_row);
@@ -925,15 +1181,16 @@ fn read_image_data(
     cursor: &mut Cursor<&[u8]>,
     ptr_imgs: &[u32],
     _buffer_size: u64,
+    strict: bool,
 ) -> Result<Vec<ImgPiece>, WanError> {
     let mut img_data = Vec::with_capacity(ptr_imgs.len());
 
     for (img_idx, &ptr_img) in ptr_imgs.iter().enumerate() {
         if let Err(e) = cursor.seek(SeekFrom::Start(ptr_img as u64)) {
-            println!(
-                "  - Warning: Failed to seek to image data for image #{}: {}",
-                img_idx, e
-            );
+            recoverable(
+                strict,
+                format!("Failed to seek to image data for image #{}: {}", img_idx, e),
+            )?;
             img_data.push(ImgPiece { img_px: Vec::new() });
             continue;
         }
@@ -946,10 +1203,13 @@ fn read_image_data(
                 Ok(val) => val,
                 Err(e) => {
                     if tile_pixels.is_empty() {
-                        println!(
-                            "  - Warning: Failed to read pixel source pointer for image #{}: {}",
-                            img_idx, e
-                        );
+                        recoverable(
+                            strict,
+                            format!(
+                                "Failed to read pixel source pointer for image #{}: {}",
+                                img_idx, e
+                            ),
+                        )?;
                     }
                     break;
                 }
@@ -958,10 +1218,10 @@ fn read_image_data(
             let num_pixels_to_read = match read_u16_le(cursor) {
                 Ok(val) => val,
                 Err(e) => {
-                    println!(
-                        "  - Warning: Failed to read pixel amount for image #{}: {}",
-                        img_idx, e
-                    );
+                    recoverable(
+                        strict,
+                        format!("Failed to read pixel amount for image #{}: {}", img_idx, e),
+                    )?;
                     break;
                 }
             };
@@ -971,18 +1231,18 @@ fn read_image_data(
             }
 
             if let Err(e) = read_u16_le(cursor) {
-                println!(
-                    "  - Warning: Failed to read unknown field for image #{}: {}",
-                    img_idx, e
-                );
+                recoverable(
+                    strict,
+                    format!("Failed to read unknown field for image #{}: {}", img_idx, e),
+                )?;
                 break;
             }
 
             if let Err(e) = read_u32_le(cursor) {
-                println!(
-                    "  - Warning: Failed to read z-sort value for image #{}: {}",
-                    img_idx, e
-                );
+                recoverable(
+                    strict,
+                    format!("Failed to read z-sort value for image #{}: {}", img_idx, e),
+                )?;
             };
 
             if ptr_pix_src == 0 {
@@ -993,7 +1253,7 @@ fn read_image_data(
 
                 if cursor.seek(SeekFrom::Start(ptr_pix_src as u64)).is_err() {
                     if let Err(seek_e) = cursor.seek(SeekFrom::Start(current_pos)) {
-                        println!("  - Warning: Failed to restore position: {}", seek_e);
+                        recoverable(strict, format!("Failed to restore position: {}", seek_e))?;
                     }
                     continue;
                 }
@@ -1005,19 +1265,27 @@ fn read_image_data(
                         valid_data = true;
                     }
                     Err(e) => {
-                        println!(
-                            "  - Warning: Partial read for image #{} at position {}: {}",
-                            img_idx,
-                            cursor.position(),
-                            e,
-                        );
+                        recoverable(
+                            strict,
+                            format!(
+                                "Partial read for image #{} at position {}: {}",
+                                img_idx,
+                                cursor.position(),
+                                e,
+                            ),
+                        )?;
                         break;
                     }
                 }
 
                 if let Err(e) = cursor.seek(SeekFrom::Start(current_pos)) {
-                    println!("  - Warning: Failed to restore position after reading pixels for image #{}: {}", 
-                        img_idx, e);
+                    recoverable(
+                        strict,
+                        format!(
+                            "Failed to restore position after reading pixels for image #{}: {}",
+                            img_idx, e
+                        ),
+                    )?;
                     break;
                 }
             }
@@ -1028,10 +1296,13 @@ fn read_image_data(
                 img_px: tile_pixels,
             });
         } else {
-            println!(
-                "  - No valid pixel data for image #{}, adding empty placeholder",
-                img_idx
-            );
+            recoverable(
+                strict,
+                format!(
+                    "No valid pixel data for image #{}, adding empty placeholder",
+                    img_idx
+                ),
+            )?;
             img_data.push(ImgPiece { img_px: Vec::new() });
         }
     }
@@ -1087,7 +1358,7 @@ fn read_meta_frames(
 
             let shape = (attr0 >> 14) & 0x3;
             let size = (attr1 >> 14) & 0x3;
-            let resolution_idx = ((shape << 2) | size) as usize;
+            let resolution_idx = checked_resolution_index(shape, size);
 
             let h_flip = (attr1 & super::flags::ATTR1_HFLIP_MASK) != 0;
             let v_flip = (attr1 & super::flags::ATTR1_VFLIP_MASK) != 0;
@@ -1150,6 +1421,17 @@ fn read_offset_data(
     Ok(offset_data)
 }
 
+/// `resolution_index` that falls back to 8x8 (index 0) with a warning on the prohibited shape
+fn checked_resolution_index(shape: u16, size: u16) -> usize {
+    super::resolution_index(shape, size).unwrap_or_else(|| {
+        println!(
+            "  - Warning: invalid OAM shape {} (size {}), treating piece as 8x8",
+            shape, size
+        );
+        0
+    })
+}
+
 /// Read animation groups from the WAN file
 fn read_animation_groups(
     cursor: &mut Cursor<&[u8]>,
@@ -1212,3 +1494,283 @@ fn read_animation_groups(
 
     Ok((anim_groups, anim_sequences))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::wan::{
+        builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+        renderer::{extract_frame, render_animation_frames, DEFAULT_MAX_FRAME_DIMENSION},
+    };
+
+    fn parse_paletteless_effect(fallback_palette: FallbackPalette) -> WanFile {
+        // Alternating index 0 / index 1 pixels, and no palette rows at all
+        let (content, data_pointer) = WanBuilder::new(WanType::Effect)
+            .image(vec![0x10; 32])
+            .frame(vec![BuilderPiece::new(0, 252, 508)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 1)]])
+            .build();
+        let options = ParseOptions {
+            fallback_palette,
+            ..Default::default()
+        };
+        parse_wan_from_sir0_content(&content, data_pointer, WanType::Effect, &options).unwrap()
+    }
+
+    #[test]
+    fn paletteless_effect_renders_transparent_not_black() {
+        let wan = parse_paletteless_effect(FallbackPalette::default());
+        let frames = render_animation_frames(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false).unwrap();
+        let pixels: Vec<_> = frames[0].0.pixels().collect();
+        assert!(pixels.iter().any(|p| p.0[3] == 0));
+        assert!(pixels.iter().any(|p| p.0[3] == 255));
+        assert!(pixels
+            .iter()
+            .filter(|p| p.0[3] > 0)
+            .all(|p| p.0[..3] != [0, 0, 0]));
+
+        let wan = parse_paletteless_effect(FallbackPalette::Transparent);
+        let frames = render_animation_frames(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false).unwrap();
+        assert!(frames[0].0.pixels().all(|p| p.0[3] == 0));
+    }
+
+    #[test]
+    fn anim_header_unknowns_are_kept_for_both_wan_types() {
+        let unknowns = [1, 0x22, 0x333, 0x4444, 0xFFFF];
+        for wan_type in [WanType::Character, WanType::Effect] {
+            let (content, data_pointer) = WanBuilder::new(wan_type)
+                .palette(vec![(0, 0, 0), (255, 255, 255)])
+                .image(vec![0x11; 32])
+                .frame(vec![BuilderPiece::new(0, 252, 508)])
+                .group(vec![vec![BuilderSeqFrame::new(0, 1)]])
+                .anim_header_unknowns(unknowns)
+                .build();
+            let wan = parse_wan_from_sir0_content(
+                &content,
+                data_pointer,
+                wan_type,
+                &ParseOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(wan.anim_header_unknowns, unknowns, "{:?}", wan_type);
+        }
+    }
+
+    #[test]
+    fn unterminated_sequence_stops_at_the_frame_limit() {
+        let frame = BuilderSeqFrame::new(0, 7);
+        let (mut content, data_pointer) = WanBuilder::new(WanType::Effect)
+            .palette(vec![(0, 0, 0), (255, 255, 255)])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, 252, 508)])
+            .group(vec![vec![frame, frame]])
+            .build();
+        // Overwrite the zero duration terminator with a third copy of the frame, so the reader
+        // runs on into the tables that follow
+        let record = [7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let terminator = content
+            .windows(36)
+            .position(|w| w[..12] == record && w[12..24] == record && w[24..] == [0; 12])
+            .unwrap()
+            + 24;
+        content[terminator..terminator + 12].copy_from_slice(&record);
+
+        let options = ParseOptions {
+            max_sequence_frames: 3,
+            ..Default::default()
+        };
+        let wan =
+            parse_wan_from_sir0_content(&content, data_pointer, WanType::Effect, &options).unwrap();
+        let AnimationStructure::Effect(groups) = &wan.animations else {
+            panic!("expected effect animations");
+        };
+        assert_eq!(groups[0][0].frames.len(), 3);
+        assert!(groups[0][0].frames.iter().all(|f| f.duration == 7));
+    }
+
+    #[test]
+    fn character_wan_decodes_8bpp_pixels_through_one_flat_palette() {
+        let mut piece = BuilderPiece::new(0, -4, -8);
+        // Ignored in 256 colour mode
+        piece.palette_index = 1;
+        let (content, data_pointer) = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (10, 10, 10)])
+            .palette(vec![(0, 0, 0), (255, 0, 0)])
+            .image(vec![17; 64])
+            .frame(vec![piece])
+            .group(vec![vec![BuilderSeqFrame::new(0, 1)]; 8])
+            .colour_256()
+            .build();
+        let wan = parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions {
+                strict: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(wan.palette_count(), 1);
+        assert_eq!(wan.palette(0).unwrap()[17], (255, 0, 0, 255));
+        let piece = &wan.frame_data[0].pieces[0];
+        assert!(piece.is_256_colour);
+        assert_eq!(piece.palette_index, 0);
+
+        let frame = extract_frame(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION).unwrap();
+        let opaque: Vec<_> = frame.pixels().filter(|p| p.0[3] > 0).collect();
+        assert_eq!(opaque.len(), 64);
+        assert!(opaque.iter().all(|p| p.0 == [255, 0, 0, 255]));
+    }
+
+    /// Reads `colours` RGBA entries as an effect palette block in Is256ColourSpr mode 4
+    fn read_mode_4_palette(colours: usize, strict: bool) -> Result<PaletteList, WanError> {
+        let data = [8u8, 16, 24, 0].repeat(colours);
+        let mut cursor = Cursor::new(&data[..]);
+        let colour_mode = EffectColourMode::decode(4, strict)?;
+        read_effect_palette_data(&mut cursor, 0, data.len() as u64, colour_mode, strict)
+    }
+
+    #[test]
+    fn mode_4_palette_fills_rows_by_16_colour_block() {
+        // A full block fills entries 16-31 of one 256 entry row
+        let palette = read_mode_4_palette(16, true).unwrap();
+        assert_eq!(palette.len(), 1);
+        assert_eq!(palette[0].len(), 256);
+        assert!(palette[0][..16].iter().all(|c| c.3 == 0));
+        assert!(palette[0][16..32].iter().all(|c| c.3 == 255));
+        assert!(palette[0][32..].iter().all(|c| c.3 == 0));
+
+        assert_eq!(read_mode_4_palette(32, true).unwrap().len(), 2);
+
+        // Less than a block still gives one partially filled row
+        let palette = read_mode_4_palette(8, true).unwrap();
+        assert_eq!(palette.len(), 1);
+        assert_eq!(palette[0].iter().filter(|c| c.3 == 255).count(), 8);
+
+        // A colour past the last full block is dropped with a warning, not silently
+        assert!(matches!(
+            read_mode_4_palette(17, true),
+            Err(WanError::Degraded(_))
+        ));
+        let palette = read_mode_4_palette(17, false).unwrap();
+        assert_eq!(palette.len(), 1);
+        assert_eq!(palette[0].iter().filter(|c| c.3 == 255).count(), 16);
+    }
+
+    #[test]
+    fn missing_palette_only_fails_when_strict() {
+        let (content, data_pointer) = WanBuilder::new(WanType::Character)
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 4)]; 8])
+            .build();
+        let parse = |strict| {
+            let options = ParseOptions {
+                strict,
+                ..Default::default()
+            };
+            parse_wan_from_sir0_content(&content, data_pointer, WanType::Character, &options)
+        };
+
+        assert!(parse(false).is_ok());
+        assert!(matches!(parse(true), Err(WanError::Degraded(_))));
+    }
+
+    #[test]
+    fn each_colour_mode_routes_to_its_palette_layout() {
+        let read_palette = |value| {
+            let data = [8u8, 16, 24, 0].repeat(16);
+            let mut cursor = Cursor::new(&data[..]);
+            let colour_mode = EffectColourMode::decode(value, true).unwrap();
+            read_effect_palette_data(&mut cursor, 0, data.len() as u64, colour_mode, true).unwrap()
+        };
+
+        assert_eq!(
+            EffectColourMode::decode(0, true).unwrap(),
+            EffectColourMode::Indexed4bpp
+        );
+        let palette = read_palette(0);
+        // One 16 colour row with colour 0 transparent
+        assert_eq!(palette[0].len(), 16);
+        assert_eq!(palette[0][0].3, 0);
+        assert!(palette[0][1..].iter().all(|c| c.3 == 255));
+
+        for value in [1, 4] {
+            assert_eq!(
+                EffectColourMode::decode(value, true).unwrap(),
+                EffectColourMode::Indexed8bpp
+            );
+            let palette = read_palette(value);
+            // The block lands at entries 16-31 of a 256 colour row
+            assert_eq!(palette[0].len(), 256);
+            assert!(palette[0][16..32].iter().all(|c| c.3 == 255));
+            assert_eq!(palette[0][0].3, 0);
+        }
+
+        // Unknown values read as 8bpp, or fail when strict
+        assert_eq!(
+            EffectColourMode::decode(2, false).unwrap(),
+            EffectColourMode::Indexed8bpp
+        );
+        assert!(matches!(
+            EffectColourMode::decode(2, true),
+            Err(WanError::Degraded(_))
+        ));
+    }
+
+    #[test]
+    fn palette_entries_render_with_their_stored_rgb() {
+        let opaque = |pixels: Vec<[u8; 4]>| -> Vec<[u8; 4]> {
+            pixels.into_iter().filter(|p| p[3] > 0).collect()
+        };
+        let colour = (200, 40, 8);
+
+        let (content, data_pointer) = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), colour])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 1)]; 8])
+            .build();
+        let wan = parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(wan.palette(0).unwrap()[1], (200, 40, 8, 255));
+        let frame = extract_frame(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION).unwrap();
+        let pixels = opaque(frame.pixels().map(|p| p.0).collect());
+        assert_eq!(pixels.len(), 64);
+        assert!(pixels.iter().all(|p| *p == [200, 40, 8, 255]));
+
+        // 4bpp effect palettes go through their own reader
+        let (content, data_pointer) = WanBuilder::new(WanType::Effect)
+            .palette(vec![(0, 0, 0), colour])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, 252, 508)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 1)]])
+            .build();
+        let wan = parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Effect,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let frames = render_animation_frames(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false).unwrap();
+        let pixels = opaque(frames[0].0.pixels().map(|p| p.0).collect());
+        assert!(!pixels.is_empty());
+        assert!(
+            pixels.iter().all(|p| *p == [200, 40, 8, 255]),
+            "{:?}",
+            pixels[0]
+        );
+
+        // The 8bpp reader rescales each channel but keeps them in R, G, B order
+        let palette = read_mode_4_palette(16, true).unwrap();
+        assert_eq!(palette[0][16], (8, 16, 24, 255));
+    }
+}
@@ -3,19 +3,53 @@
 //! This module provides functionality to render individual frames from WAN files
 //! into RGBA images, handling position offsets, flipping, and palette mapping.
 
-use crate::graphics::wan::{
-    model::{MetaFramePiece, WanFile},
-    AnimationStructure, WanError, TEX_SIZE,
+use crate::graphics::{
+    tiles::decode_tiled_indices,
+    wan::{
+        model::{MetaFramePiece, WanFile},
+        parser::recoverable,
+        AnimationStructure, WanError, TEX_SIZE,
+    },
 };
 
-use image::{imageops, Rgba, RgbaImage};
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops, Delay, Frame, Rgba, RgbaImage,
+};
 
 // This constant is used by the canvas calculation logic
 const CENTRE_X: i16 = 256;
 const CENTRE_Y: i16 = 512;
 
+/// Largest frame width/height a render will allocate unless overridden. Real WAN frames are far
+/// smaller, anything above this comes from corrupt piece offsets or resolutions.
+pub const DEFAULT_MAX_FRAME_DIMENSION: u32 = 1024;
+
+/// (sheet, frame_width, frame_height, top-left offset from the effect origin)
+pub type NativeSheet = (RgbaImage, u32, u32, (i32, i32));
+
+/// (frame image, sprite offset from its `SequenceFrame`)
+pub type PositionedFrame = (RgbaImage, (i16, i16));
+
+/// Rejects a frame canvas larger than `max_dimension` on either axis before it is allocated
+fn check_frame_dimensions(width: u32, height: u32, max_dimension: u32) -> Result<(), WanError> {
+    if width > max_dimension || height > max_dimension {
+        return Err(WanError::OutOfBounds(format!(
+            "Frame size {}x{} exceeds the maximum of {}x{}",
+            width, height, max_dimension, max_dimension
+        )));
+    }
+    Ok(())
+}
+
 /// Extract a single frame from a WAN file
-pub fn extract_frame(wan: &WanFile, frame_idx: usize) -> Result<RgbaImage, WanError> {
+pub fn extract_frame(
+    wan: &WanFile,
+    frame_idx: usize,
+    max_dimension: u32,
+) -> Result<RgbaImage, WanError> {
     if frame_idx >= wan.frame_data.len() {
         return Err(WanError::OutOfBounds(format!(
             "Frame index {} out of bounds (max: {})",
@@ -32,19 +66,19 @@ pub fn extract_frame(wan: &WanFile, frame_idx: usize) -> Result<RgbaImage, WanEr
     let frame_bounds = get_frame_bounds(wan, frame_idx)?;
     let width = (frame_bounds.2 - frame_bounds.0).max(1);
     let height = (frame_bounds.3 - frame_bounds.1).max(1);
+    check_frame_dimensions(width as u32, height as u32, max_dimension)?;
 
     let mut image = RgbaImage::new(width as u32, height as u32);
 
     for (i, piece) in frame_data.pieces.iter().enumerate() {
         let pal_num = piece.palette_index as usize;
-        if pal_num >= wan.custom_palette.len() {
+        let Some(palette) = wan.palette(pal_num) else {
             println!(
                 "Warning: Skipping piece {} in frame {} with invalid palette index {}",
                 i, frame_idx, pal_num
             );
             continue;
-        }
-        let palette = &wan.custom_palette[pal_num];
+        };
 
         let dimensions = piece.get_dimensions();
         let pos_x = piece.get_bounds().0 - frame_bounds.0;
@@ -104,42 +138,17 @@ pub fn get_effect_animation_canvas_box(
 
 /// Renders a complete animation sequence to a single horizontal sprite sheet image.
 /// If `fixed_canvas_box` is provided, uses those dimensions instead of calculating from bounds.
+///
+/// A sequence frame pointing past `frame_data` renders blank with a warning, or fails with
+/// `WanError::Degraded` when `strict` is set.
 pub fn render_effect_animation_sheet_with_canvas(
     wan_file: &WanFile,
     animation_index: usize,
     fixed_canvas_box: Option<(i16, i16, i16, i16)>,
+    max_dimension: u32,
+    strict: bool,
 ) -> Result<Option<(RgbaImage, u32, u32)>, WanError> {
-    // Animation_index is a sequence index into group 0 ONLY
-    let animation = match &wan_file.animations {
-        AnimationStructure::Effect(groups) => {
-            // ROM always uses group 0, animation_index is the sequence index
-            groups.first().and_then(|group| {
-                // Clamp out-of-bounds to 0, matching ROM behavior
-                let clamped_index = if animation_index >= group.len() {
-                    eprintln!(
-                        "Warning: animation_index {} out of bounds (max {}), clamping to 0",
-                        animation_index,
-                        group.len().saturating_sub(1)
-                    );
-                    0
-                } else {
-                    animation_index
-                };
-                group.get(clamped_index)
-            })
-        }
-        AnimationStructure::Character(_) => {
-            return Err(WanError::InvalidDataStructure(
-                "Character animation structure not supported for effect rendering".to_string(),
-            ));
-        }
-    }
-    .ok_or_else(|| {
-        WanError::OutOfBounds(format!(
-            "Animation index {} is out of bounds",
-            animation_index
-        ))
-    })?;
+    let animation = select_effect_animation(wan_file, animation_index)?;
 
     if animation.frames.is_empty() {
         return Ok(None);
@@ -159,9 +168,10 @@ pub fn render_effect_animation_sheet_with_canvas(
 
     let frame_width = (canvas_box.2 - canvas_box.0).max(1) as u32;
     let frame_height = (canvas_box.3 - canvas_box.1).max(1) as u32;
+    check_frame_dimensions(frame_width, frame_height, max_dimension)?;
 
     let mut rendered_frames = Vec::new();
-    for seq_frame in animation.frames.iter() {
+    for (i, seq_frame) in animation.frames.iter().enumerate() {
         let meta_frame_index = seq_frame.frame_index as usize;
 
         if meta_frame_index < wan_file.frame_data.len() {
@@ -173,6 +183,17 @@ pub fn render_effect_animation_sheet_with_canvas(
             )?;
             rendered_frames.push(frame_image);
         } else {
+            recoverable(
+                strict,
+                format!(
+                    "Sequence {} frame {} references meta-frame {} but only {} exist, \
+                     rendering it blank",
+                    animation_index,
+                    i,
+                    meta_frame_index,
+                    wan_file.frame_data.len()
+                ),
+            )?;
             rendered_frames.push(RgbaImage::new(frame_width, frame_height));
         }
     }
@@ -186,12 +207,241 @@ pub fn render_effect_animation_sheet_with_canvas(
     Ok(Some((sprite_sheet, frame_width, frame_height)))
 }
 
+/// Renders an animation sequence cropped to its tight bounds instead of a box centred on the
+/// effect origin. Returns the sheet, the frame size, and the offset of each frame's top-left
+/// corner from the effect origin, which the consumer uses to position it.
+pub fn render_effect_animation_sheet_native(
+    wan_file: &WanFile,
+    animation_index: usize,
+    max_dimension: u32,
+    strict: bool,
+) -> Result<Option<NativeSheet>, WanError> {
+    let animation = select_effect_animation(wan_file, animation_index)?;
+    if animation.frames.is_empty() {
+        return Ok(None);
+    }
+
+    let bounds = get_animation_bounds(wan_file, animation)?;
+    if bounds.2 <= bounds.0 || bounds.3 <= bounds.1 {
+        return Ok(None);
+    }
+
+    let offset = ((bounds.0 - CENTRE_X) as i32, (bounds.1 - CENTRE_Y) as i32);
+    Ok(render_effect_animation_sheet_with_canvas(
+        wan_file,
+        animation_index,
+        Some(bounds),
+        max_dimension,
+        strict,
+    )?
+    .map(|(sheet, width, height)| (sheet, width, height, offset)))
+}
+
 /// Renders a complete animation sequence to a single horizontal sprite sheet image
 pub fn render_effect_animation_sheet(
     wan_file: &WanFile,
     animation_index: usize,
+    max_dimension: u32,
+    strict: bool,
 ) -> Result<Option<(RgbaImage, u32, u32)>, WanError> {
-    render_effect_animation_sheet_with_canvas(wan_file, animation_index, None)
+    render_effect_animation_sheet_with_canvas(
+        wan_file,
+        animation_index,
+        None,
+        max_dimension,
+        strict,
+    )
+}
+
+/// Renders every frame of an effect sequence as its own image, paired with the sprite offset from
+/// its `SequenceFrame`. Frames share the sequence's rounded canvas box, like the cells of
+/// `render_effect_animation_sheet`, so they line up when overlaid. A frame pointing past
+/// `frame_data` comes back blank with a warning, or fails with `WanError::Degraded` when
+/// `strict` is set. Returns an empty list for empty sequences.
+pub fn render_animation_frames(
+    wan_file: &WanFile,
+    animation_index: usize,
+    max_dimension: u32,
+    strict: bool,
+) -> Result<Vec<PositionedFrame>, WanError> {
+    let animation = select_effect_animation(wan_file, animation_index)?;
+    if animation.frames.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let max_bounds = get_animation_bounds(wan_file, animation)?;
+    if max_bounds.2 <= max_bounds.0 || max_bounds.3 <= max_bounds.1 {
+        return Ok(Vec::new());
+    }
+    let canvas_box = round_up_box(max_bounds);
+    let frame_width = (canvas_box.2 - canvas_box.0).max(1) as u32;
+    let frame_height = (canvas_box.3 - canvas_box.1).max(1) as u32;
+    check_frame_dimensions(frame_width, frame_height, max_dimension)?;
+
+    let mut frames = Vec::with_capacity(animation.frames.len());
+    for (i, seq_frame) in animation.frames.iter().enumerate() {
+        let meta_frame_index = seq_frame.frame_index as usize;
+        let image = if meta_frame_index < wan_file.frame_data.len() {
+            render_meta_frame_on_canvas(wan_file, meta_frame_index, canvas_box, seq_frame.offset)?
+        } else {
+            recoverable(
+                strict,
+                format!(
+                    "Sequence {} frame {} references meta-frame {} but only {} exist, \
+                     rendering it blank",
+                    animation_index,
+                    i,
+                    meta_frame_index,
+                    wan_file.frame_data.len()
+                ),
+            )?;
+            RgbaImage::new(frame_width, frame_height)
+        };
+        frames.push((image, seq_frame.offset));
+    }
+
+    Ok(frames)
+}
+
+/// Writes effect sequence `animation_index` to `path` as a looping GIF for previewing. Frames are
+/// rendered on the sequence's shared canvas box like `render_animation_frames` and shown for
+/// their `duration` (1/60ths of a second), or for `1 / fps_override` seconds each when set.
+///
+/// Fully transparent pixels are mapped to each frame's transparent palette index and every frame
+/// clears to the background, so earlier frames don't show through. GIF delays are whole
+/// hundredths of a second, so short durations are rounded down. `max_dimension` and `strict`
+/// apply to the frames as in `render_animation_frames`.
+pub fn export_animation_gif(
+    wan_file: &WanFile,
+    animation_index: usize,
+    path: &Path,
+    fps_override: Option<u16>,
+    max_dimension: u32,
+    strict: bool,
+) -> Result<(), WanError> {
+    if fps_override == Some(0) {
+        return Err(WanError::OutOfBounds(
+            "GIF frame rate must be at least 1".to_string(),
+        ));
+    }
+
+    let animation = select_effect_animation(wan_file, animation_index)?;
+    let frames = render_animation_frames(wan_file, animation_index, max_dimension, strict)?;
+    if frames.is_empty() {
+        return Err(WanError::InvalidDataStructure(format!(
+            "Animation {} has no visible frames to export",
+            animation_index
+        )));
+    }
+
+    let gif_frames = frames
+        .into_iter()
+        .zip(&animation.frames)
+        .map(|((image, _), seq_frame)| {
+            let delay = match fps_override {
+                Some(fps) => Delay::from_numer_denom_ms(1000, fps as u32),
+                None => Delay::from_numer_denom_ms(seq_frame.duration.max(1) as u32 * 1000, 60),
+            };
+            Frame::from_parts(image, 0, 0, delay)
+        });
+
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .and_then(|_| encoder.encode_frames(gif_frames))
+        .map_err(|e| WanError::Io(std::io::Error::other(e)))
+}
+
+/// Renders an effect WAN that has image data but no animation info (`ptr_anim_info == 0`).
+/// With no meta-frames to place them, the tiles are laid out in VRAM order on a near-square
+/// grid and coloured with palette row 0, giving a one-frame sheet.
+pub fn render_static_effect(
+    wan_file: &WanFile,
+    max_dimension: u32,
+) -> Result<Option<(RgbaImage, u32, u32)>, WanError> {
+    let Some(palette) = wan_file.palette(0) else {
+        return Ok(None);
+    };
+    let bpp: u8 = if wan_file.tile_lookup_8bpp.is_some() {
+        8
+    } else {
+        4
+    };
+    let bytes_per_tile = TEX_SIZE * TEX_SIZE * bpp as usize / 8;
+
+    let pixels: Vec<u8> = wan_file
+        .img_data
+        .iter()
+        .flat_map(|piece| piece.img_px.iter().copied())
+        .collect();
+    let tile_count = pixels.len() / bytes_per_tile;
+    if tile_count == 0 {
+        return Ok(None);
+    }
+
+    let tiles_x = (tile_count as f64).sqrt().ceil() as usize;
+    let tiles_y = tile_count.div_ceil(tiles_x);
+    let width = (tiles_x * TEX_SIZE) as u32;
+    let height = (tiles_y * TEX_SIZE) as u32;
+    check_frame_dimensions(width, height, max_dimension)?;
+
+    let indices = decode_tiled_indices(&pixels, tiles_x, tiles_y, bpp);
+    let mut image = RgbaImage::new(width, height);
+    let mut has_visible_pixels = false;
+    for (i, &pal_idx) in indices.iter().enumerate() {
+        let pal_idx = pal_idx as usize;
+        if pal_idx > 0 && pal_idx < palette.len() {
+            let colour = palette[pal_idx];
+            if colour.3 > 0 {
+                image.put_pixel(
+                    i as u32 % width,
+                    i as u32 / width,
+                    Rgba([colour.0, colour.1, colour.2, colour.3]),
+                );
+                has_visible_pixels = true;
+            }
+        }
+    }
+
+    Ok(has_visible_pixels.then_some((image, width, height)))
+}
+
+/// Looks up an effect sequence in group 0, clamping an out-of-range index to 0 like the ROM
+fn select_effect_animation(
+    wan_file: &WanFile,
+    animation_index: usize,
+) -> Result<&crate::graphics::wan::model::Animation, WanError> {
+    // Animation_index is a sequence index into group 0 ONLY
+    match &wan_file.animations {
+        AnimationStructure::Effect(groups) => {
+            // ROM always uses group 0, animation_index is the sequence index
+            groups.first().and_then(|group| {
+                // Clamp out-of-bounds to 0, matching ROM behavior
+                let clamped_index = if animation_index >= group.len() {
+                    eprintln!(
+                        "Warning: animation_index {} out of bounds (max {}), clamping to 0",
+                        animation_index,
+                        group.len().saturating_sub(1)
+                    );
+                    0
+                } else {
+                    animation_index
+                };
+                group.get(clamped_index)
+            })
+        }
+        AnimationStructure::Character(_) => {
+            return Err(WanError::InvalidDataStructure(
+                "Character animation structure not supported for effect rendering".to_string(),
+            ));
+        }
+    }
+    .ok_or_else(|| {
+        WanError::OutOfBounds(format!(
+            "Animation index {} is out of bounds",
+            animation_index
+        ))
+    })
 }
 
 /// Calculates the maximum bounding box that encloses every frame in an animation sequence
@@ -244,6 +494,25 @@ fn get_animation_bounds(
     Ok(combined_bounds)
 }
 
+/// Renders meta-frame `meta_frame_index` onto a canvas covering `canvas_box` (left, top, right,
+/// bottom in the same piece coordinates as `get_frame_bounds`), so several frames can be
+/// composited on one shared canvas. Pieces outside the box are clipped.
+#[allow(dead_code)]
+pub fn render_meta_frame(
+    wan: &WanFile,
+    meta_frame_index: usize,
+    canvas_box: (i16, i16, i16, i16),
+) -> Result<RgbaImage, WanError> {
+    if meta_frame_index >= wan.frame_data.len() {
+        return Err(WanError::OutOfBounds(format!(
+            "Meta-frame index {} out of bounds (count: {})",
+            meta_frame_index,
+            wan.frame_data.len()
+        )));
+    }
+    render_meta_frame_on_canvas(wan, meta_frame_index, canvas_box, (0, 0))
+}
+
 /// Renders a single meta frame onto a canvas
 fn render_meta_frame_on_canvas(
     wan: &WanFile,
@@ -259,17 +528,14 @@ fn render_meta_frame_on_canvas(
 
     for piece in &frame_data.pieces {
         let pal_num = piece.palette_index as usize;
-
-        if pal_num >= wan.custom_palette.len() {
-            return Err(WanError::OutOfBounds(format!(
+        let palette = wan.palette(pal_num).ok_or_else(|| {
+            WanError::OutOfBounds(format!(
                 "Palette index {} is out of bounds for meta-frame {} (palette count: {}).",
                 pal_num,
                 meta_frame_index,
-                wan.custom_palette.len()
-            )));
-        }
-
-        let palette = &wan.custom_palette[pal_num];
+                wan.palette_count()
+            ))
+        })?;
         let dimensions = piece.get_dimensions();
         let pos_x = piece.x_offset + offset.0 - canvas_box.0;
         let pos_y = piece.y_offset + offset.1 - canvas_box.1;
@@ -309,6 +575,58 @@ fn round_up_box(bounds: (i16, i16, i16, i16)) -> (i16, i16, i16, i16) {
     (start_x, start_y, start_x + new_width, start_y + new_height)
 }
 
+/// Crops the transparent border every frame of a horizontal sheet shares. `frame_width` splits
+/// the sheet into frames, each keeps the same columns and rows so they stay aligned. Returns the
+/// trimmed sheet, its frame size and the `[x, y]` of the kept area within the original frame,
+/// or `None` when the sheet is fully transparent.
+pub fn trim_sheet(
+    sheet: &RgbaImage,
+    frame_width: u32,
+    frame_height: u32,
+) -> Option<(RgbaImage, u32, u32, [u32; 2])> {
+    let frame_width = frame_width.max(1);
+    let frame_count = sheet.width() / frame_width;
+    let frame_height = frame_height.min(sheet.height());
+
+    // Opaque bounds of the frames overlaid on each other
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for frame in 0..frame_count {
+        for y in 0..frame_height {
+            for x in 0..frame_width {
+                if sheet.get_pixel(frame * frame_width + x, y)[3] == 0 {
+                    continue;
+                }
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+    }
+    let (x0, y0, x1, y1) = bounds?;
+
+    let trimmed_width = x1 - x0 + 1;
+    let trimmed_height = y1 - y0 + 1;
+    let mut trimmed = RgbaImage::new(trimmed_width * frame_count, trimmed_height);
+    for frame in 0..frame_count {
+        let cropped = imageops::crop_imm(
+            sheet,
+            frame * frame_width + x0,
+            y0,
+            trimmed_width,
+            trimmed_height,
+        );
+        imageops::replace(
+            &mut trimmed,
+            &cropped.to_image(),
+            (frame * trimmed_width) as i64,
+            0,
+        );
+    }
+
+    Some((trimmed, trimmed_width, trimmed_height, [x0, y0]))
+}
+
 /// Stitches a vector of images into a single horizontal strip
 fn combine_frames_horizontally(frames: &[RgbaImage]) -> Result<RgbaImage, WanError> {
     if frames.is_empty() {
@@ -365,9 +683,8 @@ fn render_piece(
                 &[]
             }
         } else {
-            // Fallback to old method if no lookup available
-            // this shouldn't happen for effect WANs
-            &[]
+            // Character WANs have no lookup, their tile number is the image index in both modes
+            wan.img_data.get(tile_num).map_or(&[], |p| &p.img_px)
         }
     } else {
         // For 4bpp, each tile is its own ImgPiece
@@ -378,50 +695,21 @@ fn render_piece(
         return Ok(false);
     }
 
-    let tiles_x = width / TEX_SIZE;
-    let tiles_y = height / TEX_SIZE;
-
-    for ty in 0..tiles_y {
-        for tx in 0..tiles_x {
-            for y in 0..TEX_SIZE {
-                for x in 0..TEX_SIZE {
-                    let pixel_index_in_tile = y * TEX_SIZE + x;
-                    let tile_index_in_piece = ty * tiles_x + tx;
-
-                    let pal_idx = if is_256_colour_mode {
-                        let byte_index_in_buffer = tile_index_in_piece * 64 + pixel_index_in_tile;
-                        if byte_index_in_buffer >= pixel_buffer.len() {
-                            continue;
-                        }
-                        pixel_buffer[byte_index_in_buffer] as usize
-                    } else {
-                        let byte_index_in_buffer =
-                            tile_index_in_piece * 32 + (pixel_index_in_tile / 2);
-                        if byte_index_in_buffer >= pixel_buffer.len() {
-                            continue;
-                        }
-                        let byte = pixel_buffer[byte_index_in_buffer];
-                        if pixel_index_in_tile % 2 == 0 {
-                            (byte & 0x0F) as usize
-                        } else {
-                            (byte >> 4) as usize
-                        }
-                    };
-
-                    if pal_idx > 0 && pal_idx < palette.len() {
-                        let colour = palette[pal_idx];
-                        if colour.3 > 0 {
-                            let final_x = (tx * TEX_SIZE + x) as u32;
-                            let final_y = (ty * TEX_SIZE + y) as u32;
-                            piece_img.put_pixel(
-                                final_x,
-                                final_y,
-                                Rgba([colour.0, colour.1, colour.2, colour.3]),
-                            );
-                            has_visible_pixels = true;
-                        }
-                    }
-                }
+    let bpp = if is_256_colour_mode { 8 } else { 4 };
+    let indices = decode_tiled_indices(pixel_buffer, width / TEX_SIZE, height / TEX_SIZE, bpp);
+
+    // Index 0 and anything past the palette stay transparent
+    for (i, &pal_idx) in indices.iter().enumerate() {
+        let pal_idx = pal_idx as usize;
+        if pal_idx > 0 && pal_idx < palette.len() {
+            let colour = palette[pal_idx];
+            if colour.3 > 0 {
+                piece_img.put_pixel(
+                    (i % width) as u32,
+                    (i / width) as u32,
+                    Rgba([colour.0, colour.1, colour.2, colour.3]),
+                );
+                has_visible_pixels = true;
             }
         }
     }
@@ -483,3 +771,229 @@ pub fn get_frame_bounds(wan: &WanFile, frame_idx: usize) -> Result<(i16, i16, i1
 
     Ok(bounds)
 }
+
+#[cfg(test)]
+mod tests {
+    use image::{codecs::gif::GifDecoder, AnimationDecoder};
+
+    use super::*;
+    use crate::{
+        graphics::wan::{
+            builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+            parser::{parse_wan_from_sir0_content, ParseOptions},
+            WanType,
+        },
+        test_support::scratch_dir,
+    };
+
+    /// Effect with one 8x8 meta-frame, its only sequence also points at missing meta-frame 5
+    fn effect_with_dangling_frame() -> WanFile {
+        let (content, data_pointer) = WanBuilder::new(WanType::Effect)
+            .palette(vec![(0, 0, 0), (255, 255, 255)])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, 252, 508)])
+            .group(vec![vec![
+                BuilderSeqFrame::new(0, 2),
+                BuilderSeqFrame::new(5, 3),
+            ]])
+            .build();
+        parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Effect,
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn animation_frames_render_dangling_frames_blank() {
+        let wan = effect_with_dangling_frame();
+        let frames = render_animation_frames(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].0.pixels().any(|p| p.0[3] > 0));
+        assert!(frames[1].0.pixels().all(|p| p.0[3] == 0));
+        assert_eq!(frames[0].0.dimensions(), frames[1].0.dimensions());
+    }
+
+    #[test]
+    fn animation_frames_honour_strict_and_max_dimension() {
+        let wan = effect_with_dangling_frame();
+        assert!(matches!(
+            render_animation_frames(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, true),
+            Err(WanError::Degraded(_))
+        ));
+        assert!(matches!(
+            render_animation_frames(&wan, 0, 1, false),
+            Err(WanError::OutOfBounds(_))
+        ));
+    }
+
+    #[test]
+    fn effect_sheet_blanks_dangling_frames_unless_strict() {
+        let wan = effect_with_dangling_frame();
+
+        let (sheet, width, height) =
+            render_effect_animation_sheet(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false)
+                .unwrap()
+                .unwrap();
+        assert_eq!(sheet.dimensions(), (width * 2, height));
+        let opaque_in = |x_range: std::ops::Range<u32>| {
+            x_range
+                .flat_map(|x| (0..height).map(move |y| (x, y)))
+                .any(|(x, y)| sheet.get_pixel(x, y).0[3] > 0)
+        };
+        assert!(opaque_in(0..width));
+        assert!(!opaque_in(width..width * 2));
+
+        assert!(matches!(
+            render_effect_animation_sheet(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, true),
+            Err(WanError::Degraded(_))
+        ));
+    }
+
+    #[test]
+    fn meta_frame_lands_at_its_offset_on_an_oversized_canvas() {
+        let wan = effect_with_dangling_frame();
+
+        // The 8x8 piece at (252, 508) sits 12px right and 8px down from the canvas corner
+        let image = render_meta_frame(&wan, 0, (240, 500, 280, 530)).unwrap();
+
+        assert_eq!(image.dimensions(), (40, 30));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let inside = (12..20).contains(&x) && (8..16).contains(&y);
+            assert_eq!(pixel.0[3] > 0, inside, "pixel ({}, {})", x, y);
+        }
+        assert!(matches!(
+            render_meta_frame(&wan, 5, (240, 500, 280, 530)),
+            Err(WanError::OutOfBounds(_))
+        ));
+    }
+
+    #[test]
+    fn animation_gif_loops_frames_with_their_durations() {
+        let dir = scratch_dir("renderer_gif");
+        let wan = effect_with_dangling_frame();
+        let path = dir.join("preview.gif");
+        export_animation_gif(&wan, 0, &path, None, DEFAULT_MAX_FRAME_DIMENSION, false).unwrap();
+
+        let decoder = GifDecoder::new(File::open(&path).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        // 3 ticks at 60 fps are 50 ms, GIF rounds to whole hundredths
+        assert_eq!(frames[1].delay().numer_denom_ms(), (50, 1));
+        assert!(frames[0].buffer().pixels().any(|p| p.0[3] == 255));
+        assert!(frames[1].buffer().pixels().all(|p| p.0[3] == 0));
+
+        export_animation_gif(&wan, 0, &path, Some(10), DEFAULT_MAX_FRAME_DIMENSION, false).unwrap();
+        let decoder = GifDecoder::new(File::open(&path).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames[0].delay().numer_denom_ms(), (100, 1));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_frame_is_rejected_before_allocating() {
+        let mut wan = effect_with_dangling_frame();
+        // A corrupt piece far from the first one declares a ~20000px wide frame
+        let mut stray = wan.frame_data[0].pieces[0].clone();
+        stray.x_offset = 20000;
+        wan.frame_data[0].pieces.push(stray);
+
+        match extract_frame(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION) {
+            Err(WanError::OutOfBounds(message)) => {
+                assert!(message.contains("exceeds the maximum"), "{}", message)
+            }
+            other => panic!(
+                "expected OutOfBounds, got {:?}",
+                other.map(|i| i.dimensions())
+            ),
+        }
+        assert!(extract_frame(&wan, 0, 32768).is_ok());
+    }
+
+    #[test]
+    fn native_bounds_sheet_is_tighter_than_the_centred_one() {
+        // One 8x8 piece 40px right of and 20px below the effect origin
+        let (content, data_pointer) = WanBuilder::new(WanType::Effect)
+            .palette(vec![(0, 0, 0), (255, 255, 255)])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, 296, 532)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 2)]])
+            .build();
+        let wan = parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Effect,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        let (_, centred_w, centred_h) =
+            render_effect_animation_sheet(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false)
+                .unwrap()
+                .unwrap();
+        let (sheet, native_w, native_h, offset) =
+            render_effect_animation_sheet_native(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false)
+                .unwrap()
+                .unwrap();
+
+        assert_eq!((native_w, native_h), (8, 8));
+        assert_eq!(sheet.dimensions(), (8, 8));
+        assert!(native_w * native_h < centred_w * centred_h);
+        assert_eq!(offset, (40, 20));
+    }
+
+    #[test]
+    fn static_effect_renders_as_a_one_frame_sheet() {
+        let (mut content, data_pointer) = WanBuilder::new(WanType::Effect)
+            .palette(vec![(0, 0, 0), (255, 255, 255)])
+            .image(vec![0x11; 64])
+            .build();
+        // Static effects have no animation info at all
+        let header = data_pointer as usize;
+        content[header..header + 4].fill(0);
+        let wan = parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Effect,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert!(wan.frame_data.is_empty());
+        assert!(
+            render_effect_animation_sheet(&wan, 0, DEFAULT_MAX_FRAME_DIMENSION, false).is_err()
+        );
+
+        let (sheet, width, height) = render_static_effect(&wan, DEFAULT_MAX_FRAME_DIMENSION)
+            .unwrap()
+            .unwrap();
+        // Two 4bpp tiles laid out side by side
+        assert_eq!((width, height), (16, 8));
+        assert_eq!(sheet.dimensions(), (16, 8));
+        assert!(sheet.pixels().all(|p| p.0 == [255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn shared_10px_top_border_is_trimmed_and_recorded() {
+        // Two 16x24 frames, opaque from row 10 down, at different columns
+        let mut sheet = RgbaImage::new(32, 24);
+        for (frame, x) in [(0, 4), (1, 9)] {
+            for y in 10..24 {
+                sheet.put_pixel(frame * 16 + x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+
+        let (trimmed, width, height, offset) = trim_sheet(&sheet, 16, 24).unwrap();
+        assert_eq!(offset, [4, 10]);
+        assert_eq!((width, height), (6, 14));
+        assert_eq!(trimmed.dimensions(), (12, 14));
+        // Both frames keep the same crop, so they stay aligned
+        assert_eq!(trimmed.get_pixel(0, 0).0[3], 255);
+        assert_eq!(trimmed.get_pixel(6 + 5, 13).0[3], 255);
+        assert_eq!(trimmed.get_pixel(6, 0).0[3], 0);
+
+        assert!(trim_sheet(&RgbaImage::new(32, 24), 16, 24).is_none());
+    }
+}
@@ -6,11 +6,17 @@ mod effect_sprite_extractor;
 mod filesystem;
 mod move_data_extractor;
 mod move_effects_index;
+mod output;
 mod pokemon_portrait_extractor;
 mod pokemon_sprite_extractor;
 mod progress;
+mod report;
 mod rom;
+#[cfg(any(test, feature = "rom-builder"))]
+mod rom_builder;
 mod status_icon_extractor;
+#[cfg(test)]
+mod test_support;
 mod weather_manifest;
 
 mod containers;
@@ -19,11 +25,30 @@ mod dungeon;
 mod formats;
 mod graphics;
 
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
 use clap::Parser;
 
-use crate::status_icon_extractor::StatusIconExtractor;
+use crate::{
+    graphics::{
+        atlas::AtlasConfig,
+        portrait::PortraitLayout,
+        wan::{
+            parser::{FallbackPalette, DEFAULT_MAX_SEQUENCE_FRAMES},
+            renderer::DEFAULT_MAX_FRAME_DIMENSION,
+            DurationUnit,
+        },
+    },
+    output::{DirSink, ImageFormat, JsonStyle, OutputSink, ZipSink},
+    report::ExtractionReport,
+    status_icon_extractor::StatusIconExtractor,
+};
 
 use {
     animation_info_extractor::AnimationInfoExtractor, dungeon_bin_extractor::DungeonBinExtractor,
@@ -44,6 +69,208 @@ struct Cli {
     progress: PathBuf,
     #[arg(long)]
     num_pokemon: Option<u32>,
+    /// Unit for every exported animation duration, in sprite atlases and effects alike:
+    /// frames, seconds or millis.
+    #[arg(long, value_name = "UNIT", default_value_t = DurationUnit::default())]
+    duration_unit: DurationUnit,
+    /// Largest frame width/height to render, bigger frames are treated as corrupt.
+    #[arg(long, value_name = "PIXELS", default_value_t = DEFAULT_MAX_FRAME_DIMENSION)]
+    max_frame_dimension: u32,
+    /// Most frames read from one animation sequence, longer (corrupt) sequences are truncated.
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_MAX_SEQUENCE_FRAMES)]
+    frame_limit: usize,
+    /// Leave effects that have no palette data fully transparent instead of colouring them
+    /// with placeholder greys.
+    #[arg(long)]
+    transparent_fallback_palette: bool,
+    /// Also write the move -> effect relationships as a Graphviz DOT file (asset_index.dot).
+    #[arg(long)]
+    emit_dot: bool,
+    /// Integer nearest-neighbour upscale for sprite atlases and effect sheets, metadata included.
+    #[arg(long, value_name = "FACTOR", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    sprite_scale: u32,
+    /// Only extract sprites for these monster.md indices (comma separated, e.g. 25,150).
+    #[arg(long, value_name = "MD_INDEX", value_delimiter = ',')]
+    monster_ids: Vec<usize>,
+    /// Keep Pokemon forms the form name table doesn't know about, exported as `_form_N`.
+    #[arg(long)]
+    include_unknown_forms: bool,
+    /// Write a palette texture (`.pal.png`) for every dungeon tileset, not just animated ones.
+    #[arg(long)]
+    palette_dump_all: bool,
+    /// Also write each animated dungeon tileset with its palette animation baked in at this
+    /// frame (`_frameN.png`).
+    #[arg(long, value_name = "FRAME")]
+    tileset_frame: Option<usize>,
+    /// Export non-directional effect sheets cropped to their tight bounds plus an origin offset.
+    #[arg(long)]
+    effect_native_bounds: bool,
+    /// Crop the transparent border shared by every frame of centred non-directional effect
+    /// sheets, recording the crop position as trim_offset in asset_index.json.
+    #[arg(long)]
+    effect_sheet_trim: bool,
+    /// Debug aid: also write each frame of non-directional effects to effect_sprites/frames/.
+    #[arg(long)]
+    dump_effect_frames: bool,
+    /// List moves without any effect animation in asset_index.json with an empty effects list.
+    #[arg(long)]
+    include_effectless_moves: bool,
+    /// Merge into the asset_index.json already in OUTPUT_DIR instead of replacing it, to combine
+    /// partial runs. Entries from this run win where both have one.
+    #[arg(long)]
+    merge_index: bool,
+    /// Debug aid: also write the effect.bin base palette to EFFECT/base_palette.{png,json}.
+    #[arg(long)]
+    export_effect_base_palette: bool,
+    /// Write each Pokemon's and sprite effect's palette rows as GIMP .gpl and Adobe .act files
+    /// next to its atlas or sheet.
+    #[arg(long)]
+    export_palettes: bool,
+    /// Parse animation data for the effect pipeline but don't write DATA/animation_data JSON.
+    #[arg(long)]
+    skip_animation_json: bool,
+    /// Leave the per-frame head/hand/centre positions out of the sprite atlas JSON.
+    #[arg(long)]
+    omit_offsets: bool,
+    /// Also write a greyscale `_atlas_indices.png` per sprite atlas with each pixel's index into
+    /// the metadata `palette`, for engines that swap palettes at runtime.
+    #[arg(long)]
+    export_indices: bool,
+    /// Record each sprite atlas frame's opaque pixel box as `opaque_bounds` in the atlas JSON.
+    #[arg(long)]
+    frame_bounds: bool,
+    /// Crop sprite atlas frames to their opaque pixels before deduplicating, so frames that
+    /// only differ in transparent margins share a cell. Each frame's trim_offset re-centres it.
+    #[arg(long)]
+    trim_before_dedup: bool,
+    /// Keep the sprite's native palette index order (0 transparent, 1-15 as in the ROM) in
+    /// indexed atlas PNGs and the metadata `palette`, for shiny and team colour swaps.
+    #[arg(long)]
+    palette_preserve_order: bool,
+    /// Portrait atlas grid: square, power-of-two or columns=N. Recorded in the atlas JSON.
+    #[arg(long, value_name = "LAYOUT", default_value_t = PortraitLayout::Square)]
+    portrait_layout: PortraitLayout,
+    /// Write a 1x1 placeholder atlas with `"empty": true` metadata for Pokemon that fail to render.
+    #[arg(long)]
+    emit_empty: bool,
+    /// Treat recoverable WAN problems (missing palette, unreadable image data, dangling
+    /// meta-frame references, ...) as errors instead of falling back, for validating a ROM's
+    /// integrity.
+    #[arg(long)]
+    strict: bool,
+    /// Write every JSON file without indentation or newlines (pretty-printed by default).
+    #[arg(long)]
+    json_compact: bool,
+    /// Sort the keys of every JSON object so repeated runs on the same ROM write byte-identical
+    /// files (asset_index.json, move and effect maps, portrait and atlas metadata).
+    #[arg(long)]
+    deterministic: bool,
+    /// Container for sprite atlases, effect sheets and portraits: png or webp (lossless).
+    /// Metadata refers to whichever is written, index buffers and debug images stay PNG.
+    #[arg(long, value_name = "FORMAT", default_value_t = ImageFormat::Png)]
+    image_format: ImageFormat,
+    /// Write sprite atlases, portraits and effect sheets into this zip archive instead of
+    /// OUTPUT_DIR. Other stages (DATA, dungeon tilesets, status icons) still write loose files.
+    #[arg(long, value_name = "ZIP_PATH")]
+    zip_output: Option<PathBuf>,
+    /// Validate the ROM and print how many sprites, portraits, effects, tilesets and status
+    /// icons the selected stages would produce, without creating OUTPUT_DIR or writing any
+    /// file.
+    #[arg(long)]
+    dry_run: bool,
+    /// Don't regenerate sprite atlases or effect sheets whose files already exist and are newer
+    /// than the ROM. Ignored with --zip-output.
+    #[arg(long)]
+    skip_existing: bool,
+    /// Write this effect's animation to EFFECT/{EFFECT_ID}_preview.gif in OUTPUT_DIR and exit
+    /// without running any stage. Frames use their ROM durations unless --preview-fps is set.
+    #[arg(long, value_name = "EFFECT_ID")]
+    preview_gif: Option<u16>,
+    /// Compare the Pokemon sprites (monster.bin, m_attack.bin) with those of this ROM, print
+    /// the ones that differ in structure and exit without running any stage
+    #[arg(long, value_name = "OTHER_ROM")]
+    compare: Option<PathBuf>,
+    /// Frame rate for --preview-gif, overriding the per-frame durations
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u16).range(1..))]
+    preview_fps: Option<u16>,
+    /// Only run these stages (repeatable): animation, pokemon, portraits, effects, dungeon,
+    /// status-icons. Runs everything when omitted.
+    #[arg(long, value_name = "STAGE")]
+    stage: Vec<Stage>,
+}
+
+impl Cli {
+    /// Stages picked with `--stage`, every stage when none were
+    fn stages(&self) -> Vec<Stage> {
+        if self.stage.is_empty() {
+            Stage::ALL.to_vec()
+        } else {
+            self.stage.clone()
+        }
+    }
+
+    /// Whether the animation tables are parsed, the effects stage needs them as well
+    fn parses_animation_data(&self) -> bool {
+        let stages = self.stages();
+        stages.contains(&Stage::Animation) || stages.contains(&Stage::Effects)
+    }
+
+    /// Whether the parsed animation tables are written to DATA/animation_data
+    fn writes_animation_json(&self) -> bool {
+        !self.skip_animation_json && self.stages().contains(&Stage::Animation)
+    }
+
+    fn fallback_palette(&self) -> FallbackPalette {
+        if self.transparent_fallback_palette {
+            FallbackPalette::Transparent
+        } else {
+            FallbackPalette::Greys
+        }
+    }
+}
+
+/// Extraction stages that `--stage` can select
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// Animation tables and move data JSON in DATA/
+    Animation,
+    Pokemon,
+    Portraits,
+    Effects,
+    /// Dungeon tilesets, tileset properties and the weather manifest
+    Dungeon,
+    StatusIcons,
+}
+
+impl Stage {
+    const ALL: [Stage; 6] = [
+        Stage::Animation,
+        Stage::Pokemon,
+        Stage::Portraits,
+        Stage::Effects,
+        Stage::Dungeon,
+        Stage::StatusIcons,
+    ];
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "animation" => Ok(Stage::Animation),
+            "pokemon" | "sprites" => Ok(Stage::Pokemon),
+            "portraits" => Ok(Stage::Portraits),
+            "effects" => Ok(Stage::Effects),
+            "dungeon" => Ok(Stage::Dungeon),
+            "status-icons" | "status_icons" => Ok(Stage::StatusIcons),
+            other => Err(format!(
+                "unknown stage '{}', expected animation, pokemon, portraits, effects, dungeon \
+                 or status-icons",
+                other
+            )),
+        }
+    }
 }
 
 fn main() {
@@ -54,129 +281,1051 @@ fn main() {
         std::process::exit(1);
     }
 
-    if !cli.output_dir.exists() {
-        std::fs::create_dir_all(&cli.output_dir).expect("Failed to create output directory");
+    if cli.dry_run {
+        dry_run(&cli, &cli.stages());
+        return;
+    }
+
+    if let Some(effect_id) = cli.preview_gif {
+        preview_gif(&cli, effect_id);
+        return;
+    }
+
+    if let Some(other_rom) = &cli.compare {
+        compare_roms(&cli.rom_path, other_rom);
+        return;
     }
 
+    if let Err(e) = run(cli) {
+        eprintln!("{}", e);
+    }
+}
+
+/// Runs the selected stages and writes their output below `cli.output_dir`. Only a ROM that
+/// can't be read or an output that can't be created or finished fails the run, stage failures
+/// are recorded in the returned report.
+fn run(cli: Cli) -> io::Result<ExtractionReport> {
+    let stages = cli.stages();
+    let parse_animation_data = cli.parses_animation_data();
+    let write_animation_json = cli.writes_animation_json();
+    let fallback_palette = cli.fallback_palette();
+    let json_style = JsonStyle {
+        compact: cli.json_compact,
+        sorted_keys: cli.deterministic,
+    };
+
+    let mut rom = Rom::new(&cli.rom_path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to read ROM file, possibly corrupted: {}", e),
+        )
+    })?;
+    println!(
+        "Successfully parsed ROM '{}' ({}), no corruption detected",
+        rom.sanitized_title(),
+        rom.id_code
+    );
+
     let output_dir_sprites = cli.output_dir.join("MONSTER");
     let output_dir_portraits = cli.output_dir.join("PORTRAIT");
     let output_dir_jsons = cli.output_dir.join("DATA");
     let output_dir_pipeline = cli.output_dir;
 
-    for dir in [
-        &output_dir_sprites,
-        &output_dir_portraits,
-        &output_dir_jsons,
-        &output_dir_pipeline,
-    ] {
-        if !dir.exists() {
-            fs::create_dir_all(dir).expect("Failed to create output directory");
-        }
+    // Created only once the ROM has loaded, an archive is valid after `finish` and nothing may
+    // return early in between
+    let zip_sink = match &cli.zip_output {
+        Some(path) => Some(Arc::new(
+            ZipSink::create(path, &output_dir_pipeline).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Failed to create zip archive {}: {}", path.display(), e),
+                )
+            })?,
+        )),
+        None => None,
+    };
+    let sink: Arc<dyn OutputSink> = match &zip_sink {
+        Some(zip) => zip.clone(),
+        None => Arc::new(DirSink),
+    };
+    // The up-to-date check looks at loose files, an archive is always written from scratch
+    let skip_existing = cli.skip_existing && zip_sink.is_none();
+    if cli.skip_existing && !skip_existing {
+        println!("Warning: --skip-existing has no effect with --zip-output");
+    }
+
+    // DATA is written loose either way. With --zip-output the sprite and portrait trees only
+    // exist inside the archive, otherwise `DirSink` creates the folders below them as it writes.
+    let mut output_dirs = vec![&output_dir_jsons];
+    if zip_sink.is_none() {
+        output_dirs.extend([&output_dir_sprites, &output_dir_portraits]);
+    }
+    for dir in output_dirs {
+        fs::create_dir_all(dir)?;
     }
 
-    match Rom::new(cli.rom_path) {
-        Ok(mut rom) => {
-            println!("Successfully parsed ROM, no corruption detected");
+    let mut report = ExtractionReport::default();
+    let run_stage = |stage: Stage| stages.contains(&stage);
+    let skip_phase = |phase: &str| {
+        println!("Skipping {} (not in --stage)", phase);
+        write_progress(&cli.progress, 0, 0, phase, "skipped");
+    };
 
-            let mut animation_info_extractor = AnimationInfoExtractor::new(&mut rom);
-            println!("Extracting all animation data...");
+    // The effect pipeline needs the animation tables even when their JSON isn't wanted
+    let anim_data_info = if parse_animation_data {
+        let mut animation_info_extractor =
+            AnimationInfoExtractor::new(&mut rom).with_json_style(json_style);
+        println!("Extracting all animation data...");
 
-            let anim_data_info = animation_info_extractor.parse_and_transform_animation_data();
+        let anim_data_info = animation_info_extractor.parse_and_transform_animation_data();
+        if !write_animation_json {
+            println!("Skipping animation data JSON export");
+        } else {
             let _ = animation_info_extractor
                 .save_animation_info_json(&anim_data_info, &output_dir_jsons);
+        }
+        Some(anim_data_info)
+    } else {
+        None
+    };
 
-            // Tileset properties (overlay 10): weather_effect / is_water / map_color
-            let tileset_properties = match rom.extract_tileset_properties() {
-                Ok(props) => {
-                    let path = output_dir_jsons.join("tileset_properties.json");
-                    if let Err(e) = data::tileset_properties::save_json(&props, &path) {
-                        eprintln!("Failed to write tileset_properties.json: {}", e);
-                    } else {
-                        println!("Wrote {} tileset properties to DATA/", props.len());
-                    }
-                    Some(props)
-                }
-                Err(e) => {
-                    eprintln!("Failed to extract tileset properties: {}", e);
-                    None
-                }
-            };
+    if run_stage(Stage::Animation) {
+        let move_data_extractor = MoveDataExtractor::new(&rom).with_json_style(json_style);
+        let _ = move_data_extractor.extract_and_save(&output_dir_jsons);
+    }
 
-            let move_data_extractor = MoveDataExtractor::new(&rom);
-            let _ = move_data_extractor.extract_and_save(&output_dir_jsons);
+    // Includes all pokemon, female versions, different forms
+    let mut total_pokemon: usize = 572;
+    const EFFECT_SPRITE_NUM: usize = 664;
 
-            let effects_map: HashMap<u16, _> = anim_data_info
-                .effect_table
-                .clone()
-                .into_iter()
-                .enumerate()
-                .map(|(idx, info)| (idx as u16, info))
-                .collect();
+    if let Some(num) = cli.num_pokemon {
+        total_pokemon = num as usize;
+    }
+
+    if run_stage(Stage::Pokemon) {
+        write_progress(&cli.progress, 0, total_pokemon, "pokemon_sprite", "running");
+        let atlas_config = AtlasConfig {
+            max_frame_dimension: cli.max_frame_dimension,
+            max_sequence_frames: cli.frame_limit,
+            scale: cli.sprite_scale,
+            include_offsets: !cli.omit_offsets,
+            export_indices: cli.export_indices,
+            export_opaque_bounds: cli.frame_bounds,
+            trim_before_dedup: cli.trim_before_dedup,
+            preserve_palette_order: cli.palette_preserve_order,
+            sink: sink.clone(),
+            json_style,
+            image_format: cli.image_format,
+            duration_unit: cli.duration_unit,
+            ..AtlasConfig::default()
+        };
+        let sprite_extractor = PokemonSpriteExtractor::new(&rom)
+            .with_atlas_config(atlas_config)
+            .with_unknown_forms(cli.include_unknown_forms)
+            .with_emit_empty(cli.emit_empty)
+            .with_palette_export(cli.export_palettes)
+            .with_skip_existing(skip_existing)
+            .with_id_whitelist((!cli.monster_ids.is_empty()).then(|| cli.monster_ids.clone()))
+            .with_strict(cli.strict);
+        let result = sprite_extractor.extract_monster_data(
+            cli.num_pokemon,
+            &output_dir_sprites,
+            &cli.progress,
+        );
+        report.pokemon = report.record("pokemon", result);
+    } else {
+        skip_phase("pokemon_sprite");
+    }
 
-            let moves_map = anim_data_info.transform_move_data();
+    if run_stage(Stage::Portraits) {
+        write_progress(&cli.progress, 0, 2, "portrait_atlas", "running");
+        let portrait_extractor = PortraitExtractor::new(&rom)
+            .with_sink(sink.clone())
+            .with_json_style(json_style)
+            .with_image_format(cli.image_format)
+            .with_layout(cli.portrait_layout);
+        let result =
+            portrait_extractor.extract_portrait_atlases(&output_dir_portraits, &cli.progress);
+        report.portraits = report.record("portraits", result);
+    } else {
+        skip_phase("portrait_atlas");
+    }
 
-            // Includes all pokemon, female versions, different forms
-            let mut total_pokemon: usize = 572;
-            const EFFECT_SPRITE_NUM: usize = 664;
+    if run_stage(Stage::Effects) {
+        let anim_data_info = anim_data_info
+            .as_ref()
+            .expect("animation data is parsed for the effects stage");
+        let effects_map: HashMap<u16, _> = anim_data_info
+            .effect_table
+            .clone()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, info)| (idx as u16, info))
+            .collect();
+
+        let moves_map = anim_data_info.transform_move_data();
+
+        write_progress(
+            &cli.progress,
+            0,
+            EFFECT_SPRITE_NUM,
+            "move_effect_sprites",
+            "running",
+        );
+        let mut effect_pipeline = EffectAssetPipeline::new(&rom)
+            .with_duration_unit(cli.duration_unit)
+            .with_max_frame_dimension(cli.max_frame_dimension)
+            .with_max_sequence_frames(cli.frame_limit)
+            .with_dot_export(cli.emit_dot)
+            .with_sprite_scale(cli.sprite_scale)
+            .with_native_bounds(cli.effect_native_bounds)
+            .with_sheet_trim(cli.effect_sheet_trim)
+            .with_frame_dump(cli.dump_effect_frames)
+            .with_effectless_moves(cli.include_effectless_moves)
+            .with_index_merge(cli.merge_index)
+            .with_base_palette_export(cli.export_effect_base_palette)
+            .with_palette_export(cli.export_palettes)
+            .with_skip_existing(skip_existing)
+            .with_sink(sink.clone())
+            .with_json_style(json_style)
+            .with_image_format(cli.image_format)
+            .with_strict(cli.strict)
+            .with_fallback_palette(fallback_palette);
+        let result = effect_pipeline.run(
+            &effects_map,
+            &moves_map,
+            &output_dir_pipeline,
+            &cli.progress,
+            EFFECT_SPRITE_NUM,
+        );
+        report.effects = report.record("effects", result);
+    } else {
+        skip_phase("move_effect_sprites");
+    }
 
-            if let Some(num) = cli.num_pokemon {
-                total_pokemon = num as usize;
+    if run_stage(Stage::Dungeon) {
+        // Tileset properties (overlay 10): weather_effect / is_water / map_color
+        let tileset_properties = match rom.extract_tileset_properties() {
+            Ok(props) => {
+                let path = output_dir_jsons.join("tileset_properties.json");
+                if let Err(e) = data::tileset_properties::save_json(&props, &path, json_style) {
+                    eprintln!("Failed to write tileset_properties.json: {}", e);
+                } else {
+                    println!("Wrote {} tileset properties to DATA/", props.len());
+                }
+                Some(props)
+            }
+            Err(e) => {
+                eprintln!("Failed to extract tileset properties: {}", e);
+                None
             }
+        };
 
-            write_progress(&cli.progress, 0, total_pokemon, "pokemon_sprite", "running");
-            let sprite_extractor = PokemonSpriteExtractor::new(&rom);
-            let _ = sprite_extractor.extract_monster_data(
-                cli.num_pokemon,
-                &output_dir_sprites,
-                &cli.progress,
-            );
+        let output_dir_dungeons = output_dir_pipeline.join("DUNGEON").join("tilesets");
+        let dungeon_extractor = DungeonBinExtractor::new(&rom)
+            .with_palette_dump_all(cli.palette_dump_all)
+            .with_baked_frame(cli.tileset_frame)
+            .with_json_style(json_style);
+        let result = dungeon_extractor.extract_dungeon_tilesets(
+            None,
+            &output_dir_dungeons,
+            &cli.progress,
+            tileset_properties.as_deref(),
+        );
+        report.tilesets = report.record("tilesets", result);
 
-            write_progress(&cli.progress, 0, 2, "portrait_atlas", "running");
-            let portrait_extractor = PortraitExtractor::new(&rom);
-            let _ =
-                portrait_extractor.extract_portrait_atlases(&output_dir_portraits, &cli.progress);
-
-            write_progress(
-                &cli.progress,
-                0,
-                EFFECT_SPRITE_NUM,
-                "move_effect_sprites",
-                "running",
-            );
-            let mut effect_pipeline = EffectAssetPipeline::new(&rom);
-            let _ = effect_pipeline.run(
-                &effects_map,
-                &moves_map,
-                &output_dir_pipeline,
-                &cli.progress,
-                EFFECT_SPRITE_NUM,
-            );
+        if let Err(e) = weather_manifest::build_and_save(&output_dir_pipeline, json_style) {
+            eprintln!("Failed to write weather manifest: {}", e);
+        }
+    } else {
+        skip_phase("dungeon_tileset");
+    }
+
+    if run_stage(Stage::StatusIcons) {
+        let output_dir_status_icons = output_dir_pipeline.join("STATUS_ICONS");
+        write_progress(&cli.progress, 0, 33, "status_icons", "running");
+        let mut status_icon_extractor =
+            StatusIconExtractor::new(&mut rom).with_json_style(json_style);
+        if let Err(e) = status_icon_extractor.extract(&output_dir_status_icons, &cli.progress) {
+            eprintln!("Failed to extract status icons: {}", e);
+        }
+    } else {
+        skip_phase("status_icons");
+    }
+
+    let report_path = output_dir_pipeline.join("report.json");
+    if let Err(e) = report.save(&report_path, sink.as_ref(), json_style) {
+        eprintln!("Failed to write report.json: {}", e);
+    }
+
+    if let Some(zip) = &zip_sink {
+        zip.finish().map_err(|e| {
+            io::Error::new(e.kind(), format!("Failed to finish zip archive: {}", e))
+        })?;
+    }
 
-            let output_dir_dungeons = output_dir_pipeline.join("DUNGEON").join("tilesets");
-            write_progress(&cli.progress, 0, 170, "dungeon_tileset", "running");
-            let dungeon_extractor = DungeonBinExtractor::new(&rom);
-            let _ = dungeon_extractor.extract_dungeon_tilesets(
-                None,
-                &output_dir_dungeons,
-                &cli.progress,
-                tileset_properties.as_deref(),
+    write_progress(&cli.progress, 0, 0, "", "complete");
+    Ok(report)
+}
+
+/// Renders a single effect to a looping GIF for `--preview-gif`
+fn preview_gif(cli: &Cli, effect_id: u16) {
+    let mut rom = match Rom::new(cli.rom_path.clone()) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Failed to read ROM file, possibly corrupted: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let anim_data = AnimationInfoExtractor::new(&mut rom).parse_and_transform_animation_data();
+    let Some(effect_info) = anim_data.effect_table.get(effect_id as usize) else {
+        eprintln!(
+            "Error: effect {} out of range ({} effects)",
+            effect_id,
+            anim_data.effect_table.len()
+        );
+        std::process::exit(1);
+    };
+
+    let mut effect_pipeline = EffectAssetPipeline::new(&rom)
+        .with_max_sequence_frames(cli.frame_limit)
+        .with_strict(cli.strict)
+        .with_fallback_palette(cli.fallback_palette());
+    match effect_pipeline.export_preview_gif(
+        effect_id,
+        effect_info,
+        &cli.output_dir,
+        cli.preview_fps,
+    ) {
+        Ok(path) => println!(
+            "Wrote preview of effect {} to {}",
+            effect_id,
+            path.display()
+        ),
+        Err(e) => {
+            eprintln!(
+                "Failed to export preview GIF for effect {}: {}",
+                effect_id, e
             );
+            std::process::exit(1);
+        }
+    }
+}
 
-            if let Err(e) = weather_manifest::build_and_save(&output_dir_pipeline) {
-                eprintln!("Failed to write weather manifest: {}", e);
-            }
+/// `--compare`: prints every Pokemon sprite that differs between the two ROMs
+fn compare_roms(rom_path: &Path, other_path: &Path) {
+    let open = |path: &Path| match Rom::new(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!(
+                "Failed to read ROM file {}, possibly corrupted: {}",
+                path.display(),
+                e
+            );
+            std::process::exit(1);
+        }
+    };
+    let (rom, other) = (open(rom_path), open(other_path));
 
-            let output_dir_status_icons = output_dir_pipeline.join("STATUS_ICONS");
-            write_progress(&cli.progress, 0, 33, "status_icons", "running");
-            let mut status_icon_extractor = StatusIconExtractor::new(&mut rom);
-            if let Err(e) = status_icon_extractor.extract(&output_dir_status_icons, &cli.progress) {
-                eprintln!("Failed to extract status icons: {}", e);
+    match PokemonSpriteExtractor::new(&rom).compare_sprites(&PokemonSpriteExtractor::new(&other)) {
+        Ok(changes) if changes.is_empty() => println!("No sprite differences"),
+        Ok(changes) => {
+            for change in &changes {
+                println!("{}", change);
             }
-
-            write_progress(&cli.progress, 0, 0, "", "complete");
+            println!("{} sprites differ", changes.len());
         }
+        Err(e) => {
+            eprintln!("Failed to compare sprites: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `--dry-run`: parses the ROM and runs each selected stage's discovery and filtering, then
+/// prints the item counts. Nothing is written, not even the progress file.
+fn dry_run(cli: &Cli, stages: &[Stage]) {
+    let mut rom = match Rom::new(cli.rom_path.clone()) {
+        Ok(rom) => rom,
         Err(e) => {
             eprintln!("Failed to read ROM file, possibly corrupted: {}", e);
+            std::process::exit(1);
         }
+    };
+    println!(
+        "Successfully parsed ROM '{}' ({}), no corruption detected",
+        rom.sanitized_title(),
+        rom.id_code
+    );
+
+    println!("\nDry run, nothing was written:");
+    for (category, value) in dry_run_counts(&mut rom, cli, stages) {
+        println!("  {:<24} {:>8}", category, value);
+    }
+}
+
+/// (category, count) rows for `--dry-run`, "skipped" for stages that weren't selected and
+/// "error: ..." where a stage's source files can't be read
+fn dry_run_counts(rom: &mut Rom, cli: &Cli, stages: &[Stage]) -> Vec<(&'static str, String)> {
+    let count = |result: std::io::Result<usize>| match result {
+        Ok(n) => n.to_string(),
+        Err(e) => format!("error: {}", e),
+    };
+    let skipped = || "skipped".to_string();
+    let mut rows = Vec::new();
+
+    rows.push((
+        "Pokemon sprites",
+        if stages.contains(&Stage::Pokemon) {
+            let extractor = PokemonSpriteExtractor::new(rom)
+                .with_unknown_forms(cli.include_unknown_forms)
+                .with_id_whitelist((!cli.monster_ids.is_empty()).then(|| cli.monster_ids.clone()));
+            count(extractor.count_entries(cli.num_pokemon))
+        } else {
+            skipped()
+        },
+    ));
+
+    let (pokedex, expressions) = if stages.contains(&Stage::Portraits) {
+        match PortraitExtractor::new(rom).count_portraits() {
+            Ok((pokedex, expressions)) => (pokedex.to_string(), expressions.to_string()),
+            Err(e) => (format!("error: {}", e), format!("error: {}", e)),
+        }
+    } else {
+        (skipped(), skipped())
+    };
+    rows.push(("Portraits (Pokedex)", pokedex));
+    rows.push(("Portraits (expressions)", expressions));
+
+    rows.push((
+        "Move effects",
+        if stages.contains(&Stage::Effects) {
+            let anim_data = AnimationInfoExtractor::new(rom).parse_and_transform_animation_data();
+            anim_data
+                .effect_table
+                .iter()
+                .filter(|effect| effect.anim_type.is_extracted())
+                .count()
+                .to_string()
+        } else {
+            skipped()
+        },
+    ));
+
+    rows.push((
+        "Dungeon tilesets",
+        if stages.contains(&Stage::Dungeon) {
+            count(DungeonBinExtractor::new(rom).count_tilesets())
+        } else {
+            skipped()
+        },
+    ));
+
+    rows.push((
+        "Status icons",
+        if stages.contains(&Stage::StatusIcons) {
+            count(StatusIconExtractor::new(rom).count_icons())
+        } else {
+            skipped()
+        },
+    ));
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        containers::binpack::BinPack,
+        graphics::wan::{
+            builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+            WanType,
+        },
+        rom_builder::{monster_md, RomBuilder},
+        test_support::{png_palette, scratch_dir},
+    };
+
+    /// A one-frame character sprite wrapped in SIR0, as monster.bin and m_attack.bin store them
+    fn fixture_sprite() -> Vec<u8> {
+        WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40), (40, 200, 40)])
+            .image(vec![0x12; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 10)]; 8])
+            .anim_header_unknowns([1, 2, 3, 4, 5])
+            .build_sir0()
+    }
+
+    /// Writes a ROM whose monster.md holds `pokemon` as (dex, sprite index) pairs, every
+    /// sprite index sharing the fixture sprite, and returns its path
+    fn write_fixture_rom_with(dir: &Path, pokemon: &[(u16, i16)]) -> PathBuf {
+        let sprite_count = pokemon
+            .iter()
+            .map(|&(_, sprite)| sprite + 1)
+            .max()
+            .unwrap_or(0);
+        write_rom_with_sprites(dir, pokemon, vec![fixture_sprite(); sprite_count as usize])
+    }
+
+    /// Like `write_fixture_rom_with`, but sprite index `i` holds `sprites[i]`
+    fn write_rom_with_sprites(
+        dir: &Path,
+        pokemon: &[(u16, i16)],
+        sprites: Vec<Vec<u8>>,
+    ) -> PathBuf {
+        let mut monster_bin = BinPack::default();
+        let mut m_attack_bin = BinPack::default();
+        for sprite in sprites {
+            monster_bin.append(sprite.clone());
+            m_attack_bin.append(sprite);
+        }
+
+        let rom_bytes = RomBuilder::new("C2SE")
+            .title("PMD FIXTURE")
+            .file("MONSTER/monster.bin", monster_bin.to_bytes(0))
+            .file("MONSTER/m_attack.bin", m_attack_bin.to_bytes(0))
+            .file("BALANCE/monster.md", monster_md(pokemon))
+            .build();
+        let rom_path = dir.join("fixture.nds");
+        fs::write(&rom_path, rom_bytes).unwrap();
+        rom_path
+    }
+
+    /// Writes a ROM with one Pokemon (#025, sprite 0) and returns its path
+    fn write_fixture_rom(dir: &Path) -> PathBuf {
+        write_fixture_rom_with(dir, &[(25, 0)])
+    }
+
+    fn fixture_cli(dir: &Path, rom_path: &Path, extra: &[&str]) -> Cli {
+        let output_dir = dir.join("output");
+        let progress = dir.join("progress.json");
+        let mut args = vec![
+            "pmd_scraper",
+            rom_path.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--progress",
+            progress.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        Cli::parse_from(args)
+    }
+
+    #[test]
+    fn repeated_stage_flags_select_only_those_stages() {
+        let dir = Path::new("unused");
+        let rom_path = dir.join("fixture.nds");
+
+        let cli = fixture_cli(
+            dir,
+            &rom_path,
+            &["--stage", "portraits", "--stage", "sprites"],
+        );
+        assert_eq!(cli.stages(), vec![Stage::Portraits, Stage::Pokemon]);
+        assert_eq!(
+            fixture_cli(dir, &rom_path, &[]).stages(),
+            Stage::ALL.to_vec()
+        );
+        assert_eq!("Status_Icons".parse(), Ok(Stage::StatusIcons));
+        assert!("tilesets".parse::<Stage>().is_err());
+    }
+
+    #[test]
+    fn effects_stage_parses_animation_data_without_writing_its_json() {
+        let dir = Path::new("unused");
+        let rom_path = dir.join("fixture.nds");
+        let cli = |extra: &[&str]| fixture_cli(dir, &rom_path, extra);
+
+        let effects = cli(&["--stage", "effects"]);
+        assert!(effects.parses_animation_data());
+        assert!(!effects.writes_animation_json());
+
+        let animation = cli(&["--stage", "animation"]);
+        assert!(animation.parses_animation_data());
+        assert!(animation.writes_animation_json());
+
+        let skipped = cli(&["--skip-animation-json"]);
+        assert!(skipped.parses_animation_data());
+        assert!(!skipped.writes_animation_json());
+
+        assert!(!cli(&["--stage", "pokemon"]).parses_animation_data());
+    }
+
+    #[test]
+    fn pokemon_stage_writes_atlas_for_synthetic_rom() {
+        let dir = scratch_dir("e2e_pokemon");
+        let rom_path = write_fixture_rom(&dir);
+
+        let report = run(fixture_cli(&dir, &rom_path, &["--stage", "pokemon"])).unwrap();
+
+        let pokemon_dir = dir.join("output").join("MONSTER").join("pokemon_025");
+        assert!(pokemon_dir.join("025_atlas.png").is_file());
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&fs::read(pokemon_dir.join("025_atlas.json")).unwrap()).unwrap();
+        assert!(metadata.is_object());
+        assert_eq!(
+            metadata["anim_header_unknowns"]["merged"],
+            serde_json::json!([1, 2, 3, 4, 5])
+        );
+        // Both stages default to raw ticks, written as integers
+        assert_eq!(metadata["duration_unit"], "frames");
+        let animation = metadata["animations"]
+            .as_object()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap();
+        let duration = &animation["directions"][0]["frames"][0]["duration"];
+        assert!(duration.is_i64(), "{}", duration);
+        assert_eq!(duration, 10);
+        assert_eq!(report.pokemon.unwrap().processed, 1);
+        assert!(report.failures.is_empty());
+        assert!(report.effects.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pokemon_stage_generates_several_atlases_in_parallel() {
+        let dir = scratch_dir("e2e_parallel");
+        let rom_path = write_fixture_rom_with(&dir, &[(25, 0), (26, 1), (27, 2), (28, 3)]);
+
+        let report = run(fixture_cli(&dir, &rom_path, &["--stage", "pokemon"])).unwrap();
+
+        assert_eq!(report.pokemon.unwrap().processed, 4);
+        for dex in 25..=28 {
+            let atlas = dir
+                .join("output")
+                .join("MONSTER")
+                .join(format!("pokemon_{:03}", dex))
+                .join(format!("{:03}_atlas.png", dex));
+            assert!(atlas.is_file(), "{} is missing", atlas.display());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn report_pokemon_count_matches_the_atlas_folders() {
+        let dir = scratch_dir("e2e_report_count");
+        // #027 has no sprite, so it's counted as skipped and gets no folder
+        let rom_path = write_fixture_rom_with(&dir, &[(25, 0), (26, 1), (27, -1)]);
+
+        let report = run(fixture_cli(&dir, &rom_path, &["--stage", "pokemon"])).unwrap();
+
+        let atlas_folders = fs::read_dir(dir.join("output").join("MONSTER"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().is_dir())
+            .count();
+        let pokemon = report.pokemon.unwrap();
+        assert_eq!(pokemon.processed, atlas_folders);
+        assert_eq!(pokemon.processed, 2);
+
+        let saved: serde_json::Value =
+            serde_json::from_slice(&fs::read(dir.join("output").join("report.json")).unwrap())
+                .unwrap();
+        assert_eq!(saved["pokemon"]["processed"], 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_run_counts_every_selected_stage() {
+        let dir = scratch_dir("dry_run_counts");
+        let rom_path = write_fixture_rom(&dir);
+        let args = [
+            "--dry-run",
+            "--stage",
+            "pokemon",
+            "--stage",
+            "dungeon",
+            "--stage",
+            "status-icons",
+        ];
+        let cli = fixture_cli(&dir, &rom_path, &args);
+        let mut rom = Rom::new(rom_path).unwrap();
+
+        let rows: HashMap<_, _> = dry_run_counts(&mut rom, &cli, &cli.stages())
+            .into_iter()
+            .collect();
+
+        assert_eq!(rows["Pokemon sprites"], "1");
+        assert_eq!(rows["Portraits (Pokedex)"], "skipped");
+        assert_eq!(rows["Move effects"], "skipped");
+        // The fixture ROM has neither file, which the dry run reports rather than ignores
+        assert!(rows["Dungeon tilesets"].starts_with("error:"));
+        assert!(rows["Status icons"].starts_with("error:"));
+        assert!(!dir.join("output").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deterministic_zip_output_is_identical_across_runs() {
+        let dir = scratch_dir("e2e_deterministic");
+        let rom_path = write_fixture_rom_with(&dir, &[(25, 0), (26, 1), (27, 2), (28, 3)]);
+
+        let archives: Vec<Vec<u8>> = (0..2)
+            .map(|run_index| {
+                let zip_path = dir.join(format!("run{}.zip", run_index));
+                let args = [
+                    "--stage",
+                    "pokemon",
+                    "--deterministic",
+                    "--zip-output",
+                    zip_path.to_str().unwrap(),
+                ];
+                let report = run(fixture_cli(&dir, &rom_path, &args)).unwrap();
+                assert_eq!(report.pokemon.unwrap().processed, 4);
+                fs::read(&zip_path).unwrap()
+            })
+            .collect();
+
+        assert!(!archives[0].is_empty());
+        assert_eq!(archives[0], archives[1]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_output_leaves_no_loose_sprite_folders() {
+        let dir = scratch_dir("e2e_zip_no_folders");
+        let rom_path = write_fixture_rom(&dir);
+        let zip_path = dir.join("out.zip");
+        let args = [
+            "--stage",
+            "pokemon",
+            "--zip-output",
+            zip_path.to_str().unwrap(),
+        ];
+
+        let report = run(fixture_cli(&dir, &rom_path, &args)).unwrap();
+        assert_eq!(report.pokemon.unwrap().processed, 1);
+        assert!(fs::metadata(&zip_path).unwrap().len() > 0);
+        let output_dir = dir.join("output");
+        assert!(!output_dir.join("MONSTER").exists());
+        assert!(!output_dir.join("PORTRAIT").exists());
+        assert!(!output_dir.join("EFFECT").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_output_errors_instead_of_leaving_a_broken_archive() {
+        let dir = scratch_dir("e2e_zip_errors");
+        let rom_path = write_fixture_rom(&dir);
+
+        // A ROM that doesn't load fails before the archive is created
+        let zip_path = dir.join("bad_rom.zip");
+        let missing_rom = dir.join("missing.nds");
+        let args = ["--zip-output", zip_path.to_str().unwrap()];
+        assert!(run(fixture_cli(&dir, &missing_rom, &args)).is_err());
+        assert!(!zip_path.exists());
+
+        // An archive path that can't be created is an error, not a panic
+        let blocker = dir.join("blocker");
+        fs::write(&blocker, b"not a folder").unwrap();
+        let zip_path = blocker.join("out.zip");
+        let args = [
+            "--stage",
+            "pokemon",
+            "--zip-output",
+            zip_path.to_str().unwrap(),
+        ];
+        assert!(run(fixture_cli(&dir, &rom_path, &args)).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn webp_atlas_decodes_to_the_png_atlas_pixels() {
+        let dir = scratch_dir("e2e_webp");
+        let rom_path = write_fixture_rom(&dir);
+        let pokemon_dir = dir.join("output").join("MONSTER").join("pokemon_025");
+
+        let mut decoded = Vec::new();
+        for format in ["png", "webp"] {
+            run(fixture_cli(
+                &dir,
+                &rom_path,
+                &["--stage", "pokemon", "--image-format", format],
+            ))
+            .unwrap();
+            let atlas = pokemon_dir.join(format!("025_atlas.{}", format));
+            decoded.push(image::open(&atlas).unwrap().to_rgba8());
+
+            let metadata: serde_json::Value =
+                serde_json::from_slice(&fs::read(pokemon_dir.join("025_atlas.json")).unwrap())
+                    .unwrap();
+            assert_eq!(metadata["atlas_image"], format!("025_atlas.{}", format));
+        }
+
+        assert_eq!(decoded[0].dimensions(), decoded[1].dimensions());
+        assert_eq!(decoded[0], decoded[1]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compare_reports_sprites_that_gained_a_frame() {
+        let dir_a = scratch_dir("compare_a");
+        let dir_b = scratch_dir("compare_b");
+        let rom_a = Rom::new(write_fixture_rom(&dir_a)).unwrap();
+        let same = Rom::new(write_fixture_rom(&dir_b)).unwrap();
+        assert!(PokemonSpriteExtractor::new(&rom_a)
+            .compare_sprites(&PokemonSpriteExtractor::new(&same))
+            .unwrap()
+            .is_empty());
+
+        let extra_frame = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40), (40, 200, 40)])
+            .image(vec![0x12; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, -4, -4)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 10)]; 8])
+            .anim_header_unknowns([1, 2, 3, 4, 5])
+            .build_sir0();
+        let rom_b = Rom::new(write_rom_with_sprites(
+            &dir_b,
+            &[(25, 0)],
+            vec![extra_frame],
+        ))
+        .unwrap();
+
+        let changes = PokemonSpriteExtractor::new(&rom_a)
+            .compare_sprites(&PokemonSpriteExtractor::new(&rom_b))
+            .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].starts_with("MONSTER/monster.bin #0: "));
+        assert!(changes[1].starts_with("MONSTER/m_attack.bin #0: "));
+        assert!(changes.iter().all(|c| c.contains("frame count: 1 -> 2")));
+
+        let _ = fs::remove_dir_all(&dir_a);
+        let _ = fs::remove_dir_all(&dir_b);
+    }
+
+    #[test]
+    fn indexed_atlas_plte_matches_the_metadata_palette() {
+        let dir = scratch_dir("e2e_palette");
+        // Colours first appear in an order oxipng's palette sort would change
+        let mut image = vec![0x12; 8];
+        image.extend([0x33; 24]);
+        let sprite = WanBuilder::new(WanType::Character)
+            .palette(vec![
+                (0, 0, 0),
+                (250, 250, 250),
+                (120, 0, 200),
+                (10, 10, 10),
+            ])
+            .image(image)
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .group(vec![vec![BuilderSeqFrame::new(0, 10)]; 8])
+            .build_sir0();
+        let rom_path = write_rom_with_sprites(&dir, &[(25, 0)], vec![sprite]);
+        let pokemon_dir = dir.join("output").join("MONSTER").join("pokemon_025");
+
+        for extra in [&[][..], &["--palette-preserve-order"][..]] {
+            let mut args = vec!["--stage", "pokemon", "--export-indices"];
+            args.extend_from_slice(extra);
+            run(fixture_cli(&dir, &rom_path, &args)).unwrap();
+
+            let metadata: serde_json::Value =
+                serde_json::from_slice(&fs::read(pokemon_dir.join("025_atlas.json")).unwrap())
+                    .unwrap();
+            let palette: Vec<[u8; 4]> =
+                serde_json::from_value(metadata["palette"].clone()).unwrap();
+            let plte = png_palette(&fs::read(pokemon_dir.join("025_atlas.png")).unwrap());
+            // oxipng may drop trailing unused entries, never reorder them
+            assert!(!plte.is_empty());
+            assert_eq!(plte[..], palette[..plte.len()], "{:?}", extra);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn trimmed_frames_share_a_cell_and_keep_their_opaque_bounds() {
+        let dir = scratch_dir("e2e_trim");
+        // The same image drawn at two positions, the frames differ only in their margins
+        let sprite = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40), (40, 200, 40)])
+            .image(vec![0x12; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, 2, -12)])
+            .group(vec![
+                vec![
+                    BuilderSeqFrame::new(0, 10),
+                    BuilderSeqFrame::new(1, 10)
+                ];
+                8
+            ])
+            .build_sir0();
+        let rom_path = write_rom_with_sprites(&dir, &[(25, 0)], vec![sprite]);
+        let metadata_path = dir
+            .join("output")
+            .join("MONSTER")
+            .join("pokemon_025")
+            .join("025_atlas.json");
+
+        let mut runs = Vec::new();
+        for extra in [&[][..], &["--trim-before-dedup"][..]] {
+            let mut args = vec!["--stage", "pokemon", "--frame-bounds"];
+            args.extend_from_slice(extra);
+            run(fixture_cli(&dir, &rom_path, &args)).unwrap();
+            let metadata: serde_json::Value =
+                serde_json::from_slice(&fs::read(&metadata_path).unwrap()).unwrap();
+            let animation = metadata["animations"]
+                .as_object()
+                .unwrap()
+                .values()
+                .next()
+                .unwrap();
+            runs.push(
+                animation["directions"][0]["frames"]
+                    .as_array()
+                    .unwrap()
+                    .clone(),
+            );
+        }
+
+        let (plain, trimmed) = (&runs[0], &runs[1]);
+        assert_ne!(plain[0]["idx"], plain[1]["idx"]);
+        assert_eq!(trimmed[0]["idx"], trimmed[1]["idx"]);
+        assert_ne!(trimmed[0]["trim_offset"], trimmed[1]["trim_offset"]);
+        for (plain, trimmed) in plain.iter().zip(trimmed) {
+            assert!(plain["opaque_bounds"].is_array());
+            assert_eq!(plain["opaque_bounds"], trimmed["opaque_bounds"]);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pokemon_without_frames_is_skipped_and_the_rest_extracted() {
+        let dir = scratch_dir("e2e_no_frames");
+        let frameless = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40)])
+            .group(vec![vec![]; 8])
+            .build_sir0();
+        let rom_path =
+            write_rom_with_sprites(&dir, &[(25, 0), (26, 1)], vec![fixture_sprite(), frameless]);
+
+        let report = run(fixture_cli(&dir, &rom_path, &["--stage", "pokemon"])).unwrap();
+
+        let pokemon = report.pokemon.unwrap();
+        assert_eq!(
+            (pokemon.processed, pokemon.skipped, pokemon.failed),
+            (1, 1, 0)
+        );
+        let monster_dir = dir.join("output").join("MONSTER");
+        assert!(monster_dir
+            .join("pokemon_025")
+            .join("025_atlas.png")
+            .is_file());
+        assert!(!monster_dir.join("pokemon_026").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn emit_empty_writes_a_placeholder_for_a_pokemon_without_frames() {
+        let dir = scratch_dir("e2e_emit_empty");
+        let frameless = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40)])
+            .group(vec![vec![]; 8])
+            .build_sir0();
+        let rom_path =
+            write_rom_with_sprites(&dir, &[(25, 0), (26, 1)], vec![fixture_sprite(), frameless]);
+
+        run(fixture_cli(
+            &dir,
+            &rom_path,
+            &["--stage", "pokemon", "--emit-empty"],
+        ))
+        .unwrap();
+
+        let pokemon_dir = dir.join("output").join("MONSTER").join("pokemon_026");
+        let atlas = image::open(pokemon_dir.join("026_atlas.png")).unwrap();
+        assert_eq!((atlas.width(), atlas.height()), (1, 1));
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&fs::read(pokemon_dir.join("026_atlas.json")).unwrap()).unwrap();
+        assert_eq!(metadata["empty"], true);
+        // The renderable Pokemon isn't marked
+        let metadata: serde_json::Value = serde_json::from_slice(
+            &fs::read(dir.join("output/MONSTER/pokemon_025/025_atlas.json")).unwrap(),
+        )
+        .unwrap();
+        assert_ne!(metadata["empty"], true);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sprite_scale_doubles_atlas_and_metadata_coordinates() {
+        let dir = scratch_dir("e2e_scale");
+        let sprite = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), (200, 40, 40), (40, 200, 40)])
+            .image(vec![0x12; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, 2, -12)])
+            .group(vec![
+                vec![
+                    BuilderSeqFrame::new(0, 10),
+                    BuilderSeqFrame::new(1, 10)
+                ];
+                8
+            ])
+            .build_sir0();
+        let rom_path = write_rom_with_sprites(&dir, &[(25, 0)], vec![sprite]);
+        let pokemon_dir = dir.join("output").join("MONSTER").join("pokemon_025");
+
+        let mut runs = Vec::new();
+        for scale in ["1", "2"] {
+            run(fixture_cli(
+                &dir,
+                &rom_path,
+                &["--stage", "pokemon", "--sprite-scale", scale],
+            ))
+            .unwrap();
+            let atlas = image::open(pokemon_dir.join("025_atlas.png")).unwrap();
+            let metadata: serde_json::Value =
+                serde_json::from_slice(&fs::read(pokemon_dir.join("025_atlas.json")).unwrap())
+                    .unwrap();
+            runs.push(((atlas.width(), atlas.height()), metadata));
+        }
+
+        let ((width, height), native) = &runs[0];
+        let (scaled_dimensions, scaled) = &runs[1];
+        assert_eq!(*scaled_dimensions, (width * 2, height * 2));
+        let doubled = |value: &serde_json::Value| match value {
+            serde_json::Value::Number(n) => serde_json::json!(n.as_i64().unwrap() * 2),
+            serde_json::Value::Array(items) => serde_json::json!(items
+                .iter()
+                .map(|v| v.as_i64().unwrap() * 2)
+                .collect::<Vec<_>>()),
+            other => other.clone(),
+        };
+        for key in ["frame_width", "frame_height", "anchor_x", "anchor_y"] {
+            assert_eq!(scaled[key], doubled(&native[key]), "{}", key);
+        }
+
+        let mut checked_sheet_x = false;
+        for (name, animation) in native["animations"].as_object().unwrap() {
+            let native_frames = animation["directions"][0]["frames"].as_array().unwrap();
+            let scaled_frames = scaled["animations"][name]["directions"][0]["frames"]
+                .as_array()
+                .unwrap();
+            for (native_frame, scaled_frame) in native_frames.iter().zip(scaled_frames) {
+                for key in ["sheet_x", "sheet_y", "offset_x", "offset_y", "head_pos"] {
+                    assert_eq!(scaled_frame[key], doubled(&native_frame[key]), "{}", key);
+                }
+                checked_sheet_x |= native_frame["sheet_x"].as_i64() > Some(0);
+            }
+        }
+        assert!(checked_sheet_x, "no frame outside the first atlas cell");
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
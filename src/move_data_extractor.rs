@@ -39,6 +39,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     binary_utils::{read_u16_le, read_u32_le, read_u8},
     containers::sir0::Sir0,
+    output::JsonStyle,
     rom::Rom,
 };
 
@@ -191,11 +192,21 @@ pub struct MoveData {
 /// Main extractor struct
 pub struct MoveDataExtractor<'a> {
     rom: &'a Rom,
+    json_style: JsonStyle,
 }
 
 impl<'a> MoveDataExtractor<'a> {
     pub fn new(rom: &'a Rom) -> Self {
-        MoveDataExtractor { rom }
+        MoveDataExtractor {
+            rom,
+            json_style: JsonStyle::default(),
+        }
+    }
+
+    /// Formatting for `move_lookup.json` and `move_data.json`
+    pub fn with_json_style(mut self, json_style: JsonStyle) -> Self {
+        self.json_style = json_style;
+        self
     }
 
     /// Extract move data and save to JSON files
@@ -230,13 +241,13 @@ impl<'a> MoveDataExtractor<'a> {
             "MESSAGE/text_j.bin",
         ];
 
-        let text_data = possible_paths
+        let text_cursor = possible_paths
             .iter()
             .find_map(|&path| {
                 self.rom
                     .fnt
                     .get_file_id(path)
-                    .and_then(|id| self.rom.fat.get_file_data(id as usize, &self.rom.data))
+                    .and_then(|id| self.rom.fat.get_file_cursor(id as usize, &self.rom.data))
             })
             .ok_or_else(|| {
                 io::Error::new(
@@ -245,9 +256,9 @@ impl<'a> MoveDataExtractor<'a> {
                 )
             })?;
 
-        println!("  Found text file: {} bytes", text_data.len());
+        println!("  Found text file: {} bytes", text_cursor.get_ref().len());
 
-        let strings = self.parse_string_table(text_data)?;
+        let strings = self.parse_string_table(text_cursor)?;
         println!("  Parsed {} total strings from text file", strings.len());
 
         let move_names = self.extract_move_names_from_strings(&strings)?;
@@ -256,8 +267,8 @@ impl<'a> MoveDataExtractor<'a> {
     }
 
     /// Parse the text_*.str string table format
-    fn parse_string_table(&self, data: &[u8]) -> io::Result<Vec<String>> {
-        let mut cursor = Cursor::new(data);
+    fn parse_string_table(&self, mut cursor: Cursor<&[u8]>) -> io::Result<Vec<String>> {
+        let data = *cursor.get_ref();
         let mut pointers = Vec::new();
 
         loop {
@@ -477,7 +488,8 @@ impl<'a> MoveDataExtractor<'a> {
         let output_path = output_dir.join("move_lookup.json");
         let file = File::create(&output_path)?;
 
-        serde_json::to_writer_pretty(file, &lookup)
+        self.json_style
+            .write(file, &lookup)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
         println!("  Saved move lookup to {}", output_path.display());
@@ -491,7 +503,8 @@ impl<'a> MoveDataExtractor<'a> {
 
         let move_map: HashMap<u16, &MoveData> = moves.iter().map(|m| (m.move_id, m)).collect();
 
-        serde_json::to_writer_pretty(file, &move_map)
+        self.json_style
+            .write(file, &move_map)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
         println!("  Saved move data to {}", output_path.display());
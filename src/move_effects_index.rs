@@ -1,32 +1,155 @@
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::{collections::HashMap, fmt::Write, fs, io, path::Path};
+
+use crate::{
+    data::animation_info::{AnimPointType, SFX_SILENCE},
+    graphics::wan::{serialize_duration, DurationUnit},
+};
 
 /// Contains all effect definitions and move-to-effect mappings
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct MoveEffectsIndex {
+    /// Unit of every frame duration in `effects`
+    pub duration_unit: DurationUnit,
     pub effects: HashMap<String, EffectDefinition>,
     pub moves: HashMap<String, MoveData>,
 }
 
 impl MoveEffectsIndex {
-    pub fn new() -> Self {
+    pub fn new(duration_unit: DurationUnit) -> Self {
         MoveEffectsIndex {
+            duration_unit,
             effects: HashMap::new(),
             moves: HashMap::new(),
         }
     }
+
+    /// Reads an `asset_index.json` written by an earlier run
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = fs::read(path)?;
+        serde_json::from_slice(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Folds the effects and moves of another (partial) index into this one.
+    ///
+    /// IDs only present in `other` are added. When both have an ID, a non-empty entry replaces
+    /// an empty one (a sprite effect without animations, a screen effect without frames, a move
+    /// without effects). If both are non-empty and differ, this index's entry is kept with a
+    /// warning. Differing duration units are also only warned about, durations aren't converted.
+    pub fn merge(&mut self, other: MoveEffectsIndex) {
+        if other.duration_unit != self.duration_unit {
+            println!(
+                "  - Warning: Merging an index in {:?} into one in {:?}, durations are not converted",
+                other.duration_unit, self.duration_unit
+            );
+        }
+
+        for (id, effect) in other.effects {
+            match self.effects.get(&id) {
+                None => {
+                    self.effects.insert(id, effect);
+                }
+                Some(existing) if existing.is_empty() => {
+                    self.effects.insert(id, effect);
+                }
+                Some(existing) => {
+                    if !effect.is_empty() && !same_json(existing, &effect) {
+                        println!(
+                            "  - Warning: Effect {} differs between merged indices, keeping the first",
+                            id
+                        );
+                    }
+                }
+            }
+        }
+
+        for (id, move_data) in other.moves {
+            match self.moves.get(&id) {
+                None => {
+                    self.moves.insert(id, move_data);
+                }
+                Some(existing) if existing.effects.is_empty() => {
+                    self.moves.insert(id, move_data);
+                }
+                Some(existing) => {
+                    if !move_data.effects.is_empty() && !same_json(existing, &move_data) {
+                        println!(
+                            "  - Warning: Move {} differs between merged indices, keeping the first",
+                            id
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Renders the move -> effect references as a Graphviz DOT digraph.
+    ///
+    /// Moves are boxes, effects are ellipses (sprite) or diamonds (screen), and each edge is
+    /// labelled with the layer the effect plays on. Nodes are sorted by ID so output is stable.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph move_effects {\n    rankdir=LR;\n");
+
+        let mut effect_ids: Vec<&String> = self.effects.keys().collect();
+        effect_ids.sort_by_key(|id| id.parse::<u32>().unwrap_or(u32::MAX));
+        for id in effect_ids {
+            let shape = match self.effects[id] {
+                EffectDefinition::Sprite(_) => "ellipse",
+                EffectDefinition::Screen(_) => "diamond",
+            };
+            let _ = writeln!(
+                dot,
+                "    effect_{} [label=\"effect {}\", shape={}];",
+                id, id, shape
+            );
+        }
+
+        let mut move_ids: Vec<&String> = self.moves.keys().collect();
+        move_ids.sort_by_key(|id| id.parse::<u32>().unwrap_or(u32::MAX));
+        for id in move_ids {
+            let _ = writeln!(dot, "    move_{} [label=\"move {}\", shape=box];", id, id);
+            for trigger in &self.moves[id].effects {
+                let _ = writeln!(
+                    dot,
+                    "    move_{} -> effect_{} [label=\"{:?}\"];",
+                    id, trigger.id, trigger.layer
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
 }
 
 /// An enum representing the different types of effect definitions
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum EffectDefinition {
     Sprite(SpriteEffect),
     Screen(ScreenEffect),
 }
 
+impl EffectDefinition {
+    /// True if the definition has nothing to play
+    pub fn is_empty(&self) -> bool {
+        match self {
+            EffectDefinition::Sprite(sprite) => sprite.animations.is_empty(),
+            EffectDefinition::Screen(screen) => screen.frame_count == 0,
+        }
+    }
+}
+
+/// Compares two index entries by their serialised form, the types don't implement `PartialEq`
+fn same_json<T: Serialize>(a: &T, b: &T) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
 /// Defines a visual effect that is rendered from a sprite sheet
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct SpriteEffect {
     /// Path to sprite sheet. For directional effects, this is the base path
     /// and actual sheets are at `{base}_dir{0-7}.png`
@@ -48,37 +171,74 @@ pub struct SpriteEffect {
     pub base_animation_index: u32,
     /// If true, game continues without waiting for animation to complete
     pub is_non_blocking: bool,
+    /// Top-left of each frame relative to the effect origin. Only set for sheets exported
+    /// with native bounds, centred sheets are positioned about their middle instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin_offset: Option<[i32; 2]>,
+    /// Top-left of the kept area within the untrimmed frame, only set when the sheet's shared
+    /// transparent border was trimmed. Frame width/height are the trimmed size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_offset: Option<[u32; 2]>,
+    /// Raw Unk#6 to Unk#10 of the WAN's animation info header, kept for format research
+    #[serde(default)]
+    pub anim_header_unknowns: [u16; 5],
+    pub audio: AudioCue,
 }
 
 /// Defines a sequence of animation frames
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct AnimationSequence {
     #[serde(rename = "loop")]
     pub looping: bool,
+    /// Index of the first frame with the hit flag set, where damage should land
+    pub hit_frame: Option<usize>,
     #[serde(flatten)]
     pub details: AnimationDetails,
 }
 
 /// Contains the frame-by-frame timing and offset data for an animation
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum AnimationDetails {
     Simple {
         #[serde(rename = "frame_count")]
         frame_count: usize,
+        #[serde(serialize_with = "serialize_duration")]
         duration: f32,
     },
     Complex {
-        // Vec of [duration_seconds, offsetX, offsetY]
+        // Vec of [duration, offsetX, offsetY]
+        #[serde(serialize_with = "serialize_frame_details")]
         frames: Vec<[f32; 3]>,
     },
 }
 
+/// `[duration, offsetX, offsetY]` rows with whole durations written as integers, like
+/// `serialize_duration`
+fn serialize_frame_details<S: Serializer>(
+    frames: &[[f32; 3]],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    struct Duration(f32);
+    impl Serialize for Duration {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_duration(&self.0, serializer)
+        }
+    }
+
+    serializer.collect_seq(
+        frames
+            .iter()
+            .map(|&[duration, x, y]| (Duration(duration), x, y)),
+    )
+}
+
 /// Defines a screen-wide visual effect (anim_type 5).
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ScreenEffect {
-    #[serde(rename = "effect_name")]
-    pub effect_name: String,
+    /// Raw `screen_effect_param` from the effect table. Which full-screen effect each value
+    /// selects hasn't been traced in the engine yet, so it is passed through undecoded.
+    pub screen_effect_param: u8,
     /// Horizontal sheet of `frame_count` frames, each `frame_width` x `frame_height`.
     pub sprite_sheet: String,
     pub frame_width: u32,
@@ -88,13 +248,15 @@ pub struct ScreenEffect {
     pub looping: bool,
     /// If true, the game continues without waiting for the effect to finish.
     pub is_non_blocking: bool,
+    pub audio: AudioCue,
     pub frames: Vec<ScreenFrameInfo>,
 }
 
 /// Per-frame timing/blend data for a screen effect.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ScreenFrameInfo {
-    /// Frame duration in seconds (same conversion as `SpriteEffect`).
+    /// Frame duration in the index's `duration_unit` (same conversion as `SpriteEffect`).
+    #[serde(serialize_with = "serialize_duration")]
     pub duration: f32,
     /// Raw per-frame blend value. Client uses `(alpha >> 8) / 255.0`. Not baked.
     pub alpha: u16,
@@ -103,13 +265,48 @@ pub struct ScreenFrameInfo {
 }
 
 /// Defines the effects associated with a particular move
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct MoveData {
     pub effects: Vec<MoveEffectTrigger>,
+    /// User body part the effects attach to, resolved per frame from the Pokemon atlas
+    /// offsets (head/lhand/rhand/centre). Omitted when they play at the entity origin.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment_point: Option<AnimPointType>,
+    pub audio: AudioCue,
+}
+
+/// Sound to schedule alongside an effect or move animation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AudioCue {
+    /// SFX ID to play, `None` when the ROM marks the cue as silent
+    pub sfx_id: Option<u32>,
+    /// Raw `timing_offset` from the effect table. Moves have no offset of their own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing_offset: Option<u32>,
+}
+
+impl AudioCue {
+    /// Cue from a move's `sound_effect_id`, where `0x3F00` means silence
+    pub fn from_move(sound_effect_id: u16) -> Self {
+        AudioCue {
+            sfx_id: (sound_effect_id != SFX_SILENCE).then_some(sound_effect_id as u32),
+            timing_offset: None,
+        }
+    }
+
+    /// Cue from an effect's `sfx_id`, negative IDs and `0x3F00` both mean silence
+    pub fn from_effect(sfx_id: i32, timing_offset: u32) -> Self {
+        AudioCue {
+            sfx_id: u32::try_from(sfx_id)
+                .ok()
+                .filter(|&id| id != SFX_SILENCE as u32),
+            timing_offset: Some(timing_offset),
+        }
+    }
 }
 
 /// Layer purpose based on ROM reverse engineering findings
-#[derive(Serialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum EffectLayer {
     /// Layer 0 (offset 0x00): Charge-up, preparation effects
     Charge = 0,
@@ -122,9 +319,163 @@ pub enum EffectLayer {
 }
 
 /// Describes an effect that is triggered by a move
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct MoveEffectTrigger {
     pub id: String,
     pub layer: EffectLayer,
     pub trigger: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite_effect(animation_count: usize) -> EffectDefinition {
+        let animations = (0..animation_count)
+            .map(|i| {
+                let sequence = AnimationSequence {
+                    looping: false,
+                    hit_frame: Some(1),
+                    details: AnimationDetails::Simple {
+                        frame_count: 3,
+                        duration: 0.05,
+                    },
+                };
+                (i.to_string(), sequence)
+            })
+            .collect();
+        EffectDefinition::Sprite(SpriteEffect {
+            sprite_sheet: "EFFECT/1.png".to_string(),
+            frame_width: 32,
+            frame_height: 32,
+            animations,
+            is_directional: false,
+            direction_count: 1,
+            base_animation_index: 0,
+            is_non_blocking: false,
+            origin_offset: None,
+            trim_offset: None,
+            anim_header_unknowns: [0; 5],
+            audio: AudioCue::from_effect(12, 3),
+        })
+    }
+
+    fn move_data(effect_ids: &[&str]) -> MoveData {
+        MoveData {
+            effects: effect_ids
+                .iter()
+                .map(|id| MoveEffectTrigger {
+                    id: id.to_string(),
+                    layer: EffectLayer::Primary,
+                    trigger: "on_hit".to_string(),
+                })
+                .collect(),
+            attachment_point: None,
+            audio: AudioCue::from_move(SFX_SILENCE),
+        }
+    }
+
+    #[test]
+    fn silent_sound_effect_exports_as_null() {
+        let audio = |cue: AudioCue| serde_json::to_value(cue).unwrap();
+
+        assert_eq!(
+            audio(AudioCue::from_move(SFX_SILENCE)),
+            serde_json::json!({ "sfx_id": null })
+        );
+        assert_eq!(
+            audio(AudioCue::from_move(0x1F)),
+            serde_json::json!({ "sfx_id": 0x1F })
+        );
+        assert_eq!(
+            audio(AudioCue::from_effect(SFX_SILENCE as i32, 4)),
+            serde_json::json!({ "sfx_id": null, "timing_offset": 4 })
+        );
+        assert_eq!(AudioCue::from_effect(-1, 0).sfx_id, None);
+        assert_eq!(AudioCue::from_effect(12, 3).sfx_id, Some(12));
+    }
+
+    #[test]
+    fn merge_unions_partial_indices() {
+        let mut first = MoveEffectsIndex::new(DurationUnit::Seconds);
+        first.effects.insert("1".to_string(), sprite_effect(0));
+        first.effects.insert("2".to_string(), sprite_effect(1));
+        first.moves.insert("10".to_string(), move_data(&[]));
+
+        let mut second = MoveEffectsIndex::new(DurationUnit::Seconds);
+        second.effects.insert("1".to_string(), sprite_effect(2));
+        second.effects.insert("3".to_string(), sprite_effect(1));
+        second.moves.insert("10".to_string(), move_data(&["1"]));
+        second.moves.insert("11".to_string(), move_data(&["3"]));
+
+        first.merge(second);
+
+        assert_eq!(first.effects.len(), 3);
+        assert_eq!(first.moves.len(), 2);
+        // Empty entries give way to the non-empty ones from the other index
+        assert!(!first.effects["1"].is_empty());
+        assert_eq!(first.moves["10"].effects.len(), 1);
+    }
+
+    #[test]
+    fn saved_index_loads_back() {
+        let mut index = MoveEffectsIndex::new(DurationUnit::Frames);
+        index.effects.insert("2".to_string(), sprite_effect(1));
+        index.moves.insert("10".to_string(), move_data(&["2"]));
+
+        let path = std::env::temp_dir().join(format!(
+            "pmd_scraper_asset_index_{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, serde_json::to_vec(&index).unwrap()).unwrap();
+        let loaded = MoveEffectsIndex::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.duration_unit, DurationUnit::Frames);
+        assert!(same_json(&loaded.effects["2"], &index.effects["2"]));
+        assert!(same_json(&loaded.moves["10"], &index.moves["10"]));
+    }
+
+    #[test]
+    fn dot_export_has_an_edge_per_move_effect() {
+        let mut index = MoveEffectsIndex::new(DurationUnit::Frames);
+        index.effects.insert("2".to_string(), sprite_effect(1));
+        index.moves.insert("10".to_string(), move_data(&["2"]));
+
+        let dot = index.to_dot();
+
+        assert!(dot.starts_with("digraph move_effects {"));
+        assert!(dot.contains("    effect_2 [label=\"effect 2\", shape=ellipse];"));
+        assert!(dot.contains("    move_10 [label=\"move 10\", shape=box];"));
+        assert!(
+            dot.contains("    move_10 -> effect_2 [label=\"Primary\"];"),
+            "{}",
+            dot
+        );
+        assert!(dot.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn screen_effect_exports_its_raw_param() {
+        let screen = EffectDefinition::Screen(ScreenEffect {
+            screen_effect_param: 3,
+            sprite_sheet: "res://effect_sprites/7.png".to_string(),
+            frame_width: 256,
+            frame_height: 160,
+            frame_count: 1,
+            looping: false,
+            is_non_blocking: false,
+            audio: AudioCue::from_effect(-1, 0),
+            frames: vec![ScreenFrameInfo {
+                duration: 2.0,
+                alpha: 0x1000,
+                row_height: 20,
+            }],
+        });
+
+        let json = serde_json::to_value(&screen).unwrap();
+        assert_eq!(json["screen_effect_param"], 3);
+        // The effect ID is the key in `effects`, there's no generated name beside it
+        assert!(json.get("effect_name").is_none());
+    }
+}
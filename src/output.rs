@@ -0,0 +1,545 @@
+//! Output destinations for extracted assets
+//!
+//! Savers hand finished bytes to an `OutputSink` instead of touching the file system, so the
+//! same extraction can write loose files (`DirSink`) or a single archive (`ZipSink`).
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Cursor, Write},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use flate2::{
+    write::{DeflateEncoder, ZlibEncoder},
+    Compression,
+};
+use image::{codecs::webp::WebPEncoder, ColorType, GrayImage, ImageOutputFormat, RgbaImage};
+use serde::Serialize;
+
+/// Container for sprite atlases, effect sheets and portrait atlases. Index buffers and debug
+/// images are always PNG.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// oxipng optimised PNG
+    #[default]
+    Png,
+    /// Lossless WebP, usually smaller than PNG and preferred by web tools
+    WebP,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::WebP => "webp",
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "webp" => Ok(ImageFormat::WebP),
+            other => Err(format!(
+                "unknown image format '{}', expected png or webp",
+                other
+            )),
+        }
+    }
+}
+
+/// How the extractors format the JSON files they write
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonStyle {
+    /// No whitespace instead of pretty printing
+    pub compact: bool,
+    /// Sort the keys of every object, so `HashMap` backed maps come out in the same order on
+    /// every run
+    pub sorted_keys: bool,
+}
+
+impl JsonStyle {
+    /// `value` as a `serde_json::Value` when keys are sorted. Its objects are `BTreeMap`
+    /// backed, so serialising it emits keys in sorted order.
+    fn sorted_value<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> serde_json::Result<Option<serde_json::Value>> {
+        if self.sorted_keys {
+            serde_json::to_value(value).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn write_styled<W: Write, T: Serialize + ?Sized>(
+        self,
+        writer: W,
+        value: &T,
+    ) -> serde_json::Result<()> {
+        if self.compact {
+            serde_json::to_writer(writer, value)
+        } else {
+            serde_json::to_writer_pretty(writer, value)
+        }
+    }
+
+    /// Serialises `value` into `writer` in this style
+    pub fn write<W: Write, T: Serialize + ?Sized>(
+        self,
+        writer: W,
+        value: &T,
+    ) -> serde_json::Result<()> {
+        match self.sorted_value(value)? {
+            Some(sorted) => self.write_styled(writer, &sorted),
+            None => self.write_styled(writer, value),
+        }
+    }
+
+    /// Serialises `value` to bytes in this style
+    pub fn to_vec<T: Serialize + ?Sized>(self, value: &T) -> serde_json::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes, value)?;
+        Ok(bytes)
+    }
+
+    /// Serialises `value` to a string in this style
+    pub fn to_string<T: Serialize + ?Sized>(self, value: &T) -> serde_json::Result<String> {
+        let bytes = self.to_vec(value)?;
+        Ok(String::from_utf8(bytes).expect("serde_json only writes UTF-8"))
+    }
+}
+
+pub trait OutputSink: Send + Sync + fmt::Debug {
+    /// Writes `bytes` to `path`, creating parent directories as needed
+    fn write_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()>;
+}
+
+/// Writes loose files to disk at the given paths
+#[derive(Debug, Default)]
+pub struct DirSink;
+
+impl OutputSink for DirSink {
+    fn write_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, bytes)
+    }
+}
+
+/// Collects every written file into one deflate-compressed zip archive.
+///
+/// Entry names are the written paths relative to `root` (the output directory). Entries are
+/// compressed as they arrive but held in memory, and `finish` writes them sorted by name, so
+/// the archive doesn't depend on which worker thread wrote first. The archive is only valid
+/// once `finish` has run.
+pub struct ZipSink {
+    root: PathBuf,
+    archive_path: PathBuf,
+    state: Mutex<ZipState>,
+}
+
+struct ZipState {
+    writer: BufWriter<File>,
+    entries: BTreeMap<String, ZipEntry>,
+    finished: bool,
+}
+
+struct ZipEntry {
+    crc: u32,
+    size: u32,
+    compressed: Vec<u8>,
+}
+
+/// Without zip64 an archive is limited to 65535 entries and 4 GiB
+const ZIP_MAX_ENTRIES: usize = u16::MAX as usize;
+const ZIP_VERSION: u16 = 20;
+/// Bit 11: entry names are UTF-8
+const ZIP_FLAGS: u16 = 0x0800;
+const ZIP_METHOD_DEFLATE: u16 = 8;
+/// DOS date for 1980-01-01, a fixed timestamp keeps archives reproducible
+const ZIP_DOS_DATE: u16 = (1 << 5) | 1;
+
+impl ZipSink {
+    pub fn create(archive_path: &Path, root: &Path) -> io::Result<Self> {
+        if let Some(parent) = archive_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let writer = BufWriter::new(File::create(archive_path)?);
+        Ok(ZipSink {
+            root: root.to_path_buf(),
+            archive_path: archive_path.to_path_buf(),
+            state: Mutex::new(ZipState {
+                writer,
+                entries: BTreeMap::new(),
+                finished: false,
+            }),
+        })
+    }
+
+    /// Writes every entry and the central directory. Further writes fail afterwards.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut state = self.lock()?;
+        if state.finished {
+            return Ok(());
+        }
+
+        let ZipState {
+            writer, entries, ..
+        } = &mut *state;
+        let mut offset: u64 = 0;
+        let mut central = Vec::new();
+        for (name, entry) in entries.iter() {
+            let header_offset = to_u32(offset)?;
+            let compressed_size = to_u32(entry.compressed.len() as u64)?;
+
+            let mut header = Vec::with_capacity(30 + name.len());
+            header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            header.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+            header.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+            header.extend_from_slice(&ZIP_METHOD_DEFLATE.to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes()); // time
+            header.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+            header.extend_from_slice(&entry.crc.to_le_bytes());
+            header.extend_from_slice(&compressed_size.to_le_bytes());
+            header.extend_from_slice(&entry.size.to_le_bytes());
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes()); // extra length
+            header.extend_from_slice(name.as_bytes());
+            writer.write_all(&header)?;
+            writer.write_all(&entry.compressed)?;
+            offset += (header.len() + entry.compressed.len()) as u64;
+
+            central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            central.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // made by
+            central.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // needed
+            central.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+            central.extend_from_slice(&ZIP_METHOD_DEFLATE.to_le_bytes());
+            central.extend_from_slice(&0u16.to_le_bytes()); // time
+            central.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+            central.extend_from_slice(&entry.crc.to_le_bytes());
+            central.extend_from_slice(&compressed_size.to_le_bytes());
+            central.extend_from_slice(&entry.size.to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+            central.extend_from_slice(&header_offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+
+        let entry_count = entries.len() as u16;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]); // disk numbers
+        end.extend_from_slice(&entry_count.to_le_bytes());
+        end.extend_from_slice(&entry_count.to_le_bytes());
+        end.extend_from_slice(&to_u32(central.len() as u64)?.to_le_bytes());
+        end.extend_from_slice(&to_u32(offset)?.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+        writer.write_all(&central)?;
+        writer.write_all(&end)?;
+        writer.flush()?;
+        state.finished = true;
+
+        println!(
+            "Wrote {} files to {}",
+            state.entries.len(),
+            self.archive_path.display()
+        );
+        Ok(())
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, ZipState>> {
+        self.state
+            .lock()
+            .map_err(|_| io::Error::other("zip writer lock poisoned"))
+    }
+
+    /// Archive entry name: the path relative to `root`, `/` separated
+    fn entry_name(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        relative
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl fmt::Debug for ZipSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZipSink")
+            .field("root", &self.root)
+            .field("archive_path", &self.archive_path)
+            .finish()
+    }
+}
+
+impl OutputSink for ZipSink {
+    /// Rewriting a path replaces its earlier entry, like overwriting a loose file
+    fn write_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let name = self.entry_name(path);
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes)?;
+        let entry = ZipEntry {
+            crc: crc32fast::hash(bytes),
+            size: to_u32(bytes.len() as u64)?,
+            compressed: encoder.finish()?,
+        };
+
+        let mut state = self.lock()?;
+        if state.finished {
+            return Err(io::Error::other("zip archive already finished"));
+        }
+        if state.entries.len() >= ZIP_MAX_ENTRIES && !state.entries.contains_key(&name) {
+            return Err(io::Error::other(format!(
+                "zip archive is limited to {} entries",
+                ZIP_MAX_ENTRIES
+            )));
+        }
+        state.entries.insert(name, entry);
+        Ok(())
+    }
+}
+
+fn to_u32(value: u64) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::other("zip archive exceeds 4 GiB"))
+}
+
+/// Whether every file in `paths` exists on disk and was modified no earlier than `since`.
+/// Used by `--skip-existing`, it only makes sense for `DirSink` output.
+pub fn files_up_to_date(paths: &[PathBuf], since: SystemTime) -> bool {
+    paths.iter().all(|path| {
+        fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified >= since)
+    })
+}
+
+/// Encodes `image` as an unoptimised PNG
+pub fn encode_png(image: &RgbaImage) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(png)
+}
+
+/// Encodes a single channel image, such as a palette index buffer, as an 8-bit greyscale PNG
+pub fn encode_luma_png(image: &GrayImage) -> io::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(png)
+}
+
+/// Encodes an 8-bit palette PNG whose PLTE (and tRNS) are `palette` in the given order, with
+/// `indices` as the pixel data. The image crate can't write indexed PNGs, so the chunks are
+/// assembled here.
+pub fn encode_indexed_png(indices: &GrayImage, palette: &[[u8; 4]]) -> io::Result<Vec<u8>> {
+    if palette.is_empty() || palette.len() > 256 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("PNG palette needs 1-256 entries, got {}", palette.len()),
+        ));
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&indices.width().to_be_bytes());
+    ihdr.extend_from_slice(&indices.height().to_be_bytes());
+    // 8-bit depth, palette colour type, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    let plte: Vec<u8> = palette.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+    let trns: Vec<u8> = palette.iter().map(|c| c[3]).collect();
+
+    // Every scanline starts with filter type 0 (none), oxipng picks better filters later
+    let row_len = indices.width() as usize;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in indices.as_raw().chunks(row_len.max(1)) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    let idat = encoder.finish()?;
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [
+        (b"IHDR", ihdr.as_slice()),
+        (b"PLTE", plte.as_slice()),
+        (b"tRNS", trns.as_slice()),
+        (b"IDAT", idat.as_slice()),
+        (b"IEND", &[]),
+    ] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(kind);
+        crc.update(data);
+        png.extend_from_slice(&crc.finalize().to_be_bytes());
+    }
+    Ok(png)
+}
+
+/// Encodes `image` as lossless WebP
+pub fn encode_webp(image: &RgbaImage) -> io::Result<Vec<u8>> {
+    let mut webp = Vec::new();
+    WebPEncoder::new_lossless(&mut webp)
+        .encode(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            ColorType::Rgba8,
+        )
+        .map_err(io::Error::other)?;
+    Ok(webp)
+}
+
+/// Encodes `image` as `format`. PNGs go through oxipng with `options`, or are left
+/// unoptimised without them.
+pub fn encode_image(
+    image: &RgbaImage,
+    format: ImageFormat,
+    options: Option<&oxipng::Options>,
+) -> io::Result<Vec<u8>> {
+    match (format, options) {
+        (ImageFormat::WebP, _) => encode_webp(image),
+        (ImageFormat::Png, Some(options)) => encode_optimised_png(image, options),
+        (ImageFormat::Png, None) => encode_png(image),
+    }
+}
+
+/// Encodes `image` as PNG and runs it through oxipng with `options`.
+///
+/// An oxipng failure only prints a warning and returns the unoptimised PNG.
+pub fn encode_optimised_png(image: &RgbaImage, options: &oxipng::Options) -> io::Result<Vec<u8>> {
+    Ok(optimise_png(encode_png(image)?, options))
+}
+
+/// Runs an encoded PNG through oxipng, keeping `png` as is (with a warning) if that fails
+pub fn optimise_png(png: Vec<u8>, options: &oxipng::Options) -> Vec<u8> {
+    match oxipng::optimize_from_memory(&png, options) {
+        Ok(optimised) => optimised,
+        Err(e) => {
+            println!(
+                "  Warning: PNG optimisation failed: {}. Saving unoptimised.",
+                e
+            );
+            png
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn compact_json_is_smaller_and_parses_the_same() {
+        let value = serde_json::json!({
+            "frames": [{"x": 0, "y": 32, "duration": 4}, {"x": 32, "y": 32, "duration": 6}],
+            "name": "pokemon_025",
+        });
+        let pretty = JsonStyle::default().to_vec(&value).unwrap();
+        let compact = JsonStyle {
+            compact: true,
+            ..Default::default()
+        }
+        .to_vec(&value)
+        .unwrap();
+
+        assert!(compact.len() < pretty.len());
+        assert!(!compact.contains(&b'\n'));
+        let parsed: serde_json::Value = serde_json::from_slice(&compact).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::from_slice::<serde_json::Value>(&pretty).unwrap()
+        );
+        assert_eq!(parsed, value);
+    }
+
+    /// Reads every (name, contents) entry back through the central directory
+    fn read_zip(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |at: usize| u16::from_le_bytes([archive[at], archive[at + 1]]) as usize;
+        let u32_at = |at: usize| u32::from_le_bytes(archive[at..at + 4].try_into().unwrap());
+
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(end), 0x0605_4b50);
+        let mut central = u32_at(end + 16) as usize;
+        let mut entries = Vec::new();
+        for _ in 0..u16_at(end + 10) {
+            assert_eq!(u32_at(central), 0x0201_4b50);
+            let crc = u32_at(central + 16);
+            let name_len = u16_at(central + 28);
+            let name = &archive[central + 46..central + 46 + name_len];
+            let local = u32_at(central + 42) as usize;
+            central += 46 + name_len;
+
+            assert_eq!(u32_at(local), 0x0403_4b50);
+            let data_start = local + 30 + u16_at(local + 26) + u16_at(local + 28);
+            let compressed = &archive[data_start..data_start + u32_at(local + 18) as usize];
+            let mut contents = Vec::new();
+            flate2::read::DeflateDecoder::new(compressed)
+                .read_to_end(&mut contents)
+                .unwrap();
+            assert_eq!(crc32fast::hash(&contents), crc);
+            entries.push((String::from_utf8(name.to_vec()).unwrap(), contents));
+        }
+        entries
+    }
+
+    #[test]
+    fn zip_sink_entries_read_back_by_name() {
+        let dir = crate::test_support::scratch_dir("zip_sink");
+        let root = dir.join("output");
+        let archive_path = dir.join("out.zip");
+        let sink = ZipSink::create(&archive_path, &root).unwrap();
+
+        let atlas = root
+            .join("MONSTER")
+            .join("pokemon_025")
+            .join("025_atlas.png");
+        sink.write_file(&atlas, b"first").unwrap();
+        sink.write_file(&root.join("report.json"), &[b'{'; 300])
+            .unwrap();
+        sink.write_file(&atlas, b"second").unwrap();
+        sink.finish().unwrap();
+        assert!(sink.write_file(&root.join("late.json"), b"{}").is_err());
+
+        let entries = read_zip(&fs::read(&archive_path).unwrap());
+        assert_eq!(
+            entries,
+            [
+                (
+                    "MONSTER/pokemon_025/025_atlas.png".to_string(),
+                    b"second".to_vec()
+                ),
+                ("report.json".to_string(), vec![b'{'; 300]),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,27 +1,68 @@
 use std::{
-    fs::{self},
     io::{self},
     path::Path,
+    sync::Arc,
 };
 
 use crate::{
-    graphics::portrait::{create_portrait_atlas, AtlasType, KaoFile}, progress::write_progress, rom::Rom
+    graphics::portrait::{
+        count_portraits, create_portrait_atlas, AtlasType, KaoFile, PortraitLayout,
+    },
+    output::{DirSink, ImageFormat, JsonStyle, OutputSink},
+    progress::write_progress,
+    report::StageReport,
+    rom::Rom,
 };
 
 pub struct PortraitExtractor<'a> {
     rom: &'a Rom,
+    sink: Arc<dyn OutputSink>,
+    layout: PortraitLayout,
+    json_style: JsonStyle,
+    image_format: ImageFormat,
 }
 
 impl<'a> PortraitExtractor<'a> {
     pub fn new(rom: &'a Rom) -> Self {
-        PortraitExtractor { rom }
+        PortraitExtractor {
+            rom,
+            sink: Arc::new(DirSink),
+            layout: PortraitLayout::default(),
+            json_style: JsonStyle::default(),
+            image_format: ImageFormat::default(),
+        }
     }
 
-    /// Extract portrait atlases from the ROM
-    pub fn extract_portrait_atlases(&self, output_dir: &Path, progress_path: &Path) -> io::Result<()> {
-        // Create directories
-        fs::create_dir_all(output_dir)?;
+    /// Grid both portrait atlases are packed into, recorded in their JSON
+    pub fn with_layout(mut self, layout: PortraitLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sends the portrait atlases and their JSON to `sink` instead of loose files
+    pub fn with_sink(mut self, sink: Arc<dyn OutputSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Formatting for the portrait atlas JSON
+    pub fn with_json_style(mut self, json_style: JsonStyle) -> Self {
+        self.json_style = json_style;
+        self
+    }
 
+    /// Container for the portrait atlas images
+    pub fn with_image_format(mut self, image_format: ImageFormat) -> Self {
+        self.image_format = image_format;
+        self
+    }
+
+    /// Extract portrait atlases from the ROM
+    pub fn extract_portrait_atlases(
+        &self,
+        output_dir: &Path,
+        progress_path: &Path,
+    ) -> io::Result<StageReport> {
         // Get the KAO file data
         let kao_data = self.extract_kao_file()?;
 
@@ -31,13 +72,24 @@ impl<'a> PortraitExtractor<'a> {
             Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
         };
 
-        // Generate both atlas types
-        self.generate_atlas(&kao_file, AtlasType::Pokedex, output_dir)?;
+        // Generate both atlas types. One item per portrait, a failed atlas fails the whole
+        // stage.
+        let mut report = self.generate_atlas(&kao_file, AtlasType::Pokedex, output_dir)?;
         write_progress(progress_path, 1, 2, "portrait_atlas", "running");
-        self.generate_atlas(&kao_file, AtlasType::Expressions, output_dir)?;
+        report.merge(self.generate_atlas(&kao_file, AtlasType::Expressions, output_dir)?);
         write_progress(progress_path, 2, 2, "portrait_atlas", "running");
 
-        Ok(())
+        Ok(report)
+    }
+
+    /// Portraits the (Pokedex, expressions) atlases would hold, without building them
+    pub fn count_portraits(&self) -> io::Result<(usize, usize)> {
+        let kao_file = KaoFile::from_bytes(self.extract_kao_file()?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok((
+            count_portraits(&kao_file, &AtlasType::Pokedex),
+            count_portraits(&kao_file, &AtlasType::Expressions),
+        ))
     }
 
     // Helper methods
@@ -61,23 +113,37 @@ impl<'a> PortraitExtractor<'a> {
         kao_file: &KaoFile,
         atlas_type: AtlasType,
         output_dir: &Path,
-    ) -> io::Result<()> {
+    ) -> io::Result<StageReport> {
         let type_name = match atlas_type {
             AtlasType::Pokedex => "pokedex",
             AtlasType::Expressions => "expressions",
         };
 
-        let atlas_path = output_dir.join(format!("{}_atlas.png", type_name));
+        let atlas_path = output_dir.join(format!(
+            "{}_atlas.{}",
+            type_name,
+            self.image_format.extension()
+        ));
 
         println!("Generating {} atlas...", type_name);
-        match create_portrait_atlas(kao_file, &atlas_type, &atlas_path) {
-            Ok(_) => {
+        match create_portrait_atlas(
+            kao_file,
+            &atlas_type,
+            self.layout,
+            &atlas_path,
+            self.sink.as_ref(),
+            self.json_style,
+            self.image_format,
+        ) {
+            Ok((_, report)) => {
                 println!(
-                    "Successfully created {} atlas at: {}",
+                    "Successfully created {} atlas at: {} ({} portraits, {} failed)",
                     type_name,
-                    atlas_path.display()
+                    atlas_path.display(),
+                    report.processed,
+                    report.failed
                 );
-                Ok(())
+                Ok(report)
             }
             Err(e) => Err(io::Error::new(
                 io::ErrorKind::Other,
@@ -1,25 +1,31 @@
 use std::{
     collections::HashMap,
-    fs::{self},
     io::{self, Cursor, Seek, SeekFrom},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::Mutex,
 };
 
+use rayon::prelude::*;
+
 use crate::{
     binary_utils::read_u16_le,
     containers::{
+        self,
         binpack::BinPack,
-        compression::pkdpx::PkdpxContainer,
+        compression::{at4px::At4pxContainer, pkdpx::PkdpxContainer},
         sir0::{self},
-        ContainerHandler,
+        ContainerHandler, ContainerKind,
     },
-    data::{monster_md::MonsterData, MonsterEntry},
+    data::{form_names, monster_md::MonsterData, MonsterEntry},
     graphics::{
-        atlas::{create_pokemon_atlas, AtlasConfig},
-        wan::{parser, Animation, AnimationStructure, FrameOffset, WanFile},
+        atlas::{create_placeholder_atlas, create_pokemon_atlas, AtlasConfig},
+        palette,
+        wan::{diff, parser, Animation, AnimationStructure, FrameOffset, PaletteList, WanFile},
         WanType,
     },
+    output::{self, ImageFormat},
     progress::write_progress,
+    report::{ItemOutcome, StageReport},
     rom::Rom,
 };
 
@@ -35,11 +41,73 @@ struct PokemonProcessingContext<'a> {
 /// Handles extracting Pokemon sprite data from the ROM
 pub struct PokemonSpriteExtractor<'a> {
     rom: &'a Rom,
+    atlas_config: AtlasConfig,
+    include_unknown_forms: bool,
+    emit_empty: bool,
+    export_palettes: bool,
+    skip_existing: bool,
+    strict: bool,
+    id_whitelist: Option<Vec<usize>>,
 }
 
 impl<'a> PokemonSpriteExtractor<'a> {
     pub fn new(rom: &'a Rom) -> Self {
-        PokemonSpriteExtractor { rom }
+        PokemonSpriteExtractor {
+            rom,
+            atlas_config: AtlasConfig::default(),
+            include_unknown_forms: false,
+            emit_empty: false,
+            export_palettes: false,
+            skip_existing: false,
+            strict: false,
+            id_whitelist: None,
+        }
+    }
+
+    /// Only processes these monster.md indices, out of the entries that would otherwise be
+    /// extracted. `None` processes everything.
+    pub fn with_id_whitelist(mut self, id_whitelist: Option<Vec<usize>>) -> Self {
+        self.id_whitelist = id_whitelist;
+        self
+    }
+
+    /// Fails a Pokemon on the first recoverable WAN parse problem instead of using fallbacks
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Writes a placeholder atlas marked `empty` for entries that fail to render instead of
+    /// leaving a gap in the output
+    pub fn with_emit_empty(mut self, emit_empty: bool) -> Self {
+        self.emit_empty = emit_empty;
+        self
+    }
+
+    /// Also writes each sprite's palette rows as `{dex}_palette.gpl` and `{dex}_palette.act`
+    /// next to its atlas, for editing in GIMP or Photoshop.
+    pub fn with_palette_export(mut self, export_palettes: bool) -> Self {
+        self.export_palettes = export_palettes;
+        self
+    }
+
+    /// Leaves a Pokemon alone when its atlas image and JSON are already on disk and newer than
+    /// the ROM file. Only meaningful for loose file output.
+    pub fn with_skip_existing(mut self, skip_existing: bool) -> Self {
+        self.skip_existing = skip_existing;
+        self
+    }
+
+    /// Keeps forms missing from the form name table as `_form_N` instead of dropping them
+    pub fn with_unknown_forms(mut self, include_unknown_forms: bool) -> Self {
+        self.include_unknown_forms = include_unknown_forms;
+        self
+    }
+
+    /// Overrides the default atlas configuration used for every Pokemon
+    pub fn with_atlas_config(mut self, atlas_config: AtlasConfig) -> Self {
+        self.atlas_config = atlas_config;
+        self
     }
 
     pub fn extract_monster_data(
@@ -47,7 +115,7 @@ impl<'a> PokemonSpriteExtractor<'a> {
         pokemon_ids: Option<u32>,
         output_dir: &Path,
         progress_path: &Path,
-    ) -> io::Result<()> {
+    ) -> io::Result<StageReport> {
         // Load all necessary data files
         let monster_md_id = self
             .rom
@@ -92,10 +160,88 @@ impl<'a> PokemonSpriteExtractor<'a> {
         let monster_bin = BinPack::from_bytes(monster_bin_data)?;
         println!("Parsing m_attack.bin...");
         let m_attack_bin = BinPack::from_bytes(m_attack_bin_data)?;
-        fs::create_dir_all(output_dir)?;
+        let final_list = self.select_entries(&monster_md, pokemon_ids);
 
-        // Build the definitive list of entries to process
-        let final_list: Vec<(usize, String)>;
+        println!("Found {} useful entries to process.", final_list.len());
+        // An unreadable ROM mtime regenerates everything
+        let up_to_date_since = if self.skip_existing {
+            self.rom.modified().ok()
+        } else {
+            None
+        };
+        let context = PokemonProcessingContext {
+            monster_bin: &monster_bin,
+            m_attack_bin: &m_attack_bin,
+            atlas_config: &self.atlas_config,
+            output_dir,
+            all_entries: &monster_md,
+        };
+
+        // Entries are independent: the context only holds shared borrows and each Pokemon writes
+        // to its own folder, so they are processed in parallel. The stage counts are bumped
+        // and written under one lock so the progress file never goes backwards.
+        let report = Mutex::new(StageReport::default());
+        final_list
+            .par_iter()
+            .try_for_each(|(id, folder_name)| -> io::Result<()> {
+                let entry = &monster_md[*id];
+                let existing = up_to_date_since.is_some_and(|since| {
+                    output::files_up_to_date(
+                        &atlas_paths(
+                            output_dir,
+                            folder_name,
+                            entry.national_pokedex_number,
+                            self.atlas_config.image_format,
+                        ),
+                        since,
+                    )
+                });
+                let outcome = if existing {
+                    println!("Skipping {}: atlas is up to date", folder_name);
+                    ItemOutcome::Skipped
+                } else {
+                    self.process_pokemon(*id, entry, folder_name, &context)?
+                };
+                let mut report = report.lock().unwrap_or_else(|e| e.into_inner());
+                report.add(outcome);
+                write_progress(
+                    progress_path,
+                    report.total(),
+                    final_list.len(),
+                    "pokemon_sprite",
+                    if existing { "skipped" } else { "running" },
+                );
+                Ok(())
+            })?;
+        Ok(report.into_inner().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Number of monster.md entries `extract_monster_data` would turn into atlases, without
+    /// decoding any sprite
+    pub fn count_entries(&self, pokemon_ids: Option<u32>) -> io::Result<usize> {
+        let monster_md_id = self
+            .rom
+            .fnt
+            .get_file_id("BALANCE/monster.md")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "monster.md not found"))?;
+        let monster_md_data = self
+            .rom
+            .fat
+            .get_file_data(monster_md_id as usize, &self.rom.data)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Failed to extract monster.md")
+            })?;
+        let monster_md = parse_monster_md(monster_md_data)?;
+        Ok(self.select_entries(&monster_md, pokemon_ids).len())
+    }
+
+    /// Builds the definitive list of (monster.md index, folder name) entries to process
+    fn select_entries(
+        &self,
+        monster_md: &[MonsterEntry],
+        pokemon_ids: Option<u32>,
+    ) -> Vec<(usize, String)> {
+        let mut final_list: Vec<(usize, String)>;
 
         // make it num_pokemon
         if let Some(ids) = pokemon_ids {
@@ -117,6 +263,15 @@ impl<'a> PokemonSpriteExtractor<'a> {
                 std::collections::HashMap::new();
             const SUBSTITUTE_DOLL_MD_INDEX: usize = 537;
 
+            for (dex_num, forms, named) in
+                form_names::unnamed_forms(&form_names::form_counts(monster_md))
+            {
+                println!(
+                    "Note: #{:03} has {} forms in monster.md but only {} names, the rest export as form_N",
+                    dex_num, forms, named
+                );
+            }
+
             for i in 0..monster_md.len() {
                 let entry = &monster_md[i];
                 let dex_num = entry.national_pokedex_number;
@@ -127,11 +282,11 @@ impl<'a> PokemonSpriteExtractor<'a> {
                     let form_id = *form_counts.entry(dex_num).or_insert(0);
 
                     if form_id > 0 && i != SUBSTITUTE_DOLL_MD_INDEX {
-                        if let Some(form_name) = self.get_form_name(dex_num, form_id) {
+                        if let Some(form_name) = form_names::form_name(dex_num, form_id) {
                             folder_name.push_str(&format!("_{}", form_name));
                         } else {
                             folder_name.push_str(&format!("_form_{}", form_id));
-                            if dex_num > 0 {
+                            if dex_num > 0 && !self.include_unknown_forms {
                                 is_generic_form = true;
                             }
                         }
@@ -157,144 +312,102 @@ impl<'a> PokemonSpriteExtractor<'a> {
             final_list = list;
         }
 
-        println!("Found {} useful entries to process.", final_list.len());
-        let atlas_config = AtlasConfig::default();
-        let context = PokemonProcessingContext {
-            monster_bin: &monster_bin,
-            m_attack_bin: &m_attack_bin,
-            atlas_config: &atlas_config,
-            output_dir,
-            all_entries: &monster_md,
-        };
-
-        // Process the clean filtered list
-        for (i, (id, folder_name)) in final_list.iter().enumerate() {
-            let entry = &monster_md[*id];
-            self.process_pokemon(*id, entry, &folder_name, &context)?;
-            write_progress(
-                progress_path,
-                i + 1,
-                final_list.len(),
-                "pokemon_sprite",
-                "running",
-            );
+        if let Some(whitelist) = &self.id_whitelist {
+            final_list.retain(|(id, _)| whitelist.contains(id));
+            let missing: Vec<_> = whitelist
+                .iter()
+                .filter(|id| !final_list.iter().any(|(kept, _)| kept == *id))
+                .collect();
+            if !missing.is_empty() {
+                println!(
+                    "Warning: whitelisted monster.md indices {:?} have no sprite to extract",
+                    missing
+                );
+            }
         }
 
-        Ok(())
+        final_list
     }
 
-    /// Get a human-readable form name if applicable
-    fn get_form_name(&self, dex_num: u16, form_index: u16) -> Option<String> {
-        match dex_num {
-            201 => {
-                // Unown forms: A-Z, !, ?
-                match form_index {
-                    0 => Some("a".to_string()),
-                    1..=25 => Some(((b'a' + form_index as u8) as char).to_string()),
-                    26 => Some("exclamation".to_string()),
-                    27 => Some("question".to_string()),
-                    _ => None,
-                }
-            }
-            351 => {
-                // Castform forms
-                match form_index {
-                    0 => None, // Base form
-                    1 => Some("snowy".to_string()),
-                    2 => Some("sunny".to_string()),
-                    3 => Some("rainy".to_string()),
-                    _ => None,
-                }
-            }
+    /// Compares every monster.bin and m_attack.bin sprite with the same index in `other`'s ROM.
+    /// Returns one description per sprite that was added, removed, changed structurally or
+    /// can't be parsed on one side, empty when every sprite matches.
+    pub fn compare_sprites(&self, other: &PokemonSpriteExtractor) -> io::Result<Vec<String>> {
+        let mut changes = Vec::new();
+        for bin_path in ["MONSTER/monster.bin", "MONSTER/m_attack.bin"] {
+            let ours = self.read_bin_pack(bin_path)?;
+            let theirs = other.read_bin_pack(bin_path)?;
 
-            386 => {
-                // Deoxys forms
-                match form_index {
-                    0 => Some("normal".to_string()),
-                    1 => Some("attack".to_string()),
-                    2 => Some("defense".to_string()),
-                    3 => Some("speed".to_string()),
-                    _ => None,
-                }
-            }
-            412 | 413 => {
-                // Burmy/Wormadam forms
-                match form_index {
-                    0 => Some("sandy".to_string()),
-                    1 => Some("plant".to_string()),
-                    2 => Some("trash".to_string()),
-                    _ => None,
-                }
-            }
-            421 => {
-                // Cherrim forms
-                match form_index {
-                    0 => Some("overcast".to_string()),
-                    1 => Some("sunshine".to_string()),
-                    _ => None,
-                }
-            }
-            422 | 423 => {
-                // Shellos/Gastrodon forms
-                match form_index {
-                    0 => Some("west".to_string()),
-                    1 => Some("east".to_string()),
-                    _ => None,
-                }
-            }
-            479 => {
-                // Rotom forms do not exist in PMD: EoS
-                // Forms only added in Pokemon Platinum before Time/Darkness not added in Sky
-                match form_index {
-                    0 => None, // Base form
-                    _ => None,
-                }
-            }
-            483 => {
-                // Dialga forms
-                match form_index {
-                    0 => None, // Base form
-                    1 => Some("primal".to_string()),
-                    _ => None,
-                }
-            }
-            487 => {
-                // Giratina forms
-                match form_index {
-                    0 => Some("altered".to_string()),
-                    1 => Some("origin".to_string()),
-                    _ => None,
-                }
-            }
-            492 => {
-                // Shaymin forms
-                match form_index {
-                    0 => Some("land".to_string()),
-                    1 => Some("sky".to_string()),
-                    _ => None,
+            for index in 0..ours.len().max(theirs.len()) {
+                let change = match (ours.get(index), theirs.get(index)) {
+                    (Some(a), Some(b)) if a == b => None,
+                    (Some(_), None) => Some("removed".to_string()),
+                    (None, Some(_)) => Some("added".to_string()),
+                    _ => match (
+                        self.extract_wan_file(&ours, index),
+                        other.extract_wan_file(&theirs, index),
+                    ) {
+                        (Ok(a), Ok(b)) => {
+                            let wan_diff = diff::diff(&a, &b);
+                            (!wan_diff.is_empty()).then(|| wan_diff.to_string())
+                        }
+                        (Err(e), _) => Some(format!("unreadable in the first ROM: {}", e)),
+                        (_, Err(e)) => Some(format!("unreadable in the second ROM: {}", e)),
+                    },
+                };
+                if let Some(change) = change {
+                    changes.push(format!("{} #{}: {}", bin_path, index, change.trim_end()));
                 }
             }
-            _ => None,
         }
+        Ok(changes)
+    }
+
+    fn read_bin_pack(&self, bin_path: &str) -> io::Result<BinPack> {
+        let file_id = self.rom.fnt.get_file_id(bin_path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} not found", bin_path))
+        })?;
+        let data = self
+            .rom
+            .fat
+            .get_file_data(file_id as usize, &self.rom.data)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Failed to extract {}", bin_path),
+                )
+            })?;
+        BinPack::from_bytes(data)
     }
 
     /// Extract a WAN file from a bin file
     fn extract_wan_file(&self, bin_pack: &BinPack, sprite_index: usize) -> io::Result<WanFile> {
-        let sprite_data = &bin_pack[sprite_index];
+        let sprite_data = bin_pack.get(sprite_index).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Sprite index {} out of range for a {} file pack",
+                    sprite_index,
+                    bin_pack.len()
+                ),
+            )
+        })?;
 
         // Detect compression type and decompress
-        let decompressed_data = if sprite_data.starts_with(b"PKDPX") {
-            self.decompress_pkdpx_data(sprite_data)?
-        } else if sprite_data.starts_with(b"AT") {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "AT format not supported for WAN extraction".to_string(),
-            ));
-        } else {
-            sprite_data.to_vec()
+        let decompressed_data = match containers::detect(sprite_data) {
+            ContainerKind::Pkdpx => self.decompress_pkdpx_data(sprite_data)?,
+            ContainerKind::At4px => At4pxContainer::deserialise(sprite_data)?
+                .decompress()
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("AT4PX decompression failed: {}", e),
+                    )
+                })?,
+            _ => sprite_data.to_vec(),
         };
 
-        if decompressed_data.starts_with(b"SIR0") {
+        if containers::detect(&decompressed_data) == ContainerKind::Sir0 {
             self.parse_sir0_to_wan(&decompressed_data)
         } else {
             Err(io::Error::new(
@@ -380,6 +493,12 @@ impl<'a> PokemonSpriteExtractor<'a> {
         let wan_type = match img_type {
             1 => WanType::Character,
             2 | 3 => WanType::Effect,
+            _ if self.strict => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown WAN image type: {}", img_type),
+                ));
+            }
             _ => {
                 println!(
                     "  - Unknown WAN image type: {}, defaulting to Character",
@@ -393,6 +512,11 @@ impl<'a> PokemonSpriteExtractor<'a> {
             &sir0_data.content[..],
             sir0_data.data_pointer,
             wan_type,
+            &parser::ParseOptions {
+                strict: self.strict,
+                max_sequence_frames: self.atlas_config.max_sequence_frames,
+                ..Default::default()
+            },
         )
         .map_err(|e| {
             io::Error::new(
@@ -422,8 +546,47 @@ impl<'a> PokemonSpriteExtractor<'a> {
         let mut merged_img_data = monster_wan.img_data.clone();
         merged_img_data.extend(attack_wan.img_data.clone());
 
+        // The merged WAN renders everything against m_attack's palette. monster.bin rows that
+        // already exist there are reused, the rest are appended, so shared palettes only take
+        // one row in the atlas.
+        let mut merged_palette = attack_wan.custom_palette.clone();
+        let monster_palette_map = match merge_palette_rows(
+            &mut merged_palette,
+            &monster_wan.custom_palette,
+        ) {
+            Some(map) => {
+                let appended = merged_palette.len() - attack_wan.custom_palette.len();
+                if appended > 0 {
+                    println!(
+                        "  - Warning: monster.bin and m_attack.bin palettes differ, appended {} of {} monster rows",
+                        appended,
+                        monster_wan.custom_palette.len()
+                    );
+                }
+                Some(map)
+            }
+            None => {
+                eprintln!(
+                    "  - Warning: monster.bin and m_attack.bin palettes differ and cannot be \
+                     combined ({} + {} rows), monster frames will be misindexed",
+                    attack_wan.custom_palette.len(),
+                    monster_wan.custom_palette.len()
+                );
+                None
+            }
+        };
+
         // Merge frame_data, but adjust tile_num references for m_attack frames
         let mut merged_frame_data = monster_wan.frame_data.clone();
+        if let Some(map) = &monster_palette_map {
+            for frame in &mut merged_frame_data {
+                for piece in &mut frame.pieces {
+                    if let Some(&row) = map.get(piece.palette_index as usize) {
+                        piece.palette_index = row;
+                    }
+                }
+            }
+        }
 
         for mut frame in attack_wan.frame_data.clone() {
             for piece in &mut frame.pieces {
@@ -523,13 +686,14 @@ impl<'a> PokemonSpriteExtractor<'a> {
             frame_data: merged_frame_data,
             animations: AnimationStructure::Character(merged_groups),
             body_part_offset_data: merged_offsets,
-            custom_palette: attack_wan.custom_palette,
+            custom_palette: merged_palette,
             effect_specific_palette: attack_wan.effect_specific_palette,
             wan_type: attack_wan.wan_type,
             palette_offset: attack_wan.palette_offset,
             tile_lookup_8bpp: attack_wan.tile_lookup_8bpp,
             max_sequences_per_group: 8,
             offset_table_size: 0,
+            anim_header_unknowns: attack_wan.anim_header_unknowns,
         }
     }
 
@@ -540,16 +704,9 @@ impl<'a> PokemonSpriteExtractor<'a> {
         entry: &MonsterEntry,
         folder_name: &str,
         context: &PokemonProcessingContext,
-    ) -> io::Result<()> {
-        // De-duplicate visually identical gender variants
-        if id >= 600 {
-            let primary_index = id - 600;
-            if primary_index < context.all_entries.len() {
-                let primary_entry = &context.all_entries[primary_index];
-                if primary_entry.sprite_index == entry.sprite_index {
-                    return Ok(());
-                }
-            }
+    ) -> io::Result<ItemOutcome> {
+        if is_duplicate_gender_variant(id, entry, context.all_entries) {
+            return Ok(ItemOutcome::Skipped);
         }
 
         let sprite_index = entry.sprite_index as usize;
@@ -558,16 +715,51 @@ impl<'a> PokemonSpriteExtractor<'a> {
                 "Skipping Pokemon #{:03} ('{}'): Invalid sprite index {}",
                 id, folder_name, sprite_index
             );
-            return Ok(());
+            self.emit_placeholder(entry, folder_name, context);
+            return Ok(ItemOutcome::Skipped);
         }
 
         // Extract and log pre-merge stats
-        let monster_wan = self.extract_wan_file(context.monster_bin, sprite_index)?;
-        let attack_wan = self.extract_wan_file(context.m_attack_bin, sprite_index)?;
+        let wans = self
+            .extract_wan_file(context.monster_bin, sprite_index)
+            .and_then(|monster_wan| {
+                self.extract_wan_file(context.m_attack_bin, sprite_index)
+                    .map(|attack_wan| (monster_wan, attack_wan))
+            });
+        let (monster_wan, attack_wan) = match wans {
+            Ok(wans) => wans,
+            Err(e) if self.emit_empty => {
+                eprintln!("  -> Error extracting WANs for {}: {}", folder_name, e);
+                self.emit_placeholder(entry, folder_name, context);
+                return Ok(ItemOutcome::Failed);
+            }
+            Err(e) => return Err(e),
+        };
 
         // Merge and log post-merge stats
         let merged_wan = self.merge_wan_files(monster_wan, attack_wan);
+        if let Err(issues) = merged_wan.validate() {
+            println!(
+                "  - Warning: {} WAN integrity issues for {}:",
+                issues.len(),
+                folder_name
+            );
+            for issue in &issues {
+                println!("    - {}", issue);
+            }
+        }
+
+        // Move effects attach to these per-frame body part offsets, without them every move
+        // attachment point falls back to the sprite origin
+        if merged_wan.body_part_offset_data.is_empty() && !merged_wan.frame_data.is_empty() {
+            println!(
+                "  - Warning: {} has no body part offsets, move attachment points resolve to the origin",
+                folder_name
+            );
+        }
 
+        // monster.bin (idle, walk, sleep, ...) and m_attack.bin groups both live in the merged
+        // WAN, so the atlas sees every animation and dedups frames across the two sources
         let mut wan_files = HashMap::new();
         wan_files.insert("merged".to_string(), merged_wan);
 
@@ -586,18 +778,112 @@ impl<'a> PokemonSpriteExtractor<'a> {
         ) {
             Ok(atlas_result) => {
                 println!(
-                    "  -> Successfully generated atlas at: {}",
-                    atlas_result.image_path.display()
+                    "  -> Successfully generated atlas at: {} ({})",
+                    atlas_result.image_path.display(),
+                    atlas_result.color_mode
                 );
+                if self.export_palettes {
+                    if let Err(e) = palette::export_palette_rows(
+                        &wan_files["merged"].custom_palette,
+                        &context.output_dir.join(folder_name),
+                        &format!("{:03}_palette", entry.national_pokedex_number),
+                        context.atlas_config.sink.as_ref(),
+                    ) {
+                        eprintln!("  -> Failed to export palette for {}: {}", folder_name, e);
+                    }
+                }
+                Ok(ItemOutcome::Processed)
+            }
+            Err(e) if e.is_skippable() => {
+                println!("  -> Skipping atlas for {}: {}", folder_name, e);
+                self.emit_placeholder(entry, folder_name, context);
+                Ok(ItemOutcome::Skipped)
             }
             Err(e) => {
                 eprintln!("  -> Error generating atlas for {}: {:?}", folder_name, e);
+                self.emit_placeholder(entry, folder_name, context);
+                Ok(ItemOutcome::Failed)
             }
         }
-        Ok(())
+    }
+
+    /// Writes an empty placeholder atlas for `entry` when `--emit-empty` is set
+    fn emit_placeholder(
+        &self,
+        entry: &MonsterEntry,
+        folder_name: &str,
+        context: &PokemonProcessingContext,
+    ) {
+        if !self.emit_empty {
+            return;
+        }
+        match create_placeholder_atlas(
+            entry.national_pokedex_number,
+            entry.shadow_size as u8,
+            context.atlas_config,
+            context.output_dir,
+            folder_name,
+        ) {
+            Ok(atlas_result) => println!(
+                "  -> Wrote empty placeholder atlas at: {}",
+                atlas_result.image_path.display()
+            ),
+            Err(e) => eprintln!(
+                "  -> Error writing placeholder atlas for {}: {:?}",
+                folder_name, e
+            ),
+        }
     }
 }
 
+/// The atlas image and JSON `create_pokemon_atlas` writes for one Pokemon folder
+fn atlas_paths(
+    output_dir: &Path,
+    folder_name: &str,
+    dex_num: u16,
+    image_format: ImageFormat,
+) -> [PathBuf; 2] {
+    let dir = output_dir.join(folder_name);
+    [
+        dir.join(format!("{:03}_atlas.{}", dex_num, image_format.extension())),
+        dir.join(format!("{:03}_atlas.json", dex_num)),
+    ]
+}
+
+/// Whether entry `id` is a gender variant (600 onwards) drawn exactly like its primary entry.
+/// monster.md has no palette field, the palette lives in the monster.bin/m_attack.bin WANs at
+/// `sprite_index`, so variants that share a sprite index also share a palette and comparing
+/// the index alone is enough.
+fn is_duplicate_gender_variant(
+    id: usize,
+    entry: &MonsterEntry,
+    all_entries: &[MonsterEntry],
+) -> bool {
+    id.checked_sub(600)
+        .and_then(|primary_index| all_entries.get(primary_index))
+        .is_some_and(|primary| primary.sprite_index == entry.sprite_index)
+}
+
+/// Appends the rows of `extra` that aren't already in `base` and returns, for each row of
+/// `extra`, its index in the combined list. Identical rows share one entry. Returns `None`
+/// (leaving `base` untouched) if the combined list would not fit u8 palette indices.
+fn merge_palette_rows(base: &mut PaletteList, extra: &PaletteList) -> Option<Vec<u8>> {
+    let mut combined = base.clone();
+    let mut map = Vec::with_capacity(extra.len());
+    for row in extra {
+        let index = match combined.iter().position(|existing| existing == row) {
+            Some(index) => index,
+            None => {
+                combined.push(row.clone());
+                combined.len() - 1
+            }
+        };
+        map.push(u8::try_from(index).ok()?);
+    }
+    *base = combined;
+    Some(map)
+}
+
 /// Parse the monster.md file to extract monster entries
 fn parse_monster_md(data: &[u8]) -> io::Result<Vec<MonsterEntry>> {
     // Use the more comprehensive parser from monster_md.rs
@@ -609,3 +895,191 @@ fn parse_monster_md(data: &[u8]) -> io::Result<Vec<MonsterEntry>> {
     // Return the entries directly
     Ok(monster_data.entries)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        graphics::{
+            atlas::analyser::analyse_frames,
+            wan::{
+                builder::{BuilderPiece, BuilderSeqFrame, WanBuilder},
+                parser::{parse_wan_from_sir0_content, ParseOptions},
+                renderer::DEFAULT_MAX_FRAME_DIMENSION,
+            },
+        },
+        rom_builder::{monster_md, RomBuilder},
+        test_support::scratch_dir,
+    };
+
+    fn fixture_rom(name: &str) -> Rom {
+        let dir = scratch_dir(name);
+        let rom_path = dir.join("fixture.nds");
+        fs::write(&rom_path, RomBuilder::new("C2SE").build()).unwrap();
+        let rom = Rom::new(&rom_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        rom
+    }
+
+    /// Character WAN drawn entirely in colour 1 of `palette`, played by animation group
+    /// `group`. ROM WANs start with a null frame that sequence indices skip (see
+    /// `analyse_frames`), frame 0 stands in for it.
+    fn solid_wan(palette: (u8, u8, u8), group: usize) -> WanFile {
+        let mut builder = WanBuilder::new(WanType::Character)
+            .palette(vec![(0, 0, 0), palette])
+            .image(vec![0x11; 32])
+            .frame(vec![BuilderPiece::new(0, -4, -8)])
+            .frame(vec![BuilderPiece::new(0, -4, -8)]);
+        for _ in 0..group {
+            builder = builder.group(vec![vec![]; 8]);
+        }
+        let (content, data_pointer) = builder
+            .group(vec![vec![BuilderSeqFrame::new(0, 4)]; 8])
+            .build();
+        parse_wan_from_sir0_content(
+            &content,
+            data_pointer,
+            WanType::Character,
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    /// Colours of the opaque pixels of every analysed frame, keyed by animation group
+    fn colours_by_group(wan: WanFile) -> HashMap<u8, Vec<[u8; 3]>> {
+        let wan_files = HashMap::from([("merged".to_string(), wan)]);
+        let analysis = analyse_frames(&wan_files, 25, DEFAULT_MAX_FRAME_DIMENSION).unwrap();
+        let mut colours: HashMap<u8, Vec<[u8; 3]>> = HashMap::new();
+        for (anim_id, _, _, frame) in &analysis.ordered_frames {
+            let entry = colours.entry(*anim_id).or_default();
+            for pixel in frame.image.pixels().filter(|p| p[3] > 0) {
+                let rgb = [pixel[0], pixel[1], pixel[2]];
+                if !entry.contains(&rgb) {
+                    entry.push(rgb);
+                }
+            }
+        }
+        colours
+    }
+
+    #[test]
+    fn differing_palettes_keep_each_wans_colours_after_merging() {
+        let rom = fixture_rom("merge_palettes");
+        let extractor = PokemonSpriteExtractor::new(&rom);
+        // Walk (group 0) comes from monster.bin, Attack (group 1) from m_attack.bin
+        let monster = solid_wan((40, 80, 120), 0);
+        let attack = solid_wan((200, 160, 16), 1);
+
+        let merged = extractor.merge_wan_files(monster, attack);
+
+        assert_eq!(merged.palette_count(), 2);
+        let colours = colours_by_group(merged);
+        assert_eq!(colours[&0], vec![[40, 80, 120]]);
+        assert_eq!(colours[&1], vec![[200, 160, 16]]);
+    }
+
+    #[test]
+    fn identical_palettes_merge_into_one_row() {
+        let rom = fixture_rom("merge_same_palette");
+        let extractor = PokemonSpriteExtractor::new(&rom);
+        let monster = solid_wan((40, 80, 120), 0);
+        let attack = solid_wan((40, 80, 120), 1);
+
+        let merged = extractor.merge_wan_files(monster, attack);
+
+        assert_eq!(merged.palette_count(), 1);
+        let colours = colours_by_group(merged);
+        assert_eq!(colours[&0], vec![[40, 80, 120]]);
+        assert_eq!(colours[&1], vec![[40, 80, 120]]);
+    }
+
+    #[test]
+    fn gender_variant_is_only_dropped_when_it_shares_the_sprite() {
+        // Entries 600 and 601 are the gender variants of entries 0 and 1
+        let mut pokemon = vec![(25, 0), (26, 1)];
+        pokemon.resize(600, (0, 0));
+        pokemon.extend([(25, 0), (26, 2)]);
+        let entries = parse_monster_md(&monster_md(&pokemon)).unwrap();
+
+        assert!(is_duplicate_gender_variant(600, &entries[600], &entries));
+        // A different sprite index means a different WAN, and with it a different palette
+        assert!(!is_duplicate_gender_variant(601, &entries[601], &entries));
+        assert!(!is_duplicate_gender_variant(0, &entries[0], &entries));
+    }
+
+    #[test]
+    fn unmapped_form_is_kept_as_form_n_only_when_asked() {
+        let rom = fixture_rom("unknown_forms");
+        // Raichu has no form names, so its second entry is an unmapped form 1
+        assert_eq!(form_names::form_name(26, 1), None);
+        let entries = parse_monster_md(&monster_md(&[(25, 0), (26, 1), (26, 2)])).unwrap();
+
+        let default = PokemonSpriteExtractor::new(&rom).select_entries(&entries, None);
+        assert_eq!(
+            default,
+            vec![
+                (0, "pokemon_025".to_string()),
+                (1, "pokemon_026".to_string())
+            ]
+        );
+
+        let with_unknown = PokemonSpriteExtractor::new(&rom)
+            .with_unknown_forms(true)
+            .select_entries(&entries, None);
+        assert_eq!(with_unknown.len(), 3);
+        assert_eq!(with_unknown[2], (2, "pokemon_026_form_1".to_string()));
+    }
+
+    #[test]
+    fn whitelist_restricts_the_entries_to_those_indices() {
+        let rom = fixture_rom("id_whitelist");
+        let pokemon: Vec<(u16, i16)> = (0..=150).map(|i| (i, i as i16)).collect();
+        let entries = parse_monster_md(&monster_md(&pokemon)).unwrap();
+
+        let everything = PokemonSpriteExtractor::new(&rom)
+            .with_id_whitelist(None)
+            .select_entries(&entries, None);
+        assert!(everything.len() > 1, "{:?}", everything);
+
+        let whitelisted = PokemonSpriteExtractor::new(&rom)
+            .with_id_whitelist(Some(vec![25, 150]))
+            .select_entries(&entries, None);
+        assert_eq!(
+            whitelisted,
+            vec![
+                (25, "pokemon_025".to_string()),
+                (150, "pokemon_150".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_analysis_has_frames_from_both_bins() {
+        let rom = fixture_rom("merge_sources");
+        let extractor = PokemonSpriteExtractor::new(&rom);
+        let monster = solid_wan((40, 80, 120), 0);
+        let monster_frames = monster.frame_data.len();
+        let attack = solid_wan((200, 160, 16), 1);
+
+        let merged = extractor.merge_wan_files(monster, attack);
+        let wan_files = HashMap::from([("merged".to_string(), merged)]);
+        let analysis = analyse_frames(&wan_files, 25, DEFAULT_MAX_FRAME_DIMENSION).unwrap();
+
+        let wan_frames_of = |anim: u8| -> Vec<usize> {
+            analysis
+                .ordered_frames
+                .iter()
+                .filter(|(anim_id, ..)| *anim_id == anim)
+                .map(|(.., frame)| frame.original_wan_frame_index)
+                .collect()
+        };
+        // monster.bin frames keep their indices, m_attack.bin frames follow them
+        let walk = wan_frames_of(0);
+        let attack = wan_frames_of(1);
+        assert!(!walk.is_empty() && !attack.is_empty());
+        assert!(walk.iter().all(|&i| i < monster_frames), "{:?}", walk);
+        assert!(attack.iter().all(|&i| i >= monster_frames), "{:?}", attack);
+    }
+}
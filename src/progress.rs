@@ -1,7 +1,12 @@
-use std::{fs, path::Path};
+use std::{fs, path::Path, sync::Mutex};
 
 use serde_json::json;
 
+/// Serialises writers so two threads never race on the shared temp file
+static PROGRESS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Replaces the progress file with the given state. The JSON is written to a sibling temp file
+/// and renamed over `path`, so a reader polling the file never sees a partial write.
 pub fn write_progress(path: &Path, current: usize, total: usize, phase: &str, status: &str) {
     let json = json!({
         "current": current,
@@ -9,5 +14,49 @@ pub fn write_progress(path: &Path, current: usize, total: usize, phase: &str, st
         "phase": phase,
         "status": status,
     });
-    let _ = fs::write(path, json.to_string());
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let _guard = PROGRESS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if fs::write(&tmp_path, json.to_string()).is_ok() {
+        let _ = fs::rename(&tmp_path, path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    #[test]
+    fn concurrent_writers_never_leave_a_partial_file() {
+        let dir = scratch_dir("progress_concurrent");
+        let path = dir.join("progress.json");
+        write_progress(&path, 0, 400, "pokemon_sprite", "running");
+
+        thread::scope(|scope| {
+            for writer in 0..4 {
+                let path = &path;
+                scope.spawn(move || {
+                    for i in 0..100 {
+                        write_progress(path, writer * 100 + i, 400, "pokemon_sprite", "running");
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for _ in 0..400 {
+                    let text = fs::read_to_string(&path).unwrap();
+                    let progress: serde_json::Value = serde_json::from_str(&text)
+                        .unwrap_or_else(|e| panic!("{:?} is not valid JSON: {}", text, e));
+                    assert_eq!(progress["total"], 400);
+                }
+            });
+        });
+
+        assert!(!dir.join("progress.json.tmp").exists());
+    }
 }
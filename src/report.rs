@@ -0,0 +1,82 @@
+use std::{io, path::Path};
+
+use serde::Serialize;
+
+use crate::output::{JsonStyle, OutputSink};
+
+/// How one item of a stage (a Pokemon, an effect, a tileset, ...) ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemOutcome {
+    Processed,
+    /// Nothing to extract, or a placeholder was written instead
+    Skipped,
+    Failed,
+}
+
+/// Item counts for one extraction stage
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct StageReport {
+    pub processed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl StageReport {
+    pub fn add(&mut self, outcome: ItemOutcome) {
+        match outcome {
+            ItemOutcome::Processed => self.processed += 1,
+            ItemOutcome::Skipped => self.skipped += 1,
+            ItemOutcome::Failed => self.failed += 1,
+        }
+    }
+
+    /// Adds the counts of `other`, e.g. a sub-step of the same stage
+    pub fn merge(&mut self, other: StageReport) {
+        self.processed += other.processed;
+        self.skipped += other.skipped;
+        self.failed += other.failed;
+    }
+
+    /// Items handled so far, whatever their outcome
+    pub fn total(&self) -> usize {
+        self.processed + self.skipped + self.failed
+    }
+}
+
+/// Summary of a whole run, written to `report.json`. Stages that didn't run are left out,
+/// `failures` lists the stages that stopped with an error.
+#[derive(Debug, Default, Serialize)]
+pub struct ExtractionReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pokemon: Option<StageReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub portraits: Option<StageReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effects: Option<StageReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tilesets: Option<StageReport>,
+    pub failures: Vec<String>,
+}
+
+impl ExtractionReport {
+    /// Keeps a stage's counts, or records its error under `stage` and returns `None`
+    pub fn record(&mut self, stage: &str, result: io::Result<StageReport>) -> Option<StageReport> {
+        match result {
+            Ok(counts) => Some(counts),
+            Err(e) => {
+                self.failures.push(format!("{}: {}", stage, e));
+                None
+            }
+        }
+    }
+
+    pub fn save(
+        &self,
+        path: &Path,
+        sink: &dyn OutputSink,
+        json_style: JsonStyle,
+    ) -> io::Result<()> {
+        let json = json_style.to_string(self)?;
+        sink.write_file(path, json.as_bytes())
+    }
+}
@@ -49,9 +49,25 @@ impl Rom {
             )
         })?;
 
+        check_rom_size(rom_data.len(), rom_header.used_rom_size);
+
         let arm9_offset = rom_header.arm9_rom_offset as usize;
         let arm9_size = rom_header.arm9_size as usize;
-        let arm9 = rom_data[arm9_offset..arm9_offset + arm9_size].to_vec();
+        let arm9 = rom_data
+            .get(arm9_offset..arm9_offset + arm9_size)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "ARM9 binary out of bounds: offset={}, size={}, data_len={}",
+                        arm9_offset,
+                        arm9_size,
+                        rom_data.len()
+                    ),
+                )
+            })?
+            .to_vec();
+        check_secure_area(&arm9, arm9_offset)?;
 
         let arm9_overlay_table_offset = rom_header.arm9_overlay_table_offset as usize;
         let arm9_overlay_table_size = rom_header.arm9_overlay_table_size as usize;
@@ -86,7 +102,7 @@ impl Rom {
 
         let fnt = FileNameTable::read_from_rom(&rom_data, rom_header.fnt_offset)?;
 
-        Ok(Rom {
+        let rom = Rom {
             path: path_buf,
             id_code,
             developer_code: rom_header.maker_code,
@@ -101,10 +117,72 @@ impl Rom {
             fnt,
             region_data,
             loaded_overlays: HashMap::new(),
-        })
+        };
+        rom.check_filesystem_consistency();
+
+        Ok(rom)
+    }
+
+    /// Number of used entries in the FAT
+    pub fn fat_entry_count(&self) -> usize {
+        self.fat.len()
+    }
+
+    /// Number of file IDs the FNT names, i.e. highest file ID + 1
+    pub fn fnt_file_count(&self) -> usize {
+        self.fnt.max_file_id().map_or(0, |id| id as usize + 1)
+    }
+
+    /// Last modification time of the ROM file, outputs older than this are stale
+    pub fn modified(&self) -> io::Result<std::time::SystemTime> {
+        std::fs::metadata(&self.path)?.modified()
+    }
+
+    /// Header title as a filesystem-safe slug, e.g. `"POKEDUN SORA\0\0 "` -> `"pokedun_sora"`.
+    ///
+    /// ASCII letters and digits are lowercased and kept, every other run of characters
+    /// (spaces, nulls, punctuation) becomes a single `_`. Falls back to the game code if
+    /// nothing usable is left.
+    pub fn sanitized_title(&self) -> String {
+        let mut slug = String::with_capacity(self.game_title.len());
+        for c in self.game_title.chars() {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c.to_ascii_lowercase());
+            } else if !slug.is_empty() && !slug.ends_with('_') {
+                slug.push('_');
+            }
+        }
+        while slug.ends_with('_') {
+            slug.pop();
+        }
+
+        if slug.is_empty() {
+            self.id_code.to_ascii_lowercase()
+        } else {
+            slug
+        }
     }
 
-    /// Load specific overlays from the ROM
+    /// Warns when the FNT names files the FAT has no entry for.
+    ///
+    /// Such a ROM still loads, but any lookup of those files resolves through `get_file_id` and
+    /// then fails in `get_file_data`, which otherwise only surfaces as "Failed to extract ...".
+    fn check_filesystem_consistency(&self) {
+        let fat_count = self.fat_entry_count();
+        let fnt_count = self.fnt_file_count();
+        if fnt_count > fat_count {
+            eprintln!(
+                "Warning: FNT references file IDs up to {} but the FAT only has {} entries. \
+                 The ROM may be trimmed or corrupted, extraction of those files will fail.",
+                fnt_count - 1,
+                fat_count
+            );
+        }
+    }
+
+    /// Load specific overlays from the ROM. Overlay files are sliced out of the ROM bytes
+    /// already held in `data`, so a call no longer re-reads the whole ROM file from disk
+    /// (one full ROM read, tens of MB, saved per call).
     pub fn load_arm9_overlays(
         &mut self,
         ids_to_load: &[u32],
@@ -112,7 +190,7 @@ impl Rom {
         println!("Loading ARM9 overlays: {:?}", ids_to_load);
 
         // Create callback to load overlay files from FAT
-        let rom_data = self.read_rom_data()?;
+        let rom_data = &self.data;
         let fat = &self.fat;
 
         let file_callback = move |ov_id: u32, file_id: u32| -> io::Result<Vec<u8>> {
@@ -120,7 +198,7 @@ impl Rom {
                 "Callback invoked for overlay ID: {}, file ID: {}",
                 ov_id, file_id
             );
-            if let Some(data) = fat.get_file_data(file_id as usize, &rom_data) {
+            if let Some(data) = fat.get_file_data(file_id as usize, rom_data) {
                 println!("  Successfully loaded file data: {} bytes", data.len());
                 Ok(data.to_vec())
             } else {
@@ -143,14 +221,6 @@ impl Rom {
         Ok(&self.loaded_overlays)
     }
 
-    /// Read the entire ROM data
-    fn read_rom_data(&self) -> io::Result<Vec<u8>> {
-        let mut file = File::open(&self.path)?;
-        let mut rom_data = Vec::new();
-        file.read_to_end(&mut rom_data)?;
-        Ok(rom_data)
-    }
-
     /// Extract animation data from overlay 10
     pub fn extract_animation_data(&mut self) -> Result<AnimData, String> {
         println!("Starting extract_animation_data");
@@ -198,14 +268,16 @@ impl Rom {
         ]
         .concat();
 
-        parse_animation_data(&animation_data)
+        parse_animation_data(&animation_data).map_err(|e| e.to_string())
     }
 
     /// Read the overlay-10 TILESET_PROPERTIES table.
     pub fn extract_tileset_properties(
         &mut self,
     ) -> Result<Vec<crate::data::tileset_properties::TilesetProperty>, String> {
-        use crate::data::tileset_properties::{parse_tileset_properties, TILESET_COUNT};
+        use crate::data::tileset_properties::{
+            parse_tileset_properties, TILESET_COUNT, TILESET_PROPERTY_STRIDE,
+        };
 
         let addr = self.region_data.tileset_properties_addr;
         if addr == 0 {
@@ -220,16 +292,18 @@ impl Rom {
             .get(&10)
             .ok_or_else(|| "Overlay 10 not loaded".to_string())?;
 
-        let base = overlay10.ram_address;
-        if addr < base {
-            return Err(format!(
-                "TILESET_PROPERTIES addr 0x{:X} below overlay 10 base 0x{:X}",
-                addr, base
-            ));
-        }
-        let file_offset = (addr - base) as usize;
-
-        parse_tileset_properties(&overlay10.data, file_offset, TILESET_COUNT)
+        let table = overlay10
+            .read_at_ram_address(addr, TILESET_COUNT * TILESET_PROPERTY_STRIDE)
+            .ok_or_else(|| {
+                format!(
+                    "TILESET_PROPERTIES addr 0x{:X} outside overlay 10 (0x{:X}..0x{:X})",
+                    addr,
+                    overlay10.ram_address,
+                    overlay10.ram_address as usize + overlay10.data.len()
+                )
+            })?;
+
+        parse_tileset_properties(table, 0, TILESET_COUNT)
     }
 }
 
@@ -249,6 +323,8 @@ pub struct RomHeader {
     pub fnt_size: u32,
     pub fat_offset: u32,
     pub fat_size: u32,
+    /// Bytes actually used by the ROM image, everything after is padding
+    pub used_rom_size: u32,
     pub unit_code: u8,
     pub nds_region: u8,
     pub rom_version: u8,
@@ -256,6 +332,60 @@ pub struct RomHeader {
     pub encryption_seed: u8,
 }
 
+/// Reports dumps whose length doesn't match the header's used ROM size. Over-dumped images
+/// (padded, usually with 0xFF, to the chip size) and trimmed images ending right after the
+/// last file both load normally, only a dump shorter than the used size is missing data.
+fn check_rom_size(data_len: usize, used_rom_size: u32) {
+    let used = used_rom_size as usize;
+    if used == 0 || data_len == used {
+        return;
+    }
+    if data_len > used {
+        println!(
+            "ROM is over-dumped: {} bytes of padding after the {} used bytes",
+            data_len - used,
+            used
+        );
+    } else {
+        eprintln!(
+            "Warning: ROM is {} bytes but the header says {} are used, files near the end may \
+             fail to extract",
+            data_len, used
+        );
+    }
+}
+
+/// Start of the 2KB ARM9 secure area on retail cartridges
+const SECURE_AREA_OFFSET: usize = 0x4000;
+/// First 8 secure area bytes of a decrypted dump, as written by most dumpers
+const SECURE_AREA_DECRYPTED_MARKER: [u8; 8] = [0xFF, 0xDE, 0xFF, 0xE7, 0xFF, 0xDE, 0xFF, 0xE7];
+/// The same bytes when a decrypted dump keeps the plain-text identifier
+const SECURE_AREA_PLAIN_ID: &[u8; 8] = b"encryObj";
+
+/// Rejects a dump whose ARM9 secure area is still Blowfish encrypted. Decrypted dumps start
+/// the area with a known marker (or zeros when it was stripped). Anything else is the
+/// encrypted identifier, and the ARM9 and overlays would otherwise parse as garbage.
+fn check_secure_area(arm9: &[u8], arm9_offset: usize) -> io::Result<()> {
+    if arm9_offset != SECURE_AREA_OFFSET {
+        // Homebrew and rebuilt ROMs place the ARM9 elsewhere and have no secure area
+        return Ok(());
+    }
+    let Some(id) = arm9.get(..8) else {
+        return Ok(());
+    };
+    if id == SECURE_AREA_DECRYPTED_MARKER || id == SECURE_AREA_PLAIN_ID || id == [0; 8] {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "ARM9 secure area at 0x{:X} is encrypted (starts with {:02X?}). Decrypt the \
+             secure area first, most dumping and ROM tools have an option for it.",
+            SECURE_AREA_OFFSET, id
+        ),
+    ))
+}
+
 /// Read the ROM header from a file
 fn read_header(rom_data: &[u8]) -> io::Result<RomHeader> {
     let mut cursor = Cursor::new(rom_data);
@@ -304,6 +434,9 @@ fn read_header(rom_data: &[u8]) -> io::Result<RomHeader> {
     binary_utils::seek_to(&mut cursor, 0x04C)?;
     let fat_size = binary_utils::read_u32_le(&mut cursor)?;
 
+    binary_utils::seek_to(&mut cursor, 0x080)?;
+    let used_rom_size = binary_utils::read_u32_le(&mut cursor)?;
+
     Ok(RomHeader {
         game_title,
         game_code,
@@ -318,6 +451,7 @@ fn read_header(rom_data: &[u8]) -> io::Result<RomHeader> {
         fnt_size,
         fat_offset,
         fat_size,
+        used_rom_size,
         unit_code,
         nds_region,
         rom_version,
@@ -325,3 +459,147 @@ fn read_header(rom_data: &[u8]) -> io::Result<RomHeader> {
         encryption_seed,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{rom_builder::RomBuilder, test_support::scratch_dir};
+
+    fn load(name: &str, rom_bytes: &[u8]) -> io::Result<Rom> {
+        let dir = scratch_dir(name);
+        let rom_path = dir.join("fixture.nds");
+        fs::write(&rom_path, rom_bytes).unwrap();
+        let rom = Rom::new(&rom_path);
+        fs::remove_dir_all(&dir).unwrap();
+        rom
+    }
+
+    fn two_file_rom() -> Vec<u8> {
+        RomBuilder::new("C2SE")
+            .file("BALANCE/monster.md", vec![1; 8])
+            .file("MONSTER/monster.bin", vec![2; 12])
+            .build()
+    }
+
+    #[test]
+    fn fat_and_fnt_file_counts_agree_for_a_consistent_rom() {
+        let rom = load("rom_counts", &two_file_rom()).unwrap();
+        assert_eq!(rom.fat_entry_count(), 2);
+        assert_eq!(rom.fnt_file_count(), 2);
+
+        // Dropping the last FAT entry only warns, the first file still resolves
+        let mut rom_bytes = two_file_rom();
+        let fat_size = u32::from_le_bytes(rom_bytes[0x4C..0x50].try_into().unwrap());
+        rom_bytes[0x4C..0x50].copy_from_slice(&(fat_size - 8).to_le_bytes());
+        let rom = load("rom_counts_short_fat", &rom_bytes).unwrap();
+        assert_eq!(rom.fat_entry_count(), 1);
+        assert_eq!(rom.fnt_file_count(), 2);
+        let file_id = rom.fnt.get_file_id("BALANCE/monster.md").unwrap();
+        assert_eq!(
+            rom.fat.get_file_data(file_id as usize, &rom.data),
+            Some(&[1; 8][..])
+        );
+        let file_id = rom.fnt.get_file_id("MONSTER/monster.bin").unwrap();
+        assert!(rom.fat.get_file_data(file_id as usize, &rom.data).is_none());
+    }
+
+    #[test]
+    fn title_with_trailing_nulls_and_spaces_becomes_a_clean_slug() {
+        let mut rom_bytes = two_file_rom();
+        rom_bytes[..12].copy_from_slice(b"POKEDUN SO\0 ");
+        let rom = load("rom_title", &rom_bytes).unwrap();
+        assert_eq!(rom.sanitized_title(), "pokedun_so");
+
+        // Nothing usable left falls back to the game code
+        rom_bytes[..12].copy_from_slice(&[b' '; 12]);
+        let rom = load("rom_title_blank", &rom_bytes).unwrap();
+        assert_eq!(rom.sanitized_title(), "c2se");
+    }
+
+    #[test]
+    fn trimmed_and_over_dumped_roms_read_the_last_file() {
+        let rom_bytes = RomBuilder::new("C2SE")
+            .file("BALANCE/monster.md", vec![1; 8])
+            .file("MONSTER/monster.bin", vec![2; 7])
+            .build();
+        let last_file = |rom: &Rom| -> Option<Vec<u8>> {
+            let file_id = rom.fnt.get_file_id("MONSTER/monster.bin").unwrap();
+            rom.fat
+                .get_file_data(file_id as usize, &rom.data)
+                .map(<[u8]>::to_vec)
+        };
+
+        // Trimmed right after the last file, its FAT end address is the data length
+        let mut trimmed = rom_bytes.clone();
+        trimmed.truncate(rom_bytes.len() - 1);
+        let used = trimmed.len() as u32;
+        trimmed[0x80..0x84].copy_from_slice(&used.to_le_bytes());
+        let rom = load("rom_trimmed", &trimmed).unwrap();
+        assert_eq!(last_file(&rom), Some(vec![2; 7]));
+
+        // Over-dumped to the chip size with 0xFF padding
+        let mut over_dumped = trimmed.clone();
+        over_dumped.resize(trimmed.len().next_power_of_two() * 2, 0xFF);
+        let rom = load("rom_over_dumped", &over_dumped).unwrap();
+        assert_eq!(last_file(&rom), Some(vec![2; 7]));
+
+        // Cut into the last file, it's missing rather than a panic
+        let rom = load("rom_cut_short", &trimmed[..trimmed.len() - 2]).unwrap();
+        assert_eq!(last_file(&rom), None);
+    }
+
+    #[test]
+    fn encrypted_secure_area_is_a_descriptive_error() {
+        // Move the ARM9 to the retail secure area offset, still starting with encrypted bytes
+        let with_secure_area = |id: &[u8; 8]| {
+            let mut rom_bytes = two_file_rom();
+            let arm9_size = u32::from_le_bytes(rom_bytes[0x2C..0x30].try_into().unwrap());
+            rom_bytes.resize(SECURE_AREA_OFFSET + arm9_size as usize, 0);
+            rom_bytes[SECURE_AREA_OFFSET..SECURE_AREA_OFFSET + 8].copy_from_slice(id);
+            rom_bytes[0x20..0x24].copy_from_slice(&(SECURE_AREA_OFFSET as u32).to_le_bytes());
+            rom_bytes
+        };
+
+        let err = load(
+            "rom_encrypted",
+            &with_secure_area(&[0x3A, 0x91, 0x07, 0xC4, 1, 2, 3, 4]),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("is encrypted"), "{}", err);
+        assert!(
+            err.to_string().contains("Decrypt the secure area"),
+            "{}",
+            err
+        );
+
+        assert!(load(
+            "rom_decrypted",
+            &with_secure_area(&SECURE_AREA_DECRYPTED_MARKER)
+        )
+        .is_ok());
+        assert!(load("rom_plain_id", &with_secure_area(SECURE_AREA_PLAIN_ID)).is_ok());
+    }
+
+    #[test]
+    fn overlays_load_after_the_rom_file_is_gone() {
+        let rom_bytes = RomBuilder::new("C2SE")
+            .file("overlay_0010.bin", vec![7; 24])
+            .build();
+        // `load` deletes the ROM file once it's read, so overlays must come from `data`
+        let mut rom = load("rom_overlays", &rom_bytes).unwrap();
+        assert!(!rom.path.exists());
+        let file_id = rom.fnt.get_file_id("overlay_0010.bin").unwrap() as u32;
+        rom.arm9_overlay_table = [10, 0x020A_0000, 24, 0, 0, 0, file_id, 0]
+            .iter()
+            .flat_map(|field: &u32| field.to_le_bytes())
+            .collect();
+
+        let overlays = rom.load_arm9_overlays(&[10]).unwrap();
+        assert_eq!(overlays[&10].data, vec![7; 24]);
+        assert_eq!(overlays[&10].ram_address, 0x020A_0000);
+    }
+}
@@ -0,0 +1,203 @@
+//! Synthetic NDS ROM builder
+//!
+//! Lays out a minimal ROM image (header, ARM9 stub, FNT, FAT and file data) that `Rom::new`
+//! accepts, so extraction stages can be driven from fixture files instead of a real dump.
+//! Compiled for tests and with the `rom-builder` feature.
+//!
+//! ```ignore
+//! let rom_bytes = RomBuilder::new("C2SE")
+//!     .file("MONSTER/monster.bin", monster_bin.to_bytes(0))
+//!     .file("MONSTER/m_attack.bin", m_attack_bin.to_bytes(0))
+//!     .file("BALANCE/monster.md", monster_md(&[(25, 0)]))
+//!     .build();
+//! fs::write(&rom_path, rom_bytes)?;
+//! let rom = Rom::new(&rom_path)?;
+//! ```
+
+// Fixture tooling, outside of test builds nothing in the crate calls it
+#![cfg_attr(not(test), allow(dead_code))]
+
+use crate::filesystem::DIRECTORY_ID_BASE;
+
+const HEADER_SIZE: usize = 0x200;
+/// Size of the zeroed ARM9 stub, `Rom::new` only slices it
+const ARM9_STUB_SIZE: usize = 0x100;
+const ARM9_RAM_ADDRESS: u32 = 0x0200_0000;
+
+/// One FNT directory, files keep insertion order so their IDs are predictable
+#[derive(Debug, Clone, Default)]
+struct BuilderDir {
+    name: String,
+    parent: usize,
+    subdirs: Vec<usize>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+/// Builds ROM bytes from `/` separated paths and file contents.
+///
+/// File IDs are assigned directory by directory in creation order, starting at 0 for the root.
+/// There are no overlays, the overlay table is empty.
+#[derive(Debug, Clone)]
+pub struct RomBuilder {
+    game_code: [u8; 4],
+    title: String,
+    dirs: Vec<BuilderDir>,
+}
+
+impl RomBuilder {
+    /// `game_code` picks the region data, e.g. `"C2SE"` for North America
+    pub fn new(game_code: &str) -> Self {
+        let mut code = [b' '; 4];
+        for (dst, src) in code.iter_mut().zip(game_code.bytes()) {
+            *dst = src;
+        }
+        Self {
+            game_code: code,
+            title: "POKEDUN SORA".to_string(),
+            dirs: vec![BuilderDir::default()],
+        }
+    }
+
+    /// Header game title, truncated to 12 bytes
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Adds a file, creating any missing parent directories
+    pub fn file(mut self, path: &str, data: Vec<u8>) -> Self {
+        let mut parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+        let Some(file_name) = parts.pop() else {
+            return self;
+        };
+
+        let mut dir = 0;
+        for part in parts {
+            dir = match self.dirs[dir]
+                .subdirs
+                .iter()
+                .copied()
+                .find(|&child| self.dirs[child].name == part)
+            {
+                Some(child) => child,
+                None => {
+                    self.dirs.push(BuilderDir {
+                        name: part.to_string(),
+                        parent: dir,
+                        ..BuilderDir::default()
+                    });
+                    let child = self.dirs.len() - 1;
+                    self.dirs[dir].subdirs.push(child);
+                    child
+                }
+            };
+        }
+        self.dirs[dir].files.push((file_name.to_string(), data));
+        self
+    }
+
+    /// Returns the complete ROM image
+    pub fn build(&self) -> Vec<u8> {
+        let mut first_file_ids = Vec::with_capacity(self.dirs.len());
+        let mut file_count = 0u16;
+        for dir in &self.dirs {
+            first_file_ids.push(file_count);
+            file_count += dir.files.len() as u16;
+        }
+
+        let fnt = self.build_fnt(&first_file_ids);
+
+        let arm9_offset = HEADER_SIZE;
+        let fnt_offset = align4(arm9_offset + ARM9_STUB_SIZE);
+        let fat_offset = align4(fnt_offset + fnt.len());
+        let fat_size = file_count as usize * 8;
+        let data_offset = align4(fat_offset + fat_size);
+
+        let mut rom = vec![0u8; data_offset];
+        let mut fat = Vec::with_capacity(fat_size);
+        for (_, data) in self.dirs.iter().flat_map(|dir| &dir.files) {
+            let start = rom.len() as u32;
+            rom.extend_from_slice(data);
+            fat.extend_from_slice(&start.to_le_bytes());
+            fat.extend_from_slice(&(rom.len() as u32).to_le_bytes());
+            rom.resize(align4(rom.len()), 0);
+        }
+
+        let title = self.title.as_bytes();
+        let title_len = title.len().min(12);
+        rom[..title_len].copy_from_slice(&title[..title_len]);
+        rom[0x0C..0x10].copy_from_slice(&self.game_code);
+        rom[0x10..0x12].copy_from_slice(b"01");
+        put_u32(&mut rom, 0x20, arm9_offset as u32);
+        put_u32(&mut rom, 0x24, ARM9_RAM_ADDRESS);
+        put_u32(&mut rom, 0x28, ARM9_RAM_ADDRESS);
+        put_u32(&mut rom, 0x2C, ARM9_STUB_SIZE as u32);
+        put_u32(&mut rom, 0x40, fnt_offset as u32);
+        put_u32(&mut rom, 0x44, fnt.len() as u32);
+        put_u32(&mut rom, 0x48, fat_offset as u32);
+        put_u32(&mut rom, 0x4C, fat_size as u32);
+
+        rom[fnt_offset..fnt_offset + fnt.len()].copy_from_slice(&fnt);
+        rom[fat_offset..fat_offset + fat_size].copy_from_slice(&fat);
+        rom
+    }
+
+    /// Main directory table followed by one sub-table per directory
+    fn build_fnt(&self, first_file_ids: &[u16]) -> Vec<u8> {
+        let main_table_size = self.dirs.len() * 8;
+        let mut subtables = Vec::new();
+        let mut main_table = Vec::with_capacity(main_table_size);
+
+        for (index, dir) in self.dirs.iter().enumerate() {
+            let subtable_offset = (main_table_size + subtables.len()) as u32;
+            // The root stores the directory count where the others store their parent ID
+            let parent_or_total = if index == 0 {
+                self.dirs.len() as u16
+            } else {
+                DIRECTORY_ID_BASE + dir.parent as u16
+            };
+            main_table.extend_from_slice(&subtable_offset.to_le_bytes());
+            main_table.extend_from_slice(&first_file_ids[index].to_le_bytes());
+            main_table.extend_from_slice(&parent_or_total.to_le_bytes());
+
+            for (name, _) in &dir.files {
+                subtables.push(name.len().min(0x7F) as u8);
+                subtables.extend_from_slice(&name.as_bytes()[..name.len().min(0x7F)]);
+            }
+            for &child in &dir.subdirs {
+                let name = &self.dirs[child].name;
+                subtables.push(0x80 | name.len().min(0x7F) as u8);
+                subtables.extend_from_slice(&name.as_bytes()[..name.len().min(0x7F)]);
+                subtables.extend_from_slice(&(DIRECTORY_ID_BASE + child as u16).to_le_bytes());
+            }
+            subtables.push(0);
+        }
+
+        main_table.extend_from_slice(&subtables);
+        main_table
+    }
+}
+
+/// Builds a `BALANCE/monster.md` from (national dex number, sprite index) pairs, every other
+/// entry field is zero
+pub fn monster_md(entries: &[(u16, i16)]) -> Vec<u8> {
+    const ENTRY_LEN: usize = 68;
+    let mut md = Vec::with_capacity(8 + entries.len() * ENTRY_LEN);
+    md.extend_from_slice(b"MD\0\0");
+    md.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for &(dex_num, sprite_index) in entries {
+        let mut entry = [0u8; ENTRY_LEN];
+        entry[0x04..0x06].copy_from_slice(&dex_num.to_le_bytes());
+        entry[0x10..0x12].copy_from_slice(&sprite_index.to_le_bytes());
+        md.extend_from_slice(&entry);
+    }
+    md
+}
+
+fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
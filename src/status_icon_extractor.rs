@@ -12,6 +12,7 @@ use serde::Serialize;
 use crate::{
     binary_utils::{read_u16_le, read_u32_le, read_u8},
     containers::sir0::Sir0,
+    output::JsonStyle,
     progress::write_progress,
     rom::Rom,
 };
@@ -230,6 +231,8 @@ fn render_status_sheet(
 /// RAM address of the icon bit → SMA animation/palette lookup table in overlay 29 (US ROM).
 const OV29_TABLE_ADDR: u32 = 0x02350f8c;
 const TABLE_ENTRY_COUNT: usize = 34;
+/// Table entry of the persistent freeze icon, after the 32 status flags
+const FREEZE_TABLE_INDEX: usize = 33;
 
 const BIT_FLAGS: [(u8, &str); 32] = [
     (0, "sleepless"),
@@ -279,44 +282,27 @@ struct StatusIconEntry {
 
 pub struct StatusIconExtractor<'a> {
     rom: &'a mut Rom,
+    json_style: JsonStyle,
 }
 
 impl<'a> StatusIconExtractor<'a> {
     pub fn new(rom: &'a mut Rom) -> Self {
-        StatusIconExtractor { rom }
+        StatusIconExtractor {
+            rom,
+            json_style: JsonStyle::default(),
+        }
+    }
+
+    /// Formatting for `status_icons.json`
+    pub fn with_json_style(mut self, json_style: JsonStyle) -> Self {
+        self.json_style = json_style;
+        self
     }
 
     pub fn extract(&mut self, output_dir: &Path, progress_path: &Path) -> io::Result<()> {
         fs::create_dir_all(output_dir)?;
 
-        if !self.rom.loaded_overlays.contains_key(&29) {
-            self.rom.load_arm9_overlays(&[29])?;
-        }
-
-        let sma_file_id = self
-            .rom
-            .fnt
-            .get_file_id("SYSTEM/manpu_su.sma")
-            .ok_or_else(|| {
-                io::Error::new(io::ErrorKind::NotFound, "SYSTEM/manpu_su.sma not found")
-            })?;
-
-        let sma_raw = self
-            .rom
-            .fat
-            .get_file_data(sma_file_id as usize, &self.rom.data)
-            .ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidData, "Failed to extract manpu_su.sma")
-            })?;
-
-        let sma = parse_sma(sma_raw)?;
-        println!(
-            "Parsed manpu_su.sma: {} animations, {} palettes",
-            sma.anim_data.len(),
-            sma.custom_palette.len()
-        );
-
-        let rom_table = self.read_ov29_table()?;
+        let (sma, rom_table) = self.load_sma_and_table()?;
 
         let mut metadata = serde_json::Map::new();
         let total_icons = BIT_FLAGS.len() + 1;
@@ -340,8 +326,8 @@ impl<'a> StatusIconExtractor<'a> {
             write_progress(progress_path, i + 1, total_icons, "status_icons", "running");
         }
 
-        // Persistent freeze icon: table index 33
-        let (freeze_anim, freeze_pal) = rom_table[33];
+        // Persistent freeze icon
+        let (freeze_anim, freeze_pal) = rom_table[FREEZE_TABLE_INDEX];
         match render_and_save(
             &sma,
             freeze_anim,
@@ -370,7 +356,9 @@ impl<'a> StatusIconExtractor<'a> {
         );
 
         let json_path = output_dir.join("status_icons.json");
-        let json = serde_json::to_string_pretty(&metadata)
+        let json = self
+            .json_style
+            .to_string(&metadata)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
         fs::write(&json_path, json)?;
         println!("Saved status icon metadata to {}", json_path.display());
@@ -378,6 +366,53 @@ impl<'a> StatusIconExtractor<'a> {
         Ok(())
     }
 
+    /// Icons `extract` would write (the status flags plus freeze), leaving out the ones whose
+    /// animation is null
+    pub fn count_icons(&mut self) -> io::Result<usize> {
+        let (sma, rom_table) = self.load_sma_and_table()?;
+        let table_indices = BIT_FLAGS
+            .iter()
+            .map(|&(bit, _)| bit as usize + 1)
+            .chain([FREEZE_TABLE_INDEX]);
+        Ok(table_indices
+            .filter(|&table_idx| icon_animation(&sma, rom_table[table_idx].0).is_some())
+            .count())
+    }
+
+    /// Parses manpu_su.sma and reads the overlay 29 (animation, palette) table, loading the
+    /// overlay first if needed
+    fn load_sma_and_table(&mut self) -> io::Result<(SmaFile, Vec<(u32, u32)>)> {
+        if !self.rom.loaded_overlays.contains_key(&29) {
+            self.rom.load_arm9_overlays(&[29])?;
+        }
+
+        let sma_file_id = self
+            .rom
+            .fnt
+            .get_file_id("SYSTEM/manpu_su.sma")
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "SYSTEM/manpu_su.sma not found")
+            })?;
+
+        let sma_raw = self
+            .rom
+            .fat
+            .get_file_data(sma_file_id as usize, &self.rom.data)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Failed to extract manpu_su.sma")
+            })?;
+
+        let sma = parse_sma(sma_raw)?;
+        println!(
+            "Parsed manpu_su.sma: {} animations, {} palettes",
+            sma.anim_data.len(),
+            sma.custom_palette.len()
+        );
+
+        let rom_table = self.read_ov29_table()?;
+        Ok((sma, rom_table))
+    }
+
     fn read_ov29_table(&self) -> io::Result<Vec<(u32, u32)>> {
         let overlay = self
             .rom
@@ -411,6 +446,13 @@ impl<'a> StatusIconExtractor<'a> {
     }
 }
 
+/// The SMA animation at `anim_idx`, or `None` if it's missing or null (no blocks or frames)
+fn icon_animation(sma: &SmaFile, anim_idx: u32) -> Option<&SmaAnimation> {
+    sma.anim_data
+        .get(anim_idx as usize)
+        .filter(|anim| anim.block_width > 0 && anim.frame_count > 0)
+}
+
 fn render_and_save(
     sma: &SmaFile,
     anim_idx: u32,
@@ -419,9 +461,8 @@ fn render_and_save(
     icon_type: &str,
     output_dir: &Path,
 ) -> io::Result<Option<StatusIconEntry>> {
-    let anim = match sma.anim_data.get(anim_idx as usize) {
-        Some(a) if a.block_width > 0 && a.frame_count > 0 => a,
-        _ => return Ok(None),
+    let Some(anim) = icon_animation(sma, anim_idx) else {
+        return Ok(None);
     };
 
     let (sheet, frame_width, frame_height) =
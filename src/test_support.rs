@@ -0,0 +1,63 @@
+//! Helpers shared by the unit tests
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::output::OutputSink;
+
+/// Empty scratch directory under the system temp dir, unique per test name and process
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pmd_scraper_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Colours of a PNG's PLTE chunk with alpha from tRNS, missing alpha entries are opaque
+pub fn png_palette(png: &[u8]) -> Vec<[u8; 4]> {
+    let (mut plte, mut trns) = (Vec::new(), Vec::new());
+    let mut pos = 8;
+    while pos + 8 <= png.len() {
+        let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+        let data = &png[pos + 8..pos + 8 + len];
+        match &png[pos + 4..pos + 8] {
+            b"PLTE" => plte = data.to_vec(),
+            b"tRNS" => trns = data.to_vec(),
+            _ => {}
+        }
+        pos += 12 + len;
+    }
+    plte.chunks(3)
+        .enumerate()
+        .map(|(i, c)| [c[0], c[1], c[2], trns.get(i).copied().unwrap_or(255)])
+        .collect()
+}
+
+/// Keeps written files in memory, keyed by path
+#[derive(Debug, Default)]
+pub struct MemorySink(pub Mutex<BTreeMap<PathBuf, Vec<u8>>>);
+
+impl MemorySink {
+    /// Contents written to `path`, if any
+    pub fn file(&self, path: &Path) -> Option<Vec<u8>> {
+        self.0.lock().unwrap().get(path).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn write_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), bytes.to_vec());
+        Ok(())
+    }
+}
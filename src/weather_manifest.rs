@@ -6,6 +6,8 @@ use std::{io, path::Path};
 
 use serde::Serialize;
 
+use crate::output::JsonStyle;
+
 /// Fixed-point scroll step magnitude (1/256 px) — ROM constant 0x60.
 const STEP: i32 = 0x60;
 
@@ -67,6 +69,7 @@ pub struct WeatherEntry {
     pub drift_mode: Option<u8>,
 }
 
+#[rustfmt::skip]
 pub fn build() -> WeatherManifest {
     let drift_modes = vec![
         DriftMode { mode: 0, dx: 0,     dy: 0,     label: "None" },
@@ -113,7 +116,7 @@ pub fn build() -> WeatherManifest {
     }
 }
 
-pub fn build_and_save(output_dir: &Path) -> io::Result<()> {
+pub fn build_and_save(output_dir: &Path, json_style: JsonStyle) -> io::Result<()> {
     let manifest = build();
 
     // Warn-only sanity: the textures this manifest points at should already
@@ -128,7 +131,8 @@ pub fn build_and_save(output_dir: &Path) -> io::Result<()> {
         }
     }
 
-    let json = serde_json::to_string_pretty(&manifest)
+    let json = json_style
+        .to_string(&manifest)
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
     let path = output_dir.join("weather.json");
     std::fs::write(&path, json)?;